@@ -0,0 +1,69 @@
+//! Generate Rust structs for the tables in an Airtable base.
+//!
+//! Usage:
+//!
+//! ```text
+//! airtable-codegen [--base-id <id>] [--table <name>] [--field-names] [--output <file>]
+//! ```
+//!
+//! The API key is read from the `AIRTABLE_API_KEY` environment variable and the base id
+//! defaults to the `AIRTABLE_BASE_ID` environment variable.
+use std::{env, fs};
+
+use airtable_api::{
+    blocking::Airtable,
+    codegen::{generate, CodegenOptions},
+    schema::BaseSchema,
+};
+use anyhow::{bail, Result};
+
+fn main() -> Result<()> {
+    let mut base_id = env::var("AIRTABLE_BASE_ID").unwrap_or_default();
+    let mut tables: Vec<String> = Default::default();
+    let mut output: Option<String> = None;
+    let mut opts = CodegenOptions::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base-id" => base_id = next_value(&mut args, &arg)?,
+            "--table" => tables.push(next_value(&mut args, &arg)?),
+            "--output" => output = Some(next_value(&mut args, &arg)?),
+            "--field-names" => opts.rename_by_field_id = false,
+            "-h" | "--help" => {
+                println!("usage: airtable-codegen [--base-id <id>] [--table <name>] [--field-names] [--output <file>]");
+                return Ok(());
+            }
+            other => bail!("unknown argument `{}`", other),
+        }
+    }
+
+    if base_id.is_empty() {
+        bail!("a base id is required, pass --base-id or set AIRTABLE_BASE_ID");
+    }
+
+    let airtable = Airtable::new(airtable_api::api_key_from_env(), &base_id, "");
+    let mut schema: BaseSchema = airtable.get_base_schema()?;
+
+    if !tables.is_empty() {
+        schema
+            .tables
+            .retain(|t| tables.contains(&t.name) || tables.contains(&t.id));
+    }
+
+    let code = generate(&schema, &opts);
+
+    match output {
+        Some(file) => fs::write(file, code)?,
+        None => print!("{}", code),
+    }
+
+    Ok(())
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+    match args.next() {
+        Some(v) => Ok(v),
+        None => bail!("`{}` requires a value", flag),
+    }
+}
@@ -0,0 +1,222 @@
+/*!
+ * A blocking Airtable client.
+ *
+ * The blocking client wraps the async [`Airtable`](crate::Airtable) client and drives
+ * each request to completion on its own runtime, so it can be used from scripts and build
+ * tooling that do not otherwise need an async runtime.
+ *
+ * This module is only available when the `blocking` feature is enabled.
+ *
+ * The blocking client must not be used from within an async runtime, doing so will panic.
+ * If you are already inside an async context use the async client instead.
+ *
+ * Example:
+ *
+ * ```ignore
+ * use airtable_api::{blocking::Airtable, Record};
+ *
+ * let airtable = Airtable::new_from_env();
+ * let records: Vec<Record<serde_json::Value>> = airtable.list_records("Table Name", "Grid view", vec![]).unwrap();
+ * ```
+ */
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::runtime::Runtime;
+
+use crate::{
+    schema::{BaseSchema, TableSchema},
+    EnterpriseUser, Interceptor, Record, RequestOptions, User, Workspace, WorkspaceIncludes,
+};
+
+/// Entrypoint for interacting with the Airtable API in a blocking manner.
+pub struct Airtable {
+    inner: crate::Airtable,
+    rt: Runtime,
+}
+
+impl Airtable {
+    /// Create a new blocking Airtable client struct. See [`crate::Airtable::new`] for
+    /// details on the arguments.
+    pub fn new<K, B, E>(key: K, base_id: B, enterprise_account_id: E) -> Self
+    where
+        K: ToString,
+        B: ToString,
+        E: ToString,
+    {
+        Self::from_async(crate::Airtable::new(key, base_id, enterprise_account_id))
+    }
+
+    /// Create a new blocking Airtable client struct from environment variables. See
+    /// [`crate::Airtable::new_from_env`] for the variables that are read.
+    pub fn new_from_env() -> Self {
+        Self::from_async(crate::Airtable::new_from_env())
+    }
+
+    /// Create a new blocking Airtable client struct that wraps an existing async client.
+    pub fn from_async(inner: crate::Airtable) -> Self {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build();
+        match rt {
+            Ok(rt) => Self { inner, rt },
+            Err(err) => panic!("creating runtime failed: {err:?}"),
+        }
+    }
+
+    /// Set the options used when reading records.
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.inner = self.inner.with_options(options);
+        self
+    }
+
+    /// Register an interceptor that is called around every request the client makes.
+    pub fn with_interceptor<I>(mut self, interceptor: I) -> Self
+    where
+        I: Interceptor + 'static,
+    {
+        self.inner = self.inner.with_interceptor(interceptor);
+        self
+    }
+
+    /// Get the currently set API key.
+    pub fn get_key(&self) -> &str {
+        self.inner.get_key()
+    }
+
+    /// List records in a table for a particular view.
+    pub fn list_records<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        view: &str,
+        fields: Vec<&str>,
+    ) -> Result<Vec<Record<T>>> {
+        self.rt.block_on(self.inner.list_records(table, view, fields))
+    }
+
+    /// Iterate over the pages of records in a table for a particular view.
+    pub fn pages<T: DeserializeOwned>(&self, table: &str, view: &str, fields: Vec<&str>) -> Pages<T> {
+        Pages {
+            inner: self.inner.pages(table, view, fields),
+            rt: &self.rt,
+        }
+    }
+
+    /// Get the records with the given ids from a table.
+    pub fn get_records<T: DeserializeOwned>(&self, table: &str, record_ids: &[&str]) -> Result<Vec<Record<T>>> {
+        self.rt.block_on(self.inner.get_records(table, record_ids))
+    }
+
+    /// Get record from a table.
+    pub fn get_record<T: DeserializeOwned>(&self, table: &str, record_id: &str) -> Result<Record<T>> {
+        self.rt.block_on(self.inner.get_record(table, record_id))
+    }
+
+    /// Delete record from a table.
+    pub fn delete_record(&self, table: &str, record_id: &str) -> Result<()> {
+        self.rt.block_on(self.inner.delete_record(table, record_id))
+    }
+
+    /// Delete multiple records from a table.
+    ///
+    /// Due to limitations on the Airtable API, you can only bulk delete 10
+    /// records at a time.
+    pub fn delete_records<'a>(&self, table: &str, record_ids: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        self.rt.block_on(self.inner.delete_records(table, record_ids))
+    }
+
+    /// Bulk create records in a table.
+    pub fn create_records<T: Serialize + DeserializeOwned>(
+        &self,
+        table: &str,
+        records: Vec<Record<T>>,
+    ) -> Result<Vec<Record<T>>> {
+        self.rt.block_on(self.inner.create_records(table, records))
+    }
+
+    /// Bulk update records in a table.
+    pub fn update_records<T: Serialize + DeserializeOwned>(
+        &self,
+        table: &str,
+        records: Vec<Record<T>>,
+    ) -> Result<Vec<Record<T>>> {
+        self.rt.block_on(self.inner.update_records(table, records))
+    }
+
+    /// Get the schema of the tables in the base.
+    pub fn get_base_schema(&self) -> Result<BaseSchema> {
+        self.rt.block_on(self.inner.get_base_schema())
+    }
+
+    /// Get the schema of a single table in the base by its name or id.
+    pub fn get_table_schema(&self, table: &str) -> Result<TableSchema> {
+        self.rt.block_on(self.inner.get_table_schema(table))
+    }
+
+    /// Bulk update only the records in a table whose fields have changed.
+    pub fn update_changed_records<T: Serialize + DeserializeOwned + PartialEq>(
+        &self,
+        table: &str,
+        records: Vec<Record<T>>,
+        existing: &[Record<T>],
+    ) -> Result<Vec<Record<T>>> {
+        self.rt
+            .block_on(self.inner.update_changed_records(table, records, existing))
+    }
+
+    /// List users.
+    /// This is for an enterprise admin to do only.
+    pub fn list_users(&self) -> Result<Vec<User>> {
+        self.rt.block_on(self.inner.list_users())
+    }
+
+    /// Get an enterprise user.
+    /// This is for an enterprise admin to do only.
+    pub fn get_enterprise_user(&self, email: &str) -> Result<EnterpriseUser> {
+        self.rt.block_on(self.inner.get_enterprise_user(email))
+    }
+
+    /// Add a collaborator to a workspace.
+    /// This is for an enterprise admin to do only.
+    pub fn add_collaborator_to_workspace(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+        permission_level: &str,
+    ) -> Result<()> {
+        self.rt.block_on(
+            self.inner
+                .add_collaborator_to_workspace(workspace_id, user_id, permission_level),
+        )
+    }
+
+    /// Returns basic information on the workspace.
+    pub fn get_enterprise_workspace<const N: usize>(
+        &self,
+        workspace_id: &str,
+        includes: Option<[WorkspaceIncludes; N]>,
+    ) -> Result<Workspace> {
+        self.rt
+            .block_on(self.inner.get_enterprise_workspace(workspace_id, includes))
+    }
+
+    /// Delete internal user by email.
+    /// This is for an enterprise admin to do only.
+    pub fn delete_internal_user_by_email(&self, email: &str) -> Result<()> {
+        self.rt.block_on(self.inner.delete_internal_user_by_email(email))
+    }
+}
+
+/// A blocking iterator over the pages of records in a table.
+pub struct Pages<'a, T> {
+    inner: crate::Pages<'a, T>,
+    rt: &'a Runtime,
+}
+
+impl<'a, T> Iterator for Pages<'a, T>
+where
+    T: DeserializeOwned,
+{
+    type Item = Result<Vec<Record<T>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rt.block_on(self.inner.next()).transpose()
+    }
+}
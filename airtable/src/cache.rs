@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::{header, Request, Response, StatusCode};
+
+/// A cache of the responses to `GET` requests, so that a sync run that reads the same
+/// table for several models only fetches it once.
+///
+/// Responses that carry an `ETag` are revalidated with `If-None-Match` on every read, and
+/// served from the cache when Airtable answers `304 Not Modified`. Responses without one
+/// are served from the cache until `ttl` has passed. Any write to a table drops the
+/// responses cached for it.
+///
+/// Responses are keyed by their URL, so a cache should only be shared between clients that
+/// use the same API key. Set it on a client with
+/// [`Airtable::with_cache`](crate::Airtable::with_cache).
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CachedResponse {
+    status: StatusCode,
+    headers: header::HeaderMap,
+    body: Vec<u8>,
+    etag: Option<header::HeaderValue>,
+    stored_at: Instant,
+}
+
+/// What the cache has for a request.
+#[derive(Debug)]
+pub(crate) enum Lookup {
+    /// The cached response can be used as is.
+    Fresh(CachedResponse),
+    /// The cached response can be used if Airtable says it is not modified.
+    Revalidate(header::HeaderValue),
+    Miss,
+}
+
+impl CachedResponse {
+    pub(crate) fn new(status: StatusCode, headers: header::HeaderMap, body: Vec<u8>) -> Self {
+        let etag = headers.get(header::ETAG).cloned();
+        CachedResponse {
+            status,
+            headers,
+            body,
+            etag,
+            stored_at: Instant::now(),
+        }
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        ResponseCache::new(Duration::from_secs(30))
+    }
+}
+
+impl ResponseCache {
+    /// Create a cache that serves responses without an `ETag` for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        ResponseCache {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    /// Drop every cached response.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub(crate) fn lookup(&self, key: &str, now: Instant) -> Lookup {
+        let entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) => match &entry.etag {
+                Some(etag) => Lookup::Revalidate(etag.clone()),
+                None if now.duration_since(entry.stored_at) < self.ttl => Lookup::Fresh(entry.clone()),
+                None => Lookup::Miss,
+            },
+            None => Lookup::Miss,
+        }
+    }
+
+    /// Returns the cached response for a request Airtable answered with `304 Not Modified`.
+    pub(crate) fn revalidated(&self, key: &str, now: Instant) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(key)?;
+        entry.stored_at = now;
+        Some(entry.clone())
+    }
+
+    pub(crate) fn store(&self, key: &str, status: StatusCode, headers: header::HeaderMap, body: Vec<u8>, now: Instant) {
+        let entry = CachedResponse {
+            stored_at: now,
+            ..CachedResponse::new(status, headers, body)
+        };
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+    }
+
+    /// Drop the responses for the path of a request and everything under it, for example
+    /// the pages of a table and its records after the table is written to.
+    pub(crate) fn invalidate(&self, request: &Request) {
+        let path = request.url().path().trim_end_matches('/');
+        self.entries.lock().unwrap().retain(|key, _| {
+            let key_path = reqwest::Url::parse(key)
+                .map(|u| u.path().to_string())
+                .unwrap_or_default();
+            !(key_path == path || key_path.starts_with(&format!("{path}/")))
+        });
+    }
+}
+
+/// Returns the key a request is cached under.
+pub(crate) fn cache_key(request: &Request) -> String {
+    request.url().to_string()
+}
+
+impl From<CachedResponse> for Response {
+    fn from(cached: CachedResponse) -> Self {
+        let mut resp = http::Response::new(cached.body);
+        *resp.status_mut() = cached.status;
+        *resp.headers_mut() = cached.headers;
+        Response::from(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{Method, Url};
+
+    use super::*;
+
+    const PAGE: &str = "https://api.airtable.com/v0/app1/Users?pageSize=100";
+    const RECORD: &str = "https://api.airtable.com/v0/app1/Users/rec1";
+    const OTHER: &str = "https://api.airtable.com/v0/app1/Users%20Logins?pageSize=100";
+
+    #[test]
+    fn test_lookup() {
+        let cache = ResponseCache::new(Duration::from_secs(30));
+        let now = Instant::now();
+        assert!(matches!(cache.lookup(PAGE, now), Lookup::Miss));
+
+        // Without an etag the response is used until the ttl passes.
+        cache.store(PAGE, StatusCode::OK, Default::default(), b"{}".to_vec(), now);
+        assert!(matches!(
+            cache.lookup(PAGE, now + Duration::from_secs(10)),
+            Lookup::Fresh(_)
+        ));
+        assert!(matches!(
+            cache.lookup(PAGE, now + Duration::from_secs(31)),
+            Lookup::Miss
+        ));
+
+        // With an etag the response is always revalidated.
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::ETAG, header::HeaderValue::from_static("\"v1\""));
+        cache.store(RECORD, StatusCode::OK, headers, b"{}".to_vec(), now);
+        match cache.lookup(RECORD, now) {
+            Lookup::Revalidate(etag) => assert_eq!(etag, "\"v1\""),
+            l => panic!("expected a revalidation, got {l:?}"),
+        }
+        assert_eq!(cache.revalidated(RECORD, now).unwrap().body, b"{}");
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = ResponseCache::default();
+        let now = Instant::now();
+        for key in [PAGE, RECORD, OTHER] {
+            cache.store(key, StatusCode::OK, Default::default(), vec![], now);
+        }
+
+        let write = Request::new(
+            Method::PATCH,
+            Url::parse("https://api.airtable.com/v0/app1/Users").unwrap(),
+        );
+        cache.invalidate(&write);
+
+        assert!(matches!(cache.lookup(PAGE, now), Lookup::Miss));
+        assert!(matches!(cache.lookup(RECORD, now), Lookup::Miss));
+        assert!(matches!(cache.lookup(OTHER, now), Lookup::Fresh(_)));
+    }
+}
@@ -0,0 +1,519 @@
+//! A typed representation of the value of a single cell.
+//!
+//! Reading a table into `Record<CellValues>` gives access to every field without
+//! writing a struct with custom deserializers for each complex field type.
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{currency_format, duration_format, schema::FieldSchema, Attachment, RequestOptions, User};
+
+/// The fields of a record, keyed by field name or id.
+pub type CellValues = BTreeMap<String, AirtableCellValue>;
+
+/// The value of a single cell.
+/// FROM: https://airtable.com/developers/web/api/field-model
+#[derive(Debug, Clone, PartialEq)]
+pub enum AirtableCellValue {
+    /// A single line, long text, email, url, or phone number field.
+    Text(String),
+    /// A single select field.
+    SingleSelect(String),
+    /// A multiple select field.
+    MultipleSelects(Vec<String>),
+    /// A single collaborator field.
+    Collaborator(User),
+    /// A multiple collaborators field.
+    Collaborators(Vec<User>),
+    /// An attachment field.
+    Attachments(Vec<Attachment>),
+    /// A link to another record field, the values are record ids.
+    LinkedRecords(Vec<String>),
+    /// A checkbox field.
+    Checkbox(bool),
+    /// A number, percent or rating field.
+    Number(f64),
+    /// A currency field, kept exact.
+    Currency(Decimal),
+    /// A duration field, stored by Airtable as seconds.
+    Duration(Duration),
+    /// A date field.
+    Date(NaiveDate),
+    /// A date and time field.
+    DateTime(DateTime<Utc>),
+    /// Any other value, for example the result of a formula or rollup.
+    Other(serde_json::Value),
+}
+
+impl AirtableCellValue {
+    /// Parse the value of a cell using the schema of its field to pick the variant.
+    pub fn from_value(field: &FieldSchema, value: serde_json::Value) -> Result<Self> {
+        Ok(match field.type_.as_str() {
+            "singleLineText" | "multilineText" | "richText" | "email" | "url" | "phoneNumber" => {
+                AirtableCellValue::Text(serde_json::from_value(value)?)
+            }
+            "singleSelect" => AirtableCellValue::SingleSelect(serde_json::from_value(value)?),
+            "multipleSelects" => AirtableCellValue::MultipleSelects(serde_json::from_value(value)?),
+            "singleCollaborator" | "createdBy" | "lastModifiedBy" => {
+                AirtableCellValue::Collaborator(serde_json::from_value(value)?)
+            }
+            "multipleCollaborators" => AirtableCellValue::Collaborators(serde_json::from_value(value)?),
+            "multipleAttachments" => AirtableCellValue::Attachments(serde_json::from_value(value)?),
+            "multipleRecordLinks" => AirtableCellValue::LinkedRecords(serde_json::from_value(value)?),
+            "checkbox" => AirtableCellValue::Checkbox(serde_json::from_value(value)?),
+            "currency" => AirtableCellValue::Currency(currency_format::deserialize(value)?),
+            "duration" => AirtableCellValue::Duration(duration_format::deserialize(value)?),
+            "number" | "percent" | "rating" | "autoNumber" | "count" => {
+                AirtableCellValue::Number(serde_json::from_value(value)?)
+            }
+            "date" => AirtableCellValue::Date(serde_json::from_value(value)?),
+            "dateTime" | "createdTime" | "lastModifiedTime" => {
+                AirtableCellValue::DateTime(serde_json::from_value(value)?)
+            }
+            _ => AirtableCellValue::Other(value),
+        })
+    }
+
+    /// Parse the value of a cell from its shape alone. This is used when deserializing
+    /// without a schema, so select and text fields both become `Text`, and date fields
+    /// are left as `Text` for `as_date` and `as_date_time` to parse.
+    fn infer(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(s) => AirtableCellValue::Text(s),
+            serde_json::Value::Bool(b) => AirtableCellValue::Checkbox(b),
+            serde_json::Value::Number(ref n) => match n.as_f64() {
+                Some(f) => AirtableCellValue::Number(f),
+                None => AirtableCellValue::Other(value),
+            },
+            serde_json::Value::Array(ref items) if items.iter().all(|i| i.is_string()) => {
+                let strings: Vec<String> = items.iter().filter_map(|i| i.as_str().map(|s| s.to_string())).collect();
+                if !strings.is_empty() && strings.iter().all(|s| is_record_id(s)) {
+                    AirtableCellValue::LinkedRecords(strings)
+                } else {
+                    AirtableCellValue::MultipleSelects(strings)
+                }
+            }
+            serde_json::Value::Array(ref items) if items.iter().all(|i| i.get("email").is_some()) => {
+                match serde_json::from_value(value.clone()) {
+                    Ok(users) => AirtableCellValue::Collaborators(users),
+                    Err(_) => AirtableCellValue::Other(value),
+                }
+            }
+            serde_json::Value::Array(ref items) if items.iter().all(|i| i.get("url").is_some()) => {
+                match serde_json::from_value(value.clone()) {
+                    Ok(attachments) => AirtableCellValue::Attachments(attachments),
+                    Err(_) => AirtableCellValue::Other(value),
+                }
+            }
+            serde_json::Value::Object(ref o) if o.contains_key("email") => {
+                match serde_json::from_value(value.clone()) {
+                    Ok(user) => AirtableCellValue::Collaborator(user),
+                    Err(_) => AirtableCellValue::Other(value),
+                }
+            }
+            other => AirtableCellValue::Other(other),
+        }
+    }
+
+    /// Returns the value as a string, for text and single select fields.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AirtableCellValue::Text(s) | AirtableCellValue::SingleSelect(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a bool, for checkbox fields.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            AirtableCellValue::Checkbox(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float, for number, currency and duration fields. Durations
+    /// are in seconds.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            AirtableCellValue::Number(n) => Some(*n),
+            AirtableCellValue::Currency(d) => d.to_f64(),
+            AirtableCellValue::Duration(d) => Some(d.num_milliseconds() as f64 / 1000.0),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a decimal, for currency fields or numbers read without a
+    /// schema.
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        match self {
+            AirtableCellValue::Currency(d) => Some(*d),
+            // The shortest representation of the float is what Airtable sent.
+            AirtableCellValue::Number(n) => Decimal::from_str(&n.to_string()).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a duration, for duration fields or numbers of seconds read
+    /// without a schema.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            AirtableCellValue::Duration(d) => Some(*d),
+            AirtableCellValue::Number(n) => Some(Duration::milliseconds((n * 1000.0).round() as i64)),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an integer, for number fields without decimal places.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            AirtableCellValue::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a date, for date fields or text in the `YYYY-MM-DD` format.
+    pub fn as_date(&self) -> Option<NaiveDate> {
+        match self {
+            AirtableCellValue::Date(d) => Some(*d),
+            AirtableCellValue::DateTime(d) => Some(d.date_naive()),
+            AirtableCellValue::Text(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d").ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the date in the time zone of the request options, for date fields as they
+    /// are and for date and time fields, which Airtable returns in UTC.
+    pub fn as_local_date(&self, options: &RequestOptions) -> Option<NaiveDate> {
+        match self.as_date_time() {
+            Some(at) => options.local_date(at).ok(),
+            None => self.as_date(),
+        }
+    }
+
+    /// Returns the value as a timestamp, for date and time fields or RFC 3339 text.
+    pub fn as_date_time(&self) -> Option<DateTime<Utc>> {
+        match self {
+            AirtableCellValue::DateTime(d) => Some(*d),
+            AirtableCellValue::Text(s) => DateTime::parse_from_rfc3339(s).ok().map(|d| d.with_timezone(&Utc)),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of a multiple select or linked record field.
+    pub fn as_strings(&self) -> Option<&[String]> {
+        match self {
+            AirtableCellValue::MultipleSelects(v) | AirtableCellValue::LinkedRecords(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the emails of the users in a collaborator field.
+    pub fn as_emails(&self) -> Option<Vec<String>> {
+        match self {
+            AirtableCellValue::Collaborator(u) => Some(vec![u.email.to_string()]),
+            AirtableCellValue::Collaborators(u) => Some(u.iter().map(|u| u.email.to_string()).collect()),
+            _ => None,
+        }
+    }
+
+    /// Returns the urls of the files in an attachment field.
+    pub fn as_attachment_urls(&self) -> Option<Vec<String>> {
+        match self {
+            AirtableCellValue::Attachments(a) => Some(a.iter().map(|a| a.url.to_string()).collect()),
+            _ => None,
+        }
+    }
+
+    /// Convert a cell value into the type given.
+    pub fn try_into_type<T: TryFrom<AirtableCellValue, Error = anyhow::Error>>(self) -> Result<T> {
+        T::try_from(self)
+    }
+}
+
+/// Returns if a string looks like an Airtable record id, for example `recXXXXXXXXXXXXXX`.
+fn is_record_id(s: &str) -> bool {
+    s.len() == 17 && s.starts_with("rec") && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+impl Serialize for AirtableCellValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            AirtableCellValue::Text(s) | AirtableCellValue::SingleSelect(s) => s.serialize(serializer),
+            AirtableCellValue::MultipleSelects(v) | AirtableCellValue::LinkedRecords(v) => v.serialize(serializer),
+            AirtableCellValue::Collaborator(u) => u.serialize(serializer),
+            AirtableCellValue::Collaborators(u) => u.serialize(serializer),
+            AirtableCellValue::Attachments(a) => a.serialize(serializer),
+            AirtableCellValue::Checkbox(b) => b.serialize(serializer),
+            AirtableCellValue::Number(n) => n.serialize(serializer),
+            AirtableCellValue::Currency(d) => currency_format::serialize(d, serializer),
+            AirtableCellValue::Duration(d) => duration_format::serialize(d, serializer),
+            AirtableCellValue::Date(d) => d.format("%Y-%m-%d").to_string().serialize(serializer),
+            AirtableCellValue::DateTime(d) => d.serialize(serializer),
+            AirtableCellValue::Other(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AirtableCellValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Ok(AirtableCellValue::infer(value))
+    }
+}
+
+impl From<String> for AirtableCellValue {
+    fn from(s: String) -> Self {
+        AirtableCellValue::Text(s)
+    }
+}
+
+impl From<&str> for AirtableCellValue {
+    fn from(s: &str) -> Self {
+        AirtableCellValue::Text(s.to_string())
+    }
+}
+
+impl From<bool> for AirtableCellValue {
+    fn from(b: bool) -> Self {
+        AirtableCellValue::Checkbox(b)
+    }
+}
+
+impl From<f64> for AirtableCellValue {
+    fn from(n: f64) -> Self {
+        AirtableCellValue::Number(n)
+    }
+}
+
+impl From<i64> for AirtableCellValue {
+    fn from(n: i64) -> Self {
+        AirtableCellValue::Number(n as f64)
+    }
+}
+
+impl From<Decimal> for AirtableCellValue {
+    fn from(d: Decimal) -> Self {
+        AirtableCellValue::Currency(d)
+    }
+}
+
+impl From<Duration> for AirtableCellValue {
+    fn from(d: Duration) -> Self {
+        AirtableCellValue::Duration(d)
+    }
+}
+
+impl From<NaiveDate> for AirtableCellValue {
+    fn from(d: NaiveDate) -> Self {
+        AirtableCellValue::Date(d)
+    }
+}
+
+impl From<DateTime<Utc>> for AirtableCellValue {
+    fn from(d: DateTime<Utc>) -> Self {
+        AirtableCellValue::DateTime(d)
+    }
+}
+
+impl TryFrom<AirtableCellValue> for String {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value {
+            AirtableCellValue::Text(s) | AirtableCellValue::SingleSelect(s) => Ok(s),
+            v => bail!("cell value `{:?}` is not a string", v),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for bool {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_bool() {
+            Some(b) => Ok(b),
+            None => bail!("cell value `{:?}` is not a checkbox", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for f64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_f64() {
+            Some(n) => Ok(n),
+            None => bail!("cell value `{:?}` is not a number", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_i64() {
+            Some(n) => Ok(n),
+            None => bail!("cell value `{:?}` is not an integer", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for Decimal {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_decimal() {
+            Some(d) => Ok(d),
+            None => bail!("cell value `{:?}` is not an amount", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for Duration {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_duration() {
+            Some(d) => Ok(d),
+            None => bail!("cell value `{:?}` is not a duration", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for NaiveDate {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_date() {
+            Some(d) => Ok(d),
+            None => bail!("cell value `{:?}` is not a date", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for DateTime<Utc> {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value.as_date_time() {
+            Some(d) => Ok(d),
+            None => bail!("cell value `{:?}` is not a date and time", value),
+        }
+    }
+}
+
+impl TryFrom<AirtableCellValue> for Vec<String> {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AirtableCellValue) -> Result<Self> {
+        match value {
+            AirtableCellValue::MultipleSelects(v) | AirtableCellValue::LinkedRecords(v) => Ok(v),
+            AirtableCellValue::Collaborator(_) | AirtableCellValue::Collaborators(_) => {
+                Ok(value.as_emails().unwrap_or_default())
+            }
+            AirtableCellValue::Attachments(_) => Ok(value.as_attachment_urls().unwrap_or_default()),
+            v => bail!("cell value `{:?}` is not a list", v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_cell_values() {
+        let fields: CellValues = serde_json::from_value(serde_json::json!({
+            "Name": "Jane",
+            "Active": true,
+            "Logins": 42,
+            "Tags": ["a", "b"],
+            "Auth User": ["recABCDEFGHIJKLMN"],
+            "Owner": {"id": "usr1", "email": "jane@example.com", "name": "Jane"},
+            "Files": [{"id": "att1", "url": "https://example.com/a.png", "filename": "a.png"}],
+            "Started": "2021-03-04"
+        }))
+        .unwrap();
+
+        assert_eq!(fields["Name"].as_str(), Some("Jane"));
+        assert_eq!(fields["Active"].as_bool(), Some(true));
+        assert_eq!(fields["Logins"].as_i64(), Some(42));
+        assert_eq!(
+            fields["Tags"],
+            AirtableCellValue::MultipleSelects(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(
+            fields["Auth User"],
+            AirtableCellValue::LinkedRecords(vec!["recABCDEFGHIJKLMN".to_string()])
+        );
+        assert_eq!(fields["Owner"].as_emails(), Some(vec!["jane@example.com".to_string()]));
+        assert_eq!(
+            fields["Files"].as_attachment_urls(),
+            Some(vec!["https://example.com/a.png".to_string()])
+        );
+        assert_eq!(fields["Started"].as_date(), NaiveDate::from_ymd_opt(2021, 3, 4));
+    }
+
+    #[test]
+    fn test_from_value_with_schema() {
+        let field = FieldSchema {
+            type_: "date".to_string(),
+            ..Default::default()
+        };
+        let value = AirtableCellValue::from_value(&field, serde_json::json!("2021-03-04")).unwrap();
+        assert_eq!(
+            value,
+            AirtableCellValue::Date(NaiveDate::from_ymd_opt(2021, 3, 4).unwrap())
+        );
+        assert_eq!(serde_json::to_value(&value).unwrap(), serde_json::json!("2021-03-04"));
+    }
+
+    #[test]
+    fn test_rich_types_with_schema() {
+        let field = |type_: &str| FieldSchema {
+            type_: type_.to_string(),
+            ..Default::default()
+        };
+
+        let amount = AirtableCellValue::from_value(&field("currency"), serde_json::json!(1234.1)).unwrap();
+        assert_eq!(amount.as_decimal(), Some(Decimal::new(12341, 1)));
+        assert_eq!(serde_json::to_value(&amount).unwrap(), serde_json::json!(1234.1));
+        // The float closest to 0.1 is not 0.1, the amount still is.
+        let amount = AirtableCellValue::from_value(&field("currency"), serde_json::json!(0.1)).unwrap();
+        assert_eq!(amount.as_decimal(), Some(Decimal::new(1, 1)));
+
+        let duration = AirtableCellValue::from_value(&field("duration"), serde_json::json!(5400.5)).unwrap();
+        assert_eq!(duration.as_duration(), Some(Duration::milliseconds(5_400_500)));
+        assert_eq!(duration.as_f64(), Some(5400.5));
+        let duration = AirtableCellValue::from(Duration::minutes(90));
+        assert_eq!(serde_json::to_value(&duration).unwrap(), serde_json::json!(5400));
+
+        // Late in the evening in Los Angeles is the next day in UTC.
+        let at =
+            AirtableCellValue::from_value(&field("dateTime"), serde_json::json!("2021-03-05T06:30:00.000Z")).unwrap();
+        let options = RequestOptions {
+            time_zone: Some("America/Los_Angeles".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(at.as_local_date(&options), NaiveDate::from_ymd_opt(2021, 3, 4));
+        assert_eq!(
+            at.as_local_date(&RequestOptions::default()),
+            NaiveDate::from_ymd_opt(2021, 3, 5)
+        );
+        let local = NaiveDate::from_ymd_opt(2021, 3, 4)
+            .unwrap()
+            .and_hms_opt(22, 30, 0)
+            .unwrap();
+        assert_eq!(options.to_utc(local).ok(), at.as_date_time());
+    }
+}
@@ -0,0 +1,314 @@
+//! Generate Rust structs from the schema of a base.
+//!
+//! This powers the `airtable-codegen` binary, but can also be used directly to generate
+//! the model structs for a table as part of a build script.
+use std::fmt::Write;
+
+use crate::schema::{BaseSchema, FieldSchema, TableSchema};
+
+/// Options that control the generated code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodegenOptions {
+    /// Rename the struct fields to the Airtable field ids instead of the field names.
+    /// This should match the `return_fields_by_field_id` request option the client is
+    /// using. Renaming by id means renaming a column in Airtable does not break the model.
+    pub rename_by_field_id: bool,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        CodegenOptions {
+            rename_by_field_id: true,
+        }
+    }
+}
+
+/// Generate the Rust structs for every table in the base.
+pub fn generate(schema: &BaseSchema, opts: &CodegenOptions) -> String {
+    let mut out = String::new();
+    out.push_str("// This file is generated by airtable-codegen. Do not edit it by hand.\n");
+    out.push_str("#[allow(unused_imports)]\n");
+    out.push_str("use chrono::{DateTime, NaiveDate, Utc};\n");
+    out.push_str("use serde::{Deserialize, Serialize};\n");
+
+    for table in &schema.tables {
+        out.push('\n');
+        out.push_str(&generate_table(table, opts));
+    }
+
+    out
+}
+
+/// Generate the Rust struct for a single table.
+pub fn generate_table(table: &TableSchema, opts: &CodegenOptions) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "/// The data type for the `{}` table.", table.name).unwrap();
+    if !table.description.is_empty() {
+        writeln!(out, "///").unwrap();
+        for line in table.description.lines() {
+            writeln!(out, "/// {}", line).unwrap();
+        }
+    }
+    writeln!(
+        out,
+        "#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]"
+    )
+    .unwrap();
+    writeln!(out, "pub struct {} {{", to_type_name(&table.name)).unwrap();
+
+    let mut used: Vec<String> = Default::default();
+    for field in &table.fields {
+        let mut ident = to_field_name(&field.name);
+        // Two Airtable fields can map to the same identifier, for example `Name` and `name`.
+        while used.contains(&ident) {
+            ident.push('_');
+        }
+        used.push(ident.clone());
+
+        let (ty, attrs) = field_type(field);
+        let rename = if opts.rename_by_field_id {
+            &field.id
+        } else {
+            &field.name
+        };
+
+        writeln!(out, "    /// {}", field.name).unwrap();
+        let mut serde_attrs = vec!["default".to_string()];
+        serde_attrs.extend(attrs.iter().map(|a| a.to_string()));
+        if field.is_computed() {
+            // Computed fields are read-only, sending them back on update fails the request.
+            serde_attrs.retain(|a| !a.starts_with("skip_serializing_if") && !a.starts_with("serialize_with"));
+            serde_attrs.push("skip_serializing".to_string());
+        }
+        serde_attrs.push(format!("rename = {:?}", rename));
+        writeln!(out, "    #[serde({})]", serde_attrs.join(", ")).unwrap();
+        writeln!(out, "    pub {}: {},", ident, ty).unwrap();
+    }
+
+    out.push_str("}\n");
+
+    out
+}
+
+/// Returns the Rust type and serde attributes used for a field.
+fn field_type(field: &FieldSchema) -> (&'static str, Vec<&'static str>) {
+    match field.type_.as_str() {
+        "singleLineText" | "multilineText" | "richText" | "email" | "url" | "phoneNumber" | "singleSelect" => {
+            ("String", vec!["skip_serializing_if = \"String::is_empty\""])
+        }
+        "multipleSelects" | "multipleRecordLinks" => ("Vec<String>", vec!["skip_serializing_if = \"Vec::is_empty\""]),
+        "multipleAttachments" => (
+            "Vec<String>",
+            vec![
+                "skip_serializing_if = \"Vec::is_empty\"",
+                "serialize_with = \"airtable_api::attachment_format_as_array_of_strings::serialize\"",
+                "deserialize_with = \"airtable_api::attachment_format_as_array_of_strings::deserialize\"",
+            ],
+        ),
+        "singleCollaborator" | "createdBy" | "lastModifiedBy" => (
+            "String",
+            vec![
+                "skip_serializing_if = \"String::is_empty\"",
+                "serialize_with = \"airtable_api::user_format_as_string::serialize\"",
+                "deserialize_with = \"airtable_api::user_format_as_string::deserialize\"",
+            ],
+        ),
+        "multipleCollaborators" => (
+            "Vec<String>",
+            vec![
+                "skip_serializing_if = \"Vec::is_empty\"",
+                "serialize_with = \"airtable_api::user_format_as_array_of_strings::serialize\"",
+                "deserialize_with = \"airtable_api::user_format_as_array_of_strings::deserialize\"",
+            ],
+        ),
+        "barcode" => (
+            "String",
+            vec![
+                "skip_serializing_if = \"String::is_empty\"",
+                "serialize_with = \"airtable_api::barcode_format_as_string::serialize\"",
+                "deserialize_with = \"airtable_api::barcode_format_as_string::deserialize\"",
+            ],
+        ),
+        "checkbox" => ("bool", vec![]),
+        "autoNumber" | "count" | "rating" => ("i64", vec![]),
+        "currency" => (
+            "rust_decimal::Decimal",
+            vec![
+                "serialize_with = \"airtable_api::currency_format::serialize\"",
+                "deserialize_with = \"airtable_api::currency_format::deserialize\"",
+            ],
+        ),
+        "number" | "percent" => {
+            if field.precision() == Some(0) {
+                ("i64", vec![])
+            } else {
+                ("f64", vec![])
+            }
+        }
+        "duration" => (
+            "Option<chrono::Duration>",
+            vec![
+                "skip_serializing_if = \"Option::is_none\"",
+                "with = \"airtable_api::duration_format::option\"",
+            ],
+        ),
+        "date" => ("Option<NaiveDate>", vec!["skip_serializing_if = \"Option::is_none\""]),
+        "dateTime" | "createdTime" | "lastModifiedTime" => (
+            "Option<DateTime<Utc>>",
+            vec!["skip_serializing_if = \"Option::is_none\""],
+        ),
+        _ => (
+            "serde_json::Value",
+            vec!["skip_serializing_if = \"serde_json::Value::is_null\""],
+        ),
+    }
+}
+
+/// Convert a table name into a Rust type name, for example `Auth Users` into `AuthUsers`.
+pub fn to_type_name(name: &str) -> String {
+    let mut out = String::new();
+    for word in name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.push_str(chars.as_str());
+        }
+    }
+
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'T');
+    }
+
+    out
+}
+
+/// Convert a field name into a Rust field name, for example `Last Login (UTC)` into
+/// `last_login_utc`.
+pub fn to_field_name(name: &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && prev_lower {
+                out.push('_');
+            }
+            prev_lower = c.is_ascii_lowercase();
+            out.push(c.to_ascii_lowercase());
+        } else {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = false;
+        }
+    }
+    let mut out = out.trim_end_matches('_').to_string();
+
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert_str(0, "field_");
+    }
+
+    if is_keyword(&out) {
+        out.push('_');
+    }
+
+    out
+}
+
+fn is_keyword(s: &str) -> bool {
+    matches!(
+        s,
+        "as" | "async"
+            | "await"
+            | "break"
+            | "const"
+            | "continue"
+            | "crate"
+            | "dyn"
+            | "else"
+            | "enum"
+            | "extern"
+            | "false"
+            | "fn"
+            | "for"
+            | "if"
+            | "impl"
+            | "in"
+            | "let"
+            | "loop"
+            | "match"
+            | "mod"
+            | "move"
+            | "mut"
+            | "pub"
+            | "ref"
+            | "return"
+            | "self"
+            | "static"
+            | "struct"
+            | "super"
+            | "trait"
+            | "true"
+            | "type"
+            | "unsafe"
+            | "use"
+            | "where"
+            | "while"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_field_name() {
+        assert_eq!(to_field_name("Last Login (UTC)"), "last_login_utc");
+        assert_eq!(to_field_name("userId"), "user_id");
+        assert_eq!(to_field_name("Type"), "type_");
+        assert_eq!(to_field_name("2FA enabled"), "field_2fa_enabled");
+        assert_eq!(to_field_name("???"), "field_");
+    }
+
+    #[test]
+    fn test_to_type_name() {
+        assert_eq!(to_type_name("Auth Users"), "AuthUsers");
+        assert_eq!(to_type_name("page views"), "PageViews");
+        assert_eq!(to_type_name("2021 Budget"), "T2021Budget");
+    }
+
+    #[test]
+    fn test_generate_table() {
+        let table: TableSchema = serde_json::from_value(serde_json::json!({
+            "id": "tbl1",
+            "name": "Auth Users",
+            "primaryFieldId": "fld1",
+            "fields": [
+                {"id": "fld1", "name": "User ID", "type": "singleLineText"},
+                {"id": "fld2", "name": "Logins", "type": "number", "options": {"precision": 0}},
+                {"id": "fld3", "name": "Summary", "type": "formula"},
+                {"id": "fld4", "name": "Budget", "type": "currency", "options": {"precision": 2}},
+                {"id": "fld5", "name": "Session Length", "type": "duration"}
+            ]
+        }))
+        .unwrap();
+
+        let out = generate_table(
+            &table,
+            &CodegenOptions {
+                rename_by_field_id: false,
+            },
+        );
+        assert!(out.contains("pub struct AuthUsers {"));
+        assert!(out.contains(
+            "#[serde(default, skip_serializing_if = \"String::is_empty\", rename = \"User ID\")]\n    pub user_id: String,"
+        ));
+        assert!(out.contains("pub logins: i64,"));
+        assert!(out.contains("#[serde(default, skip_serializing, rename = \"Summary\")]"));
+        assert!(out.contains("pub budget: rust_decimal::Decimal,"));
+        assert!(out.contains("pub session_length: Option<chrono::Duration>,"));
+    }
+}
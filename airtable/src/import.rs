@@ -0,0 +1,338 @@
+//! Loading records into a table from CSV, for example to seed a new base from a dump of
+//! historical data.
+use std::{collections::HashMap, io::Read};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde_json::{json, Value};
+
+use crate::{
+    export::{csv_cell, RECORD_ID_COLUMN},
+    schema::{FieldSchema, TableSchema},
+    Airtable,
+};
+
+/// The rows read ahead of the writes to the table.
+const IMPORT_BUFFER: usize = 50;
+
+/// The outcome of a CSV import.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    /// Rows that were left out because a value could not be coerced to the type of its
+    /// field, or the key was empty.
+    pub skipped: usize,
+}
+
+impl Airtable {
+    /// Read records from CSV into a table, creating the rows whose `key_column` matches no
+    /// record in the table and updating the ones that do.
+    ///
+    /// The header of the CSV names the fields. Each value is coerced to the type of its
+    /// field in the table schema: numbers, checkboxes, dates, and lists of selects, links,
+    /// collaborators and attachments are parsed from the text the way `export_csv` writes
+    /// them. Empty cells leave the field alone. Rows with a value that can't be coerced are
+    /// logged and skipped, so one bad row does not stop the import. The record id column of
+    /// an export is ignored, ids don't carry over between bases.
+    ///
+    /// Rows are written in batches as they are read, so a CSV that can't be read to the
+    /// end leaves the rows before the error in the table.
+    pub async fn import_csv<R: Read>(&self, table: &str, reader: R, key_column: &str) -> Result<ImportSummary> {
+        let schema = self.get_table_schema(table).await?;
+        let key = schema
+            .field(key_column)
+            .ok_or_else(|| anyhow!("table `{}` has no field `{}`", table, key_column))?
+            .clone();
+
+        let mut csv = csv::Reader::from_reader(reader);
+        let columns = csv_columns(&schema, csv.headers()?)?;
+        if !columns.iter().flatten().any(|f| f.name == key.name) {
+            bail!("the csv has no `{}` column to match records on", key.name);
+        }
+
+        // The id of every record in the table, by the text of its key.
+        let key_field = self.options().field_key(&key);
+        let existing: HashMap<String, String> = self
+            .list_records::<serde_json::Map<String, Value>>(table, "", vec![&key.name])
+            .await?
+            .into_iter()
+            .filter_map(|mut r| {
+                let value = csv_cell(&key, r.fields.remove(key_field)?);
+                Some((value, r.id))
+            })
+            .collect();
+
+        // The rows are written as they are read, the writer holds the reading back when
+        // Airtable can't keep up.
+        let (mut writer, written) = self.writer(table, IMPORT_BUFFER);
+        let read = async move {
+            let mut summary = ImportSummary::default();
+            for (i, row) in csv.records().enumerate() {
+                let row = row?;
+                let key_value = columns
+                    .iter()
+                    .zip(row.iter())
+                    .find(|(f, _)| f.map(|f| f.name == key.name).unwrap_or_default())
+                    .map(|(_, v)| v.trim())
+                    .unwrap_or_default();
+                if key_value.is_empty() {
+                    log::warn!(
+                        "[airtable-api] Skipping row {} of the csv, `{}` is empty",
+                        i + 1,
+                        key.name
+                    );
+                    summary.skipped += 1;
+                    continue;
+                }
+
+                let fields: serde_json::Map<String, Value> = match csv_fields(&columns, &row) {
+                    Ok(fields) => fields,
+                    Err(e) => {
+                        log::warn!("[airtable-api] Skipping row {} of the csv: {}", i + 1, e);
+                        summary.skipped += 1;
+                        continue;
+                    }
+                };
+
+                match existing.get(key_value) {
+                    Some(id) => {
+                        writer.update(id, fields).await?;
+                        summary.updated += 1;
+                    }
+                    None => {
+                        writer.create(fields).await?;
+                        summary.created += 1;
+                    }
+                }
+            }
+
+            Ok::<_, anyhow::Error>(summary)
+        };
+        let (summary, _) = futures::try_join!(read, written)?;
+
+        Ok(summary)
+    }
+}
+
+/// Returns the field of each column of the CSV. Columns without a field to write to, like
+/// the record id or a computed field, are `None`.
+fn csv_columns<'a>(schema: &'a TableSchema, headers: &csv::StringRecord) -> Result<Vec<Option<&'a FieldSchema>>> {
+    headers
+        .iter()
+        .map(|h| {
+            if h == RECORD_ID_COLUMN {
+                return Ok(None);
+            }
+
+            match schema.field(h) {
+                Some(f) if f.is_computed() => {
+                    log::warn!(
+                        "[airtable-api] Ignoring column `{}`, its field is computed by Airtable",
+                        h
+                    );
+                    Ok(None)
+                }
+                Some(f) => Ok(Some(f)),
+                None => bail!("table `{}` has no field `{}`", schema.name, h),
+            }
+        })
+        .collect()
+}
+
+/// Returns the fields of a row of the CSV, coerced to the types of their fields.
+fn csv_fields(columns: &[Option<&FieldSchema>], row: &csv::StringRecord) -> Result<serde_json::Map<String, Value>> {
+    let mut fields = serde_json::Map::new();
+    for (field, text) in columns.iter().zip(row.iter()) {
+        let field = match field {
+            Some(field) => field,
+            None => continue,
+        };
+
+        if let Some(value) = coerce(field, text).map_err(|e| anyhow!("`{}`: {}", field.name, e))? {
+            fields.insert(field.name.to_string(), value);
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Coerce the text of a cell to the value Airtable expects for the type of the field.
+/// Returns `None` for an empty cell.
+fn coerce(field: &FieldSchema, text: &str) -> Result<Option<Value>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    let list = || {
+        text.split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    Ok(Some(match field.type_.as_str() {
+        "number" | "currency" | "rating" | "duration" => json!(parse_number(text)?),
+        // Percents are stored as a fraction, but dumps usually write them out of 100.
+        "percent" => match text.strip_suffix('%') {
+            Some(percent) => json!(parse_number(percent)? / 100.0),
+            None => json!(parse_number(text)?),
+        },
+        "checkbox" => match text.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" | "x" | "checked" => json!(true),
+            "false" | "no" | "n" | "0" | "unchecked" => json!(false),
+            _ => bail!("`{}` is not a checkbox value", text),
+        },
+        "date" => json!(parse_date(text)?.format("%Y-%m-%d").to_string()),
+        "dateTime" => json!(parse_date_time(text)?.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        "multipleSelects" | "multipleRecordLinks" => json!(list()),
+        "singleCollaborator" => json!({ "email": text }),
+        "multipleCollaborators" => Value::Array(list().into_iter().map(|e| json!({ "email": e })).collect()),
+        "multipleAttachments" => Value::Array(list().into_iter().map(|u| json!({ "url": u })).collect()),
+        _ => json!(text),
+    }))
+}
+
+/// Parse a number, allowing the currency signs and thousands separators of a formatted
+/// number.
+fn parse_number(text: &str) -> Result<f64> {
+    let cleaned: String = text
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, '$' | '€' | '£' | ',' | ' '))
+        .collect();
+
+    cleaned.parse().map_err(|_| anyhow!("`{}` is not a number", text))
+}
+
+/// Parse a date written as `2024-01-02` or `1/2/2024`.
+fn parse_date(text: &str) -> Result<NaiveDate> {
+    ["%Y-%m-%d", "%m/%d/%Y"]
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(text, f).ok())
+        .ok_or_else(|| anyhow!("`{}` is not a date", text))
+}
+
+/// Parse a time in RFC 3339, or without an offset in UTC.
+fn parse_date_time(text: &str) -> Result<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(text) {
+        return Ok(t.with_timezone(&Utc));
+    }
+
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%m/%d/%Y %H:%M:%S"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(text, f).ok())
+        .map(|t| Utc.from_utc_datetime(&t))
+        .ok_or_else(|| anyhow!("`{}` is not a date and time", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::{Method, StatusCode};
+
+    use super::*;
+    use crate::MockTransport;
+
+    fn field(type_: &str) -> FieldSchema {
+        FieldSchema {
+            name: "Field".to_string(),
+            type_: type_.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_coerce() {
+        assert_eq!(coerce(&field("number"), " $1,234.5 ").unwrap(), Some(json!(1234.5)));
+        assert_eq!(coerce(&field("percent"), "50%").unwrap(), Some(json!(0.5)));
+        assert_eq!(coerce(&field("checkbox"), "Yes").unwrap(), Some(json!(true)));
+        assert_eq!(coerce(&field("date"), "1/2/2024").unwrap(), Some(json!("2024-01-02")));
+        assert_eq!(
+            coerce(&field("dateTime"), "2024-01-02 03:04:05").unwrap(),
+            Some(json!("2024-01-02T03:04:05.000Z"))
+        );
+        assert_eq!(
+            coerce(&field("multipleSelects"), "admin, github,").unwrap(),
+            Some(json!(["admin", "github"]))
+        );
+        assert_eq!(
+            coerce(&field("singleCollaborator"), "jess@example.com").unwrap(),
+            Some(json!({ "email": "jess@example.com" }))
+        );
+        assert_eq!(coerce(&field("singleLineText"), "  ").unwrap(), None);
+
+        assert!(coerce(&field("number"), "twelve").is_err());
+        assert!(coerce(&field("checkbox"), "maybe").is_err());
+    }
+
+    #[test]
+    fn test_csv_columns() {
+        let schema: TableSchema = serde_json::from_value(json!({
+            "name": "Auth Users",
+            "fields": [
+                { "id": "fld1", "name": "Email", "type": "email" },
+                { "id": "fld2", "name": "Logins", "type": "count" },
+            ],
+        }))
+        .unwrap();
+
+        let columns = csv_columns(&schema, &csv::StringRecord::from(vec!["Record ID", "Email", "Logins"])).unwrap();
+        let names: Vec<Option<&str>> = columns.iter().map(|f| f.map(|f| f.name.as_str())).collect();
+        assert_eq!(names, vec![None, Some("Email"), None]);
+
+        assert!(csv_columns(&schema, &csv::StringRecord::from(vec!["Name"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_csv() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .respond_json(
+                    Method::GET,
+                    "/v0/meta/bases/app1/tables",
+                    StatusCode::OK,
+                    &json!({"tables": [{
+                        "id": "tbl1",
+                        "name": "Users",
+                        "fields": [
+                            { "id": "fld1", "name": "Email", "type": "email" },
+                            { "id": "fld2", "name": "Logins", "type": "number" },
+                        ],
+                    }]}),
+                )
+                // Keyed by field id, as the client asks for by default.
+                .respond_json(
+                    Method::GET,
+                    "/v0/app1/Users",
+                    StatusCode::OK,
+                    &json!({"records": [{"id": "rec1", "fields": {"fld1": "jess@example.com"}}]}),
+                )
+                .respond_json(Method::POST, "/v0/app1/Users", StatusCode::OK, &json!({"records": []}))
+                .respond_json(Method::PATCH, "/v0/app1/Users", StatusCode::OK, &json!({"records": []})),
+        );
+        let airtable = Airtable::new("key", "app1", "").with_transport(transport.clone());
+
+        let csv = "Record ID,Email,Logins\nrecOld,jess@example.com,3\nrecOld2,sam@example.com,1\n";
+        let summary = airtable.import_csv("Users", csv.as_bytes(), "Email").await.unwrap();
+
+        assert_eq!(
+            summary,
+            ImportSummary {
+                created: 1,
+                updated: 1,
+                skipped: 0,
+            }
+        );
+        let writes: Vec<Method> = transport
+            .requests()
+            .into_iter()
+            .map(|(method, _)| method)
+            .filter(|method| *method != Method::GET)
+            .collect();
+        assert_eq!(writes.len(), 2);
+        assert!(writes.contains(&Method::POST) && writes.contains(&Method::PATCH));
+    }
+}
@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use reqwest::{Request, Response};
+
+/// Hooks that are called around every request the Airtable client makes.
+///
+/// Interceptors can be used to attach tracing spans, record latency metrics, or add
+/// headers such as request IDs to every API call. Both hooks have default no-op
+/// implementations so an interceptor only needs to implement the ones it cares about.
+///
+/// Interceptors are registered with [`Airtable::with_interceptor`](crate::Airtable::with_interceptor)
+/// and are called in the order they were registered.
+pub trait Interceptor: Send + Sync {
+    /// Called before a request is sent. The request may be modified, for example to add
+    /// headers.
+    fn on_request(&self, _request: &mut Request) {}
+
+    /// Called after a request has completed, or failed to complete, along with the time it
+    /// took to get a response.
+    fn on_response(&self, _response: Result<&Response, &reqwest_middleware::Error>, _elapsed: Duration) {}
+}
@@ -34,29 +34,205 @@
  *     pub x: bool,
  * }
  * ```
+ *
+ * A blocking client is available in the [`blocking`] module when the `blocking` feature
+ * is enabled.
  */
 #![allow(clippy::field_reassign_with_default)]
-use std::{env, fmt, fmt::Debug, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt,
+    fmt::Debug,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use anyhow::{bail, Result};
-use chrono::{offset::Utc, DateTime};
-use reqwest::{header, Method, Request, StatusCode, Url};
+use anyhow::{anyhow, bail, Result};
+use chrono::{offset::Utc, DateTime, LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use reqwest::{header, Method, Request, Response, StatusCode, Url};
 use schemars::JsonSchema;
 use serde::{
     de::{DeserializeOwned, MapAccess, SeqAccess, Visitor},
     Deserialize, Deserializer, Serialize,
 };
+use tracing::Instrument;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod cache;
+mod cell;
+pub mod codegen;
+mod export;
+mod import;
+mod interceptor;
+mod links;
+mod prefetch;
+mod queue;
+mod rate_limit;
+mod resume;
+pub mod schema;
+pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod trace;
+mod transport;
+mod webhooks;
+mod writer;
+
+pub use cache::ResponseCache;
+pub use cell::{AirtableCellValue, CellValues};
+pub use import::ImportSummary;
+pub use interceptor::Interceptor;
+pub use links::LinkResolver;
+pub use prefetch::{created_time_partitions, Partition};
+pub use queue::{QueueSummary, WriteQueue};
+pub use rate_limit::{RateLimiter, REQUESTS_PER_SECOND};
+pub use resume::{ListKey, MemoryOffsetStore, OffsetStore};
+pub use trace::{new_request_id, ApiError, RequestId, REQUEST_ID_HEADER};
+pub use transport::{HttpTransport, MockTransport};
+pub use webhooks::{
+    parse_webhook_mac, verify_webhook_mac, ActionMetadata, ChangedRecord, CreatedRecord, CreatedWebhook, RecordCells,
+    TableChanges, WebhookBase, WebhookNotification, WebhookPayload, WebhookPayloads, WebhookRef, WEBHOOK_MAC_HEADER,
+};
+pub use writer::{AirtableWriter, Operation, WriteSummary, MAX_RECORDS_PER_REQUEST};
 
 /// Endpoint for the Airtable API.
 const ENDPOINT: &str = "https://api.airtable.com/v0/";
 
+/// Where people open bases in their browser.
+const WEB_URL: &str = "https://airtable.com/";
+
+/// The number of record ids to look up in a single request in `get_records`.
+const GET_RECORDS_BATCH_SIZE: usize = 50;
+
 /// Entrypoint for interacting with the Airtable API.
 pub struct Airtable {
     key: String,
     base_id: String,
+    /// The URL of the API, `ENDPOINT` unless it is pointed at a mock server.
+    endpoint: String,
     enterprise_account_id: String,
+    options: RequestOptions,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    cache: Option<Arc<ResponseCache>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// The read-only fields of each table, by table name and id.
+    read_only_fields: Mutex<HashMap<String, HashSet<String>>>,
 
     pub(crate) client: reqwest_middleware::ClientWithMiddleware,
+    /// Sends the requests built with `client`.
+    transport: Arc<dyn HttpTransport>,
+}
+
+/// The format that cell values are returned in.
+/// FROM: https://airtable.com/developers/web/api/list-records#query-cellformat
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CellFormat {
+    /// Cells are returned as JSON. The shape depends on the field type.
+    #[default]
+    Json,
+    /// Cells are returned as the strings shown in the Airtable UI. When using this format
+    /// both `time_zone` and `user_locale` are required.
+    String,
+}
+
+impl fmt::Display for CellFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CellFormat::Json => write!(f, "json"),
+            CellFormat::String => write!(f, "string"),
+        }
+    }
+}
+
+/// Options that control how records are returned by the Airtable API. These are sent
+/// along with every request that reads records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Key the fields of returned records by field id instead of field name.
+    pub return_fields_by_field_id: bool,
+    /// The format that cell values are returned in.
+    pub cell_format: CellFormat,
+    /// The time zone used to format dates when `cell_format` is `CellFormat::String`,
+    /// for example `America/Los_Angeles`.
+    pub time_zone: Option<String>,
+    /// The locale used to format dates when `cell_format` is `CellFormat::String`,
+    /// for example `en-us`.
+    pub user_locale: Option<String>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        RequestOptions {
+            return_fields_by_field_id: true,
+            cell_format: CellFormat::Json,
+            time_zone: None,
+            user_locale: None,
+        }
+    }
+}
+
+impl RequestOptions {
+    /// Returns the query parameters for these options.
+    pub(crate) fn query(&self) -> Result<Vec<(&'static str, String)>> {
+        if self.cell_format == CellFormat::String && (self.time_zone.is_none() || self.user_locale.is_none()) {
+            bail!("a time zone and user locale are required when requesting the string cell format");
+        }
+
+        let mut params = vec![
+            ("returnFieldsByFieldId", self.return_fields_by_field_id.to_string()),
+            ("cellFormat", self.cell_format.to_string()),
+        ];
+
+        if let Some(time_zone) = &self.time_zone {
+            params.push(("timeZone", time_zone.to_string()));
+        }
+
+        if let Some(user_locale) = &self.user_locale {
+            params.push(("userLocale", user_locale.to_string()));
+        }
+
+        Ok(params)
+    }
+
+    /// Returns the time zone of `time_zone`, UTC when it is not set.
+    pub fn tz(&self) -> Result<Tz> {
+        match &self.time_zone {
+            Some(time_zone) => time_zone
+                .parse()
+                .map_err(|e| anyhow!("unknown time zone `{}`: {}", time_zone, e)),
+            None => Ok(Tz::UTC),
+        }
+    }
+
+    /// Returns the date a timestamp falls on in `time_zone`, for a date and time field
+    /// whose day matters more than its instant.
+    pub fn local_date(&self, at: DateTime<Utc>) -> Result<NaiveDate> {
+        Ok(at.with_timezone(&self.tz()?).date_naive())
+    }
+
+    /// Returns the timestamp of a wall clock time in `time_zone`, to write to a date and
+    /// time field. Times skipped by a daylight saving change are an error, ambiguous ones
+    /// take the earlier instant.
+    pub fn to_utc(&self, local: NaiveDateTime) -> Result<DateTime<Utc>> {
+        let tz = self.tz()?;
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => Ok(at.with_timezone(&Utc)),
+            LocalResult::None => bail!("`{}` does not exist in `{}`", local, tz),
+        }
+    }
+
+    /// Returns the key a field is under in the records read with these options, its id or
+    /// its name depending on `return_fields_by_field_id`.
+    pub fn field_key<'a>(&self, field: &'a schema::FieldSchema) -> &'a str {
+        if self.return_fields_by_field_id {
+            &field.id
+        } else {
+            &field.name
+        }
+    }
 }
 
 /// Get the API key from the AIRTABLE_API_KEY env variable.
@@ -64,6 +240,19 @@ pub fn api_key_from_env() -> String {
     env::var("AIRTABLE_API_KEY").unwrap_or_default()
 }
 
+/// Returns the link that opens a record in Airtable, for pointing people at it from an
+/// alert or a log. Use the id of the table, the link only works with a name if the
+/// table was never renamed.
+pub fn record_url(base_id: &str, table: &str, record_id: &str) -> String {
+    let mut url = Url::parse(WEB_URL).expect("the airtable url is valid");
+    url.path_segments_mut()
+        .expect("the airtable url has a path")
+        .pop_if_empty()
+        .extend([base_id, table, record_id]);
+
+    url.to_string()
+}
+
 impl Airtable {
     /// Create a new Airtable client struct. It takes a type that can convert into
     /// an &str (`String` or `Vec<u8>` for example). As long as the function is
@@ -90,8 +279,15 @@ impl Airtable {
                 Self {
                     key: key.to_string(),
                     base_id: base_id.to_string(),
+                    endpoint: ENDPOINT.to_string(),
                     enterprise_account_id: enterprise_account_id.to_string(),
+                    options: Default::default(),
+                    interceptors: Default::default(),
+                    cache: None,
+                    rate_limiter: None,
+                    read_only_fields: Default::default(),
 
+                    transport: Arc::new(client.clone()),
                     client,
                 }
             }
@@ -115,6 +311,139 @@ impl Airtable {
         &self.key
     }
 
+    /// Returns the link that opens a record of a table in this base, see `record_url`.
+    pub fn record_url(&self, table: &str, record_id: &str) -> String {
+        record_url(&self.base_id, table, record_id)
+    }
+
+    /// Set the options used when reading records.
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Get the options used when reading records.
+    pub fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    /// Register an interceptor that is called around every request the client makes.
+    pub fn with_interceptor<I>(mut self, interceptor: I) -> Self
+    where
+        I: Interceptor + 'static,
+    {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Cache the responses to the reads the client makes. The cache can be shared between
+    /// clients, so every client created for a sync run reads from the same one.
+    pub fn with_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Space out the requests the client makes with the limiter. The limiter can be shared
+    /// between clients, so every client of a base stays under its rate limit together.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Send the requests of the client to another URL than the Airtable API, for example a
+    /// mock server in a test. The URL should end in the version of the API, like `/v0/`.
+    pub fn with_endpoint<E: ToString>(mut self, endpoint: E) -> Self {
+        self.endpoint = endpoint.to_string();
+        if !self.endpoint.ends_with('/') {
+            self.endpoint.push('/');
+        }
+        self
+    }
+
+    /// Send the requests of the client through the transport instead of `reqwest`, for
+    /// example to answer them with canned responses in a test.
+    pub fn with_transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Execute a request, calling the registered interceptors before and after. Reads are
+    /// served from the response cache when one is set and it has them.
+    pub(crate) async fn execute(&self, mut request: Request) -> reqwest_middleware::Result<Response> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.execute_uncached(request).await,
+        };
+
+        if request.method() != Method::GET {
+            cache.invalidate(&request);
+            return self.execute_uncached(request).await;
+        }
+
+        let key = cache::cache_key(&request);
+        match cache.lookup(&key, Instant::now()) {
+            cache::Lookup::Fresh(cached) => {
+                log::debug!("[airtable-api] Serving {} from the cache", key);
+                return Ok(cached.into());
+            }
+            cache::Lookup::Revalidate(etag) => {
+                request.headers_mut().insert(header::IF_NONE_MATCH, etag);
+            }
+            cache::Lookup::Miss => (),
+        }
+
+        let resp = self.execute_uncached(request).await?;
+        match resp.status() {
+            StatusCode::NOT_MODIFIED => match cache.revalidated(&key, Instant::now()) {
+                Some(cached) => {
+                    log::debug!("[airtable-api] {} is not modified, serving it from the cache", key);
+                    Ok(cached.into())
+                }
+                None => Ok(resp),
+            },
+            StatusCode::OK => {
+                let status = resp.status();
+                let headers = resp.headers().clone();
+                let body = resp.bytes().await?.to_vec();
+                cache.store(&key, status, headers.clone(), body.clone(), Instant::now());
+
+                Ok(cache::CachedResponse::new(status, headers, body).into())
+            }
+            _ => Ok(resp),
+        }
+    }
+
+    async fn execute_uncached(&self, mut request: Request) -> reqwest_middleware::Result<Response> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        for interceptor in &self.interceptors {
+            interceptor.on_request(&mut request);
+        }
+
+        let request_id = trace::tag_request(&mut request);
+        let span = tracing::info_span!(
+            "airtable_request",
+            request_id = %request_id,
+            method = %request.method(),
+            path = request.url().path(),
+        );
+
+        let start = Instant::now();
+        let resp = self.transport.execute(request).instrument(span).await.map(|mut resp| {
+            resp.extensions_mut().insert(RequestId(request_id));
+            resp
+        });
+        let elapsed = start.elapsed();
+
+        for interceptor in &self.interceptors {
+            interceptor.on_response(resp.as_ref(), elapsed);
+        }
+
+        resp
+    }
+
     pub(crate) fn request<B>(
         &self,
         method: Method,
@@ -125,9 +454,24 @@ impl Airtable {
     where
         B: Serialize,
     {
-        let base = Url::parse(ENDPOINT)?;
+        let base = Url::parse(&self.endpoint)?;
         let url = base.join(&(self.base_id.to_string() + "/" + &path))?;
 
+        self.request_url(method, url, body, query)
+    }
+
+    /// Build a request for a URL that is not scoped to the base, for example the
+    /// Metadata API.
+    pub(crate) fn request_url<B>(
+        &self,
+        method: Method,
+        url: Url,
+        body: B,
+        query: Option<Vec<(&str, String)>>,
+    ) -> Result<Request>
+    where
+        B: Serialize,
+    {
         let bt = format!("Bearer {}", self.key);
         let bearer = header::HeaderValue::from_str(&bt)?;
 
@@ -158,84 +502,77 @@ impl Airtable {
     }
 
     /// List records in a table for a particular view.
+    ///
+    /// Only the records that are visible in the view are returned, in the order the view
+    /// sorts them, so a curated view (for example "Active Users") can be used to filter
+    /// records on the Airtable side. Pass an empty view to list every record in the table.
     pub async fn list_records<T: DeserializeOwned>(
         &self,
         table: &str,
         view: &str,
         fields: Vec<&str>,
     ) -> Result<Vec<Record<T>>> {
-        let mut params = vec![("pageSize", "100".to_string()), ("view", view.to_string()), ("returnFieldsByFieldId", "true".to_string())];
-        for field in fields {
-            params.push(("fields[]", field.to_string()));
-        }
-
-        // Build the request.
-        let mut request = self.request(Method::GET, table.to_string(), (), Some(params))?;
-
-        let mut resp = self.client.execute(request).await?;
-        match resp.status() {
-            StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
-        };
-
-        // Try to deserialize the response.
-        let mut r: APICall<T> = resp.json().await?;
-
-        let mut records = r.records;
-
-        let mut offset = r.offset;
-
-        // Paginate if we should.
-        // TODO: make this more DRY
-        while !offset.is_empty() {
-            request = self.request(
-                Method::GET,
-                table.to_string(),
-                (),
-                Some(vec![
-                    ("pageSize", "100".to_string()),
-                    ("view", view.to_string()),
-                    ("offset", offset),
-                    ("returnFieldsByFieldId", "true".to_string())
-                ]),
-            )?;
-
-            resp = self.client.execute(request).await?;
-            match resp.status() {
-                StatusCode::OK => (),
-                s => {
-                    bail!("status code: {}, body: {}", s, resp.text().await?);
-                }
-            };
-
-            // Try to deserialize the response.
-            r = resp.json().await?;
-
-            records.append(&mut r.records);
+        let mut pages = self.pages(table, view, fields);
 
-            offset = r.offset;
+        let mut records = Vec::new();
+        while let Some(mut page) = pages.next().await? {
+            records.append(&mut page);
         }
 
         Ok(records)
     }
 
+    /// Iterate over the pages of records in a table for a particular view. Pass an empty
+    /// view to iterate over every record in the table.
     pub fn pages<T: DeserializeOwned>(&self, table: &str, view: &str, fields: Vec<&str>) -> Pages<T> {
         Pages::new(self, table, view, &fields)
     }
 
+    /// Get the records with the given ids from a table.
+    ///
+    /// The records are fetched with a `filterByFormula` of `RECORD_ID()` comparisons so that
+    /// many records can be looked up in a few requests, instead of one request per record or
+    /// paging through the whole table. The ids are sent in batches to keep the request URL
+    /// under the length Airtable accepts. Records are returned in the order of `record_ids`,
+    /// ids that do not exist in the table are skipped.
+    pub async fn get_records<T: DeserializeOwned>(&self, table: &str, record_ids: &[&str]) -> Result<Vec<Record<T>>> {
+        let mut found: HashMap<String, Record<T>> = HashMap::new();
+
+        for chunk in record_ids.chunks(GET_RECORDS_BATCH_SIZE) {
+            let formula = format!(
+                "OR({})",
+                chunk
+                    .iter()
+                    .map(|id| format!("RECORD_ID()='{}'", id.replace('\'', "\\'")))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+
+            let mut pages = self.pages(table, "", vec![]).filter_by_formula(&formula);
+            while let Some(page) = pages.next().await? {
+                for record in page {
+                    found.insert(record.id.to_string(), record);
+                }
+            }
+        }
+
+        Ok(record_ids.iter().filter_map(|id| found.remove(*id)).collect())
+    }
+
     /// Get record from a table.
     pub async fn get_record<T: DeserializeOwned>(&self, table: &str, record_id: &str) -> Result<Record<T>> {
         // Build the request.
-        let request = self.request(Method::GET, format!("{table}/{record_id}"), (), Some(vec![("returnFieldsByFieldId", "true".to_string())]))?;
+        let request = self.request(
+            Method::GET,
+            format!("{table}/{record_id}"),
+            (),
+            Some(self.options.query()?),
+        )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         // Try to deserialize the response.
@@ -254,12 +591,10 @@ impl Airtable {
             Some(vec![("records[]", record_id.to_string())]),
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         Ok(())
@@ -283,12 +618,10 @@ impl Airtable {
             ),
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         Ok(())
@@ -296,6 +629,9 @@ impl Airtable {
 
     /// Bulk create records in a table.
     ///
+    /// Fields that are computed by Airtable are left out of the records, see
+    /// `update_records`.
+    ///
     /// The Airtable API limits record creation requests to 10 records per request. Because of
     /// this, calls to this function containing more than 10 records will send multiple requests
     /// (one request for each chunk of 10 records) to the Airtable API.
@@ -304,6 +640,9 @@ impl Airtable {
         table: &str,
         records: Vec<Record<T>>,
     ) -> Result<Vec<Record<T>>> {
+        let read_only = self.read_only_fields(table).await;
+        let records = schema::strip_read_only_fields(table, records, &read_only)?;
+
         if records.len() <= 10 {
             self.create_records_inner(table, records).await
         } else {
@@ -322,10 +661,10 @@ impl Airtable {
     /// Bulk create records in a table.
     ///
     /// The provided `records` vector MUST contain at most 10 items, or this function will panic.
-    async fn create_records_inner<T: Serialize + DeserializeOwned>(
+    async fn create_records_inner<T: DeserializeOwned>(
         &self,
         table: &str,
-        records: Vec<Record<T>>,
+        records: Vec<Record<serde_json::Value>>,
     ) -> Result<Vec<Record<T>>> {
         assert!(records.len() <= 10);
 
@@ -341,12 +680,10 @@ impl Airtable {
             None,
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         // Try to deserialize the response.
@@ -357,6 +694,10 @@ impl Airtable {
 
     /// Bulk update records in a table.
     ///
+    /// Fields that are computed by Airtable, like formulas, rollups and created times, can't
+    /// be written and fail the whole request. They are looked up in the schema of the base
+    /// and left out of the records with a warning, so the rest of each record is written.
+    ///
     /// The Airtable API limits record update requests to 10 records per request. Because of
     /// this, calls to this function containing more than 10 records will send multiple requests
     /// (one request for each chunk of 10 records) to the Airtable API.
@@ -365,6 +706,9 @@ impl Airtable {
         table: &str,
         records: Vec<Record<T>>,
     ) -> Result<Vec<Record<T>>> {
+        let read_only = self.read_only_fields(table).await;
+        let records = schema::strip_read_only_fields(table, records, &read_only)?;
+
         if records.len() <= 10 {
             self.update_records_inner(table, records).await
         } else {
@@ -383,10 +727,10 @@ impl Airtable {
     /// Bulk update records in a table.
     ///
     /// The provided `records` vector MUST contain at most 10 items, or this function will panic.
-    async fn update_records_inner<T: Serialize + DeserializeOwned>(
+    async fn update_records_inner<T: DeserializeOwned>(
         &self,
         table: &str,
-        records: Vec<Record<T>>,
+        records: Vec<Record<serde_json::Value>>,
     ) -> Result<Vec<Record<T>>> {
         assert!(records.len() <= 10);
 
@@ -402,12 +746,10 @@ impl Airtable {
             None,
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         // Try to deserialize the response.
@@ -437,12 +779,10 @@ impl Airtable {
             Some(vec![("state", "provisioned".to_string())]),
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         // Try to deserialize the response.
@@ -472,13 +812,11 @@ impl Airtable {
             ]),
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
 
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         let r: EnterpriseUsersResponse = resp.json().await?;
@@ -522,12 +860,10 @@ impl Airtable {
             None,
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         Ok(())
@@ -566,12 +902,10 @@ impl Airtable {
             }),
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         let r: Workspace = resp.json().await?;
@@ -597,12 +931,10 @@ impl Airtable {
             Some(vec![("email", email.to_string())]),
         )?;
 
-        let resp = self.client.execute(request).await?;
+        let resp = self.execute(request).await?;
         match resp.status() {
             StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
-            }
+            _ => return Err(ApiError::from_response(resp).await.into()),
         };
 
         // Try to deserialize the response.
@@ -620,6 +952,7 @@ pub struct Pages<'a, T> {
     table: String,
     view: String,
     fields: Vec<String>,
+    formula: Option<String>,
     offset: Option<String>,
     record_type: PhantomData<T>,
 }
@@ -634,18 +967,43 @@ where
             table: table.to_string(),
             view: view.to_string(),
             fields: fields.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            formula: None,
             offset: Some(String::new()),
             record_type: PhantomData,
         }
     }
 
+    /// Only return the records for which the formula evaluates to a truthy value.
+    /// FROM: https://support.airtable.com/docs/formula-field-reference
+    pub fn filter_by_formula(mut self, formula: &str) -> Self {
+        self.formula = Some(formula.to_string());
+        self
+    }
+
+    /// Start at the page with the offset, one returned by `offset` during an earlier read
+    /// of the same table, view and formula.
+    pub fn starting_at(mut self, offset: &str) -> Self {
+        self.offset = Some(offset.to_string());
+        self
+    }
+
+    /// Returns the offset of the next page, empty before the first page is read, and
+    /// `None` once every page was read.
+    pub fn offset(&self) -> Option<&str> {
+        self.offset.as_deref()
+    }
+
     pub async fn next(&mut self) -> Result<Option<Vec<Record<T>>>> {
         if self.offset.is_none() {
             log::debug!("[airtable-api] Page does not have an offset. Returning.");
             return Ok(None);
         }
 
-        let mut params = vec![("pageSize", "100".to_string()), ("view", self.view.to_string())];
+        let mut params = vec![("pageSize", "100".to_string())];
+        if !self.view.is_empty() {
+            params.push(("view", self.view.to_string()));
+        }
+        params.extend(self.client.options.query()?);
 
         if let Some(offset) = &self.offset {
             if !offset.is_empty() {
@@ -660,12 +1018,16 @@ where
             params.push(("fields[]", field.to_string()));
         }
 
+        if let Some(formula) = &self.formula {
+            params.push(("filterByFormula", formula.to_string()));
+        }
+
         // Build the request.
         let request = self
             .client
             .request(Method::GET, self.table.to_string(), (), Some(params))?;
 
-        let response = self.client.client.execute(request).await?;
+        let response = self.client.execute(request).await?;
 
         match response.status() {
             StatusCode::OK => {
@@ -680,13 +1042,13 @@ where
 
                 Ok(Some(api_response.records))
             }
-            s => {
+            _ => {
                 log::debug!("[airtable-api] Pagination request returned an error. Stopping requests.");
 
                 // Once we hit an error we stop pagination
                 self.offset = None;
 
-                bail!("status code: {}, body: {}", s, response.text().await?);
+                Err(ApiError::from_response(response).await.into())
             }
         }
     }
@@ -720,7 +1082,7 @@ pub struct Record<T> {
 }
 
 /// An airtable user.
-#[derive(Debug, Default, Clone, Serialize, JsonSchema, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, JsonSchema, Deserialize)]
 pub struct User {
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub id: String,
@@ -885,7 +1247,7 @@ pub struct AttachmentShort {
     pub url: String,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Attachment {
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub id: String,
@@ -901,7 +1263,7 @@ pub struct Attachment {
     pub thumbnails: Thumbnails,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Thumbnails {
     #[serde(default)]
     pub small: Full,
@@ -911,7 +1273,7 @@ pub struct Thumbnails {
     pub full: Full,
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Full {
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub url: String,
@@ -1474,6 +1836,91 @@ pub mod barcode_format_as_string {
     }
 }
 
+/// A duration field, as the number of seconds Airtable stores, to and from a
+/// `chrono::Duration`. Fractions of a second are kept to the millisecond.
+pub mod duration_format {
+    use chrono::Duration;
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if duration.num_milliseconds() % 1000 == 0 {
+            serializer.serialize_i64(duration.num_seconds())
+        } else {
+            serializer.serialize_f64(duration.num_milliseconds() as f64 / 1000.0)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let seconds = Option::<f64>::deserialize(deserializer)?.unwrap_or_default();
+
+        Ok(Duration::milliseconds((seconds * 1000.0).round() as i64))
+    }
+
+    /// The same for an `Option<chrono::Duration>`, `None` for an empty cell.
+    pub mod option {
+        use chrono::Duration;
+        use serde::{self, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match duration {
+                Some(d) => super::serialize(d, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Ok(Option::<f64>::deserialize(deserializer)?
+                .map(|seconds| Duration::milliseconds((seconds * 1000.0).round() as i64)))
+        }
+    }
+}
+
+/// A currency field, as the number Airtable stores, to and from a `rust_decimal::Decimal`,
+/// so amounts are not rounded like floats on the way through.
+pub mod currency_format {
+    use std::str::FromStr;
+
+    use rust_decimal::{prelude::ToPrimitive, Decimal};
+    use serde::{self, de::Error, ser, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(amount: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match amount.to_f64() {
+            Some(n) => serializer.serialize_f64(n),
+            None => Err(ser::Error::custom(format!("`{}` does not fit in a number", amount))),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Parse the shortest digits of the number rather than its binary value, so 0.1 comes
+        // out as 0.1. serde_json still reads the number into a float, without
+        // `arbitrary_precision`, so amounts past the ~15 digits of an f64 are rounded.
+        match Option::<serde_json::Number>::deserialize(deserializer)? {
+            Some(n) => Decimal::from_str(&n.to_string())
+                .or_else(|_| Decimal::from_scientific(&n.to_string()))
+                .map_err(D::Error::custom),
+            None => Ok(Decimal::ZERO),
+        }
+    }
+}
+
 pub mod deserialize_null_string {
     use serde::{self, Deserialize, Deserializer};
 
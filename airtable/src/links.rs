@@ -0,0 +1,146 @@
+//! Resolving the record ids in linked record fields to the records they link to.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Airtable, Record};
+
+/// Resolves linked record ids to the records of the table they link to.
+///
+/// The records it fetches are kept, so resolving the links of many records that point at
+/// the same few records, like every login of a user pointing at the user, only fetches
+/// each of them once. Ids are looked up in batches with `Airtable::get_records`.
+pub struct LinkResolver<T> {
+    table: String,
+    /// The records fetched so far, by id. Ids that are not in the table are `None`, so they
+    /// are not looked up again.
+    records: HashMap<String, Option<Record<T>>>,
+}
+
+impl<T> LinkResolver<T>
+where
+    T: DeserializeOwned + Clone,
+{
+    /// Create a resolver for links to the given table.
+    pub fn new(table: &str) -> Self {
+        LinkResolver {
+            table: table.to_string(),
+            records: Default::default(),
+        }
+    }
+
+    /// Returns the records with the given ids, in the same order. Ids that are not in the
+    /// table are skipped.
+    pub async fn resolve(&mut self, airtable: &Airtable, ids: &[String]) -> Result<Vec<Record<T>>> {
+        self.fetch(airtable, ids.iter().map(|id| id.as_str())).await?;
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| self.records.get(id).cloned().flatten())
+            .collect())
+    }
+
+    /// Returns the records linked to from `field` of each record, in the order of `records`.
+    ///
+    /// `field` is the key of the link field in the serialized fields of the records, so
+    /// its Airtable name, or its id when reading fields by id. The ids of all the records
+    /// are fetched together.
+    pub async fn resolve_field<F: Serialize>(
+        &mut self,
+        airtable: &Airtable,
+        records: &[Record<F>],
+        field: &str,
+    ) -> Result<Vec<Vec<Record<T>>>> {
+        let links = records
+            .iter()
+            .map(|r| Ok(link_ids(&serde_json::to_value(&r.fields)?, field)))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.fetch(airtable, links.iter().flatten().map(|id| id.as_str()))
+            .await?;
+
+        Ok(links
+            .iter()
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| self.records.get(id).cloned().flatten())
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Fetch the records with the ids that have not been looked up yet.
+    async fn fetch<'a>(&mut self, airtable: &Airtable, ids: impl Iterator<Item = &'a str>) -> Result<()> {
+        let mut seen = HashSet::new();
+        let missing: Vec<&str> = ids
+            .filter(|id| !self.records.contains_key(*id) && seen.insert(*id))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let mut found: HashMap<String, Record<T>> = airtable
+            .get_records::<T>(&self.table, &missing)
+            .await?
+            .into_iter()
+            .map(|r| (r.id.to_string(), r))
+            .collect();
+        for id in missing {
+            self.records.insert(id.to_string(), found.remove(id));
+        }
+
+        Ok(())
+    }
+}
+
+impl Airtable {
+    /// Returns the records linked to from `field` of each record, in the order of
+    /// `records`, read from the table the field links to.
+    ///
+    /// See `LinkResolver` to keep the fetched records around between calls.
+    pub async fn resolve_links<F, T>(
+        &self,
+        records: &[Record<F>],
+        field: &str,
+        table: &str,
+    ) -> Result<Vec<Vec<Record<T>>>>
+    where
+        F: Serialize,
+        T: DeserializeOwned + Clone,
+    {
+        LinkResolver::new(table).resolve_field(self, records, field).await
+    }
+}
+
+/// Returns the record ids in a linked record field. Fields that are missing or not a list
+/// of ids have none.
+fn link_ids(fields: &serde_json::Value, field: &str) -> Vec<String> {
+    fields
+        .get(field)
+        .and_then(|v| v.as_array())
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str())
+                .map(|id| id.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_ids() {
+        let fields = serde_json::json!({
+            "Email": "jess@example.com",
+            "Auth User Logins": ["rec1", "rec2"],
+        });
+
+        assert_eq!(link_ids(&fields, "Auth User Logins"), vec!["rec1", "rec2"]);
+        assert!(link_ids(&fields, "Email").is_empty());
+        assert!(link_ids(&fields, "Page Views").is_empty());
+    }
+}
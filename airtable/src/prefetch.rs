@@ -0,0 +1,131 @@
+//! Reading a large table with several requests at once.
+//!
+//! The pages of a listing have to be fetched one after the other, since each one needs the
+//! offset the previous one returned. Splitting the table into partitions that are listed
+//! side by side gets around that, with the rate limiter of the client keeping the requests
+//! under the limit of the base.
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::{Airtable, Record};
+
+/// A part of a table to list on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Partition {
+    /// The records in a view.
+    View(String),
+    /// The records for which the formula evaluates to a truthy value.
+    Formula(String),
+}
+
+impl Airtable {
+    /// List the records in a table, listing the partitions `concurrency` at a time.
+    ///
+    /// The partitions should not overlap, records that are in more than one are only
+    /// returned once. Records in none of them are not returned, see
+    /// `created_time_partitions` for partitions that cover the whole table. The records are
+    /// returned in the order of the partitions.
+    pub async fn list_records_partitioned<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        fields: Vec<&str>,
+        partitions: Vec<Partition>,
+        concurrency: usize,
+    ) -> Result<Vec<Record<T>>> {
+        let listings: Vec<Vec<Record<T>>> = stream::iter(partitions)
+            .map(|partition| {
+                let fields = fields.clone();
+                async move {
+                    let mut pages = match &partition {
+                        Partition::View(view) => self.pages(table, view, fields),
+                        Partition::Formula(formula) => self.pages(table, "", fields).filter_by_formula(formula),
+                    };
+
+                    let mut records = Vec::new();
+                    while let Some(mut page) = pages.next().await? {
+                        records.append(&mut page);
+                    }
+                    log::debug!(
+                        "[airtable-api] Listed {} records of {:?} in {}",
+                        records.len(),
+                        partition,
+                        table
+                    );
+
+                    Ok::<_, anyhow::Error>(records)
+                }
+            })
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        let mut seen = HashSet::new();
+        Ok(listings
+            .into_iter()
+            .flatten()
+            .filter(|r| seen.insert(r.id.to_string()))
+            .collect())
+    }
+}
+
+/// Returns partitions of a table by the time its records were created, split at each of
+/// `bounds`, which are sorted. The first partition has everything before the first bound
+/// and the last everything after the last one, so together they cover the whole table.
+pub fn created_time_partitions(bounds: &[DateTime<Utc>]) -> Vec<Partition> {
+    let parse = |t: &DateTime<Utc>| format!("DATETIME_PARSE('{}')", t.to_rfc3339_opts(SecondsFormat::Secs, true));
+
+    if bounds.is_empty() {
+        return vec![Partition::Formula("TRUE()".to_string())];
+    }
+
+    let mut partitions = vec![Partition::Formula(format!(
+        "IS_BEFORE(CREATED_TIME(), {})",
+        parse(&bounds[0])
+    ))];
+    for window in bounds.windows(2) {
+        partitions.push(Partition::Formula(format!(
+            "AND(NOT(IS_BEFORE(CREATED_TIME(), {})), IS_BEFORE(CREATED_TIME(), {}))",
+            parse(&window[0]),
+            parse(&window[1])
+        )));
+    }
+    partitions.push(Partition::Formula(format!(
+        "NOT(IS_BEFORE(CREATED_TIME(), {}))",
+        parse(&bounds[bounds.len() - 1])
+    )));
+
+    partitions
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_created_time_partitions() {
+        let jan = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let feb = Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            created_time_partitions(&[jan, feb]),
+            vec![
+                Partition::Formula("IS_BEFORE(CREATED_TIME(), DATETIME_PARSE('2024-01-01T00:00:00Z'))".to_string()),
+                Partition::Formula(
+                    "AND(NOT(IS_BEFORE(CREATED_TIME(), DATETIME_PARSE('2024-01-01T00:00:00Z'))), \
+                     IS_BEFORE(CREATED_TIME(), DATETIME_PARSE('2024-02-01T00:00:00Z')))"
+                        .to_string()
+                ),
+                Partition::Formula(
+                    "NOT(IS_BEFORE(CREATED_TIME(), DATETIME_PARSE('2024-02-01T00:00:00Z')))".to_string()
+                ),
+            ]
+        );
+        assert_eq!(created_time_partitions(&[]).len(), 1);
+    }
+}
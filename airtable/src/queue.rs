@@ -0,0 +1,266 @@
+//! Coalescing the updates of callers that write a record at a time.
+//!
+//! Code that goes through records one by one, like the huddle sync in `cio` that updates a
+//! meeting at a time between calls to Google Calendar, or reacts to events one by one,
+//! tends to send a request per record, and to update the same few records over and over.
+//! The queue holds the updates for a short window, merges the ones to the same record,
+//! and sends what is left in full batches, so those callers don't cost a request per
+//! update.
+use std::{collections::BTreeMap, future::Future, mem, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use futures::{channel::mpsc, StreamExt};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::time::Instant;
+
+use crate::{Airtable, Record, MAX_RECORDS_PER_REQUEST};
+
+/// An update to the fields of a record, waiting in a queue.
+#[derive(Debug)]
+struct QueuedUpdate {
+    table: String,
+    id: String,
+    fields: Map<String, Value>,
+}
+
+/// What a queue sent, see `Airtable::write_queue`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QueueSummary {
+    /// The updates the queue was sent.
+    pub queued: usize,
+    /// The records updated in Airtable, after merging the updates to the same record.
+    pub updated: usize,
+    /// The requests sent to Airtable.
+    pub requests: usize,
+}
+
+/// The sending half of a write queue, see `Airtable::write_queue`.
+///
+/// Clones send to the same queue. Drop every clone of it once all the updates are sent,
+/// so the queue flushes what it holds and finishes.
+#[derive(Debug, Clone)]
+pub struct WriteQueue {
+    sender: mpsc::UnboundedSender<QueuedUpdate>,
+}
+
+impl WriteQueue {
+    /// Queue an update of the record with the id in the table. The fields must serialize
+    /// to an object, they are merged over the fields of the updates to the record still in
+    /// the queue.
+    ///
+    /// Never waits. Fails if the queue stopped, because a request failed.
+    pub fn update<T: Serialize>(&self, table: &str, id: &str, fields: T) -> Result<()> {
+        let fields = match serde_json::to_value(fields)? {
+            Value::Object(fields) => fields,
+            v => bail!(
+                "the fields of an update to `{}` in `{}` are not an object: {}",
+                id,
+                table,
+                v
+            ),
+        };
+
+        self.sender
+            .unbounded_send(QueuedUpdate {
+                table: table.to_string(),
+                id: id.to_string(),
+                fields,
+            })
+            .map_err(|_| anyhow!("the airtable write queue stopped"))
+    }
+}
+
+/// The updates in a queue, merged by table and record.
+#[derive(Debug, Default)]
+struct Pending {
+    tables: BTreeMap<String, BTreeMap<String, Map<String, Value>>>,
+}
+
+impl Pending {
+    fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    fn push(&mut self, update: QueuedUpdate) {
+        self.tables
+            .entry(update.table)
+            .or_default()
+            .entry(update.id)
+            .or_default()
+            .extend(update.fields);
+    }
+}
+
+impl Airtable {
+    /// Returns a queue that coalesces updates to records, and the future that sends them.
+    ///
+    /// The updates sent in the `window` after the first one reaches an empty queue are
+    /// held back, merged by record, and sent together when it closes, in batches of
+    /// `MAX_RECORDS_PER_REQUEST`. The future has to be polled alongside the code sending
+    /// to the queue, and finishes, flushing what is left, once every clone of the queue is
+    /// dropped. It stops at the first request that fails, and the updates still held are
+    /// dropped with it.
+    pub fn write_queue(&self, window: Duration) -> (WriteQueue, impl Future<Output = Result<QueueSummary>> + '_) {
+        let (sender, mut receiver) = mpsc::unbounded();
+
+        let flusher = async move {
+            let mut summary = QueueSummary::default();
+            let mut pending = Pending::default();
+            let mut deadline: Option<Instant> = None;
+            loop {
+                let next = match deadline {
+                    Some(at) => match tokio::time::timeout_at(at, receiver.next()).await {
+                        Ok(next) => next,
+                        // The window closed.
+                        Err(_) => {
+                            self.flush_queue(mem::take(&mut pending), &mut summary).await?;
+                            deadline = None;
+                            continue;
+                        }
+                    },
+                    None => receiver.next().await,
+                };
+
+                match next {
+                    Some(update) => {
+                        if pending.is_empty() {
+                            deadline = Some(Instant::now() + window);
+                        }
+                        pending.push(update);
+                        summary.queued += 1;
+                    }
+                    None => break,
+                }
+            }
+            self.flush_queue(pending, &mut summary).await?;
+
+            log::debug!(
+                "[airtable-api] Sent {} queued updates as {} record updates in {} requests",
+                summary.queued,
+                summary.updated,
+                summary.requests
+            );
+
+            Ok(summary)
+        };
+
+        (WriteQueue { sender }, flusher)
+    }
+
+    async fn flush_queue(&self, pending: Pending, summary: &mut QueueSummary) -> Result<()> {
+        for (table, records) in pending.tables {
+            let records: Vec<Record<Value>> = records
+                .into_iter()
+                .map(|(id, fields)| Record {
+                    id,
+                    fields: Value::Object(fields),
+                    created_time: None,
+                })
+                .collect();
+
+            for batch in records.chunks(MAX_RECORDS_PER_REQUEST) {
+                self.update_records::<Value>(&table, batch.to_vec()).await?;
+                summary.updated += batch.len();
+                summary.requests += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::{Method, StatusCode};
+    use serde_json::json;
+
+    use super::*;
+    use crate::MockTransport;
+
+    #[tokio::test]
+    async fn test_write_queue_coalesces() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .respond_json(Method::GET, "/v0/meta/bases/app1/tables", StatusCode::OK, &json!({}))
+                .respond_json(Method::PATCH, "/v0/app1/Users", StatusCode::OK, &json!({"records": []})),
+        );
+        let airtable = Airtable::new("key", "app1", "").with_transport(transport.clone());
+
+        let (queue, flusher) = airtable.write_queue(Duration::from_millis(50));
+        let send = async move {
+            for logins in 1..=20 {
+                queue.update("Users", "rec1", json!({ "Logins": logins }))?;
+                queue.update("Users", &format!("rec{}", logins % 3 + 2), json!({ "Seen": true }))?;
+            }
+            queue.update("Users", "rec1", json!({ "Name": "Jess" }))?;
+            assert!(queue.update("Users", "rec1", json!(["not", "fields"])).is_err());
+            Ok::<_, anyhow::Error>(())
+        };
+        let (_, summary) = futures::try_join!(send, flusher).unwrap();
+
+        assert_eq!(
+            summary,
+            QueueSummary {
+                queued: 41,
+                updated: 4,
+                requests: 1,
+            }
+        );
+        let patches: Vec<_> = transport
+            .requests()
+            .into_iter()
+            .filter(|(method, _)| method == Method::PATCH)
+            .collect();
+        assert_eq!(patches.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_write_queue_flushes_when_the_window_closes() {
+        tokio::time::pause();
+
+        let transport = Arc::new(
+            MockTransport::new()
+                .respond_json(Method::GET, "/v0/meta/bases/app1/tables", StatusCode::OK, &json!({}))
+                .respond_json(Method::PATCH, "/v0/app1/Users", StatusCode::OK, &json!({"records": []})),
+        );
+        let airtable = Airtable::new("key", "app1", "").with_transport(transport.clone());
+        let patches = || {
+            transport
+                .requests()
+                .into_iter()
+                .filter(|(method, _)| method == Method::PATCH)
+                .count()
+        };
+
+        let window = Duration::from_millis(50);
+        let (queue, flusher) = airtable.write_queue(window);
+        let send = async move {
+            queue.update("Users", "rec1", json!({ "Logins": 1 }))?;
+            tokio::time::advance(window / 2).await;
+            queue.update("Users", "rec1", json!({ "Logins": 2 }))?;
+            assert_eq!(patches(), 0);
+
+            // The paused clock skips ahead to the end of the window, and the queue flushes
+            // while it is still open.
+            tokio::time::sleep(window * 2).await;
+            assert_eq!(patches(), 1);
+
+            queue.update("Users", "rec2", json!({ "Logins": 1 }))?;
+            Ok::<_, anyhow::Error>(())
+        };
+        let (_, summary) = futures::try_join!(send, flusher).unwrap();
+
+        assert_eq!(
+            summary,
+            QueueSummary {
+                queued: 3,
+                updated: 2,
+                requests: 2,
+            }
+        );
+        assert_eq!(patches(), 2);
+    }
+}
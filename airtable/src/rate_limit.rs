@@ -0,0 +1,73 @@
+use std::{sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+/// The number of requests Airtable allows per second for a base.
+/// FROM: https://airtable.com/developers/web/api/rate-limits
+pub const REQUESTS_PER_SECOND: u32 = 5;
+
+/// Spaces out the requests of the clients that share it, so that reading a table with many
+/// requests at once stays under the rate limit of the base instead of getting `429`s.
+///
+/// Share one limiter between every client of the same base with
+/// [`Airtable::with_rate_limiter`](crate::Airtable::with_rate_limiter).
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    /// The earliest time the next request can be sent.
+    next: Mutex<Option<Instant>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new(REQUESTS_PER_SECOND)
+    }
+}
+
+impl RateLimiter {
+    /// Create a limiter that allows `requests_per_second` requests a second.
+    pub fn new(requests_per_second: u32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(1) / requests_per_second.max(1),
+            next: Default::default(),
+        }
+    }
+
+    /// Wait until the next request can be sent.
+    pub async fn acquire(&self) {
+        let at = self.reserve(&mut self.next.lock().unwrap(), Instant::now());
+
+        tokio::time::sleep_until(at).await;
+    }
+
+    /// Returns the time the request can be sent at, and moves the next slot along.
+    fn reserve(&self, next: &mut Option<Instant>, now: Instant) -> Instant {
+        let at = match *next {
+            Some(n) if n > now => n,
+            _ => now,
+        };
+        *next = Some(at + self.interval);
+
+        at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limiter = RateLimiter::new(5);
+        let now = Instant::now();
+        let mut next = None;
+
+        assert_eq!(limiter.reserve(&mut next, now), now);
+        assert_eq!(limiter.reserve(&mut next, now), now + Duration::from_millis(200));
+        assert_eq!(limiter.reserve(&mut next, now), now + Duration::from_millis(400));
+
+        // Once the slots have passed a request goes out right away.
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.reserve(&mut next, later), later);
+    }
+}
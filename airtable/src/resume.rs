@@ -0,0 +1,209 @@
+//! Reading a whole table across runs, for tables too large to read in one go.
+//!
+//! The offset of the next page is saved in an `OffsetStore` after each page is handled,
+//! so a read that is interrupted picks up at the page it stopped at, rather than at the
+//! first record. Airtable only keeps the offsets of a listing for a while, so a read that
+//! resumes too late starts over.
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use serde::de::DeserializeOwned;
+
+use crate::{Airtable, Record};
+
+/// The error code Airtable answers with once the offsets of a listing expired.
+const EXPIRED_OFFSET: &str = "LIST_RECORDS_ITERATOR_NOT_AVAILABLE";
+
+/// What a listing was of, so its offset is only used to resume the same listing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ListKey {
+    pub base_id: String,
+    pub table: String,
+    /// The view the records were listed from, empty for the whole table.
+    pub view: String,
+    /// The `filterByFormula` of the listing, empty for none.
+    pub formula: String,
+}
+
+/// Keeps the offsets of the listings that have not finished yet.
+pub trait OffsetStore: Send + Sync {
+    /// Returns the offset the listing stopped at, if it did not finish.
+    fn load<'a>(&'a self, key: &'a ListKey) -> BoxFuture<'a, Result<Option<String>>>;
+
+    /// Save the offset of the next page of the listing.
+    fn save<'a>(&'a self, key: &'a ListKey, offset: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Forget the offset of the listing, once it finished.
+    fn clear<'a>(&'a self, key: &'a ListKey) -> BoxFuture<'a, Result<()>>;
+}
+
+/// An `OffsetStore` that keeps the offsets in memory, for tests and for reads that only
+/// need to resume within a process.
+#[derive(Debug, Default)]
+pub struct MemoryOffsetStore {
+    offsets: Mutex<HashMap<ListKey, String>>,
+}
+
+impl OffsetStore for MemoryOffsetStore {
+    fn load<'a>(&'a self, key: &'a ListKey) -> BoxFuture<'a, Result<Option<String>>> {
+        let offset = self.offsets.lock().unwrap().get(key).cloned();
+
+        futures::future::ready(Ok(offset)).boxed()
+    }
+
+    fn save<'a>(&'a self, key: &'a ListKey, offset: &'a str) -> BoxFuture<'a, Result<()>> {
+        self.offsets.lock().unwrap().insert(key.clone(), offset.to_string());
+
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn clear<'a>(&'a self, key: &'a ListKey) -> BoxFuture<'a, Result<()>> {
+        self.offsets.lock().unwrap().remove(key);
+
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+impl Airtable {
+    /// List the records in a table a page at a time, resuming where an earlier call for the
+    /// same table, view and formula stopped, returning the number of records read.
+    ///
+    /// Each page is handed to `on_page`, and the offset of the next one is saved to the
+    /// store once it returns, so a page may be handed over again if the read is
+    /// interrupted in between. The offset is cleared once the last page is read. Pass an
+    /// empty view or formula to not filter the records.
+    pub async fn resume_list_records<T, F, Fut>(
+        &self,
+        table: &str,
+        view: &str,
+        formula: &str,
+        fields: Vec<&str>,
+        store: &dyn OffsetStore,
+        mut on_page: F,
+    ) -> Result<usize>
+    where
+        T: DeserializeOwned,
+        F: FnMut(Vec<Record<T>>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let key = ListKey {
+            base_id: self.base_id.to_string(),
+            table: table.to_string(),
+            view: view.to_string(),
+            formula: formula.to_string(),
+        };
+        let start = || {
+            let pages = self.pages::<T>(table, view, fields.clone());
+            if formula.is_empty() {
+                pages
+            } else {
+                pages.filter_by_formula(formula)
+            }
+        };
+
+        let mut pages = start();
+        let mut resumed = false;
+        if let Some(offset) = store.load(&key).await? {
+            log::info!(
+                "[airtable-api] Resuming the listing of `{}` at offset {}",
+                table,
+                offset
+            );
+            pages = pages.starting_at(&offset);
+            resumed = true;
+        }
+
+        let mut read = 0;
+        loop {
+            let page = match pages.next().await {
+                Ok(Some(page)) => page,
+                Ok(None) => break,
+                Err(e) if resumed && e.to_string().contains(EXPIRED_OFFSET) => {
+                    log::warn!(
+                        "[airtable-api] The saved offset of the listing of `{}` expired, starting over",
+                        table
+                    );
+                    pages = start();
+                    resumed = false;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            read += page.len();
+            on_page(page).await?;
+            // After the last page there is no offset, and the listing is cleared below.
+            if let Some(offset) = pages.offset() {
+                store.save(&key, offset).await?;
+            }
+        }
+        store.clear(&key).await?;
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reqwest::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::MockTransport;
+
+    #[tokio::test]
+    async fn test_resume_list_records() {
+        let transport = Arc::new(
+            MockTransport::new()
+                .respond_json(
+                    Method::GET,
+                    "/v0/app1/PageViews",
+                    StatusCode::OK,
+                    &json!({"records": [{"id": "rec1", "fields": {}}], "offset": "itr1/rec1"}),
+                )
+                .respond(Method::GET, "/v0/app1/PageViews", StatusCode::BAD_GATEWAY, "")
+                .respond_json(
+                    Method::GET,
+                    "/v0/app1/PageViews",
+                    StatusCode::OK,
+                    &json!({"records": [{"id": "rec2", "fields": {}}, {"id": "rec3", "fields": {}}]}),
+                ),
+        );
+        let airtable = Airtable::new("key", "app1", "").with_transport(transport.clone());
+        let store = MemoryOffsetStore::default();
+        let key = ListKey {
+            base_id: "app1".to_string(),
+            table: "PageViews".to_string(),
+            view: String::new(),
+            formula: String::new(),
+        };
+
+        let mut seen = Vec::new();
+        let interrupted = airtable
+            .resume_list_records("PageViews", "", "", vec![], &store, |page: Vec<Record<Value>>| {
+                seen.extend(page.into_iter().map(|r| r.id));
+                async { Ok(()) }
+            })
+            .await;
+        assert!(interrupted.is_err());
+        assert_eq!(store.load(&key).await.unwrap().as_deref(), Some("itr1/rec1"));
+
+        let read = airtable
+            .resume_list_records("PageViews", "", "", vec![], &store, |page: Vec<Record<Value>>| {
+                seen.extend(page.into_iter().map(|r| r.id));
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(read, 2);
+        assert_eq!(seen, vec!["rec1", "rec2", "rec3"]);
+        assert_eq!(store.load(&key).await.unwrap(), None);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].1.query().unwrap_or_default().contains("offset=itr1%2Frec1"));
+    }
+}
@@ -0,0 +1,435 @@
+//! Types and methods for reading the schema of a base through the Metadata API.
+//! FROM: https://airtable.com/developers/web/api/get-base-schema
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+};
+
+use anyhow::{bail, Result};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{Airtable, ApiError, Record};
+
+/// The schema of a base.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BaseSchema {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<TableSchema>,
+}
+
+impl BaseSchema {
+    /// Find a table in the schema by its name or id.
+    pub fn table(&self, name_or_id: &str) -> Option<&TableSchema> {
+        self.tables.iter().find(|t| t.name == name_or_id || t.id == name_or_id)
+    }
+}
+
+/// The schema of a table.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TableSchema {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename = "primaryFieldId")]
+    pub primary_field_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldSchema>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub views: Vec<ViewSchema>,
+}
+
+impl TableSchema {
+    /// Find a field in the table by its name or id.
+    pub fn field(&self, name_or_id: &str) -> Option<&FieldSchema> {
+        self.fields.iter().find(|f| f.name == name_or_id || f.id == name_or_id)
+    }
+
+    /// Returns the names and ids of the fields that are computed by Airtable, and so can't
+    /// be written through the API.
+    pub fn read_only_fields(&self) -> HashSet<String> {
+        self.fields
+            .iter()
+            .filter(|f| f.is_computed())
+            .flat_map(|f| [f.name.to_string(), f.id.to_string()])
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// Compare the fields of records about to be written to the table with its schema.
+    ///
+    /// A field renamed in the base leaves a column that none of our writes fill in, so the
+    /// fields that are not in the table are reported, along with the fields given values
+    /// their column can't hold. Requests are sent with `typecast`, which converts between
+    /// text, numbers and checkboxes, so only a list going into a single value or the other
+    /// way around counts as the wrong type. Each field is reported once, in order of name.
+    pub fn diff<T: Serialize>(&self, records: &[T]) -> Result<SchemaDiff> {
+        let mut mismatches: BTreeMap<String, FieldMismatch> = Default::default();
+        for record in records {
+            let fields = match serde_json::to_value(record)? {
+                serde_json::Value::Object(fields) => fields,
+                _ => continue,
+            };
+            for (name, value) in fields {
+                if mismatches.contains_key(&name) {
+                    continue;
+                }
+
+                let mismatch = match self.field(&name) {
+                    None => FieldMismatch::Missing {
+                        field: name.to_string(),
+                    },
+                    Some(field) if !field.accepts(&value) => FieldMismatch::WrongType {
+                        field: name.to_string(),
+                        field_type: field.type_.to_string(),
+                        value_type: value_type(&value),
+                    },
+                    Some(_) => continue,
+                };
+                mismatches.insert(name, mismatch);
+            }
+        }
+
+        Ok(SchemaDiff {
+            table: self.name.to_string(),
+            mismatches: mismatches.into_values().collect(),
+        })
+    }
+}
+
+/// A field written to a table that does not match the schema of the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldMismatch {
+    /// The table has no field with the name.
+    Missing { field: String },
+    /// The field can't hold the values written to it.
+    WrongType {
+        field: String,
+        field_type: String,
+        value_type: &'static str,
+    },
+}
+
+impl fmt::Display for FieldMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldMismatch::Missing { field } => write!(f, "- `{}` is not a field of the table", field),
+            FieldMismatch::WrongType {
+                field,
+                field_type,
+                value_type,
+            } => write!(
+                f,
+                "~ `{}` is a field of type `{}`, but is written {}",
+                field, field_type, value_type
+            ),
+        }
+    }
+}
+
+/// The fields written to a table that don't match its schema, see `TableSchema::diff`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub table: String,
+    pub mismatches: Vec<FieldMismatch>,
+}
+
+impl SchemaDiff {
+    /// Returns true if every field written matches the schema.
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the fields written to `{}` don't match its schema:", self.table)?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {}", mismatch)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns how a value is described in a `SchemaDiff`.
+fn value_type(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "empty",
+        serde_json::Value::Bool(_) => "a checkbox",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "text",
+        serde_json::Value::Array(_) => "a list",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// The schema of a field.
+/// FROM: https://airtable.com/developers/web/api/field-model
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FieldSchema {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// The type specific options for the field, for example the choices of a select field
+    /// or the precision of a number field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<serde_json::Value>,
+}
+
+impl FieldSchema {
+    /// Returns if the value of the field is computed by Airtable, meaning it cannot be
+    /// written through the API.
+    pub fn is_computed(&self) -> bool {
+        matches!(
+            self.type_.as_str(),
+            "autoNumber"
+                | "button"
+                | "count"
+                | "createdBy"
+                | "createdTime"
+                | "externalSyncSource"
+                | "formula"
+                | "lastModifiedBy"
+                | "lastModifiedTime"
+                | "lookup"
+                | "multipleLookupValues"
+                | "rollup"
+        )
+    }
+
+    /// Returns if the value can be written to the field, going by whether the field holds
+    /// a list. Fields of types we don't know take anything.
+    pub fn accepts(&self, value: &serde_json::Value) -> bool {
+        let list = match self.type_.as_str() {
+            "multipleAttachments" | "multipleCollaborators" | "multipleRecordLinks" | "multipleSelects" => true,
+            "checkbox" | "currency" | "date" | "dateTime" | "duration" | "email" | "multilineText" | "number"
+            | "percent" | "phoneNumber" | "rating" | "richText" | "singleLineText" | "singleSelect" | "url" => false,
+            _ => return true,
+        };
+
+        match value {
+            serde_json::Value::Null => true,
+            serde_json::Value::Array(_) => list,
+            serde_json::Value::Object(_) => false,
+            _ => !list,
+        }
+    }
+
+    /// Returns the number of decimal places for a number field, if it has one set.
+    pub fn precision(&self) -> Option<u64> {
+        self.options
+            .as_ref()
+            .and_then(|o| o.get("precision"))
+            .and_then(|p| p.as_u64())
+    }
+}
+
+/// The schema of a view.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ViewSchema {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename = "type")]
+    pub type_: String,
+}
+
+impl Airtable {
+    /// Get the schema of the tables in the base.
+    /// FROM: https://airtable.com/developers/web/api/get-base-schema
+    pub async fn get_base_schema(&self) -> Result<BaseSchema> {
+        let url = Url::parse(&self.endpoint)?.join(&format!("meta/bases/{}/tables", self.base_id))?;
+
+        // Build the request.
+        let request = self.request_url(Method::GET, url, (), None)?;
+
+        let resp = self.execute(request).await?;
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => return Err(ApiError::from_response(resp).await.into()),
+        };
+
+        // Try to deserialize the response.
+        let schema: BaseSchema = resp.json().await?;
+
+        Ok(schema)
+    }
+
+    /// Returns the read-only fields of a table, see `TableSchema::read_only_fields`.
+    ///
+    /// The schema of the base is only read once per client. When it can't be read, for
+    /// example because the API key lacks the `schema.bases:read` scope, no fields are
+    /// treated as read-only.
+    pub(crate) async fn read_only_fields(&self, table: &str) -> HashSet<String> {
+        if let Some(fields) = self.read_only_fields.lock().unwrap().get(table) {
+            return fields.clone();
+        }
+
+        match self.get_base_schema().await {
+            Ok(schema) => {
+                let mut cached = self.read_only_fields.lock().unwrap();
+                for t in &schema.tables {
+                    let fields = t.read_only_fields();
+                    cached.insert(t.id.to_string(), fields.clone());
+                    cached.insert(t.name.to_string(), fields);
+                }
+                cached.entry(table.to_string()).or_default().clone()
+            }
+            Err(e) => {
+                log::warn!(
+                    "[airtable-api] Reading the schema of base `{}` failed, sending every field: {}",
+                    self.base_id,
+                    e
+                );
+                self.read_only_fields
+                    .lock()
+                    .unwrap()
+                    .insert(table.to_string(), Default::default());
+                Default::default()
+            }
+        }
+    }
+
+    /// Get the schema of a single table in the base by its name or id.
+    pub async fn get_table_schema(&self, table: &str) -> Result<TableSchema> {
+        let schema = self.get_base_schema().await?;
+
+        match schema.table(table) {
+            Some(t) => Ok(t.clone()),
+            None => bail!("table `{}` was not found in the base schema", table),
+        }
+    }
+}
+
+/// Remove the read-only fields from records about to be written to a table. Airtable
+/// rejects the whole request with a 422 when a computed field is written, so the fields are
+/// dropped with a warning instead, and the rest of the record is still written.
+pub(crate) fn strip_read_only_fields<T: Serialize>(
+    table: &str,
+    records: Vec<Record<T>>,
+    read_only: &HashSet<String>,
+) -> Result<Vec<Record<serde_json::Value>>> {
+    let mut stripped: BTreeSet<String> = Default::default();
+    let records = records
+        .into_iter()
+        .map(|r| {
+            let mut fields = serde_json::to_value(r.fields)?;
+            if let Some(map) = fields.as_object_mut() {
+                map.retain(|name, _| {
+                    if read_only.contains(name) {
+                        stripped.insert(name.to_string());
+                        false
+                    } else {
+                        true
+                    }
+                });
+            }
+
+            Ok(Record {
+                id: r.id,
+                fields,
+                created_time: r.created_time,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if !stripped.is_empty() {
+        log::warn!(
+            "[airtable-api] Not writing the read-only fields {:?} of table `{}`, they are computed by Airtable",
+            stripped,
+            table
+        );
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_read_only_fields() {
+        let schema: TableSchema = serde_json::from_value(serde_json::json!({
+            "id": "tbl1",
+            "name": "Auth Users",
+            "fields": [
+                { "id": "fld1", "name": "Email", "type": "email" },
+                { "id": "fld2", "name": "Logins", "type": "count" },
+                { "id": "fld3", "name": "Name", "type": "formula" },
+            ],
+        }))
+        .unwrap();
+
+        let read_only = schema.read_only_fields();
+        assert_eq!(
+            read_only,
+            ["Logins", "fld2", "Name", "fld3"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+
+        let records = vec![Record {
+            id: "rec1".to_string(),
+            fields: serde_json::json!({ "Email": "jess@example.com", "Logins": 3, "fld3": "Jess" }),
+            created_time: None,
+        }];
+        let records = strip_read_only_fields("Auth Users", records, &read_only).unwrap();
+        assert_eq!(records[0].id, "rec1");
+        assert_eq!(records[0].fields, serde_json::json!({ "Email": "jess@example.com" }));
+    }
+
+    #[test]
+    fn test_diff() {
+        let schema: TableSchema = serde_json::from_value(serde_json::json!({
+            "id": "tbl1",
+            "name": "Auth Users",
+            "fields": [
+                { "id": "fld1", "name": "Email", "type": "email" },
+                { "id": "fld2", "name": "Logins", "type": "number" },
+                { "id": "fld3", "name": "Link to People", "type": "multipleRecordLinks" },
+                { "id": "fld4", "name": "Modified", "type": "lastModifiedTime" },
+            ],
+        }))
+        .unwrap();
+
+        let records = vec![
+            serde_json::json!({ "Email": "jess@example.com", "Logins": "3", "Link to People": [] }),
+            serde_json::json!({ "Email": "sam@example.com", "Link To People": ["rec1"], "Modified": null }),
+            serde_json::json!({ "Email": ["sam@example.com"], "Logins": null }),
+        ];
+        let diff = schema.diff(&records).unwrap();
+        assert_eq!(
+            diff.mismatches,
+            vec![
+                FieldMismatch::WrongType {
+                    field: "Email".to_string(),
+                    field_type: "email".to_string(),
+                    value_type: "a list",
+                },
+                FieldMismatch::Missing {
+                    field: "Link To People".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "the fields written to `Auth Users` don't match its schema:\n  \
+             ~ `Email` is a field of type `email`, but is written a list\n  \
+             - `Link To People` is not a field of the table"
+        );
+
+        assert!(schema.diff(&records[..1]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,56 @@
+//! Helpers for keeping a table in sync with another source of records.
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Airtable, Record};
+
+/// Returns the records whose fields differ from the record with the same id in `existing`.
+///
+/// Records without an id, or with an id that is not in `existing`, are always returned
+/// since there is nothing to compare them against. Comparison uses the `PartialEq`
+/// implementation of the fields, so types can implement it themselves to ignore fields
+/// that Airtable returns in a different format than they are sent in.
+pub fn changed_records<T: PartialEq>(records: Vec<Record<T>>, existing: &[Record<T>]) -> Vec<Record<T>> {
+    let existing: HashMap<&str, &T> = existing.iter().map(|r| (r.id.as_str(), &r.fields)).collect();
+
+    records
+        .into_iter()
+        .filter(|r| match existing.get(r.id.as_str()) {
+            Some(fields) => **fields != r.fields,
+            None => true,
+        })
+        .collect()
+}
+
+impl Airtable {
+    /// Bulk update only the records in a table whose fields have changed.
+    ///
+    /// `existing` is the current state of the table, usually from a call to
+    /// `list_records`. Records that are equal to their existing counterpart are skipped
+    /// so that unchanged records are not rewritten, which saves on rate limits and keeps
+    /// the record modification times meaningful. Returns the records that were updated.
+    pub async fn update_changed_records<T: Serialize + DeserializeOwned + PartialEq>(
+        &self,
+        table: &str,
+        records: Vec<Record<T>>,
+        existing: &[Record<T>],
+    ) -> Result<Vec<Record<T>>> {
+        let total = records.len();
+        let changed = changed_records(records, existing);
+
+        log::debug!(
+            "[airtable-api] {} of {} records in `{}` changed, skipping the rest",
+            changed.len(),
+            total,
+            table
+        );
+
+        if changed.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.update_records(table, changed).await
+    }
+}
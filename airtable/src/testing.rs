@@ -0,0 +1,139 @@
+//! Recorded responses of the Airtable API, and a mock server that serves them, so code that
+//! talks to Airtable can be tested without a network or an API key.
+//!
+//! Enabled with the `testing` feature. The fixtures are a small `Users` table, listed in two
+//! pages, along with its schema and the errors Airtable returns for rate limits and bad
+//! values.
+//!
+//! ```ignore
+//! let airtable = MockAirtable::start("appTest").await;
+//! airtable.mount_list_records("Users").await;
+//!
+//! let records: Vec<Record<serde_json::Value>> =
+//!     airtable.client().list_records("Users", "", vec![]).await?;
+//! assert_eq!(records.len(), 3);
+//! ```
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::Airtable;
+
+/// The recorded response bodies.
+pub mod fixtures {
+    /// The first page of the `Users` table, with an offset to the second.
+    pub const LIST_RECORDS_PAGE_1: &str = include_str!("../fixtures/list_records_page_1.json");
+    /// The offset the first page points at.
+    pub const LIST_RECORDS_OFFSET: &str = "itrPage2/recUser00000002";
+    /// The last page of the `Users` table.
+    pub const LIST_RECORDS_PAGE_2: &str = include_str!("../fixtures/list_records_page_2.json");
+    /// The response to creating a record in the `Users` table.
+    pub const CREATE_RECORDS: &str = include_str!("../fixtures/create_records.json");
+    /// The schema of the base, with a computed `Last Modified` field.
+    pub const BASE_SCHEMA: &str = include_str!("../fixtures/base_schema.json");
+    /// The body of a `429 Too Many Requests`.
+    pub const RATE_LIMITED: &str = include_str!("../fixtures/rate_limited.json");
+    /// The body of a `422 Unprocessable Entity` for a value of the wrong type.
+    pub const INVALID_VALUE: &str = include_str!("../fixtures/invalid_value.json");
+}
+
+/// The number of records in the `Users` table of the fixtures.
+pub const USERS_TABLE_RECORDS: usize = 3;
+
+/// A mock Airtable API for a base.
+pub struct MockAirtable {
+    server: MockServer,
+    base_id: String,
+}
+
+impl MockAirtable {
+    /// Start a mock server for the base, with nothing mounted yet.
+    pub async fn start(base_id: &str) -> Self {
+        MockAirtable {
+            server: MockServer::start().await,
+            base_id: base_id.to_string(),
+        }
+    }
+
+    /// Returns the server, to mount other responses or look at the requests it got.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Returns the URL to point a client at, see `Airtable::with_endpoint`.
+    pub fn endpoint(&self) -> String {
+        format!("{}/v0/", self.server.uri())
+    }
+
+    /// Returns a client for the base that talks to the mock server.
+    pub fn client(&self) -> Airtable {
+        Airtable::new("keyTest", &self.base_id, "").with_endpoint(self.endpoint())
+    }
+
+    fn table_path(&self, table: &str) -> String {
+        format!("/v0/{}/{}", self.base_id, table)
+    }
+
+    /// Serve the two pages of the `Users` fixtures when the table is listed.
+    pub async fn mount_list_records(&self, table: &str) {
+        Mock::given(method("GET"))
+            .and(path(self.table_path(table)))
+            .and(query_param("offset", fixtures::LIST_RECORDS_OFFSET))
+            .respond_with(json(200, fixtures::LIST_RECORDS_PAGE_2))
+            .with_priority(2)
+            .mount(&self.server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(self.table_path(table)))
+            .respond_with(json(200, fixtures::LIST_RECORDS_PAGE_1))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer record creation in the table with the created record of the fixtures.
+    pub async fn mount_create_records(&self, table: &str) {
+        Mock::given(method("POST"))
+            .and(path(self.table_path(table)))
+            .respond_with(json(200, fixtures::CREATE_RECORDS))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serve the schema of the base, which the client reads before it writes records.
+    pub async fn mount_base_schema(&self) {
+        Mock::given(method("GET"))
+            .and(path(format!("/v0/meta/bases/{}/tables", self.base_id)))
+            .respond_with(json(200, fixtures::BASE_SCHEMA))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer the next `times` requests with the method to the table with a
+    /// `429 Too Many Requests`, before any other response.
+    pub async fn mount_rate_limited(&self, http_method: &str, table: &str, times: u64) {
+        Mock::given(method(http_method))
+            .and(path(self.table_path(table)))
+            .respond_with(json(429, fixtures::RATE_LIMITED))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer requests with the method to the table with an error, before any other
+    /// response.
+    pub async fn mount_error(&self, http_method: &str, table: &str, status: u16, body: &str) {
+        Mock::given(method(http_method))
+            .and(path(self.table_path(table)))
+            .respond_with(json(status, body))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+}
+
+/// Returns a response with the status and JSON body.
+pub fn json(status: u16, body: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_raw(body.as_bytes().to_vec(), "application/json")
+}
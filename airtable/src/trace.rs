@@ -0,0 +1,117 @@
+//! Correlating the requests a client sends with the logs on both ends.
+//!
+//! Every request goes out with a generated id in the `x-request-id` header, and is sent
+//! in a tracing span that carries the same id. Error responses are turned into an
+//! [`ApiError`] that names the id, along with the trace id the server returned, so a
+//! failed request can be found in our logs and in the ones of the API.
+use std::fmt;
+
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Request, Response, StatusCode,
+};
+
+/// The header the id of a request is sent in.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The headers servers return their own id of a request in, by preference.
+const TRACE_ID_HEADERS: &[&str] = &["x-trace-id", "x-amzn-trace-id", "x-amz-cf-id", REQUEST_ID_HEADER];
+
+/// The id a request was sent with, kept in the extensions of its response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Returns a new id for a request.
+pub fn new_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Make sure the request has an id and return it. An id set by an interceptor is kept.
+pub fn tag_request(request: &mut Request) -> String {
+    if let Some(id) = request.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()) {
+        return id.to_string();
+    }
+
+    let id = new_request_id();
+    request.headers_mut().insert(
+        REQUEST_ID_HEADER,
+        HeaderValue::from_str(&id).expect("a uuid is a valid header"),
+    );
+    id
+}
+
+/// Returns the id the server gave the request, if it returned one that is not just the
+/// id we sent echoed back.
+pub fn trace_id(headers: &HeaderMap, request_id: Option<&str>) -> Option<String> {
+    TRACE_ID_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name)?.to_str().ok())
+        .find(|id| !id.is_empty() && Some(*id) != request_id)
+        .map(|id| id.to_string())
+}
+
+/// An error response from the API, with the ids to look the request up by.
+///
+/// This is returned wrapped in an [`anyhow::Error`], callers can get it back with
+/// `err.downcast_ref::<ApiError>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: String,
+    /// The id we sent the request with, if it was sent by a client that tags requests.
+    pub request_id: Option<String>,
+    /// The id the server returned for the request, if any.
+    pub trace_id: Option<String>,
+}
+
+impl ApiError {
+    /// Read the error from a response that was not successful.
+    pub async fn from_response(resp: Response) -> Self {
+        let request_id = resp.extensions().get::<RequestId>().map(|id| id.0.to_string());
+        let trace_id = trace_id(resp.headers(), request_id.as_deref());
+
+        ApiError {
+            status: resp.status(),
+            body: resp.text().await.unwrap_or_default(),
+            request_id,
+            trace_id,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status code: {}, body: {}", self.status, self.body)?;
+        if let Some(id) = &self.request_id {
+            write!(f, ", request id: {}", id)?;
+        }
+        if let Some(id) = &self.trace_id {
+            write!(f, ", trace id: {}", id)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use super::{trace_id, REQUEST_ID_HEADER};
+
+    #[test]
+    fn test_trace_id() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(trace_id(&headers, Some("ours")), None);
+
+        // An echo of the id we sent is not the id of the server.
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("ours"));
+        assert_eq!(trace_id(&headers, Some("ours")), None);
+        assert_eq!(trace_id(&headers, None), Some("ours".to_string()));
+
+        headers.insert("x-amzn-trace-id", HeaderValue::from_static("Root=1-abc"));
+        assert_eq!(trace_id(&headers, Some("ours")), Some("Root=1-abc".to_string()));
+    }
+}
@@ -0,0 +1,175 @@
+//! The HTTP layer under the clients, so tests can answer their requests without a network.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use futures::future::{BoxFuture, FutureExt};
+use reqwest::{header, Method, Request, Response, StatusCode, Url};
+
+/// Sends the requests a client builds.
+///
+/// The clients send through `reqwest` unless they are given another transport, for
+/// example a `MockTransport` with canned responses in a test.
+pub trait HttpTransport: Send + Sync {
+    /// Send the request and return the response.
+    fn execute(&self, request: Request) -> BoxFuture<'_, reqwest_middleware::Result<Response>>;
+}
+
+impl HttpTransport for reqwest::Client {
+    fn execute(&self, request: Request) -> BoxFuture<'_, reqwest_middleware::Result<Response>> {
+        reqwest::Client::execute(self, request)
+            .map(|r| r.map_err(reqwest_middleware::Error::from))
+            .boxed()
+    }
+}
+
+impl HttpTransport for reqwest_middleware::ClientWithMiddleware {
+    fn execute(&self, request: Request) -> BoxFuture<'_, reqwest_middleware::Result<Response>> {
+        reqwest_middleware::ClientWithMiddleware::execute(self, request).boxed()
+    }
+}
+
+/// A response for `MockTransport` to answer a request with.
+#[derive(Debug, Clone)]
+struct CannedResponse {
+    status: StatusCode,
+    headers: header::HeaderMap,
+    body: Vec<u8>,
+}
+
+impl From<CannedResponse> for Response {
+    fn from(canned: CannedResponse) -> Self {
+        let mut resp = http::Response::new(canned.body);
+        *resp.status_mut() = canned.status;
+        *resp.headers_mut() = canned.headers;
+        Response::from(resp)
+    }
+}
+
+/// A transport that answers requests with canned responses, by method and path.
+///
+/// The responses for a path are used in the order they were added, and the last one is
+/// repeated, so a paginated listing can be answered page by page. Requests nothing was
+/// added for get a `404 Not Found`. Every request is recorded, see `requests`.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    responses: Mutex<HashMap<(Method, String), VecDeque<CannedResponse>>>,
+    requests: Mutex<Vec<(Method, Url)>>,
+}
+
+impl MockTransport {
+    /// Create a transport with no responses.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Answer the next request to the path, without the query, with the status and body.
+    pub fn respond<B: Into<Vec<u8>>>(self, method: Method, path: &str, status: StatusCode, body: B) -> Self {
+        self.respond_with_headers(method, path, status, header::HeaderMap::new(), body)
+    }
+
+    /// Answer the next request to the path with a JSON body.
+    pub fn respond_json(self, method: Method, path: &str, status: StatusCode, body: &serde_json::Value) -> Self {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        self.respond_with_headers(method, path, status, headers, body.to_string())
+    }
+
+    /// Answer the next request to the path with the status, headers and body.
+    pub fn respond_with_headers<B: Into<Vec<u8>>>(
+        self,
+        method: Method,
+        path: &str,
+        status: StatusCode,
+        headers: header::HeaderMap,
+        body: B,
+    ) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .entry((method, path.to_string()))
+            .or_default()
+            .push_back(CannedResponse {
+                status,
+                headers,
+                body: body.into(),
+            });
+        self
+    }
+
+    /// Returns the method and URL of every request sent so far, in order.
+    pub fn requests(&self) -> Vec<(Method, Url)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn respond_to(&self, request: &Request) -> CannedResponse {
+        self.requests
+            .lock()
+            .unwrap()
+            .push((request.method().clone(), request.url().clone()));
+
+        let key = (request.method().clone(), request.url().path().to_string());
+        let mut responses = self.responses.lock().unwrap();
+        match responses.get_mut(&key) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap(),
+            Some(queue) if !queue.is_empty() => queue[0].clone(),
+            _ => CannedResponse {
+                status: StatusCode::NOT_FOUND,
+                headers: Default::default(),
+                body: format!("no canned response for {} {}", key.0, key.1).into(),
+            },
+        }
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, reqwest_middleware::Result<Response>> {
+        let response = self.respond_to(&request).into();
+
+        futures::future::ready(Ok(response)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Airtable, Record};
+
+    #[tokio::test]
+    async fn test_mock_transport_pages() {
+        let transport = MockTransport::new()
+            .respond_json(
+                Method::GET,
+                "/v0/app1/Users",
+                StatusCode::OK,
+                &json!({"records": [{"id": "rec1", "fields": {"Name": "Jess"}}], "offset": "page2"}),
+            )
+            .respond_json(
+                Method::GET,
+                "/v0/app1/Users",
+                StatusCode::OK,
+                &json!({"records": [{"id": "rec2", "fields": {"Name": "Sam"}}]}),
+            );
+        let transport = Arc::new(transport);
+        let airtable = Airtable::new("key", "app1", "").with_transport(transport.clone());
+
+        let records: Vec<Record<serde_json::Value>> = airtable.list_records("Users", "", vec![]).await.unwrap();
+        let ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["rec1", "rec2"]);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].1.query().unwrap_or_default().contains("offset=page2"));
+
+        assert!(airtable.get_record::<serde_json::Value>("Users", "rec3").await.is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Webhooks, for hearing about changes to a base as they happen rather than polling it.
+//!
+//! Airtable does not send the changes themselves. It POSTs a `WebhookNotification` to the
+//! notification URL, signed with the MAC secret handed out when the webhook was created,
+//! and the receiver lists the payloads of the webhook from the last cursor it read. See
+//! [the docs](https://airtable.com/developers/web/api/webhooks-overview).
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{offset::Utc, DateTime};
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::{Airtable, ApiError};
+
+/// The header Airtable signs notifications with.
+pub const WEBHOOK_MAC_HEADER: &str = "X-Airtable-Content-MAC";
+
+/// The prefix of the signature in the `X-Airtable-Content-MAC` header.
+const MAC_PREFIX: &str = "hmac-sha256=";
+
+/// Verify the `X-Airtable-Content-MAC` header of a notification against its raw body,
+/// using the `macSecretBase64` returned when the webhook was created.
+pub fn verify_webhook_mac(mac_secret_base64: &str, body: &[u8], header: &str) -> Result<()> {
+    let mac = parse_webhook_mac(header)?;
+
+    let secret =
+        base64::decode(mac_secret_base64).map_err(|e| anyhow!("the webhook MAC secret is not base64: {}", e))?;
+    let mut expected = Hmac::<Sha256>::new_from_slice(&secret)?;
+    expected.update(body);
+    expected
+        .verify_slice(&mac)
+        .map_err(|_| anyhow!("the webhook notification MAC does not match its body"))
+}
+
+/// Returns the bytes of the signature in an `X-Airtable-Content-MAC` header.
+pub fn parse_webhook_mac(header: &str) -> Result<Vec<u8>> {
+    let hex_mac = header
+        .trim()
+        .strip_prefix(MAC_PREFIX)
+        .ok_or_else(|| anyhow!("the webhook MAC `{}` does not start with `{}`", header, MAC_PREFIX))?;
+
+    hex::decode(hex_mac).map_err(|e| anyhow!("the webhook MAC is not hex: {}", e))
+}
+
+/// The body of the request Airtable sends to the notification URL of a webhook.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+pub struct WebhookNotification {
+    #[serde(default)]
+    pub base: WebhookBase,
+    #[serde(default)]
+    pub webhook: WebhookRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// The base a notification is for.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+pub struct WebhookBase {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+/// The webhook a notification is for.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+pub struct WebhookRef {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+/// A webhook that was created, see `Airtable::create_webhook`.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedWebhook {
+    pub id: String,
+    /// The secret notifications are signed with, only returned when the webhook is created.
+    pub mac_secret_base64: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_time: Option<DateTime<Utc>>,
+}
+
+/// A page of the payloads of a webhook, see `Airtable::list_webhook_payloads`.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayloads {
+    #[serde(default)]
+    pub payloads: Vec<WebhookPayload>,
+    /// The cursor to list the next payloads from.
+    pub cursor: i64,
+    /// Whether there are more payloads after the cursor already.
+    #[serde(default)]
+    pub might_have_more: bool,
+}
+
+/// A change to a base.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub timestamp: DateTime<Utc>,
+    pub base_transaction_number: i64,
+    #[serde(default)]
+    pub payload_format: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_metadata: Option<ActionMetadata>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub changed_tables_by_id: HashMap<String, TableChanges>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub created_tables_by_id: HashMap<String, Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destroyed_table_ids: Vec<String>,
+    /// Set when Airtable could not build the payload, the changes are missing then.
+    #[serde(default)]
+    pub error: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub code: String,
+}
+
+/// What made a change, `client` for a person, `automation`, `publicApi` and so on.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionMetadata {
+    #[serde(default)]
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub source_metadata: Value,
+}
+
+/// The changes to the records of a table.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableChanges {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub created_records_by_id: HashMap<String, CreatedRecord>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub changed_records_by_id: HashMap<String, ChangedRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destroyed_record_ids: Vec<String>,
+}
+
+/// A record that was created, with its cells by field id.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub cell_values_by_field_id: HashMap<String, Value>,
+}
+
+/// A record that was changed. `current` has the cells that changed, `previous` what they
+/// were before if the webhook asked for it.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+pub struct ChangedRecord {
+    #[serde(default)]
+    pub current: RecordCells,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<RecordCells>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unchanged: Option<RecordCells>,
+}
+
+/// The cells of a record by field id.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordCells {
+    #[serde(default)]
+    pub cell_values_by_field_id: HashMap<String, Value>,
+}
+
+impl Airtable {
+    /// Create a webhook on the base that notifies the URL of changes to the records.
+    /// Keep the MAC secret it returns, it is needed to verify the notifications.
+    /// FROM: https://airtable.com/developers/web/api/create-a-webhook
+    pub async fn create_webhook(&self, notification_url: &str) -> Result<CreatedWebhook> {
+        let url = Url::parse(&self.endpoint)?.join(&format!("bases/{}/webhooks", self.base_id))?;
+        let body = serde_json::json!({
+            "notificationUrl": notification_url,
+            "specification": {"options": {"filters": {"dataTypes": ["tableData"]}}},
+        });
+
+        // Build the request.
+        let request = self.request_url(Method::POST, url, body, None)?;
+
+        let resp = self.execute(request).await?;
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => return Err(ApiError::from_response(resp).await.into()),
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// List the payloads of a webhook after the cursor, starting at 1 for the first.
+    /// FROM: https://airtable.com/developers/web/api/list-webhook-payloads
+    pub async fn list_webhook_payloads(&self, webhook_id: &str, cursor: i64) -> Result<WebhookPayloads> {
+        let url =
+            Url::parse(&self.endpoint)?.join(&format!("bases/{}/webhooks/{}/payloads", self.base_id, webhook_id))?;
+
+        // Build the request.
+        let request = self.request_url(Method::GET, url, (), Some(vec![("cursor", cursor.to_string())]))?;
+
+        let resp = self.execute(request).await?;
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => return Err(ApiError::from_response(resp).await.into()),
+        };
+
+        Ok(resp.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_webhook_mac() {
+        let secret = base64::encode(b"the mac secret");
+        let body = br#"{"base":{"id":"app1"},"webhook":{"id":"ach1"},"timestamp":"2022-02-01T21:25:05.663Z"}"#;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"the mac secret").unwrap();
+        mac.update(body);
+        let header = format!("hmac-sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        verify_webhook_mac(&secret, body, &header).unwrap();
+        assert!(verify_webhook_mac(&secret, b"{}", &header).is_err());
+        assert!(verify_webhook_mac(&secret, body, header.trim_start_matches(MAC_PREFIX)).is_err());
+
+        let notification: WebhookNotification = serde_json::from_slice(body).unwrap();
+        assert_eq!(notification.base.id, "app1");
+        assert_eq!(notification.webhook.id, "ach1");
+    }
+
+    #[test]
+    fn test_webhook_payload() {
+        let payloads: WebhookPayloads = serde_json::from_value(serde_json::json!({
+            "cursor": 2,
+            "mightHaveMore": false,
+            "payloads": [{
+                "timestamp": "2022-02-01T21:25:05.663Z",
+                "baseTransactionNumber": 4,
+                "payloadFormat": "v0",
+                "actionMetadata": {"source": "client", "sourceMetadata": {"user": {"id": "usr1"}}},
+                "changedTablesById": {
+                    "tbl1": {
+                        "changedRecordsById": {
+                            "rec1": {
+                                "current": {"cellValuesByFieldId": {"fld1": "new"}},
+                                "previous": {"cellValuesByFieldId": {"fld1": "old"}}
+                            }
+                        },
+                        "destroyedRecordIds": ["rec2"]
+                    }
+                }
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(payloads.cursor, 2);
+        let changes = &payloads.payloads[0].changed_tables_by_id["tbl1"];
+        assert_eq!(
+            changes.changed_records_by_id["rec1"].current.cell_values_by_field_id["fld1"],
+            "new"
+        );
+        assert_eq!(changes.destroyed_record_ids, vec!["rec2"]);
+        assert!(changes.created_records_by_id.is_empty());
+    }
+}
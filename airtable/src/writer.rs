@@ -0,0 +1,278 @@
+//! Writing a stream of changes to a table in as few requests as possible.
+//!
+//! Airtable takes at most 10 records per create, update or delete request. The writer
+//! collects the operations it is sent into full batches of each kind, and sends a batch as
+//! soon as it fills up, through the rate limiter of the client. Producers feed it through a
+//! bounded channel, so they wait while the writer is behind instead of queueing up the
+//! whole table in memory.
+use std::{future::Future, mem};
+
+use anyhow::{anyhow, Result};
+use futures::{channel::mpsc, SinkExt, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Airtable, Record};
+
+/// The most records Airtable takes in a single write request.
+pub const MAX_RECORDS_PER_REQUEST: usize = 10;
+
+/// A change to a record in a table.
+#[derive(Debug, Clone)]
+pub enum Operation<T> {
+    /// Create a record, its id is ignored.
+    Create(Record<T>),
+    /// Update the fields of a record.
+    Update(Record<T>),
+    /// Delete the record with the id.
+    Delete(String),
+}
+
+/// The records written by a writer.
+#[derive(Debug, Clone)]
+pub struct WriteSummary<T> {
+    /// The records that were created, with their new ids.
+    pub created: Vec<Record<T>>,
+    pub updated: Vec<Record<T>>,
+    pub deleted: usize,
+}
+
+impl<T> Default for WriteSummary<T> {
+    fn default() -> Self {
+        WriteSummary {
+            created: Default::default(),
+            updated: Default::default(),
+            deleted: 0,
+        }
+    }
+}
+
+/// The sending half of a writer, see `Airtable::writer`.
+///
+/// Drop every clone of it once all the operations are sent, so the writer sends the last
+/// batches and finishes.
+#[derive(Debug)]
+pub struct AirtableWriter<T> {
+    sender: mpsc::Sender<Operation<T>>,
+}
+
+impl<T> Clone for AirtableWriter<T> {
+    fn clone(&self) -> Self {
+        AirtableWriter {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> AirtableWriter<T> {
+    /// Queue an operation, waiting while the buffer of the writer is full.
+    ///
+    /// Fails if the writer stopped, because a request failed.
+    pub async fn send(&mut self, operation: Operation<T>) -> Result<()> {
+        self.sender
+            .send(operation)
+            .await
+            .map_err(|_| anyhow!("the airtable writer stopped"))
+    }
+
+    /// Queue the creation of a record with the fields.
+    pub async fn create(&mut self, fields: T) -> Result<()> {
+        self.send(Operation::Create(Record {
+            id: String::new(),
+            fields,
+            created_time: None,
+        }))
+        .await
+    }
+
+    /// Queue an update of the record with the id to the fields.
+    pub async fn update(&mut self, id: &str, fields: T) -> Result<()> {
+        self.send(Operation::Update(Record {
+            id: id.to_string(),
+            fields,
+            created_time: None,
+        }))
+        .await
+    }
+
+    /// Queue the deletion of the record with the id.
+    pub async fn delete(&mut self, id: &str) -> Result<()> {
+        self.send(Operation::Delete(id.to_string())).await
+    }
+}
+
+/// A full or final batch of operations of one kind.
+#[derive(Debug)]
+enum Batch<T> {
+    Create(Vec<Record<T>>),
+    Update(Vec<Record<T>>),
+    Delete(Vec<String>),
+}
+
+/// Groups operations into batches of `MAX_RECORDS_PER_REQUEST`.
+#[derive(Debug)]
+struct Batcher<T> {
+    creates: Vec<Record<T>>,
+    updates: Vec<Record<T>>,
+    deletes: Vec<String>,
+}
+
+impl<T> Default for Batcher<T> {
+    fn default() -> Self {
+        Batcher {
+            creates: Default::default(),
+            updates: Default::default(),
+            deletes: Default::default(),
+        }
+    }
+}
+
+impl<T> Batcher<T> {
+    /// Add an operation, returning its batch if it is now full.
+    fn push(&mut self, operation: Operation<T>) -> Option<Batch<T>> {
+        match operation {
+            Operation::Create(record) => {
+                self.creates.push(record);
+                (self.creates.len() == MAX_RECORDS_PER_REQUEST).then(|| Batch::Create(mem::take(&mut self.creates)))
+            }
+            Operation::Update(record) => {
+                self.updates.push(record);
+                (self.updates.len() == MAX_RECORDS_PER_REQUEST).then(|| Batch::Update(mem::take(&mut self.updates)))
+            }
+            Operation::Delete(id) => {
+                self.deletes.push(id);
+                (self.deletes.len() == MAX_RECORDS_PER_REQUEST).then(|| Batch::Delete(mem::take(&mut self.deletes)))
+            }
+        }
+    }
+
+    /// Returns the batches that are not full, once there are no more operations.
+    fn finish(self) -> Vec<Batch<T>> {
+        let mut batches = Vec::new();
+        if !self.creates.is_empty() {
+            batches.push(Batch::Create(self.creates));
+        }
+        if !self.updates.is_empty() {
+            batches.push(Batch::Update(self.updates));
+        }
+        if !self.deletes.is_empty() {
+            batches.push(Batch::Delete(self.deletes));
+        }
+
+        batches
+    }
+}
+
+impl Airtable {
+    /// Returns a writer for the table, and the future that does the writing.
+    ///
+    /// The future has to be polled alongside the code sending to the writer, for example
+    /// with `futures::try_join!`, and finishes once every clone of the writer is dropped.
+    /// At most `buffer` operations wait in the writer, after that sending waits for the
+    /// batches in flight. See `write_records` for how the operations are sent.
+    pub fn writer<'a, T>(
+        &'a self,
+        table: &'a str,
+        buffer: usize,
+    ) -> (AirtableWriter<T>, impl Future<Output = Result<WriteSummary<T>>> + 'a)
+    where
+        T: Serialize + DeserializeOwned + 'a,
+    {
+        let (sender, receiver) = mpsc::channel(buffer);
+
+        (AirtableWriter { sender }, self.write_records(table, receiver))
+    }
+
+    /// Write a stream of operations to a table, pulling the next operation only once the
+    /// previous batch is sent.
+    ///
+    /// Each kind of operation is sent in batches of `MAX_RECORDS_PER_REQUEST`, and what is
+    /// left over once the stream ends is sent last. Operations of the same kind are sent in
+    /// order, but not in order with the other kinds, so don't send more than one operation
+    /// for a record. Stops at the first request that fails.
+    pub async fn write_records<T, S>(&self, table: &str, operations: S) -> Result<WriteSummary<T>>
+    where
+        T: Serialize + DeserializeOwned,
+        S: Stream<Item = Operation<T>>,
+    {
+        futures::pin_mut!(operations);
+
+        let mut summary = WriteSummary::default();
+        let mut batcher = Batcher::default();
+        while let Some(operation) = operations.next().await {
+            if let Some(batch) = batcher.push(operation) {
+                self.write_batch(table, batch, &mut summary).await?;
+            }
+        }
+        for batch in batcher.finish() {
+            self.write_batch(table, batch, &mut summary).await?;
+        }
+
+        log::debug!(
+            "[airtable-api] Wrote to `{}`: {} created, {} updated, {} deleted",
+            table,
+            summary.created.len(),
+            summary.updated.len(),
+            summary.deleted
+        );
+
+        Ok(summary)
+    }
+
+    async fn write_batch<T: Serialize + DeserializeOwned>(
+        &self,
+        table: &str,
+        batch: Batch<T>,
+        summary: &mut WriteSummary<T>,
+    ) -> Result<()> {
+        match batch {
+            Batch::Create(records) => summary.created.extend(self.create_records(table, records).await?),
+            Batch::Update(records) => summary.updated.extend(self.update_records(table, records).await?),
+            Batch::Delete(ids) => {
+                self.delete_records(table, ids.iter().map(|id| id.as_str())).await?;
+                summary.deleted += ids.len();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str) -> Record<u32> {
+        Record {
+            id: id.to_string(),
+            fields: 0,
+            created_time: None,
+        }
+    }
+
+    #[test]
+    fn test_batcher() {
+        let mut batcher = Batcher::default();
+        for i in 0..MAX_RECORDS_PER_REQUEST - 1 {
+            assert!(batcher.push(Operation::Update(record(&i.to_string()))).is_none());
+            assert!(batcher.push(Operation::Delete(i.to_string())).is_none());
+        }
+        assert!(batcher.push(Operation::Create(record(""))).is_none());
+
+        // The tenth update fills its batch, the others keep waiting.
+        match batcher.push(Operation::Update(record("9"))) {
+            Some(Batch::Update(records)) => {
+                let ids: Vec<String> = records.into_iter().map(|r| r.id).collect();
+                assert_eq!(
+                    ids,
+                    (0..MAX_RECORDS_PER_REQUEST).map(|i| i.to_string()).collect::<Vec<_>>()
+                );
+            }
+            batch => panic!("expected a batch of updates, got {:?}", batch),
+        }
+
+        let batches = batcher.finish();
+        assert_eq!(batches.len(), 2);
+        assert!(matches!(&batches[0], Batch::Create(records) if records.len() == 1));
+        assert!(matches!(&batches[1], Batch::Delete(ids) if ids.len() == MAX_RECORDS_PER_REQUEST - 1));
+    }
+}
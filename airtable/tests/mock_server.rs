@@ -0,0 +1,93 @@
+use airtable_api::{
+    testing::{fixtures, MockAirtable, USERS_TABLE_RECORDS},
+    Operation, Record,
+};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn test_list_records_follows_pages() {
+    let airtable = MockAirtable::start("appTest").await;
+    airtable.mount_list_records("Users").await;
+
+    let records: Vec<Record<Value>> = airtable.client().list_records("Users", "", vec![]).await.unwrap();
+
+    assert_eq!(records.len(), USERS_TABLE_RECORDS);
+    assert_eq!(records[0].fields["Email"], json!("jess@oxide.computer"));
+    assert_eq!(records[2].id, "recUser00000003");
+}
+
+#[tokio::test]
+async fn test_list_records_retries_rate_limits() {
+    let airtable = MockAirtable::start("appTest").await;
+    airtable.mount_list_records("Users").await;
+    airtable.mount_rate_limited("GET", "Users", 1).await;
+
+    let records: Vec<Record<Value>> = airtable.client().list_records("Users", "", vec![]).await.unwrap();
+
+    assert_eq!(records.len(), USERS_TABLE_RECORDS);
+    // The rate limited request, its retry and the second page.
+    let requests = airtable.server().received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3);
+}
+
+#[tokio::test]
+async fn test_writer_batches_creates() {
+    let airtable = MockAirtable::start("appTest").await;
+    airtable.mount_base_schema().await;
+    airtable.mount_create_records("Users").await;
+
+    let operations = (0..12).map(|i| {
+        Operation::Create(Record {
+            id: String::new(),
+            fields: json!({ "Email": format!("user{}@oxide.computer", i), "Last Modified": "ignored" }),
+            created_time: None,
+        })
+    });
+    let written = airtable
+        .client()
+        .write_records("Users", futures::stream::iter(operations))
+        .await
+        .unwrap();
+
+    // One record comes back for each of the two requests of the fixtures.
+    assert_eq!(written.created.len(), 2);
+
+    let creates: Vec<Value> = airtable
+        .server()
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|r| r.method.to_string() == "POST")
+        .map(|r| r.body_json().unwrap())
+        .collect();
+    assert_eq!(creates.len(), 2);
+    assert_eq!(creates[0]["records"].as_array().unwrap().len(), 10);
+    assert_eq!(creates[1]["records"].as_array().unwrap().len(), 2);
+    // The computed field is left out of the writes.
+    assert!(creates[0]["records"][0]["fields"].get("Last Modified").is_none());
+}
+
+#[tokio::test]
+async fn test_update_records_error() {
+    let airtable = MockAirtable::start("appTest").await;
+    airtable.mount_base_schema().await;
+    airtable
+        .mount_error("PATCH", "Users", 422, fixtures::INVALID_VALUE)
+        .await;
+
+    let err = airtable
+        .client()
+        .update_records(
+            "Users",
+            vec![Record {
+                id: "recUser00000001".to_string(),
+                fields: json!({ "Logins": "many" }),
+                created_time: None,
+            }],
+        )
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("INVALID_VALUE_FOR_COLUMN"), "{}", err);
+}
@@ -0,0 +1,13 @@
+CREATE TABLE auth_user_roles (
+    id SERIAL PRIMARY KEY,
+    user_id VARCHAR NOT NULL,
+    email VARCHAR NOT NULL,
+    role_id VARCHAR NOT NULL,
+    role_name VARCHAR NOT NULL,
+    role_description VARCHAR NOT NULL,
+    permissions TEXT [] NOT NULL,
+    link_to_auth_user TEXT [] NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (user_id, role_id)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE auth_user_roles DROP COLUMN tenant;
+ALTER TABLE auth_user_logins DROP COLUMN tenant;
+
+ALTER TABLE auth_users DROP CONSTRAINT IF EXISTS auth_users_user_id_tenant_key;
+ALTER TABLE auth_users ADD CONSTRAINT auth_users_user_id_key UNIQUE (user_id);
+ALTER TABLE auth_users DROP COLUMN tenant;
@@ -0,0 +1,6 @@
+ALTER TABLE auth_users ADD COLUMN tenant VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_users DROP CONSTRAINT IF EXISTS auth_users_user_id_key;
+ALTER TABLE auth_users ADD CONSTRAINT auth_users_user_id_tenant_key UNIQUE (user_id, tenant);
+
+ALTER TABLE auth_user_logins ADD COLUMN tenant VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_roles ADD COLUMN tenant VARCHAR NOT NULL DEFAULT '';
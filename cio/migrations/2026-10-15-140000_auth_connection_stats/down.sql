@@ -0,0 +1 @@
+DROP TABLE auth_connection_stats
@@ -0,0 +1,12 @@
+CREATE TABLE auth_connection_stats (
+    id SERIAL PRIMARY KEY,
+    date DATE NOT NULL,
+    connection_id VARCHAR NOT NULL,
+    connection_name VARCHAR NOT NULL,
+    strategy VARCHAR NOT NULL,
+    user_count BIGINT NOT NULL,
+    tenant VARCHAR NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (date, tenant, connection_id)
+);
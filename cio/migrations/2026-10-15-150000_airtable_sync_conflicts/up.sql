@@ -0,0 +1,12 @@
+CREATE TABLE airtable_sync_conflicts (
+    id SERIAL PRIMARY KEY,
+    table_name VARCHAR NOT NULL,
+    record_key VARCHAR NOT NULL,
+    field VARCHAR NOT NULL,
+    database_value VARCHAR NOT NULL,
+    airtable_value VARCHAR NOT NULL,
+    resolution VARCHAR NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT ''
+);
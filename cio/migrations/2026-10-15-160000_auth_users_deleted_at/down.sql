@@ -0,0 +1 @@
+ALTER TABLE auth_users DROP COLUMN deleted_at;
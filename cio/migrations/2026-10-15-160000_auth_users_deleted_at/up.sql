@@ -0,0 +1 @@
+ALTER TABLE auth_users ADD COLUMN deleted_at TIMESTAMPTZ;
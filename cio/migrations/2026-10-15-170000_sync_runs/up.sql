@@ -0,0 +1,16 @@
+CREATE TABLE sync_runs (
+    id SERIAL PRIMARY KEY,
+    job VARCHAR NOT NULL,
+    status VARCHAR NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ,
+    records_created INTEGER NOT NULL DEFAULT 0,
+    records_updated INTEGER NOT NULL DEFAULT 0,
+    records_deleted INTEGER NOT NULL DEFAULT 0,
+    errors INTEGER NOT NULL DEFAULT 0,
+    error VARCHAR NOT NULL DEFAULT '',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT ''
+);
+
+CREATE INDEX sync_runs_job_started_at_idx ON sync_runs (job, started_at DESC);
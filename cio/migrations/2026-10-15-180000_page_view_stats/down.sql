@@ -0,0 +1 @@
+DROP TABLE page_view_stats
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE page_view_stats (
+    id SERIAL PRIMARY KEY,
+    date DATE NOT NULL,
+    domain VARCHAR NOT NULL,
+    path VARCHAR NOT NULL,
+    user_email VARCHAR NOT NULL,
+    page_link VARCHAR NOT NULL,
+    views BIGINT NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (date, domain, path, user_email)
+);
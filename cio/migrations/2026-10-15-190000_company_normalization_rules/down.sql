@@ -0,0 +1 @@
+DROP TABLE company_normalization_rules
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE company_normalization_rules (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    pattern VARCHAR NOT NULL,
+    company VARCHAR NOT NULL DEFAULT '',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (kind, pattern, cio_company_id)
+);
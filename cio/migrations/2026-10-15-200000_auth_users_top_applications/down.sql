@@ -0,0 +1 @@
+ALTER TABLE auth_users DROP COLUMN top_applications;
\ No newline at end of file
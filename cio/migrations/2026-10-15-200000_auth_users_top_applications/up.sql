@@ -0,0 +1 @@
+ALTER TABLE auth_users ADD COLUMN top_applications TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1 @@
+DROP TABLE auth_user_sync_checkpoints
\ No newline at end of file
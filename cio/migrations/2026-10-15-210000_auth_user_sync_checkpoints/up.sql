@@ -0,0 +1,12 @@
+CREATE TABLE auth_user_sync_checkpoints (
+    id SERIAL PRIMARY KEY,
+    tenant VARCHAR NOT NULL,
+    q VARCHAR NOT NULL DEFAULT '',
+    next_page INTEGER NOT NULL DEFAULT 0,
+    last_user_id VARCHAR NOT NULL DEFAULT '',
+    started_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (tenant, cio_company_id)
+);
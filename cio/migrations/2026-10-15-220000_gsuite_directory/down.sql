@@ -0,0 +1,2 @@
+DROP TABLE gsuite_directory_groups;
+DROP TABLE gsuite_directory_users;
\ No newline at end of file
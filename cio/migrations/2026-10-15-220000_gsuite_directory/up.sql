@@ -0,0 +1,30 @@
+CREATE TABLE gsuite_directory_users (
+    id SERIAL PRIMARY KEY,
+    gsuite_id VARCHAR NOT NULL DEFAULT '',
+    primary_email VARCHAR NOT NULL,
+    full_name VARCHAR NOT NULL DEFAULT '',
+    aliases TEXT[] NOT NULL DEFAULT '{}',
+    org_unit_path VARCHAR NOT NULL DEFAULT '',
+    is_admin BOOLEAN NOT NULL DEFAULT false,
+    suspended BOOLEAN NOT NULL DEFAULT false,
+    last_login_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ,
+    groups TEXT[] NOT NULL DEFAULT '{}',
+    link_to_auth_users TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (primary_email, cio_company_id)
+);
+
+CREATE TABLE gsuite_directory_groups (
+    id SERIAL PRIMARY KEY,
+    gsuite_id VARCHAR NOT NULL DEFAULT '',
+    email VARCHAR NOT NULL,
+    name VARCHAR NOT NULL DEFAULT '',
+    description VARCHAR NOT NULL DEFAULT '',
+    aliases TEXT[] NOT NULL DEFAULT '{}',
+    members TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (email, cio_company_id)
+);
@@ -0,0 +1 @@
+DROP TABLE github_org_members
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE github_org_members (
+    id SERIAL PRIMARY KEY,
+    login VARCHAR NOT NULL,
+    github_id BIGINT NOT NULL,
+    role VARCHAR NOT NULL,
+    teams TEXT[] NOT NULL DEFAULT '{}',
+    site_admin BOOLEAN NOT NULL DEFAULT false,
+    link_to_auth_users TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (login, cio_company_id)
+);
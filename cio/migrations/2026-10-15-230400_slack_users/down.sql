@@ -0,0 +1 @@
+DROP TABLE slack_users
\ No newline at end of file
@@ -0,0 +1,20 @@
+CREATE TABLE slack_users (
+    id SERIAL PRIMARY KEY,
+    slack_id VARCHAR NOT NULL,
+    name VARCHAR NOT NULL DEFAULT '',
+    real_name VARCHAR NOT NULL DEFAULT '',
+    display_name VARCHAR NOT NULL DEFAULT '',
+    email VARCHAR NOT NULL DEFAULT '',
+    title VARCHAR NOT NULL DEFAULT '',
+    is_admin BOOLEAN NOT NULL DEFAULT false,
+    is_owner BOOLEAN NOT NULL DEFAULT false,
+    is_guest BOOLEAN NOT NULL DEFAULT false,
+    deleted BOOLEAN NOT NULL DEFAULT false,
+    has_2fa BOOLEAN NOT NULL DEFAULT false,
+    tz VARCHAR NOT NULL DEFAULT '',
+    link_to_auth_users TEXT[] NOT NULL DEFAULT '{}',
+    link_to_people TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (slack_id, cio_company_id)
+);
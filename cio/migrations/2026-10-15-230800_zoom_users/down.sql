@@ -0,0 +1 @@
+DROP TABLE zoom_users
\ No newline at end of file
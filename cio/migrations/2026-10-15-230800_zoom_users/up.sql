@@ -0,0 +1,16 @@
+CREATE TABLE zoom_users (
+    id SERIAL PRIMARY KEY,
+    zoom_id VARCHAR NOT NULL,
+    email VARCHAR NOT NULL,
+    first_name VARCHAR NOT NULL DEFAULT '',
+    last_name VARCHAR NOT NULL DEFAULT '',
+    department VARCHAR NOT NULL DEFAULT '',
+    license_type VARCHAR NOT NULL,
+    status VARCHAR NOT NULL,
+    last_login_at TIMESTAMPTZ,
+    inactive_license BOOLEAN NOT NULL DEFAULT false,
+    link_to_auth_users TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (zoom_id, cio_company_id)
+);
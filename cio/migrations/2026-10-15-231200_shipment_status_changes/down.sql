@@ -0,0 +1 @@
+DROP TABLE shipment_status_changes;
\ No newline at end of file
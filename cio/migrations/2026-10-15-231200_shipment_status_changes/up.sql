@@ -0,0 +1,15 @@
+CREATE TABLE shipment_status_changes (
+    id SERIAL PRIMARY KEY,
+    direction VARCHAR NOT NULL,
+    carrier VARCHAR NOT NULL,
+    tracking_number VARCHAR NOT NULL,
+    from_status VARCHAR NOT NULL DEFAULT '',
+    to_status VARCHAR NOT NULL,
+    status_details VARCHAR NOT NULL DEFAULT '',
+    changed_at TIMESTAMPTZ NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (tracking_number, changed_at)
+);
+
+CREATE INDEX shipment_status_changes_tracking_number_idx ON shipment_status_changes (carrier, tracking_number);
@@ -0,0 +1 @@
+DROP TABLE gusto_employees;
\ No newline at end of file
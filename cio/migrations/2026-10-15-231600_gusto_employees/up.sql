@@ -0,0 +1,16 @@
+CREATE TABLE gusto_employees (
+    id SERIAL PRIMARY KEY,
+    gusto_id VARCHAR NOT NULL,
+    first_name VARCHAR NOT NULL DEFAULT '',
+    last_name VARCHAR NOT NULL DEFAULT '',
+    email VARCHAR NOT NULL DEFAULT '',
+    work_email VARCHAR NOT NULL DEFAULT '',
+    title VARCHAR NOT NULL DEFAULT '',
+    department VARCHAR NOT NULL DEFAULT '',
+    start_date DATE,
+    terminated BOOLEAN NOT NULL DEFAULT false,
+    link_to_people TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (gusto_id, cio_company_id)
+);
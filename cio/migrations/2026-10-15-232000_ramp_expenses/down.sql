@@ -0,0 +1 @@
+DROP TABLE ramp_expenses;
\ No newline at end of file
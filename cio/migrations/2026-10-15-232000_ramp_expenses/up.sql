@@ -0,0 +1,18 @@
+CREATE TABLE ramp_expenses (
+    id SERIAL PRIMARY KEY,
+    ramp_id VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    amount REAL NOT NULL DEFAULT 0,
+    currency VARCHAR NOT NULL DEFAULT '',
+    merchant_name VARCHAR NOT NULL DEFAULT '',
+    category_name VARCHAR NOT NULL DEFAULT '',
+    state VARCHAR NOT NULL DEFAULT '',
+    memo VARCHAR NOT NULL DEFAULT '',
+    employee_email VARCHAR NOT NULL DEFAULT '',
+    time TIMESTAMPTZ NOT NULL,
+    receipts TEXT[] NOT NULL DEFAULT '{}',
+    link_to_people TEXT[] NOT NULL DEFAULT '{}',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (ramp_id, cio_company_id)
+);
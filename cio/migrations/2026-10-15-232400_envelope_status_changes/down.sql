@@ -0,0 +1 @@
+DROP TABLE envelope_status_changes;
\ No newline at end of file
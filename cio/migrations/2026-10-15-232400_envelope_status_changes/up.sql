@@ -0,0 +1,12 @@
+CREATE TABLE envelope_status_changes (
+    id SERIAL PRIMARY KEY,
+    envelope_id VARCHAR NOT NULL,
+    kind VARCHAR NOT NULL,
+    applicant_email VARCHAR NOT NULL,
+    from_status VARCHAR NOT NULL DEFAULT '',
+    to_status VARCHAR NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (envelope_id, to_status)
+);
@@ -0,0 +1,7 @@
+ALTER TABLE auth_user_logins DROP COLUMN city;
+ALTER TABLE auth_user_logins DROP COLUMN country;
+
+ALTER TABLE auth_users DROP COLUMN last_ip_city;
+ALTER TABLE auth_users DROP COLUMN last_ip_country;
+
+DROP TABLE ip_locations;
\ No newline at end of file
@@ -0,0 +1,17 @@
+CREATE TABLE ip_locations (
+    id SERIAL PRIMARY KEY,
+    ip VARCHAR NOT NULL,
+    country VARCHAR NOT NULL DEFAULT '',
+    region VARCHAR NOT NULL DEFAULT '',
+    city VARCHAR NOT NULL DEFAULT '',
+    resolved_at TIMESTAMPTZ NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (ip, cio_company_id)
+);
+
+ALTER TABLE auth_users ADD COLUMN last_ip_country VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_users ADD COLUMN last_ip_city VARCHAR NOT NULL DEFAULT '';
+
+ALTER TABLE auth_user_logins ADD COLUMN country VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_logins ADD COLUMN city VARCHAR NOT NULL DEFAULT '';
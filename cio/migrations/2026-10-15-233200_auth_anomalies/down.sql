@@ -0,0 +1 @@
+DROP TABLE auth_anomalies;
\ No newline at end of file
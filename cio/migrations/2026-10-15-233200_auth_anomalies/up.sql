@@ -0,0 +1,16 @@
+CREATE TABLE auth_anomalies (
+    id SERIAL PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    user_id VARCHAR NOT NULL,
+    email VARCHAR NOT NULL DEFAULT '',
+    log_id VARCHAR NOT NULL,
+    date TIMESTAMPTZ NOT NULL,
+    ip VARCHAR NOT NULL DEFAULT '',
+    country VARCHAR NOT NULL DEFAULT '',
+    city VARCHAR NOT NULL DEFAULT '',
+    description VARCHAR NOT NULL DEFAULT '',
+    tenant VARCHAR NOT NULL DEFAULT '',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (kind, log_id, cio_company_id)
+);
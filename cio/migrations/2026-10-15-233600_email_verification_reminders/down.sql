@@ -0,0 +1 @@
+DROP TABLE email_verification_reminders;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE email_verification_reminders (
+    id SERIAL PRIMARY KEY,
+    user_id VARCHAR NOT NULL,
+    email VARCHAR NOT NULL DEFAULT '',
+    job_id VARCHAR NOT NULL DEFAULT '',
+    reminders_sent INTEGER NOT NULL DEFAULT 0,
+    last_sent_at TIMESTAMPTZ NOT NULL,
+    tenant VARCHAR NOT NULL DEFAULT '',
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (user_id, tenant, cio_company_id)
+);
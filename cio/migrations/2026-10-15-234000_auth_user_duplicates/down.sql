@@ -0,0 +1 @@
+ALTER TABLE auth_users DROP COLUMN duplicate_of;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE auth_users ADD COLUMN duplicate_of VARCHAR NOT NULL DEFAULT '';
@@ -0,0 +1 @@
+DROP TABLE airtable_list_offsets
\ No newline at end of file
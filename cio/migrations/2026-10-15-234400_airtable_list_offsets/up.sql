@@ -0,0 +1,12 @@
+CREATE TABLE airtable_list_offsets (
+    id SERIAL PRIMARY KEY,
+    base_id VARCHAR NOT NULL,
+    table_name VARCHAR NOT NULL,
+    view VARCHAR NOT NULL DEFAULT '',
+    formula VARCHAR NOT NULL DEFAULT '',
+    next_offset VARCHAR NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    cio_company_id INTEGER NOT NULL REFERENCES companys(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    airtable_record_id VARCHAR NOT NULL DEFAULT '',
+    UNIQUE (base_id, table_name, view, formula)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE auth_user_logins DROP COLUMN device;
+ALTER TABLE auth_user_logins DROP COLUMN os;
+ALTER TABLE auth_user_logins DROP COLUMN browser;
+ALTER TABLE auth_user_logins DROP COLUMN outcome;
+ALTER TABLE auth_user_logins DROP COLUMN event;
@@ -0,0 +1,5 @@
+ALTER TABLE auth_user_logins ADD COLUMN event VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_logins ADD COLUMN outcome VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_logins ADD COLUMN browser VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_logins ADD COLUMN os VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_logins ADD COLUMN device VARCHAR NOT NULL DEFAULT '';
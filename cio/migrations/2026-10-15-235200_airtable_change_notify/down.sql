@@ -0,0 +1,3 @@
+DROP TRIGGER page_views_notify_change ON page_views;
+DROP TRIGGER auth_users_notify_change ON auth_users;
+DROP FUNCTION cio_notify_change();
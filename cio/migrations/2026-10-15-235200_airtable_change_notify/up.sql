@@ -0,0 +1,19 @@
+-- Tell the listeners on `cio_changes` which rows changed, so they push them to Airtable.
+-- Saving the Airtable id of a row is the push itself, so it is not a change.
+CREATE FUNCTION cio_notify_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (to_jsonb(NEW) - 'airtable_record_id') = (to_jsonb(OLD) - 'airtable_record_id') THEN
+        RETURN NEW;
+    END IF;
+    PERFORM pg_notify('cio_changes', json_build_object('table', TG_TABLE_NAME, 'id', NEW.id)::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER auth_users_notify_change
+    AFTER INSERT OR UPDATE ON auth_users
+    FOR EACH ROW EXECUTE FUNCTION cio_notify_change();
+
+CREATE TRIGGER page_views_notify_change
+    AFTER INSERT OR UPDATE ON page_views
+    FOR EACH ROW EXECUTE FUNCTION cio_notify_change();
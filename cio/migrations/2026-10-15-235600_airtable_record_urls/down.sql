@@ -0,0 +1,28 @@
+CREATE OR REPLACE FUNCTION cio_notify_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (to_jsonb(NEW) - 'airtable_record_id') = (to_jsonb(OLD) - 'airtable_record_id') THEN
+        RETURN NEW;
+    END IF;
+    PERFORM pg_notify('cio_changes', json_build_object('table', TG_TABLE_NAME, 'id', NEW.id)::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE zoom_users DROP COLUMN airtable_record_url;
+ALTER TABLE sync_runs DROP COLUMN airtable_record_url;
+ALTER TABLE slack_users DROP COLUMN airtable_record_url;
+ALTER TABLE rfds DROP COLUMN airtable_record_url;
+ALTER TABLE ramp_expenses DROP COLUMN airtable_record_url;
+ALTER TABLE page_views DROP COLUMN airtable_record_url;
+ALTER TABLE page_view_stats DROP COLUMN airtable_record_url;
+ALTER TABLE outbound_shipments DROP COLUMN airtable_record_url;
+ALTER TABLE mailing_list_subscribers DROP COLUMN airtable_record_url;
+ALTER TABLE inbound_shipments DROP COLUMN airtable_record_url;
+ALTER TABLE gsuite_directory_users DROP COLUMN airtable_record_url;
+ALTER TABLE gsuite_directory_groups DROP COLUMN airtable_record_url;
+ALTER TABLE github_org_members DROP COLUMN airtable_record_url;
+ALTER TABLE auth_users DROP COLUMN airtable_record_url;
+ALTER TABLE auth_user_roles DROP COLUMN airtable_record_url;
+ALTER TABLE auth_user_logins DROP COLUMN airtable_record_url;
+ALTER TABLE auth_connection_stats DROP COLUMN airtable_record_url;
+ALTER TABLE applicants DROP COLUMN airtable_record_url;
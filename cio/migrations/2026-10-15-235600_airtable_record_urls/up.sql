@@ -0,0 +1,29 @@
+ALTER TABLE applicants ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_connection_stats ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_logins ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_user_roles ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE auth_users ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE github_org_members ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE gsuite_directory_groups ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE gsuite_directory_users ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE inbound_shipments ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE mailing_list_subscribers ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE outbound_shipments ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE page_view_stats ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE page_views ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE ramp_expenses ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE rfds ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE slack_users ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE sync_runs ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE zoom_users ADD COLUMN airtable_record_url VARCHAR NOT NULL DEFAULT '';
+
+-- Saving the link to the record in Airtable is part of the push too.
+CREATE OR REPLACE FUNCTION cio_notify_change() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (to_jsonb(NEW) - ARRAY['airtable_record_id', 'airtable_record_url']) = (to_jsonb(OLD) - ARRAY['airtable_record_id', 'airtable_record_url']) THEN
+        RETURN NEW;
+    END IF;
+    PERFORM pg_notify('cio_changes', json_build_object('table', TG_TABLE_NAME, 'id', NEW.id)::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
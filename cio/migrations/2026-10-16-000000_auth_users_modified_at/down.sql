@@ -0,0 +1,3 @@
+DROP TRIGGER auth_users_set_modified_at ON auth_users;
+DROP FUNCTION auth_users_set_modified_at();
+ALTER TABLE auth_users DROP COLUMN modified_at;
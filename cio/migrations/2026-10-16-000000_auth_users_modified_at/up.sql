@@ -0,0 +1,24 @@
+-- When the row last changed, for `ConflictPolicy::NewestWins`. Auth0 bumps `updated_at` on
+-- every login, so the activity it refreshes on a login is not a change here.
+ALTER TABLE auth_users ADD COLUMN modified_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE FUNCTION auth_users_set_modified_at() RETURNS trigger AS $$
+DECLARE
+    activity TEXT[] := ARRAY[
+        'updated_at', 'last_login', 'last_application_accessed', 'top_applications',
+        'last_ip', 'last_ip_country', 'last_ip_city', 'logins_count',
+        'airtable_record_id', 'airtable_record_url', 'modified_at'
+    ];
+BEGIN
+    IF TG_OP = 'INSERT' OR (to_jsonb(NEW) - activity) <> (to_jsonb(OLD) - activity) THEN
+        NEW.modified_at = now();
+    ELSE
+        NEW.modified_at = OLD.modified_at;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER auth_users_set_modified_at
+    BEFORE INSERT OR UPDATE ON auth_users
+    FOR EACH ROW EXECUTE FUNCTION auth_users_set_modified_at();
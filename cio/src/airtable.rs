@@ -1,9 +1,19 @@
 pub static AIRTABLE_MAILING_LIST_SIGNUPS_TABLE: &str = "Mailing List Signups";
 pub static AIRTABLE_RACK_LINE_SIGNUPS_TABLE: &str = "Rack Line Signups";
 pub static AIRTABLE_CUSTOMER_INTERACTIONS_TABLE: &str = "Interactions";
+pub static AIRTABLE_PEOPLE_TABLE: &str = "People";
 pub static AIRTABLE_AUTH_USERS_TABLE: &str = "Auth Users";
 pub static AIRTABLE_AUTH_USER_LOGINS_TABLE: &str = "Auth User Logins";
+pub static AIRTABLE_AUTH_USER_ROLES_TABLE: &str = "Auth User Roles";
+pub static AIRTABLE_AUTH_CONNECTION_STATS_TABLE: &str = "Auth Connection Stats";
 pub static AIRTABLE_PAGE_VIEWS_TABLE: &str = "Page Views";
+pub static AIRTABLE_PAGE_VIEW_STATS_TABLE: &str = "Page View Stats";
+pub static AIRTABLE_GSUITE_USERS_TABLE: &str = "GSuite Users";
+pub static AIRTABLE_GSUITE_GROUPS_TABLE: &str = "GSuite Groups";
+pub static AIRTABLE_GITHUB_MEMBERS_TABLE: &str = "GitHub Members";
+pub static AIRTABLE_SLACK_USERS_TABLE: &str = "Slack Users";
+pub static AIRTABLE_ZOOM_USERS_TABLE: &str = "Zoom Users";
+pub static AIRTABLE_RAMP_EXPENSES_TABLE: &str = "Ramp Expenses";
 
 pub static AIRTABLE_EMPLOYEES_TABLE: &str = "Employees";
 pub static AIRTABLE_GROUPS_TABLE: &str = "Groups";
@@ -45,6 +55,7 @@ pub static AIRTABLE_ASSET_ITEMS_TABLE: &str = "Items";
 pub static AIRTABLE_API_TOKENS_TABLE: &str = "API Tokens";
 pub static AIRTABLE_COMPANIES_TABLE: &str = "Companies";
 pub static AIRTABLE_FUNCTIONS_TABLE: &str = "Functions";
+pub static AIRTABLE_SYNC_RUNS_TABLE: &str = "Sync Log";
 
 pub static AIRTABLE_BOOKINGS_TABLE: &str = "Bookings";
 
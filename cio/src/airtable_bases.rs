@@ -0,0 +1,257 @@
+//! The Airtable bases we sync to, by the logical name models refer to them with.
+//!
+//! Models declare the base their table is in as an `AirtableBase`, and the `BaseRegistry`
+//! turns that into the id of the base and the key to authenticate with. The registry is
+//! read from the TOML file at the `CIO_AIRTABLE_BASES` environment variable, for example:
+//!
+//! ```toml
+//! [bases.directory]
+//! id = "appXXXXXXXXXXXXXX"
+//!
+//! [bases.hiring]
+//! id = "appYYYYYYYYYYYYYY"
+//! # A key for a base the company key has no access to.
+//! api_key = "patZZZZZZZZZZZZZZ"
+//! ```
+//!
+//! Bases missing from the file, or every base when the variable is not set, use the base
+//! ids and API key stored on the company.
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fmt, fs,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use airtable_api::{Airtable, RateLimiter, RequestOptions};
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::companies::Company;
+
+lazy_static! {
+    /// The rate limiter of each base, by id. Airtable limits the requests to a base, not
+    /// to a client, so every client of a base in the process shares one.
+    static ref RATE_LIMITERS: Mutex<HashMap<String, Arc<RateLimiter>>> = Default::default();
+}
+
+/// Returns a client for the base that reads the fields of records by name, the way our
+/// types and configs name them. Every client we build goes through here.
+pub fn airtable_client(api_key: &str, base_id: &str, enterprise_account_id: &str) -> Airtable {
+    Airtable::new(api_key, base_id, enterprise_account_id).with_options(RequestOptions {
+        return_fields_by_field_id: false,
+        ..Default::default()
+    })
+}
+
+/// Returns the rate limiter shared by the clients of a base.
+pub(crate) fn rate_limiter(base_id: &str) -> Arc<RateLimiter> {
+    RATE_LIMITERS
+        .lock()
+        .unwrap()
+        .entry(base_id.to_string())
+        .or_default()
+        .clone()
+}
+
+/// The logical name of an Airtable base.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AirtableBase {
+    CustomerLeads,
+    Directory,
+    Misc,
+    Roadmap,
+    Hiring,
+    Shipments,
+    Finance,
+    Swag,
+    Assets,
+    Travel,
+    Cio,
+}
+
+const AIRTABLE_BASES: [AirtableBase; 11] = [
+    AirtableBase::CustomerLeads,
+    AirtableBase::Directory,
+    AirtableBase::Misc,
+    AirtableBase::Roadmap,
+    AirtableBase::Hiring,
+    AirtableBase::Shipments,
+    AirtableBase::Finance,
+    AirtableBase::Swag,
+    AirtableBase::Assets,
+    AirtableBase::Travel,
+    AirtableBase::Cio,
+];
+
+impl AirtableBase {
+    /// The name of the base in the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AirtableBase::CustomerLeads => "customer_leads",
+            AirtableBase::Directory => "directory",
+            AirtableBase::Misc => "misc",
+            AirtableBase::Roadmap => "roadmap",
+            AirtableBase::Hiring => "hiring",
+            AirtableBase::Shipments => "shipments",
+            AirtableBase::Finance => "finance",
+            AirtableBase::Swag => "swag",
+            AirtableBase::Assets => "assets",
+            AirtableBase::Travel => "travel",
+            AirtableBase::Cio => "cio",
+        }
+    }
+
+    /// Returns the id of the base stored on the company.
+    pub fn company_base_id<'a>(&self, company: &'a Company) -> &'a str {
+        match self {
+            AirtableBase::CustomerLeads => &company.airtable_base_id_customer_leads,
+            AirtableBase::Directory => &company.airtable_base_id_directory,
+            AirtableBase::Misc => &company.airtable_base_id_misc,
+            AirtableBase::Roadmap => &company.airtable_base_id_roadmap,
+            AirtableBase::Hiring => &company.airtable_base_id_hiring,
+            AirtableBase::Shipments => &company.airtable_base_id_shipments,
+            AirtableBase::Finance => &company.airtable_base_id_finance,
+            AirtableBase::Swag => &company.airtable_base_id_swag,
+            AirtableBase::Assets => &company.airtable_base_id_assets,
+            AirtableBase::Travel => &company.airtable_base_id_travel,
+            AirtableBase::Cio => &company.airtable_base_id_cio,
+        }
+    }
+}
+
+impl fmt::Display for AirtableBase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AirtableBase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AIRTABLE_BASES
+            .iter()
+            .find(|b| b.name() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown airtable base `{}`", s))
+    }
+}
+
+/// Where a base is, and how to get in.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct BaseConfig {
+    /// The id of the base, for example `appXXXXXXXXXXXXXX`.
+    pub id: String,
+    /// The key to authenticate with. Empty uses the API key of the company.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub api_key: String,
+}
+
+/// The bases we sync to, by their logical name.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct BaseRegistry {
+    #[serde(default)]
+    pub bases: BTreeMap<String, BaseConfig>,
+}
+
+impl BaseRegistry {
+    /// Read the registry from the file at `CIO_AIRTABLE_BASES`. If the variable is not set,
+    /// every base comes from the company.
+    pub fn from_env() -> Result<Self> {
+        match env::var("CIO_AIRTABLE_BASES") {
+            Ok(path) => Self::from_file(&path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Read the registry from a TOML file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| anyhow!("reading airtable bases `{}` failed: {}", path, e))?;
+
+        Self::from_toml(&contents).map_err(|e| anyhow!("parsing airtable bases `{}` failed: {}", path, e))
+    }
+
+    /// Parse the registry, rejecting names that are not a base, so a typo does not
+    /// silently fall back to the base of the company.
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let registry: BaseRegistry = toml::from_str(contents)?;
+        for name in registry.bases.keys() {
+            name.parse::<AirtableBase>()?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Returns the id of the base.
+    pub fn base_id(&self, base: AirtableBase, company: &Company) -> String {
+        match self.bases.get(base.name()) {
+            Some(config) if !config.id.is_empty() => config.id.to_string(),
+            _ => base.company_base_id(company).to_string(),
+        }
+    }
+
+    /// Returns a client for the base, sharing the rate limiter of the base.
+    pub fn authenticate(&self, base: AirtableBase, company: &Company) -> Airtable {
+        let api_key = match self.bases.get(base.name()) {
+            Some(config) if !config.api_key.is_empty() => &config.api_key,
+            _ => &company.airtable_api_key,
+        };
+        let base_id = self.base_id(base, company);
+
+        airtable_client(api_key, &base_id, &company.airtable_enterprise_account_id)
+            .with_rate_limiter(rate_limiter(&base_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    #[test]
+    fn test_base_registry() {
+        let mut company = mock_company();
+        company.airtable_base_id_directory = "appCompanyDirectory".to_string();
+        company.airtable_base_id_hiring = "appCompanyHiring".to_string();
+
+        let registry = BaseRegistry::from_toml(
+            r#"
+[bases.directory]
+id = "appDirectory"
+api_key = "patDirectory"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(registry.base_id(AirtableBase::Directory, &company), "appDirectory");
+        assert_eq!(registry.base_id(AirtableBase::Hiring, &company), "appCompanyHiring");
+        assert_eq!(
+            registry.authenticate(AirtableBase::Directory, &company).get_key(),
+            "patDirectory"
+        );
+
+        // Every base comes from the company when there is no config.
+        assert_eq!(
+            BaseRegistry::default().base_id(AirtableBase::Directory, &company),
+            "appCompanyDirectory"
+        );
+
+        assert!(BaseRegistry::from_toml("[bases.directroy]\nid = \"app\"").is_err());
+    }
+
+    #[test]
+    fn test_airtable_base_names() {
+        for base in AIRTABLE_BASES {
+            assert_eq!(base.name().parse::<AirtableBase>().unwrap(), base);
+            assert_eq!(
+                serde_json::to_value(base).unwrap(),
+                serde_json::Value::String(base.name().to_string())
+            );
+        }
+    }
+}
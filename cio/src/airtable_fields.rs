@@ -0,0 +1,298 @@
+//! The mapping of the fields of the synced models to the columns of their Airtable tables.
+//!
+//! By default every field of a model is pushed to the column of the same name, and the
+//! columns the model lists in `AIRTABLE_FIELD_POLICIES` are pulled back. The TOML file at
+//! the `CIO_AIRTABLE_FIELDS` environment variable overrides that per table, so renaming a
+//! column in Airtable, or handing a column over to the people using the base, does not
+//! need a release:
+//!
+//! ```toml
+//! [tables."Auth Users"]
+//! # The column of each field that is named differently in Airtable.
+//! columns = { last_application_accessed = "Last App" }
+//! # Only these fields are pushed. Every field is pushed when this is not set.
+//! push = ["user_id", "email", "name", "last_application_accessed"]
+//! # The fields that are edited in Airtable too, and who wins when they differ.
+//! pull = { link_to_people = "airtable", notes = "newest_wins" }
+//! # The fields only people edit, in Airtable. They are never pushed and Airtable wins.
+//! human_owned = ["tags"]
+//! # The column with the time a record was last modified, for `newest_wins`.
+//! modified_column = "Modified"
+//! # The column to write the id of the row in the database to.
+//! database_id_column = "Database ID"
+//! ```
+//!
+//! Fields are named as the model serializes them. A pulled field is only copied into the
+//! database if the model knows it, see `AirtableSyncable::pull_airtable_field`, the others
+//! are still left alone in Airtable.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::airtable_sync::ConflictPolicy;
+
+/// How the fields of a model map to the columns of its table.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize, Serialize)]
+pub struct FieldMapping {
+    /// The column of each field that is named differently in Airtable.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, String>,
+    /// The fields that are pushed, every field when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push: Option<BTreeSet<String>>,
+    /// The fields that are pulled, over the policies of the model.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pull: BTreeMap<String, ConflictPolicy>,
+    /// The fields that are never pushed, where Airtable always wins.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub human_owned: BTreeSet<String>,
+    /// The column with the time a record was last modified, over the one of the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_column: Option<String>,
+    /// The column the id of the row in the database is written to, none when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database_id_column: Option<String>,
+}
+
+impl FieldMapping {
+    /// Returns the column of the field in Airtable.
+    pub fn column<'a>(&'a self, field: &'a str) -> &'a str {
+        self.columns.get(field).map(|c| c.as_str()).unwrap_or(field)
+    }
+
+    /// Returns true if the field is sent to Airtable.
+    pub fn is_pushed(&self, field: &str) -> bool {
+        !self.human_owned.contains(field) && self.push.as_ref().map(|p| p.contains(field)).unwrap_or(true)
+    }
+
+    /// Returns true if some fields are not pushed.
+    pub fn restricts_push(&self) -> bool {
+        self.push.is_some() || !self.human_owned.is_empty()
+    }
+
+    /// Returns the fields that are pulled and their policies, the policies of the model
+    /// first, then the ones only in the mapping.
+    pub fn policies(&self, model: &[(&str, ConflictPolicy)]) -> Vec<(String, ConflictPolicy)> {
+        let mut policies: Vec<(String, ConflictPolicy)> = model
+            .iter()
+            .map(|(field, policy)| (field.to_string(), self.policy(field).unwrap_or(*policy)))
+            .collect();
+        let fields: BTreeSet<&String> = self.pull.keys().chain(self.human_owned.iter()).collect();
+        for field in fields {
+            if !model.iter().any(|(f, _)| *f == field.as_str()) {
+                policies.push((
+                    field.to_string(),
+                    self.policy(field).unwrap_or(ConflictPolicy::Airtable),
+                ));
+            }
+        }
+
+        policies
+    }
+
+    fn policy(&self, field: &str) -> Option<ConflictPolicy> {
+        if self.human_owned.contains(field) {
+            return Some(ConflictPolicy::Airtable);
+        }
+
+        self.pull.get(field).copied()
+    }
+
+    /// Returns the column with the time a record was last modified.
+    pub fn modified_column<'a>(&'a self, model: &'a str) -> &'a str {
+        self.modified_column.as_deref().unwrap_or(model)
+    }
+
+    /// Turn the fields of a model, as JSON, into the columns to send to Airtable.
+    pub fn to_airtable(&self, fields: Value) -> Value {
+        match fields {
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .filter(|(field, _)| self.is_pushed(field))
+                    .map(|(field, value)| (self.column(&field).to_string(), value))
+                    .collect(),
+            ),
+            v => v,
+        }
+    }
+
+    /// Add the id of the row in the database to the columns to send to Airtable, if the
+    /// table has a column for it.
+    pub fn link_database_id(&self, columns: &mut Value, id: i32) {
+        if let (Some(column), Value::Object(columns)) = (&self.database_id_column, columns) {
+            columns.insert(column.to_string(), Value::from(id));
+        }
+    }
+
+    /// Returns true if the record in Airtable, as read back with `from_airtable`, lacks
+    /// the id of its row in the database.
+    pub fn lacks_database_id(&self, fields: &Value, id: i32) -> bool {
+        match &self.database_id_column {
+            Some(column) => fields.get(column) != Some(&Value::from(id)),
+            None => false,
+        }
+    }
+
+    /// Turn the columns of a record in Airtable back into the fields of the model, as JSON.
+    /// Columns that are not renamed keep their name.
+    pub fn from_airtable(&self, columns: Value) -> Value {
+        if self.columns.is_empty() {
+            return columns;
+        }
+
+        let fields: HashMap<&str, &str> = self.columns.iter().map(|(f, c)| (c.as_str(), f.as_str())).collect();
+        match columns {
+            Value::Object(columns) => {
+                let mut out = Map::new();
+                for (column, value) in columns {
+                    match fields.get(column.as_str()) {
+                        Some(field) => {
+                            out.insert(field.to_string(), value);
+                        }
+                        // A column named like a field that was renamed is not that field.
+                        None if self.columns.contains_key(&column) => {}
+                        None => {
+                            out.insert(column, value);
+                        }
+                    }
+                }
+                Value::Object(out)
+            }
+            v => v,
+        }
+    }
+
+    /// Check the mapping makes sense on its own.
+    fn validate(&self) -> Result<()> {
+        let mut fields: HashMap<&str, &str> = Default::default();
+        for (field, column) in self.columns.iter() {
+            if let Some(other) = fields.insert(column, field) {
+                bail!("`{}` and `{}` both map to column `{}`", other, field, column);
+            }
+        }
+
+        for field in self.human_owned.iter() {
+            if self.push.as_ref().map(|p| p.contains(field)).unwrap_or(false) {
+                bail!("`{}` is human owned, it can't be pushed", field);
+            }
+            if matches!(self.pull.get(field), Some(p) if *p != ConflictPolicy::Airtable) {
+                bail!("`{}` is human owned, Airtable always wins", field);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The field mappings of the tables, by the name of the table in Airtable.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize, Serialize)]
+pub struct FieldMappings {
+    #[serde(default)]
+    pub tables: BTreeMap<String, FieldMapping>,
+}
+
+impl FieldMappings {
+    /// Read the mappings from the file at `CIO_AIRTABLE_FIELDS`. If the variable is not
+    /// set, every table uses the fields of its model as they are.
+    pub fn from_env() -> Result<Self> {
+        match env::var("CIO_AIRTABLE_FIELDS") {
+            Ok(path) => Self::from_file(&path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Read the mappings from a TOML file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| anyhow!("reading airtable fields `{}` failed: {}", path, e))?;
+
+        Self::from_toml(&contents).map_err(|e| anyhow!("parsing airtable fields `{}` failed: {}", path, e))
+    }
+
+    /// Parse the mappings, rejecting the ones that contradict themselves.
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let mappings: FieldMappings = toml::from_str(contents)?;
+        for (table, mapping) in mappings.tables.iter() {
+            mapping.validate().map_err(|e| anyhow!("table `{}`: {}", table, e))?;
+        }
+
+        Ok(mappings)
+    }
+
+    /// Returns the mapping of the table.
+    pub fn table(&self, table: &str) -> FieldMapping {
+        self.tables.get(table).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_field_mapping() {
+        let mappings = FieldMappings::from_toml(
+            r#"
+[tables."Auth Users"]
+columns = { name = "Full Name", last_application_accessed = "Last App" }
+pull = { link_to_people = "database" }
+human_owned = ["notes"]
+database_id_column = "Database ID"
+"#,
+        )
+        .unwrap();
+        let mapping = mappings.table("Auth Users");
+
+        let fields = json!({"name": "Jess", "email": "jess@oxide.computer", "notes": "stale"});
+        let columns = mapping.to_airtable(fields);
+        assert_eq!(columns, json!({"Full Name": "Jess", "email": "jess@oxide.computer"}));
+
+        let columns = json!({"Full Name": "Jess", "name": "unrelated", "notes": "hi", "Last Modified": "2024"});
+        assert_eq!(
+            mapping.from_airtable(columns),
+            json!({"name": "Jess", "notes": "hi", "Last Modified": "2024"})
+        );
+
+        assert_eq!(
+            mapping.policies(&[("link_to_people", ConflictPolicy::Airtable)]),
+            vec![
+                ("link_to_people".to_string(), ConflictPolicy::Database),
+                ("notes".to_string(), ConflictPolicy::Airtable),
+            ]
+        );
+        assert!(mapping.restricts_push());
+
+        let mut columns = json!({"email": "jess@oxide.computer"});
+        assert!(mapping.lacks_database_id(&columns, 7));
+        mapping.link_database_id(&mut columns, 7);
+        assert_eq!(columns, json!({"email": "jess@oxide.computer", "Database ID": 7}));
+        assert!(!mapping.lacks_database_id(&columns, 7));
+
+        // Tables without a mapping are left as they are.
+        let mapping = mappings.table("Auth User Logins");
+        assert_eq!(mapping.to_airtable(json!({"ip": "1.1.1.1"})), json!({"ip": "1.1.1.1"}));
+        assert!(!mapping.restricts_push());
+        assert_eq!(mapping.modified_column("Last Modified"), "Last Modified");
+        let mut columns = json!({"ip": "1.1.1.1"});
+        mapping.link_database_id(&mut columns, 7);
+        assert_eq!(columns, json!({"ip": "1.1.1.1"}));
+    }
+
+    #[test]
+    fn test_field_mapping_validate() {
+        assert!(FieldMappings::from_toml("[tables.Users]\ncolumns = { a = \"A\", b = \"A\" }").is_err());
+        assert!(FieldMappings::from_toml("[tables.Users]\npush = [\"a\"]\nhuman_owned = [\"a\"]").is_err());
+        assert!(
+            FieldMappings::from_toml("[tables.Users]\npull = { a = \"database\" }\nhuman_owned = [\"a\"]").is_err()
+        );
+        assert!(FieldMappings::from_toml("[tables.Users]\npull = { a = \"sometimes\" }").is_err());
+    }
+}
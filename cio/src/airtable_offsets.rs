@@ -0,0 +1,104 @@
+#![allow(clippy::from_over_into)]
+//! The offsets of Airtable listings that were interrupted, so a read of a large table like
+//! the page views resumes at the page it stopped at, see
+//! `airtable_api::Airtable::resume_list_records`.
+use airtable_api::{ListKey, OffsetStore};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{companies::Company, db::Database, schema::airtable_list_offsets};
+
+/// The offset of the next page of a listing that did not finish. The offset is removed
+/// once the listing finishes.
+#[db {
+    new_struct_name = "AirtableListOffset",
+    match_on = {
+        "base_id" = "String",
+        "table_name" = "String",
+        "view" = "String",
+        "formula" = "String",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = airtable_list_offsets)]
+pub struct NewAirtableListOffset {
+    pub base_id: String,
+    pub table_name: String,
+    /// The view the records are listed from, empty for the whole table.
+    #[serde(default)]
+    pub view: String,
+    /// The `filterByFormula` of the listing, empty for none.
+    #[serde(default)]
+    pub formula: String,
+    pub next_offset: String,
+    pub updated_at: DateTime<Utc>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+/// Keeps the offsets of the listings of a company in the database.
+pub struct DbOffsetStore {
+    db: Database,
+    company_id: i32,
+}
+
+impl DbOffsetStore {
+    pub fn new(db: &Database, company: &Company) -> Self {
+        DbOffsetStore {
+            db: db.clone(),
+            company_id: company.id,
+        }
+    }
+
+    async fn get(&self, key: &ListKey) -> Option<AirtableListOffset> {
+        AirtableListOffset::get_from_db(
+            &self.db,
+            key.base_id.to_string(),
+            key.table.to_string(),
+            key.view.to_string(),
+            key.formula.to_string(),
+        )
+        .await
+    }
+}
+
+impl OffsetStore for DbOffsetStore {
+    fn load<'a>(&'a self, key: &'a ListKey) -> BoxFuture<'a, Result<Option<String>>> {
+        async move { Ok(self.get(key).await.map(|o| o.next_offset)) }.boxed()
+    }
+
+    fn save<'a>(&'a self, key: &'a ListKey, offset: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            NewAirtableListOffset {
+                base_id: key.base_id.to_string(),
+                table_name: key.table.to_string(),
+                view: key.view.to_string(),
+                formula: key.formula.to_string(),
+                next_offset: offset.to_string(),
+                updated_at: Utc::now(),
+                cio_company_id: self.company_id,
+            }
+            .upsert(&self.db)
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn clear<'a>(&'a self, key: &'a ListKey) -> BoxFuture<'a, Result<()>> {
+        async move {
+            if let Some(offset) = self.get(key).await {
+                offset.delete(&self.db).await?;
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,399 @@
+//! Mirroring a table from one of our bases into another base, for example a subset of the
+//! auth users into a base shared with contractors.
+//!
+//! Only the whitelisted fields are read from the source and written to the target, so the
+//! rest of the table never leaves our base. Records are matched across the bases by the
+//! value of their `key` field, since record ids differ between bases, and records in the
+//! target that are no longer in the source are deleted. The replications are read from
+//! the TOML file at the `CIO_AIRTABLE_REPLICATIONS` environment variable:
+//!
+//! ```toml
+//! [[replications]]
+//! name = "contractor-auth-users"
+//! key = "email"
+//! fields = ["email", "name", "company", "last_application_accessed"]
+//!
+//! [replications.from]
+//! base = "directory"
+//! table = "Auth Users"
+//! # Only replicate the records in this view.
+//! view = "Contractors"
+//!
+//! [replications.to]
+//! base_id = "appXXXXXXXXXXXXXX"
+//! table = "Auth Users"
+//! # A key for the target base, empty uses the API key of the company.
+//! api_key = "patZZZZZZZZZZZZZZ"
+//! ```
+//!
+//! Links to other records are ids in the source base, so they mean nothing in the target
+//! and should not be whitelisted.
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs,
+};
+
+use airtable_api::{Operation, Record};
+use anyhow::{anyhow, bail, Result};
+use futures::stream;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    airtable_bases::{airtable_client, rate_limiter, AirtableBase, BaseRegistry},
+    airtable_sync::SyncSummary,
+    companies::Company,
+    core::DryRun,
+    error::CioError,
+    metrics::{self, Outcome},
+};
+
+/// Where a replication reads from, one of our bases.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ReplicationSource {
+    /// The logical name of the base, see `airtable_bases`.
+    pub base: AirtableBase,
+    pub table: String,
+    /// The view to read the records from, empty for the whole table.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub view: String,
+}
+
+/// Where a replication writes to, usually a base that is not ours.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ReplicationTarget {
+    pub base_id: String,
+    pub table: String,
+    /// The key to authenticate with. Empty uses the API key of the company.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub api_key: String,
+}
+
+/// A table mirrored from one base into another.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Replication {
+    pub name: String,
+    pub from: ReplicationSource,
+    pub to: ReplicationTarget,
+    /// The field that identifies a record in both tables.
+    pub key: String,
+    /// The fields that are replicated, the key among them.
+    pub fields: Vec<String>,
+    /// Whether the records in the target that are not in the source are deleted.
+    #[serde(default = "default_delete_stale")]
+    pub delete_stale: bool,
+}
+
+fn default_delete_stale() -> bool {
+    true
+}
+
+impl Replication {
+    /// Check the replication makes sense on its own.
+    fn validate(&self) -> Result<()> {
+        if self.to.base_id.is_empty() {
+            bail!("the target base id is empty");
+        }
+        if !self.fields.contains(&self.key) {
+            bail!("the key `{}` is not one of the fields", self.key);
+        }
+        let mut seen = BTreeSet::new();
+        for field in self.fields.iter() {
+            if !seen.insert(field) {
+                bail!("`{}` is listed twice in the fields", field);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the whitelisted fields of a record, with the ones it does not have set to
+    /// null, so a field emptied in the source is emptied in the target too.
+    fn whitelisted(&self, fields: &Value) -> Map<String, Value> {
+        self.fields
+            .iter()
+            .map(|field| (field.to_string(), fields.get(field).cloned().unwrap_or(Value::Null)))
+            .collect()
+    }
+
+    /// Returns the value of the key of a record, empty if it has none.
+    fn key_of(&self, fields: &Map<String, Value>) -> String {
+        match fields.get(&self.key) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.to_string(),
+            Some(v) => v.to_string(),
+        }
+    }
+
+    /// Returns the writes that make the target match the source.
+    pub fn plan(&self, source: &[Record<Value>], target: &[Record<Value>]) -> Vec<Operation<Value>> {
+        let mut existing: HashMap<String, (&str, Map<String, Value>)> = Default::default();
+        let mut stale: Vec<&str> = Default::default();
+        for record in target {
+            let fields = self.whitelisted(&record.fields);
+            let key = self.key_of(&fields);
+            if key.is_empty() || existing.contains_key(&key) {
+                // A duplicate or a record without a key can't be matched, it is stale.
+                stale.push(&record.id);
+                continue;
+            }
+            existing.insert(key, (&record.id, fields));
+        }
+
+        let mut operations = Vec::new();
+        let mut seen = BTreeSet::new();
+        for record in source {
+            let fields = self.whitelisted(&record.fields);
+            let key = self.key_of(&fields);
+            if key.is_empty() {
+                warn!(
+                    "skipping record `{}` of replication `{}`, it has no `{}`",
+                    record.id, self.name, self.key
+                );
+                continue;
+            }
+            if !seen.insert(key.to_string()) {
+                warn!(
+                    "skipping record `{}` of replication `{}`, `{}` is a duplicate",
+                    record.id, self.name, key
+                );
+                continue;
+            }
+
+            match existing.get(&key) {
+                Some((_, current)) if *current == fields => (),
+                Some((id, _)) => operations.push(Operation::Update(Record {
+                    id: id.to_string(),
+                    fields: Value::Object(fields),
+                    created_time: None,
+                })),
+                None => operations.push(Operation::Create(Record {
+                    id: String::new(),
+                    fields: Value::Object(fields.into_iter().filter(|(_, v)| !v.is_null()).collect()),
+                    created_time: None,
+                })),
+            }
+        }
+
+        if self.delete_stale {
+            stale.extend(
+                existing
+                    .iter()
+                    .filter(|(key, _)| !seen.contains(key.as_str()))
+                    .map(|(_, (id, _))| *id),
+            );
+            stale.sort_unstable();
+            operations.extend(stale.into_iter().map(|id| Operation::Delete(id.to_string())));
+        }
+
+        operations
+    }
+}
+
+/// The replications to run.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Replications {
+    #[serde(default)]
+    pub replications: Vec<Replication>,
+}
+
+impl Replications {
+    /// Read the replications from the file at `CIO_AIRTABLE_REPLICATIONS`. If the variable
+    /// is not set, there are none.
+    pub fn from_env() -> Result<Self> {
+        match env::var("CIO_AIRTABLE_REPLICATIONS") {
+            Ok(path) => Self::from_file(&path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Read the replications from a TOML file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| anyhow!("reading airtable replications `{}` failed: {}", path, e))?;
+
+        Self::from_toml(&contents).map_err(|e| anyhow!("parsing airtable replications `{}` failed: {}", path, e))
+    }
+
+    /// Parse the replications, rejecting the ones that can't work.
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let replications: Replications = toml::from_str(contents)?;
+        let mut names = BTreeSet::new();
+        for replication in replications.replications.iter() {
+            if !names.insert(&replication.name) {
+                bail!("there are two replications named `{}`", replication.name);
+            }
+            replication
+                .validate()
+                .map_err(|e| anyhow!("replication `{}`: {}", replication.name, e))?;
+        }
+
+        Ok(replications)
+    }
+}
+
+/// Run every replication of the company.
+pub async fn refresh_airtable_replications(company: &Company, dry_run: DryRun) -> Result<SyncSummary, CioError> {
+    let replications = Replications::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+    let bases = BaseRegistry::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+
+    let mut summary = SyncSummary::default();
+    for replication in replications.replications.iter() {
+        summary += replicate(&bases, company, replication, dry_run).await?;
+    }
+
+    Ok(summary)
+}
+
+/// Make the target table of the replication match the source.
+pub async fn replicate(
+    bases: &BaseRegistry,
+    company: &Company,
+    replication: &Replication,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    // Both clients read the fields by name, the whitelist names them and the field ids
+    // differ between the bases.
+    let source = bases.authenticate(replication.from.base, company);
+    let api_key = if replication.to.api_key.is_empty() {
+        &company.airtable_api_key
+    } else {
+        &replication.to.api_key
+    };
+    let target = airtable_client(
+        api_key,
+        &replication.to.base_id,
+        &company.airtable_enterprise_account_id,
+    )
+    .with_rate_limiter(rate_limiter(&replication.to.base_id));
+
+    let fields: Vec<&str> = replication.fields.iter().map(|f| f.as_str()).collect();
+    let from: Vec<Record<Value>> = source
+        .list_records(&replication.from.table, &replication.from.view, fields.clone())
+        .await
+        .map_err(CioError::Airtable)?;
+    let to: Vec<Record<Value>> = target
+        .list_records(&replication.to.table, "", fields)
+        .await
+        .map_err(CioError::Airtable)?;
+    metrics::record(&replication.to.table, Outcome::Fetched, from.len());
+
+    let operations = replication.plan(&from, &to);
+    let mut summary = SyncSummary::default();
+    for operation in operations.iter() {
+        match operation {
+            Operation::Create(_) => summary.created += 1,
+            Operation::Update(_) => summary.updated += 1,
+            Operation::Delete(_) => summary.deleted += 1,
+        }
+    }
+
+    if dry_run.is_enabled() {
+        info!(
+            "[dry-run] replication `{}` would create {}, update {} and delete {} records in `{}`",
+            replication.name, summary.created, summary.updated, summary.deleted, replication.to.table
+        );
+        return Ok(summary);
+    }
+
+    let written = target
+        .write_records(&replication.to.table, stream::iter(operations))
+        .await
+        .map_err(CioError::Airtable)?;
+    metrics::record(
+        &replication.to.table,
+        Outcome::Updated,
+        written.created.len() + written.updated.len(),
+    );
+
+    let summary = SyncSummary {
+        created: written.created.len(),
+        updated: written.updated.len(),
+        deleted: written.deleted,
+        ..Default::default()
+    };
+    info!(
+        "replicated `{}` to `{}`: {} created, {} updated, {} deleted",
+        replication.name, replication.to.table, summary.created, summary.updated, summary.deleted
+    );
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn record(id: &str, fields: Value) -> Record<Value> {
+        Record {
+            id: id.to_string(),
+            fields,
+            created_time: None,
+        }
+    }
+
+    #[test]
+    fn test_replication_plan() {
+        let replications = Replications::from_toml(
+            r#"
+[[replications]]
+name = "contractors"
+key = "email"
+fields = ["email", "name"]
+from = { base = "directory", table = "Auth Users", view = "Contractors" }
+to = { base_id = "appShared", table = "People" }
+"#,
+        )
+        .unwrap();
+        let replication = &replications.replications[0];
+        assert!(replication.delete_stale);
+
+        let source = vec![
+            record("recA", json!({"email": "a@example.com", "name": "A", "salary": 1})),
+            record("recB", json!({"email": "b@example.com", "name": "B"})),
+            record("recC", json!({"email": "c@example.com"})),
+        ];
+        let target = vec![
+            record("rec1", json!({"email": "a@example.com", "name": "A"})),
+            record("rec2", json!({"email": "b@example.com", "name": "Old"})),
+            record("rec3", json!({"email": "gone@example.com", "name": "Gone"})),
+        ];
+
+        let operations = replication.plan(&source, &target);
+        assert_eq!(operations.len(), 3);
+        match &operations[0] {
+            Operation::Update(r) => {
+                assert_eq!(r.id, "rec2");
+                assert_eq!(r.fields, json!({"email": "b@example.com", "name": "B"}));
+            }
+            o => panic!("expected an update, got {:?}", o),
+        }
+        match &operations[1] {
+            // Only the whitelisted fields go over, and the empty ones are left out.
+            Operation::Create(r) => assert_eq!(r.fields, json!({"email": "c@example.com"})),
+            o => panic!("expected a create, got {:?}", o),
+        }
+        match &operations[2] {
+            Operation::Delete(id) => assert_eq!(id, "rec3"),
+            o => panic!("expected a delete, got {:?}", o),
+        }
+    }
+
+    #[test]
+    fn test_replications_validate() {
+        let replication = |key: &str, fields: &str| {
+            format!(
+                "[[replications]]\nname = \"r\"\nkey = \"{}\"\nfields = {}\nfrom = {{ base = \"directory\", table = \
+                 \"A\" }}\nto = {{ base_id = \"app1\", table = \"B\" }}",
+                key, fields
+            )
+        };
+        assert!(Replications::from_toml(&replication("email", "[\"email\"]")).is_ok());
+        assert!(Replications::from_toml(&replication("email", "[\"name\"]")).is_err());
+        assert!(Replications::from_toml(&replication("email", "[\"email\", \"email\"]")).is_err());
+        assert!(Replications::from_toml(&replication("email", "[\"email\"]").replace("directory", "nope")).is_err());
+    }
+}
@@ -0,0 +1,1118 @@
+//! A generic engine for mirroring the records in a database table to an Airtable table.
+//!
+//! Models implement `AirtableSyncable` to describe where their records live in Airtable
+//! and how to tell them apart, and `sync_to_airtable` does the listing, comparing,
+//! creating, updating and deleting for them. The bookkeeping they share, `AirtableRow`,
+//! is implemented by `#[db]` from the `airtable_table` of the model.
+//!
+//! The sync goes both ways for the columns people edit by hand in Airtable, like links
+//! to other tables. Each model lists those columns with a `ConflictPolicy` that decides
+//! who wins when the database and Airtable disagree. The winning value is saved on both
+//! sides, and the conflict is recorded in the `airtable_sync_conflicts` table so nothing
+//! is clobbered silently.
+//!
+//! A run that syncs several models can share an `AirtableCache`, so a table that more
+//! than one of them reads is only downloaded once.
+//!
+//! The columns the fields are written to, which of them are pushed and which are pulled
+//! can be changed per table without a release, see `airtable_fields`.
+//!
+//! Each synced row keeps the link to its record in Airtable, so alerts and logs can point
+//! people at it, and the mapping can have the id of the row written back to Airtable.
+//!
+//! Before anything is written, the fields of the model are checked against the schema of
+//! the table, so a column renamed in Airtable stops the sync with a readable diff.
+//!
+//! A sync stops between batches once the process is shutting down, see `cancel`. The
+//! batches already collected are still written, and the ids of the records they created
+//! are saved, so the next sync does not create them again.
+#![allow(clippy::from_over_into)]
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use airtable_api::{
+    record_url, schema::BaseSchema, sync::changed_records, Airtable, Operation, Partition, Record,
+    MAX_RECORDS_PER_REQUEST,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{future, stream, StreamExt};
+use log::{error, info, warn};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    airtable_bases::{AirtableBase, BaseRegistry},
+    airtable_fields::{FieldMapping, FieldMappings},
+    cancel::{shutdown, Cancellation},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    metrics::{self, Outcome},
+    progress::{self, ProgressUpdate},
+    schema::airtable_sync_conflicts,
+};
+
+/// The number of partitions of a table to list at once, see
+/// `AirtableSyncable::airtable_read_partitions`.
+const AIRTABLE_READ_CONCURRENCY: usize = 4;
+
+/// Who wins when a column differs between the database and Airtable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The database is the source of truth, the value in Airtable is overwritten.
+    Database,
+    /// Airtable is the source of truth, the value is pulled into the database.
+    Airtable,
+    /// The side that was modified most recently wins. If we can't tell, Airtable wins.
+    NewestWins,
+}
+
+/// The side whose value was kept when resolving a conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Database,
+    Airtable,
+}
+
+impl Resolution {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Database => "database",
+            Resolution::Airtable => "airtable",
+        }
+    }
+}
+
+impl ConflictPolicy {
+    /// Returns the side that wins under this policy, given when each side was last
+    /// modified.
+    pub fn resolve(
+        &self,
+        database_modified: Option<DateTime<Utc>>,
+        airtable_modified: Option<DateTime<Utc>>,
+    ) -> Resolution {
+        match self {
+            ConflictPolicy::Database => Resolution::Database,
+            ConflictPolicy::Airtable => Resolution::Airtable,
+            ConflictPolicy::NewestWins => match (database_modified, airtable_modified) {
+                (Some(d), Some(a)) if d > a => Resolution::Database,
+                _ => Resolution::Airtable,
+            },
+        }
+    }
+}
+
+/// A column that differed between the database and Airtable during a sync.
+#[db {
+    new_struct_name = "AirtableSyncConflict",
+    match_on = {
+        "table_name" = "String",
+        "record_key" = "String",
+        "field" = "String",
+        "detected_at" = "DateTime<Utc>",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = airtable_sync_conflicts)]
+pub struct NewAirtableSyncConflict {
+    /// The Airtable table of the record.
+    pub table_name: String,
+    /// The unique key of the record, see `AirtableSyncable::unique_key`.
+    pub record_key: String,
+    pub field: String,
+    /// The values as JSON.
+    pub database_value: String,
+    pub airtable_value: String,
+    /// The side whose value was kept.
+    pub resolution: String,
+    pub detected_at: DateTime<Utc>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+/// The bookkeeping of a database record that is mirrored to a table in Airtable. This is
+/// implemented by `#[db]` for the models with an `airtable_table`.
+#[async_trait]
+pub trait AirtableRow: Sized + Send + Sync {
+    /// The fields sent to Airtable, the `New` struct of the model.
+    type Fields: Serialize + DeserializeOwned + PartialEq + Clone + Send + Sync + for<'a> From<&'a Self>;
+
+    /// The name of the table in Airtable.
+    const AIRTABLE_TABLE: &'static str;
+
+    /// Returns the id of the record in Airtable, empty if it has not been created yet.
+    fn airtable_record_id(&self) -> &str;
+
+    /// Set the id of the record in Airtable.
+    fn set_airtable_record_id(&mut self, id: String);
+
+    /// Returns the link to the record in Airtable, empty if it has not been created yet.
+    fn airtable_record_url(&self) -> &str;
+
+    /// Set the link to the record in Airtable, see `airtable_api::record_url`.
+    fn set_airtable_record_url(&mut self, url: String);
+
+    /// Returns the id of the row in the database, written to Airtable when the mapping of
+    /// the table has a `database_id_column`.
+    fn database_id(&self) -> i32;
+
+    /// Save the record to the database.
+    async fn save(&self, db: &Database) -> Result<()>;
+
+    /// Returns every record of the company from the database.
+    async fn list_from_db(db: &Database, company: &Company) -> Result<Vec<Self>>;
+}
+
+/// A database record that is mirrored to a table in Airtable.
+#[async_trait]
+pub trait AirtableSyncable: AirtableRow {
+    /// Whether to delete the records in Airtable that are no longer in the database.
+    const DELETE_STALE: bool = false;
+
+    /// The columns that are edited by hand in Airtable, and who wins when they differ
+    /// from the database. The other columns are only ever pushed. The mapping of the
+    /// table can override these, see `airtable_fields`.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] = &[];
+
+    /// The Airtable column with the time the record was last modified, used by
+    /// `ConflictPolicy::NewestWins`.
+    const AIRTABLE_MODIFIED_FIELD: &'static str = "Last Modified";
+
+    /// The Airtable base the table is in, see `BaseRegistry`.
+    const AIRTABLE_BASE: AirtableBase;
+
+    /// Returns a key that is unique to the record. It is used to match the records we
+    /// create with the records in the database, and to adopt records in Airtable whose
+    /// id we failed to save.
+    fn unique_key(fields: &Self::Fields) -> String;
+
+    /// Returns the fields to send to Airtable.
+    fn airtable_fields(&self) -> Self::Fields {
+        self.into()
+    }
+
+    /// Returns the records of the company from the database to sync.
+    async fn list_for_airtable(db: &Database, company: &Company) -> Result<Vec<Self>> {
+        Self::list_from_db(db, company).await
+    }
+
+    /// Returns when the record was last modified in the database, used by
+    /// `ConflictPolicy::NewestWins`.
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Copy a column listed in `AIRTABLE_FIELD_POLICIES`, or pulled by the mapping of the
+    /// table, from the record in Airtable into this record. Ignore the columns the model
+    /// can't take from Airtable.
+    fn pull_airtable_field(&mut self, field: &str, airtable: &Self::Fields);
+
+    /// Returns the partitions to list the table in, side by side. Large tables can list
+    /// faster this way, see `airtable_api::created_time_partitions`. The table is listed
+    /// in one go when this is empty.
+    fn airtable_read_partitions() -> Vec<Partition> {
+        Default::default()
+    }
+}
+
+/// The number of records changed by a sync.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// The columns that differed between the database and Airtable.
+    pub conflicts: usize,
+    /// The records that failed to save.
+    pub errors: usize,
+    /// Whether the sync was cancelled before it was done.
+    pub cancelled: bool,
+}
+
+impl std::ops::AddAssign for SyncSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.created += other.created;
+        self.updated += other.updated;
+        self.deleted += other.deleted;
+        self.conflicts += other.conflicts;
+        self.errors += other.errors;
+        self.cancelled |= other.cancelled;
+    }
+}
+
+/// The Airtable tables listed during a run, keyed by base and table, and the schemas of
+/// the bases.
+///
+/// A listing is dropped from the cache once the run writes to its table, so a later read
+/// sees the changes. The cache is cheap to clone, clones share the listings.
+#[derive(Debug, Default, Clone)]
+pub struct AirtableCache {
+    tables: Arc<Mutex<HashMap<(String, String), Arc<Vec<Record<Value>>>>>>,
+    schemas: Arc<Mutex<HashMap<String, Arc<BaseSchema>>>>,
+}
+
+impl AirtableCache {
+    /// Returns the records in the table, listing them from Airtable if they are not cached.
+    pub async fn list(&self, airtable: &Airtable, base_id: &str, table: &str) -> Result<Arc<Vec<Record<Value>>>> {
+        self.list_partitioned(airtable, base_id, table, vec![]).await
+    }
+
+    /// Returns the records in the table like `list`, listing the partitions side by side if
+    /// they are not cached. The partitions must cover the whole table.
+    pub async fn list_partitioned(
+        &self,
+        airtable: &Airtable,
+        base_id: &str,
+        table: &str,
+        partitions: Vec<Partition>,
+    ) -> Result<Arc<Vec<Record<Value>>>> {
+        let key = (base_id.to_string(), table.to_string());
+        if let Some(records) = self.tables.lock().unwrap().get(&key) {
+            return Ok(records.clone());
+        }
+
+        let timer = metrics::time_api("airtable", "list");
+        let records = if partitions.is_empty() {
+            airtable.list_records(table, "", vec![]).await?
+        } else {
+            airtable
+                .list_records_partitioned(table, vec![], partitions, AIRTABLE_READ_CONCURRENCY)
+                .await?
+        };
+        let records = Arc::new(records);
+        timer.observe_duration();
+
+        self.tables.lock().unwrap().insert(key, records.clone());
+
+        Ok(records)
+    }
+
+    /// Drop the cached records of the table.
+    pub fn invalidate(&self, base_id: &str, table: &str) {
+        self.tables
+            .lock()
+            .unwrap()
+            .remove(&(base_id.to_string(), table.to_string()));
+    }
+
+    /// Returns true if the records of the table are cached.
+    pub fn contains(&self, base_id: &str, table: &str) -> bool {
+        self.tables
+            .lock()
+            .unwrap()
+            .contains_key(&(base_id.to_string(), table.to_string()))
+    }
+
+    /// Returns the schema of the base, reading it from the Metadata API if it is not
+    /// cached.
+    pub async fn base_schema(&self, airtable: &Airtable, base_id: &str) -> Result<Arc<BaseSchema>> {
+        if let Some(schema) = self.schemas.lock().unwrap().get(base_id) {
+            return Ok(schema.clone());
+        }
+
+        let timer = metrics::time_api("airtable", "schema");
+        let schema = Arc::new(airtable.get_base_schema().await?);
+        timer.observe_duration();
+
+        self.schemas.lock().unwrap().insert(base_id.to_string(), schema.clone());
+
+        Ok(schema)
+    }
+}
+
+/// Sync the records of the company in the database to their table in Airtable.
+///
+/// Only records whose fields changed are updated. A record that fails to save its
+/// Airtable id is logged and picked up by its unique key on the next sync.
+///
+/// In a dry run nothing is written to Airtable or the database, the changes are logged
+/// and counted in the summary instead.
+///
+/// If the process starts shutting down part way, the sync writes the batches it has and
+/// returns a summary marked as cancelled.
+pub async fn sync_to_airtable<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    sync_to_airtable_cached::<T>(db, company, &AirtableCache::default(), dry_run).await
+}
+
+/// Sync the records of the company to their table in Airtable, like `sync_to_airtable`,
+/// reading the table through the cache of the run.
+pub async fn sync_to_airtable_cached<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    cache: &AirtableCache,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let records = T::list_for_airtable(db, company).await.map_err(CioError::Database)?;
+
+    sync_records(db, company, cache, records, T::DELETE_STALE, shutdown(), dry_run).await
+}
+
+/// Sync some of the records of the company to their table in Airtable, for example the
+/// ones created since a date. Records in Airtable that are not in the list are left alone,
+/// even for models that delete stale records.
+pub async fn sync_records_to_airtable<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    cache: &AirtableCache,
+    records: Vec<T>,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    sync_records(db, company, cache, records, false, shutdown(), dry_run).await
+}
+
+/// Push some of the records of the company straight to Airtable, without listing the
+/// table, so a few rows that just changed show up within seconds, see `change_listener`.
+///
+/// Records with an Airtable id are updated and the rest are created. Since the copies in
+/// Airtable are not read, the columns that are pulled are left out of the updates rather
+/// than risk overwriting a hand edit, the next full sync resolves them.
+pub async fn push_records_to_airtable<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    cache: &AirtableCache,
+    mut records: Vec<T>,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let bases = BaseRegistry::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+    let base_id = bases.base_id(T::AIRTABLE_BASE, company);
+    let airtable = bases.authenticate(T::AIRTABLE_BASE, company);
+    let mapping = FieldMappings::from_env()
+        .map_err(|e| CioError::Config(e.to_string()))?
+        .table(T::AIRTABLE_TABLE);
+
+    check_schema(cache, &airtable, &base_id, &mapping, &records, dry_run).await?;
+    let table_id = table_id::<T>(cache, &airtable, &base_id).await;
+
+    let pulled: Vec<String> = mapping
+        .policies(T::AIRTABLE_FIELD_POLICIES)
+        .into_iter()
+        .map(|(field, _)| field)
+        .collect();
+    let mut operations: Vec<Operation<Value>> = Default::default();
+    for record in records.iter() {
+        let mut fields = serde_json::to_value(record.airtable_fields()).unwrap_or(Value::Null);
+        let id = record.airtable_record_id();
+        if id.is_empty() {
+            let mut fields = mapping.to_airtable(fields);
+            mapping.link_database_id(&mut fields, record.database_id());
+            operations.push(Operation::Create(Record {
+                id: String::new(),
+                fields,
+                created_time: None,
+            }));
+            continue;
+        }
+
+        if let Value::Object(fields) = &mut fields {
+            for field in pulled.iter() {
+                fields.remove(field);
+            }
+        }
+        let mut fields = mapping.to_airtable(fields);
+        mapping.link_database_id(&mut fields, record.database_id());
+        operations.push(Operation::Update(Record {
+            id: id.to_string(),
+            fields,
+            created_time: None,
+        }));
+    }
+
+    let (to_create, to_update) = operations.iter().fold((0, 0), |(c, u), o| match o {
+        Operation::Create(_) => (c + 1, u),
+        _ => (c, u + 1),
+    });
+    if dry_run.is_enabled() {
+        info!(
+            "[dry-run] would push {} new and {} changed records to `{}`",
+            to_create,
+            to_update,
+            T::AIRTABLE_TABLE
+        );
+
+        return Ok(SyncSummary {
+            created: to_create,
+            updated: to_update,
+            ..Default::default()
+        });
+    }
+
+    cache.invalidate(&base_id, T::AIRTABLE_TABLE);
+
+    let timer = metrics::time_api("airtable", "write");
+    let written = airtable
+        .write_records(T::AIRTABLE_TABLE, stream::iter(operations))
+        .await
+        .map_err(CioError::Airtable)?;
+    timer.observe_duration();
+    metrics::record(
+        T::AIRTABLE_TABLE,
+        Outcome::Updated,
+        written.updated.len() + written.created.len(),
+    );
+
+    let mut summary = SyncSummary {
+        created: written.created.len(),
+        updated: written.updated.len(),
+        ..Default::default()
+    };
+    summary.errors += save_created_ids(db, &mapping, &base_id, &table_id, &mut records, written.created).await;
+
+    info!(
+        "pushed `{}` to airtable: {} created, {} updated, {} errors",
+        T::AIRTABLE_TABLE,
+        summary.created,
+        summary.updated,
+        summary.errors
+    );
+
+    Ok(summary)
+}
+
+async fn sync_records<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    cache: &AirtableCache,
+    mut records: Vec<T>,
+    delete_stale: bool,
+    cancel: &Cancellation,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let bases = BaseRegistry::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+    let base_id = bases.base_id(T::AIRTABLE_BASE, company);
+    let airtable = bases.authenticate(T::AIRTABLE_BASE, company);
+    let mapping = FieldMappings::from_env()
+        .map_err(|e| CioError::Config(e.to_string()))?
+        .table(T::AIRTABLE_TABLE);
+
+    check_schema(cache, &airtable, &base_id, &mapping, &records, dry_run).await?;
+    let table_id = table_id::<T>(cache, &airtable, &base_id).await;
+
+    // List the raw records too, so we can compare single columns and read the time they
+    // were modified, which is not one of the fields of the model. The columns are named
+    // as the fields of the model from here on, until we write.
+    let raw = cache
+        .list_partitioned(&airtable, &base_id, T::AIRTABLE_TABLE, T::airtable_read_partitions())
+        .await
+        .map_err(CioError::Airtable)?;
+    let mut existing: Vec<Record<T::Fields>> = Default::default();
+    let mut raw_by_id: HashMap<&str, Value> = Default::default();
+    for r in raw.iter() {
+        let raw_fields = mapping.from_airtable(r.fields.clone());
+        match serde_json::from_value(raw_fields.clone()) {
+            Ok(fields) => {
+                raw_by_id.insert(&r.id, raw_fields);
+                existing.push(Record {
+                    id: r.id.to_string(),
+                    fields,
+                    created_time: r.created_time,
+                });
+            }
+            Err(e) => {
+                warn!("skipping record `{}` in `{}`: {}", r.id, T::AIRTABLE_TABLE, e);
+                metrics::record(T::AIRTABLE_TABLE, Outcome::Skipped, 1);
+            }
+        }
+    }
+    let mut index = RecordIndex::new(&existing, T::unique_key);
+
+    metrics::record(T::AIRTABLE_TABLE, Outcome::Fetched, records.len());
+
+    // The records to create, with the ids of their rows.
+    let mut to_create: Vec<(i32, Record<T::Fields>)> = Default::default();
+    let mut to_update: Vec<Record<T::Fields>> = Default::default();
+    // The ids of the rows of the records to update, by the id of the record in Airtable.
+    let mut database_ids: HashMap<String, i32> = Default::default();
+    // The records to update in any case, since they lack the id of their row.
+    let mut unlinked: HashSet<String> = Default::default();
+    let mut conflicts = 0;
+    let mut errors = 0;
+    for record in records.iter_mut() {
+        let key = T::unique_key(&record.airtable_fields());
+
+        match index.find(record.airtable_record_id(), &key) {
+            Some(existing) => {
+                // Resolve the hand edits before we push, so we don't overwrite them.
+                let raw = raw_by_id.get(existing.id.as_str()).unwrap_or(&Value::Null);
+                let resolved =
+                    resolve_conflicts(db, company, &mapping, record, &key, &existing.fields, raw, dry_run).await;
+                conflicts += resolved.conflicts;
+
+                let mut changed = resolved.pulled;
+                if record.airtable_record_id() != existing.id {
+                    record.set_airtable_record_id(existing.id.to_string());
+                    changed = true;
+                }
+                let url = record_url(&base_id, &table_id, &existing.id);
+                if record.airtable_record_url() != url {
+                    record.set_airtable_record_url(url);
+                    changed = true;
+                }
+                if mapping.lacks_database_id(raw, record.database_id()) {
+                    unlinked.insert(existing.id.to_string());
+                }
+
+                if changed && dry_run.is_enabled() {
+                    info!(
+                        "[dry-run] would save the changes to `{}` from `{}`",
+                        key,
+                        T::AIRTABLE_TABLE
+                    );
+                } else if changed {
+                    if let Err(e) = record.save(db).await {
+                        error!(
+                            "saving the changes to `{}` from `{}` failed: {}",
+                            key,
+                            T::AIRTABLE_TABLE,
+                            e
+                        );
+                        metrics::record(T::AIRTABLE_TABLE, Outcome::Errored, 1);
+                        errors += 1;
+                    }
+                }
+
+                database_ids.insert(existing.id.to_string(), record.database_id());
+                to_update.push(Record {
+                    id: existing.id.to_string(),
+                    fields: record.airtable_fields(),
+                    created_time: None,
+                });
+            }
+            None => to_create.push((
+                record.database_id(),
+                Record {
+                    id: String::new(),
+                    fields: record.airtable_fields(),
+                    created_time: None,
+                },
+            )),
+        }
+    }
+
+    let stale: Vec<&str> = if delete_stale {
+        index.unmatched().collect()
+    } else {
+        vec![]
+    };
+
+    // Only the records that changed are rewritten, which saves on rate limits and keeps
+    // the modification times in Airtable meaningful.
+    let unchanged = to_update.len();
+    let (unlinked, to_update): (Vec<_>, Vec<_>) = to_update.into_iter().partition(|r| unlinked.contains(&r.id));
+    let mut updated = changed_fields(&mapping, to_update, &existing);
+    updated.extend(unlinked);
+
+    if dry_run.is_enabled() {
+        for record in updated.iter() {
+            info!(
+                "[dry-run] would update record `{}` in `{}`",
+                record.id,
+                T::AIRTABLE_TABLE
+            );
+        }
+        for (_, record) in to_create.iter() {
+            info!(
+                "[dry-run] would create `{}` in `{}`",
+                T::unique_key(&record.fields),
+                T::AIRTABLE_TABLE
+            );
+        }
+        for id in stale.iter() {
+            info!("[dry-run] would delete record `{}` from `{}`", id, T::AIRTABLE_TABLE);
+        }
+
+        return Ok(SyncSummary {
+            created: to_create.len(),
+            updated: updated.len(),
+            deleted: stale.len(),
+            conflicts,
+            errors,
+            cancelled: false,
+        });
+    }
+
+    // We are about to write to the table, so the cached listing goes stale.
+    cache.invalidate(&base_id, T::AIRTABLE_TABLE);
+
+    let operations = updated
+        .into_iter()
+        .map(|r| {
+            let id = database_ids.get(&r.id).copied().unwrap_or_default();
+            Operation::Update(to_airtable_record(&mapping, r, id))
+        })
+        .chain(
+            to_create
+                .into_iter()
+                .map(|(id, r)| Operation::Create(to_airtable_record(&mapping, r, id))),
+        )
+        .chain(stale.iter().map(|id| Operation::Delete(id.to_string())))
+        .collect::<Vec<_>>();
+    let total = operations.len() as u64;
+    let mut progress = progress::reporter().start(&format!("airtable `{}`", T::AIRTABLE_TABLE));
+    // Once cancelled, the writer gets no more operations and sends what it has collected.
+    let operations = stream::iter(operations.into_iter().enumerate())
+        .take_while(|_| future::ready(!cancel.is_cancelled()))
+        .map(|(i, operation)| {
+            // The writer only asks for more once the batch before is sent.
+            if i > 0 && i % MAX_RECORDS_PER_REQUEST == 0 {
+                progress.update(ProgressUpdate {
+                    pages: (i / MAX_RECORDS_PER_REQUEST) as u64,
+                    records: i as u64,
+                    total: Some(total),
+                });
+            }
+            operation
+        });
+    let timer = metrics::time_api("airtable", "write");
+    let written = airtable
+        .write_records(T::AIRTABLE_TABLE, operations)
+        .await
+        .map_err(CioError::Airtable)?;
+    timer.observe_duration();
+    let (created, updated, deleted) = (written.created, written.updated, written.deleted);
+    let done = created.len() + updated.len() + deleted;
+    progress.update(ProgressUpdate {
+        pages: done.div_ceil(MAX_RECORDS_PER_REQUEST) as u64,
+        records: done as u64,
+        total: Some(total),
+    });
+    progress.finish();
+
+    metrics::record(T::AIRTABLE_TABLE, Outcome::Updated, updated.len() + created.len());
+    metrics::record(
+        T::AIRTABLE_TABLE,
+        Outcome::Skipped,
+        unchanged.saturating_sub(updated.len()),
+    );
+
+    let mut summary = SyncSummary {
+        created: created.len(),
+        updated: updated.len(),
+        deleted,
+        conflicts,
+        errors,
+        cancelled: cancel.is_cancelled(),
+    };
+
+    summary.errors += save_created_ids(db, &mapping, &base_id, &table_id, &mut records, created).await;
+
+    info!(
+        "{} `{}` to airtable: {} created, {} updated, {} deleted, {} conflicts, {} errors",
+        if summary.cancelled {
+            "cancelled the sync of"
+        } else {
+            "synced"
+        },
+        T::AIRTABLE_TABLE,
+        summary.created,
+        summary.updated,
+        summary.deleted,
+        summary.conflicts,
+        summary.errors
+    );
+
+    Ok(summary)
+}
+
+/// Save the ids of the records created in Airtable, and the links to them, on their rows,
+/// matching them by unique key, so the next sync updates them instead. Returns the number
+/// of ids not saved.
+async fn save_created_ids<T: AirtableSyncable>(
+    db: &Database,
+    mapping: &FieldMapping,
+    base_id: &str,
+    table_id: &str,
+    records: &mut [T],
+    created: Vec<Record<Value>>,
+) -> usize {
+    let mut errors = 0;
+    let mut by_key: HashMap<String, &mut T> = records
+        .iter_mut()
+        .map(|r| (T::unique_key(&r.airtable_fields()), r))
+        .collect();
+    for new in created {
+        let key = match serde_json::from_value::<T::Fields>(mapping.from_airtable(new.fields)) {
+            Ok(fields) => T::unique_key(&fields),
+            Err(e) => {
+                error!(
+                    "reading back record `{}` created in `{}` failed, its id is not saved: {}",
+                    new.id,
+                    T::AIRTABLE_TABLE,
+                    e
+                );
+                metrics::record(T::AIRTABLE_TABLE, Outcome::Errored, 1);
+                errors += 1;
+                continue;
+            }
+        };
+        if let Some(record) = by_key.get_mut(&key) {
+            record.set_airtable_record_url(record_url(base_id, table_id, &new.id));
+            record.set_airtable_record_id(new.id);
+            if let Err(e) = record.save(db).await {
+                error!(
+                    "saving the airtable record id of `{}` in `{}` failed: {}",
+                    key,
+                    T::AIRTABLE_TABLE,
+                    e
+                );
+                metrics::record(T::AIRTABLE_TABLE, Outcome::Errored, 1);
+                errors += 1;
+            }
+        }
+    }
+
+    errors
+}
+
+/// Compare the fields we are about to write with the schema of the table in the base, so
+/// a column renamed in Airtable fails the sync with the differences, instead of leaving
+/// the column empty on every record. A dry run only logs the differences.
+///
+/// The check is skipped when the schema can't be read, for example because the API key
+/// lacks the `schema.bases:read` scope.
+async fn check_schema<T: AirtableSyncable>(
+    cache: &AirtableCache,
+    airtable: &Airtable,
+    base_id: &str,
+    mapping: &FieldMapping,
+    records: &[T],
+    dry_run: DryRun,
+) -> Result<(), CioError> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let schema = match cache.base_schema(airtable, base_id).await {
+        Ok(schema) => schema,
+        Err(e) => {
+            warn!(
+                "reading the schema of base `{}` failed, not checking the fields of `{}`: {}",
+                base_id,
+                T::AIRTABLE_TABLE,
+                e
+            );
+            return Ok(());
+        }
+    };
+
+    let problem = match schema.table(T::AIRTABLE_TABLE) {
+        None => format!("table `{}` is not in base `{}`", T::AIRTABLE_TABLE, base_id),
+        Some(table) => {
+            let fields: Vec<Value> = records
+                .iter()
+                .map(|r| {
+                    let mut fields =
+                        mapping.to_airtable(serde_json::to_value(r.airtable_fields()).unwrap_or(Value::Null));
+                    mapping.link_database_id(&mut fields, r.database_id());
+                    fields
+                })
+                .collect();
+            let diff = table.diff(&fields).map_err(CioError::Airtable)?;
+            if diff.is_empty() {
+                return Ok(());
+            }
+            diff.to_string()
+        }
+    };
+
+    if dry_run.is_enabled() {
+        warn!("[dry-run] {}", problem);
+        return Ok(());
+    }
+
+    Err(CioError::Config(problem))
+}
+
+/// Returns the records to update that differ from their copy in Airtable. When the
+/// mapping of the table leaves some fields out of the push, only the fields that are
+/// pushed count, since the others may differ for good.
+fn changed_fields<F: Serialize + PartialEq>(
+    mapping: &FieldMapping,
+    records: Vec<Record<F>>,
+    existing: &[Record<F>],
+) -> Vec<Record<F>> {
+    let changed = changed_records(records, existing);
+    if !mapping.restricts_push() {
+        return changed;
+    }
+
+    let pushed = |fields: &F| mapping.to_airtable(serde_json::to_value(fields).unwrap_or(Value::Null));
+    let existing: HashMap<&str, &F> = existing.iter().map(|r| (r.id.as_str(), &r.fields)).collect();
+    changed
+        .into_iter()
+        .filter(|r| match existing.get(r.id.as_str()) {
+            Some(fields) => pushed(fields) != pushed(&r.fields),
+            None => true,
+        })
+        .collect()
+}
+
+/// Returns the record with its fields as the columns to send to Airtable, along with the
+/// id of its row if the table has a column for it.
+fn to_airtable_record<F: Serialize>(mapping: &FieldMapping, record: Record<F>, database_id: i32) -> Record<Value> {
+    let mut fields = mapping.to_airtable(serde_json::to_value(record.fields).unwrap_or(Value::Null));
+    mapping.link_database_id(&mut fields, database_id);
+
+    Record {
+        id: record.id,
+        fields,
+        created_time: record.created_time,
+    }
+}
+
+/// Returns the id of the table of the model, for linking to its records. Falls back to
+/// the name of the table when the schema of the base can't be read.
+async fn table_id<T: AirtableSyncable>(cache: &AirtableCache, airtable: &Airtable, base_id: &str) -> String {
+    match cache.base_schema(airtable, base_id).await {
+        Ok(schema) => schema
+            .table(T::AIRTABLE_TABLE)
+            .map(|t| t.id.to_string())
+            .unwrap_or_else(|| T::AIRTABLE_TABLE.to_string()),
+        Err(_) => T::AIRTABLE_TABLE.to_string(),
+    }
+}
+
+/// The records of a table, indexed by id and by unique key, so a sync matches each
+/// database record with its copy in memory instead of looking it up in Airtable.
+///
+/// The index is built once per run from the listing of the table, and remembers which
+/// records were matched so the rest can be deleted as stale.
+struct RecordIndex<'a, F> {
+    records: &'a [Record<F>],
+    by_id: HashMap<&'a str, &'a Record<F>>,
+    by_key: HashMap<String, &'a Record<F>>,
+    matched: HashSet<&'a str>,
+}
+
+impl<'a, F> RecordIndex<'a, F> {
+    fn new(records: &'a [Record<F>], unique_key: impl Fn(&F) -> String) -> Self {
+        RecordIndex {
+            records,
+            by_id: records.iter().map(|r| (r.id.as_str(), r)).collect(),
+            by_key: records.iter().map(|r| (unique_key(&r.fields), r)).collect(),
+            matched: Default::default(),
+        }
+    }
+
+    /// Returns the record with the id, or failing that the one with the unique key, and
+    /// marks it as matched.
+    fn find(&mut self, id: &str, key: &str) -> Option<&'a Record<F>> {
+        let found = self.by_id.get(id).or_else(|| self.by_key.get(key)).copied()?;
+        self.matched.insert(found.id.as_str());
+
+        Some(found)
+    }
+
+    /// Returns the ids of the records that were never matched, in the order of the listing.
+    fn unmatched(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.records
+            .iter()
+            .map(|r| r.id.as_str())
+            .filter(|id| !self.matched.contains(id))
+    }
+}
+
+/// The outcome of resolving the conflicts of a record.
+#[derive(Debug, Default)]
+struct Resolved {
+    /// The number of columns that differed.
+    conflicts: usize,
+    /// Whether any values were pulled from Airtable into the record.
+    pulled: bool,
+}
+
+/// Compare the editable columns of the record with its copy in Airtable and resolve the
+/// ones that differ following the policies of the model. Each conflict is recorded in
+/// the database, unless this is a dry run.
+#[allow(clippy::too_many_arguments)]
+async fn resolve_conflicts<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    mapping: &FieldMapping,
+    record: &mut T,
+    key: &str,
+    airtable: &T::Fields,
+    raw: &Value,
+    dry_run: DryRun,
+) -> Resolved {
+    let mut resolved = Resolved::default();
+    let policies = mapping.policies(T::AIRTABLE_FIELD_POLICIES);
+    if policies.is_empty() {
+        return resolved;
+    }
+
+    let ours = serde_json::to_value(record.airtable_fields()).unwrap_or(Value::Null);
+    let airtable_modified = raw
+        .get(mapping.modified_column(T::AIRTABLE_MODIFIED_FIELD))
+        .and_then(|v| v.as_str())
+        .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        .map(|v| v.with_timezone(&Utc));
+
+    for (field, policy) in policies.iter() {
+        let database_value = ours.get(field).unwrap_or(&Value::Null);
+        let airtable_value = raw.get(field).unwrap_or(&Value::Null);
+        if !values_differ(database_value, airtable_value) {
+            continue;
+        }
+
+        let resolution = policy.resolve(record.modified_at(), airtable_modified);
+        info!(
+            "`{}` of `{}` in `{}` differs from the database, keeping the {} value",
+            field,
+            key,
+            T::AIRTABLE_TABLE,
+            resolution.as_str()
+        );
+
+        let conflict = NewAirtableSyncConflict {
+            table_name: T::AIRTABLE_TABLE.to_string(),
+            record_key: key.to_string(),
+            field: field.to_string(),
+            database_value: database_value.to_string(),
+            airtable_value: airtable_value.to_string(),
+            resolution: resolution.as_str().to_string(),
+            detected_at: Utc::now(),
+            cio_company_id: company.id,
+        };
+        if !dry_run.is_enabled() {
+            if let Err(e) = conflict.create(db).await {
+                error!("recording the conflict in `{}` of `{}` failed: {}", field, key, e);
+            }
+        }
+
+        if resolution == Resolution::Airtable {
+            record.pull_airtable_field(field, airtable);
+            resolved.pulled = true;
+        }
+        resolved.conflicts += 1;
+    }
+
+    resolved
+}
+
+/// Returns true if the values of a column differ. Airtable leaves empty columns out of
+/// the records it returns, so a missing value is the same as an empty one.
+fn values_differ(a: &Value, b: &Value) -> bool {
+    fn is_empty(v: &Value) -> bool {
+        match v {
+            Value::Null => true,
+            Value::String(s) => s.is_empty(),
+            Value::Array(a) => a.is_empty(),
+            Value::Bool(b) => !b,
+            _ => false,
+        }
+    }
+
+    if is_empty(a) && is_empty(b) {
+        return false;
+    }
+
+    a != b
+}
+
+#[cfg(test)]
+mod tests {
+    use airtable_api::MockTransport;
+    use chrono::TimeZone;
+    use reqwest::{Method, StatusCode};
+    use serde_json::json;
+
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    #[tokio::test]
+    async fn test_cache_lists_fields_by_name() {
+        let transport = Arc::new(MockTransport::new().respond_json(
+            Method::GET,
+            "/v0/appCompanyDirectory/Users",
+            StatusCode::OK,
+            &json!({"records": [{"id": "rec1", "fields": {"email": "jess@example.com"}}]}),
+        ));
+        let mut company = mock_company();
+        company.airtable_base_id_directory = "appCompanyDirectory".to_string();
+        // The client the engine syncs with.
+        let airtable = BaseRegistry::default()
+            .authenticate(AirtableBase::Directory, &company)
+            .with_transport(transport.clone());
+
+        let records = AirtableCache::default()
+            .list(&airtable, "appCompanyDirectory", "Users")
+            .await
+            .unwrap();
+
+        assert_eq!(records[0].fields["email"], "jess@example.com");
+        let (_, url) = &transport.requests()[0];
+        assert!(url
+            .query_pairs()
+            .any(|(name, value)| name == "returnFieldsByFieldId" && value == "false"));
+    }
+
+    #[test]
+    fn test_record_index() {
+        let records: Vec<Record<String>> = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(i, key)| Record {
+                id: format!("rec{}", i),
+                fields: key.to_string(),
+                created_time: None,
+            })
+            .collect();
+        let mut index = RecordIndex::new(&records, |fields: &String| fields.to_string());
+
+        assert_eq!(index.find("rec0", "").map(|r| r.id.as_str()), Some("rec0"));
+        // A record whose id we failed to save is adopted by its key.
+        assert_eq!(index.find("", "c").map(|r| r.id.as_str()), Some("rec2"));
+        assert!(index.find("", "d").is_none());
+
+        assert_eq!(index.unmatched().collect::<Vec<_>>(), vec!["rec1"]);
+    }
+
+    #[test]
+    fn test_values_differ() {
+        assert!(!values_differ(&Value::Null, &json!("")));
+        assert!(!values_differ(&json!([]), &Value::Null));
+        assert!(!values_differ(&json!(["rec1"]), &json!(["rec1"])));
+        assert!(values_differ(&json!("Oxide"), &json!("Oxide Computer")));
+        assert!(values_differ(&Value::Null, &json!(["rec1"])));
+    }
+
+    #[test]
+    fn test_conflict_policy_resolve() {
+        let older = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let newer = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            ConflictPolicy::Database.resolve(None, Some(newer)),
+            Resolution::Database
+        );
+        assert_eq!(
+            ConflictPolicy::Airtable.resolve(Some(newer), None),
+            Resolution::Airtable
+        );
+        assert_eq!(
+            ConflictPolicy::NewestWins.resolve(Some(newer), Some(older)),
+            Resolution::Database
+        );
+        assert_eq!(
+            ConflictPolicy::NewestWins.resolve(Some(older), Some(newer)),
+            Resolution::Airtable
+        );
+        assert_eq!(
+            ConflictPolicy::NewestWins.resolve(Some(newer), None),
+            Resolution::Airtable
+        );
+    }
+}
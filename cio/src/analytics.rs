@@ -1,23 +1,38 @@
 #![allow(clippy::from_over_into)]
+use airtable_api::{created_time_partitions, Partition};
 use anyhow::Result;
 use async_bb8_diesel::AsyncRunQueryDsl;
-use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use log::{error, info, warn};
 use macros::db;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    airtable::AIRTABLE_PAGE_VIEWS_TABLE,
-    auth_logins::AuthUsers,
+    airtable::{AIRTABLE_PAGE_VIEWS_TABLE, AIRTABLE_PAGE_VIEW_STATS_TABLE},
+    airtable_bases::AirtableBase,
+    airtable_sync::{
+        sync_records_to_airtable, sync_to_airtable_cached, AirtableCache, AirtableSyncable, ConflictPolicy, SyncSummary,
+    },
+    app_config::AnalyticsConfig,
+    auth_config::AuthConfig,
+    auth_logins::auth_user_records_by_email,
     companies::{Company, Companys},
-    core::UpdateAirtableRecord,
+    core::{DryRun, UpdateAirtableRecord},
     db::Database,
-    schema::page_views,
+    error::CioError,
+    schema::{page_view_stats, page_views},
 };
 
+/// The number of months of page views the Airtable table is split into when it is listed.
+const PAGE_VIEW_READ_MONTHS: i64 = 6;
+
 #[db {
     new_struct_name = "PageView",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_PAGE_VIEWS_TABLE",
     match_on = {
         "time" = "DateTime<Utc>",
         "user_email" = "String",
@@ -59,3 +74,498 @@ impl NewPageView {
         Ok(())
     }
 }
+
+impl AirtableSyncable for PageView {
+    // The link to the user is made in Airtable.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] =
+        &[("link_to_auth_user", ConflictPolicy::Airtable)];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewPageView) -> String {
+        format!("{}/{}", fields.time.to_rfc3339(), fields.user_email)
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewPageView) {
+        if field == "link_to_auth_user" {
+            self.link_to_auth_user = airtable.link_to_auth_user.clone();
+        }
+    }
+
+    // Most page views are recent, so split the table by month for the last few months.
+    fn airtable_read_partitions() -> Vec<Partition> {
+        let now = Utc::now();
+        let bounds: Vec<DateTime<Utc>> = (1..PAGE_VIEW_READ_MONTHS)
+            .rev()
+            .map(|months| now - Duration::days(30 * months))
+            .collect();
+
+        created_time_partitions(&bounds)
+    }
+}
+
+/// The data type for a NewPageViewStat, the number of times a user viewed a page on a
+/// day. Airtable runs out of rows quickly with every page view, so we sync these too.
+#[db {
+    new_struct_name = "PageViewStat",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_PAGE_VIEW_STATS_TABLE",
+    match_on = {
+        "date" = "NaiveDate",
+        "domain" = "String",
+        "path" = "String",
+        "user_email" = "String",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = page_view_stats)]
+pub struct NewPageViewStat {
+    pub date: NaiveDate,
+    pub domain: String,
+    pub path: String,
+    pub user_email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub page_link: String,
+    #[serde(default)]
+    pub views: i64,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for PageViewStat {
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewPageViewStat) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            fields.date, fields.domain, fields.path, fields.user_email
+        )
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewPageViewStat) {}
+}
+
+/// Returns the page views of the company from `since` on.
+async fn list_page_views_since(db: &Database, company: &Company, since: DateTime<Utc>) -> Result<Vec<PageView>> {
+    Ok(page_views::dsl::page_views
+        .filter(page_views::dsl::cio_company_id.eq(company.id))
+        .filter(page_views::dsl::time.ge(since))
+        .load_async::<PageView>(db.pool())
+        .await?)
+}
+
+/// Count the page views per day, page and user.
+pub fn rollup_page_views(page_views: &[PageView]) -> Vec<NewPageViewStat> {
+    let mut stats: BTreeMap<(NaiveDate, &str, &str, &str), NewPageViewStat> = Default::default();
+    for page_view in page_views {
+        let date = page_view.time.date_naive();
+        stats
+            .entry((date, &page_view.domain, &page_view.path, &page_view.user_email))
+            .or_insert_with(|| NewPageViewStat {
+                date,
+                domain: page_view.domain.to_string(),
+                path: page_view.path.to_string(),
+                user_email: page_view.user_email.to_string(),
+                page_link: page_view.page_link.to_string(),
+                views: 0,
+                cio_company_id: page_view.cio_company_id,
+            })
+            .views += 1;
+    }
+
+    stats.into_values().collect()
+}
+
+/// Roll the page views of the company up into daily stats. If `since` is set, only the days
+/// from then on are counted again.
+pub async fn refresh_page_view_stats(
+    db: &Database,
+    company: &Company,
+    since: Option<DateTime<Utc>>,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    // Count whole days, otherwise we would overwrite the stats of the first day with a
+    // partial count.
+    let since = since
+        .map(|since| Utc.from_utc_datetime(&since.date_naive().and_hms_opt(0, 0, 0).unwrap()))
+        .unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap());
+
+    let page_views = list_page_views_since(db, company, since)
+        .await
+        .map_err(CioError::Database)?;
+    let stats = rollup_page_views(&page_views);
+    info!(
+        "rolled up {} page views since {} into {} daily stats",
+        page_views.len(),
+        since,
+        stats.len()
+    );
+
+    let mut summary = SyncSummary::default();
+    for stat in stats {
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would save {} views of `{}` by `{}` on {}",
+                stat.views, stat.page_link, stat.user_email, stat.date
+            );
+            summary.updated += 1;
+        } else if let Err(e) = stat.upsert(db).await {
+            error!(
+                "saving the views of `{}` by `{}` on {} failed: {}",
+                stat.page_link, stat.user_email, stat.date, e
+            );
+            summary.errors += 1;
+        } else {
+            summary.updated += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Sync the daily page view stats of the company to Airtable. If `since` is set, only the
+/// stats from then on are synced.
+pub async fn sync_page_view_stats_to_airtable(
+    db: &Database,
+    company: &Company,
+    since: Option<DateTime<Utc>>,
+    cache: &AirtableCache,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let since = match since {
+        Some(since) => since,
+        None => return sync_to_airtable_cached::<PageViewStat>(db, company, cache, dry_run).await,
+    };
+
+    let stats = page_view_stats::dsl::page_view_stats
+        .filter(page_view_stats::dsl::cio_company_id.eq(company.id))
+        .filter(page_view_stats::dsl::date.ge(since.date_naive()))
+        .load_async::<PageViewStat>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+    info!("syncing {} page view stats since {} to airtable", stats.len(), since);
+
+    sync_records_to_airtable(db, company, cache, stats, dry_run).await
+}
+
+/// Sync the page views of the company to Airtable. If `since` is set, only the page views
+/// from then on are synced.
+pub async fn sync_page_views_to_airtable(
+    db: &Database,
+    company: &Company,
+    since: Option<DateTime<Utc>>,
+    cache: &AirtableCache,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let since = match since {
+        Some(since) => since,
+        None => return sync_to_airtable_cached::<PageView>(db, company, cache, dry_run).await,
+    };
+
+    let page_views = list_page_views_since(db, company, since)
+        .await
+        .map_err(CioError::Database)?;
+    info!("syncing {} page views since {} to airtable", page_views.len(), since);
+
+    sync_records_to_airtable(db, company, cache, page_views, dry_run).await
+}
+
+/// The Google Analytics 4 Data API.
+const GA4_DATA_API: &str = "https://analyticsdata.googleapis.com/v1beta";
+
+/// The number of rows to ask for in each page of a report.
+const GA4_PAGE_SIZE: i64 = 100_000;
+
+/// The number of days to pull when no start date is given. Google Analytics can take up
+/// to two days to process the events, so pulling them again catches the late ones.
+const GA4_LOOKBACK_DAYS: i64 = 2;
+
+/// A page of a Google Analytics report.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportResponse {
+    #[serde(default)]
+    rows: Vec<ReportRow>,
+    #[serde(default)]
+    row_count: i64,
+}
+
+/// A row of a report, with the values in the order the dimensions and metrics were asked for.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportRow {
+    #[serde(default)]
+    dimension_values: Vec<ReportValue>,
+    #[serde(default)]
+    metric_values: Vec<ReportValue>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ReportValue {
+    #[serde(default)]
+    value: String,
+}
+
+/// Convert the rows of a report of the views per minute, site, page and user into page
+/// views.
+///
+/// The report only has the minute of each view, and we store one page view per user per
+/// time, so the views of a user within a minute are spread out a second apart. The rows
+/// are sorted first, so pulling the same report again yields the same page views.
+fn page_views_from_report(
+    rows: &[ReportRow],
+    timezone: &chrono_tz::Tz,
+    config: &AuthConfig,
+    auth_users: &HashMap<String, Vec<String>>,
+    company: &Company,
+) -> Vec<NewPageView> {
+    let mut views: Vec<(DateTime<Utc>, &str, &str, &str, i64)> = rows
+        .iter()
+        .filter_map(|row| {
+            let value = |i: usize| row.dimension_values.get(i).map(|v| v.value.as_str());
+            let minute = NaiveDateTime::parse_from_str(&format!("{}00", value(0)?), "%Y%m%d%H%M%S").ok()?;
+            let time = timezone.from_local_datetime(&minute).earliest()?.with_timezone(&Utc);
+            let count = row.metric_values.first()?.value.parse().ok()?;
+
+            Some((time, value(1)?, value(2)?, value(3)?, count))
+        })
+        .collect();
+    views.sort();
+
+    let mut seconds: HashMap<(DateTime<Utc>, &str), i64> = Default::default();
+    let mut page_views: Vec<NewPageView> = Default::default();
+    for (time, domain, path, user, count) in views {
+        // Google Analytics leaves out the user when it does not know them.
+        let user = if user == "(not set)" { "" } else { user };
+        let email = if user.contains('@') {
+            config.canonical_email(user)
+        } else {
+            String::new()
+        };
+
+        for _ in 0..count {
+            let second = seconds.entry((time, user)).or_default();
+            if *second >= 60 {
+                warn!(
+                    "dropping page views of `{}` at {}, there are more than one a second",
+                    user, time
+                );
+                break;
+            }
+
+            let mut page_view = NewPageView {
+                time: time + Duration::seconds(*second),
+                domain: domain.to_string(),
+                path: path.to_string(),
+                // Users we don't have an email for are kept by their client id.
+                user_email: user.to_string(),
+                page_link: Default::default(),
+                link_to_auth_user: auth_users.get(&email).cloned().unwrap_or_default(),
+                cio_company_id: company.id,
+            };
+            page_view.set_page_link();
+            page_views.push(page_view);
+            *second += 1;
+        }
+    }
+
+    page_views
+}
+
+/// Pull the page views of the company from Google Analytics into our database, attributed
+/// to the auth users with the same email. If `since` is not set, the last couple of days
+/// are pulled.
+pub async fn refresh_ga4_page_views(
+    db: &Database,
+    company: &Company,
+    analytics: &AnalyticsConfig,
+    since: Option<DateTime<Utc>>,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    if analytics.ga4_property_id.is_empty() {
+        return Ok(SyncSummary::default());
+    }
+
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let token = company
+        .get_google_analytics_token()
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let auth_users = auth_user_records_by_email(db, company, &config)
+        .await
+        .map_err(CioError::Database)?;
+
+    let since = since.unwrap_or_else(|| Utc::now() - Duration::days(GA4_LOOKBACK_DAYS));
+    let url = format!("{}/properties/{}:runReport", GA4_DATA_API, analytics.ga4_property_id);
+    let client = reqwest::Client::new();
+
+    let mut rows: Vec<ReportRow> = Default::default();
+    loop {
+        let body = serde_json::json!({
+            "dateRanges": [{
+                "startDate": since.with_timezone(&analytics.timezone).format("%Y-%m-%d").to_string(),
+                "endDate": "today",
+            }],
+            "dimensions": [
+                { "name": "dateHourMinute" },
+                { "name": "hostName" },
+                { "name": "pagePath" },
+                { "name": analytics.user_dimension },
+            ],
+            "metrics": [{ "name": "screenPageViews" }],
+            "limit": GA4_PAGE_SIZE,
+            "offset": rows.len(),
+        });
+
+        let resp = client
+            .post(&url)
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| CioError::GoogleAnalytics(e.into()))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(CioError::GoogleAnalytics(anyhow::anyhow!(
+                "running the page views report failed with {}: {}",
+                status,
+                text
+            )));
+        }
+
+        let page: ReportResponse = resp.json().await.map_err(|e| CioError::GoogleAnalytics(e.into()))?;
+        let fetched = page.rows.len();
+        rows.extend(page.rows);
+        if fetched == 0 || rows.len() as i64 >= page.row_count {
+            break;
+        }
+    }
+
+    let page_views = page_views_from_report(&rows, &analytics.timezone, &config, &auth_users, company);
+    info!(
+        "pulled {} page views since {} from google analytics property `{}`",
+        page_views.len(),
+        since,
+        analytics.ga4_property_id
+    );
+
+    let mut summary = SyncSummary::default();
+    if dry_run.is_enabled() {
+        info!("[dry-run] would save {} page views", page_views.len());
+        return Ok(summary);
+    }
+
+    for page_view in page_views {
+        match page_view.upsert(db).await {
+            Ok(_) => summary.updated += 1,
+            Err(e) => {
+                error!(
+                    "saving the view of `{}` by `{}` at {} failed: {}",
+                    page_view.page_link, page_view.user_email, page_view.time, e
+                );
+                summary.errors += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    fn page_view(time: &str, path: &str, user_email: &str) -> PageView {
+        PageView {
+            id: 0,
+            time: DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc),
+            domain: "oxide.computer".to_string(),
+            path: path.to_string(),
+            user_email: user_email.to_string(),
+            page_link: format!("https://oxide.computer/{}", path),
+            link_to_auth_user: vec![],
+            cio_company_id: 1,
+            airtable_record_id: String::new(),
+            airtable_record_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_rollup_page_views() {
+        let stats = rollup_page_views(&[
+            page_view("2024-01-01T09:00:00Z", "docs", "jess@example.com"),
+            page_view("2024-01-01T17:00:00Z", "docs", "jess@example.com"),
+            page_view("2024-01-01T17:00:00Z", "docs", "sam@example.com"),
+            page_view("2024-01-02T09:00:00Z", "docs", "jess@example.com"),
+        ]);
+
+        let views: Vec<(String, &str, i64)> = stats
+            .iter()
+            .map(|s| (s.date.to_string(), s.user_email.as_str(), s.views))
+            .collect();
+        assert_eq!(
+            views,
+            vec![
+                ("2024-01-01".to_string(), "jess@example.com", 2),
+                ("2024-01-01".to_string(), "sam@example.com", 1),
+                ("2024-01-02".to_string(), "jess@example.com", 1),
+            ]
+        );
+    }
+
+    fn report_row(values: &[&str], views: i64) -> ReportRow {
+        ReportRow {
+            dimension_values: values.iter().map(|v| ReportValue { value: v.to_string() }).collect(),
+            metric_values: vec![ReportValue {
+                value: views.to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_page_views_from_report() {
+        let config = AuthConfig::default();
+        let auth_users: HashMap<String, Vec<String>> =
+            [("jess@example.com".to_string(), vec!["recJess".to_string()])].into();
+
+        let page_views = page_views_from_report(
+            &[
+                report_row(&["202401020304", "oxide.computer", "/docs", "sam.1234"], 1),
+                report_row(&["202401020304", "oxide.computer", "/docs", "Jess@Example.com"], 2),
+                report_row(&["202401020304", "oxide.computer", "/blog", "Jess@Example.com"], 1),
+                report_row(&["not-a-time", "oxide.computer", "/docs", "sam.1234"], 1),
+            ],
+            &chrono_tz::America::New_York,
+            &config,
+            &auth_users,
+            &mock_company(),
+        );
+
+        let views: Vec<(String, &str, &str, usize)> = page_views
+            .iter()
+            .map(|p| {
+                (
+                    p.time.to_rfc3339(),
+                    p.path.as_str(),
+                    p.user_email.as_str(),
+                    p.link_to_auth_user.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            views,
+            vec![
+                ("2024-01-02T08:04:00+00:00".to_string(), "/blog", "Jess@Example.com", 1),
+                ("2024-01-02T08:04:01+00:00".to_string(), "/docs", "Jess@Example.com", 1),
+                ("2024-01-02T08:04:02+00:00".to_string(), "/docs", "Jess@Example.com", 1),
+                ("2024-01-02T08:04:00+00:00".to_string(), "/docs", "sam.1234", 0),
+            ]
+        );
+        assert_eq!(page_views[0].page_link, "https://oxide.computer/blog");
+    }
+}
@@ -109,6 +109,25 @@ pub struct Letter {
 pub struct ApplyConfig {
     received: Letter,
     rejection: HashMap<String, Letter>,
+    /// The Google Sheets that collect the responses of application forms.
+    #[serde(default)]
+    pub sheets: Vec<ApplicationSheet>,
+}
+
+/// A Google Sheet that collects the responses of an application form.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ApplicationSheet {
+    /// The id of the spreadsheet.
+    pub id: String,
+    /// The role the applicants applied for.
+    pub role: String,
+    /// The range the responses are in, with a header row first.
+    #[serde(default = "default_application_sheet_range")]
+    pub range: String,
+}
+
+fn default_application_sheet_range() -> String {
+    "Form Responses 1".to_string()
 }
 
 impl ApplyConfig {
@@ -166,6 +185,40 @@ pub struct GitHubConfig {
     pub ignored_repos: Vec<String>,
 }
 
+/// Where to pull the page views of our sites from.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnalyticsConfig {
+    /// The id of the Google Analytics 4 property, for example `123456789`. Page views are
+    /// only pulled when this is set.
+    #[serde(default)]
+    pub ga4_property_id: String,
+    /// The custom dimension holding the email, or failing that the client id, of the user
+    /// who viewed the page.
+    #[serde(default = "default_analytics_user_dimension")]
+    pub user_dimension: String,
+    /// The time zone of the property, which the times in the reports are in.
+    #[serde(default = "default_analytics_timezone")]
+    pub timezone: chrono_tz::Tz,
+}
+
+fn default_analytics_user_dimension() -> String {
+    "customUser:user_email".to_string()
+}
+
+fn default_analytics_timezone() -> chrono_tz::Tz {
+    chrono_tz::UTC
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        AnalyticsConfig {
+            ga4_property_id: String::new(),
+            user_dimension: default_analytics_user_dimension(),
+            timezone: default_analytics_timezone(),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct AppConfig {
     pub envelopes: DocuSignConfig,
@@ -174,6 +227,8 @@ pub struct AppConfig {
     pub finance: FinanceConfig,
     #[serde(default)]
     pub github: GitHubConfig,
+    #[serde(default)]
+    pub analytics: AnalyticsConfig,
 }
 
 #[cfg(test)]
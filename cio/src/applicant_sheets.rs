@@ -0,0 +1,332 @@
+//! Applicants that applied through a Google Form, whose responses land in a Google Sheet.
+//!
+//! The responses are read from the sheets listed in the apply config, normalized into
+//! applicants and synced to the hiring base in Airtable. Once an applicant is in the
+//! database, only the fields the applicant filled in are refreshed from the sheet; the
+//! status and everything else the hiring team changes are kept.
+use std::str::FromStr;
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use log::{error, info, warn};
+
+use crate::{
+    airtable_sync::{sync_to_airtable, SyncSummary},
+    app_config::{AppConfig, ApplicationSheet},
+    applicant_status::Status,
+    applicants::{clean_interested_in, Applicant, NewApplicant},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+};
+
+/// The format of the timestamps Google Forms writes to the sheet.
+const FORM_TIMESTAMP_FORMAT: &str = "%m/%d/%Y %H:%M:%S";
+
+/// The index of each column we read in a sheet of responses, found by the header.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct SheetColumns {
+    timestamp: Option<usize>,
+    name: Option<usize>,
+    email: Option<usize>,
+    phone: Option<usize>,
+    location: Option<usize>,
+    github: Option<usize>,
+    gitlab: Option<usize>,
+    linkedin: Option<usize>,
+    portfolio: Option<usize>,
+    website: Option<usize>,
+    resume: Option<usize>,
+    materials: Option<usize>,
+    interested_in: Option<usize>,
+    status: Option<usize>,
+}
+
+impl SheetColumns {
+    /// Find the columns in the header row. The form questions change wording over time,
+    /// so the headers are matched loosely.
+    fn from_header(header: &[String]) -> Self {
+        let find = |needles: &[&str]| {
+            header.iter().position(|h| {
+                let h = h.trim().to_lowercase();
+                needles.iter().any(|n| h.contains(n))
+            })
+        };
+
+        SheetColumns {
+            timestamp: find(&["timestamp"]),
+            name: find(&["name"]),
+            email: find(&["email"]),
+            phone: find(&["phone"]),
+            location: find(&["location"]),
+            github: find(&["github"]),
+            gitlab: find(&["gitlab"]),
+            linkedin: find(&["linkedin"]),
+            portfolio: find(&["portfolio"]),
+            website: find(&["website"]),
+            resume: find(&["resume"]),
+            materials: find(&["materials"]),
+            interested_in: find(&["interested in"]),
+            status: find(&["status"]),
+        }
+    }
+
+    /// Normalize a row of responses into an applicant. Rows without an email are skipped.
+    fn parse_row(&self, row: &[String], sheet: &ApplicationSheet, company: &Company) -> Option<NewApplicant> {
+        let get = |column: Option<usize>| {
+            column
+                .and_then(|i| row.get(i))
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let email = get(self.email).to_lowercase();
+        if email.is_empty() {
+            return None;
+        }
+
+        let submitted_time = NaiveDateTime::parse_from_str(&get(self.timestamp), FORM_TIMESTAMP_FORMAT)
+            .map(|t| Utc.from_utc_datetime(&t))
+            .unwrap_or_else(|_| Utc::now());
+        let raw_status = get(self.status);
+        let status = Status::from_str(&raw_status).unwrap_or_default();
+
+        Some(NewApplicant {
+            name: get(self.name),
+            role: sheet.role.to_string(),
+            sheet_id: sheet.id.to_string(),
+            status: status.to_string(),
+            raw_status,
+            submitted_time,
+            email,
+            phone: get(self.phone),
+            location: get(self.location),
+            github: clean_github(&get(self.github)),
+            gitlab: get(self.gitlab),
+            linkedin: get(self.linkedin),
+            portfolio: get(self.portfolio),
+            website: get(self.website),
+            resume: get(self.resume),
+            materials: get(self.materials),
+            interested_in: get(self.interested_in)
+                .split(',')
+                .map(|s| clean_interested_in(s.trim()))
+                .filter(|s| !s.is_empty())
+                .collect(),
+            cio_company_id: company.id,
+            ..Default::default()
+        })
+    }
+}
+
+/// Returns the GitHub handle of a response, which is sometimes a link to the profile.
+fn clean_github(github: &str) -> String {
+    let github = github
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .trim_start_matches("github.com/")
+        .trim_start_matches('@')
+        .trim_end_matches('/');
+
+    if github.is_empty() {
+        String::new()
+    } else {
+        format!("@{}", github)
+    }
+}
+
+/// Refresh the applicant with a new response from the sheet. Only the fields the
+/// applicant filled in are taken from the response, the status, reviews and everything
+/// else we keep on the applicant are left as they are.
+fn merge_response(existing: Option<NewApplicant>, response: NewApplicant) -> NewApplicant {
+    let mut applicant = match existing {
+        Some(existing) => existing,
+        None => return response,
+    };
+
+    applicant.name = response.name;
+    applicant.role = response.role;
+    applicant.submitted_time = response.submitted_time;
+    applicant.phone = response.phone;
+    applicant.location = response.location;
+    applicant.github = response.github;
+    applicant.gitlab = response.gitlab;
+    applicant.linkedin = response.linkedin;
+    applicant.portfolio = response.portfolio;
+    applicant.website = response.website;
+    applicant.resume = response.resume;
+    applicant.materials = response.materials;
+    applicant.interested_in = response.interested_in;
+
+    applicant
+}
+
+/// Read the responses of one application sheet.
+async fn get_sheet_responses(
+    sheets: &sheets::Client,
+    sheet: &ApplicationSheet,
+    company: &Company,
+) -> Result<Vec<NewApplicant>, CioError> {
+    let values = sheets
+        .spreadsheets()
+        .values_get(
+            &sheet.id,
+            &sheet.range,
+            sheets::types::DateTimeRenderOption::FormattedString,
+            sheets::types::Dimension::Rows,
+            sheets::types::ValueRenderOption::FormattedValue,
+        )
+        .await
+        .map_err(|e| CioError::GoogleSheets(e.into()))?
+        .body
+        .values;
+
+    let mut rows = values.iter();
+    let columns = match rows.next() {
+        Some(header) => SheetColumns::from_header(header),
+        None => return Ok(vec![]),
+    };
+    if columns.email.is_none() {
+        warn!("application sheet `{}` has no email column, skipping", sheet.id);
+        return Ok(vec![]);
+    }
+
+    Ok(rows.filter_map(|row| columns.parse_row(row, sheet, company)).collect())
+}
+
+/// Sync the applicants from the application sheets with our database and the hiring base
+/// in Airtable.
+pub async fn refresh_sheet_applicants(
+    db: &Database,
+    company: &Company,
+    app_config: &AppConfig,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    if company.airtable_base_id_hiring.is_empty() || app_config.apply.sheets.is_empty() {
+        return Ok(SyncSummary::default());
+    }
+
+    let sheets = company
+        .authenticate_google_sheets(db)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let mut summary = SyncSummary::default();
+    for sheet in &app_config.apply.sheets {
+        let responses = get_sheet_responses(&sheets, sheet, company).await?;
+        info!(
+            "syncing {} applicants from application sheet `{}`",
+            responses.len(),
+            sheet.id
+        );
+
+        if dry_run.is_enabled() {
+            info!("[dry-run] would save {} applicants", responses.len());
+            continue;
+        }
+
+        for response in responses {
+            let existing: Option<NewApplicant> =
+                Applicant::get_from_db(db, response.email.to_string(), response.sheet_id.to_string())
+                    .await
+                    .map(Into::into);
+            let applicant = merge_response(existing, response);
+
+            if let Err(e) = applicant.upsert(db).await {
+                error!("saving applicant `{}` failed: {}", applicant.email, e);
+                summary.errors += 1;
+            }
+        }
+    }
+
+    summary += sync_to_airtable::<Applicant>(db, company, dry_run).await?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_row() {
+        let columns = SheetColumns::from_header(&strings(&[
+            "Timestamp",
+            "Name",
+            "Email Address",
+            "GitHub Profile",
+            "Which roles are you interested in?",
+            "Status",
+        ]));
+        let sheet = ApplicationSheet {
+            id: "sheet1".to_string(),
+            role: "Engineering".to_string(),
+            range: "Form Responses 1".to_string(),
+        };
+
+        let applicant = columns
+            .parse_row(
+                &strings(&[
+                    "1/2/2020 3:04:05",
+                    " Jess Doe ",
+                    "Jess@Example.com",
+                    "https://github.com/jess/",
+                    "Hardware Engineer, software engineer: web",
+                    "Next steps",
+                ]),
+                &sheet,
+                &mock_company(),
+            )
+            .unwrap();
+        assert_eq!(applicant.name, "Jess Doe");
+        assert_eq!(applicant.email, "jess@example.com");
+        assert_eq!(applicant.github, "@jess");
+        assert_eq!(applicant.sheet_id, "sheet1");
+        assert_eq!(applicant.status, Status::NextSteps.to_string());
+        assert_eq!(
+            applicant.interested_in,
+            vec!["Hardware Engineer", "Software Engineer: Web"]
+        );
+        assert_eq!(
+            applicant.submitted_time,
+            Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap()
+        );
+
+        // Rows without an email are skipped.
+        assert!(columns
+            .parse_row(&strings(&["1/2/2020 3:04:05", "Sam"]), &sheet, &mock_company())
+            .is_none());
+    }
+
+    #[test]
+    fn test_merge_response_keeps_status() {
+        let existing = NewApplicant {
+            email: "jess@example.com".to_string(),
+            status: Status::Interviewing.to_string(),
+            raw_status: "Interviewing".to_string(),
+            scorers: vec!["sam@example.com".to_string()],
+            phone: "555-0100".to_string(),
+            ..Default::default()
+        };
+        let response = NewApplicant {
+            email: "jess@example.com".to_string(),
+            phone: "555-0199".to_string(),
+            ..Default::default()
+        };
+
+        let applicant = merge_response(Some(existing), response.clone());
+        assert_eq!(applicant.status, Status::Interviewing.to_string());
+        assert_eq!(applicant.raw_status, "Interviewing");
+        assert_eq!(applicant.scorers, vec!["sam@example.com"]);
+        assert_eq!(applicant.phone, "555-0199");
+
+        assert_eq!(merge_response(None, response.clone()), response);
+    }
+}
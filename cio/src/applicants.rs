@@ -18,24 +18,29 @@ use regex::Regex;
 use schemars::JsonSchema;
 use sendgrid_api::{traits::MailOps, Client as SendGrid};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "slack")]
 use slack_chat_api::{
     FormattedMessage, MessageAttachment, MessageBlock, MessageBlockText, MessageBlockType, MessageType,
 };
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
+#[cfg(feature = "github")]
+use crate::utils::check_if_github_issue_exists;
 use crate::{
     airtable::{AIRTABLE_APPLICATIONS_TABLE, AIRTABLE_REVIEWER_LEADERBOARD_TABLE},
+    airtable_bases::{AirtableBase, BaseRegistry},
+    airtable_sync::{sync_records_to_airtable, AirtableCache, AirtableSyncable, ConflictPolicy},
     app_config::{AppConfig, ApplyConfig, Letter, NewHireIssue},
     applicant_reviews::ApplicantReview,
     companies::Company,
     configs::User,
-    core::UpdateAirtableRecord,
+    core::{DryRun, UpdateAirtableRecord},
     db::Database,
     enclose,
     interviews::ApplicantInterview,
     schema::{applicant_interviews, applicant_reviewers, applicants, users},
-    utils::{check_if_github_issue_exists, truncate},
+    utils::truncate,
 };
 
 // The line breaks that get parsed are weird thats why we have the random asterisks here.
@@ -64,6 +69,8 @@ impl From<&OnboardingAssignees> for Vec<String> {
 /// The data type for a NewApplicant.
 #[db {
     new_struct_name = "Applicant",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_APPLICATIONS_TABLE",
     match_on = {
         "email" = "String",
         "sheet_id" = "String",
@@ -246,6 +253,130 @@ pub struct NewApplicant {
     pub cio_company_id: i32,
 }
 
+impl Default for NewApplicant {
+    fn default() -> Self {
+        NewApplicant {
+            name: String::new(),
+            role: String::new(),
+            sheet_id: String::new(),
+            status: crate::applicant_status::Status::NeedsToBeTriaged.to_string(),
+            raw_status: String::new(),
+            submitted_time: Utc::now(),
+            email: String::new(),
+            phone: String::new(),
+            country_code: String::new(),
+            location: String::new(),
+            latitude: 0.0,
+            longitude: 0.0,
+            github: String::new(),
+            gitlab: String::new(),
+            linkedin: String::new(),
+            portfolio: String::new(),
+            portfolio_pdf: String::new(),
+            website: String::new(),
+            resume: String::new(),
+            materials: String::new(),
+            sent_email_received: false,
+            sent_email_follow_up: false,
+            rejection_sent_date_time: None,
+            value_reflected: String::new(),
+            value_violated: String::new(),
+            values_in_tension: Default::default(),
+            resume_contents: String::new(),
+            materials_contents: String::new(),
+            work_samples: String::new(),
+            writing_samples: String::new(),
+            analysis_samples: String::new(),
+            presentation_samples: String::new(),
+            exploratory_samples: String::new(),
+            question_technically_challenging: String::new(),
+            question_proud_of: String::new(),
+            question_happiest: String::new(),
+            question_unhappiest: String::new(),
+            question_value_reflected: String::new(),
+            question_value_violated: String::new(),
+            question_values_in_tension: String::new(),
+            question_why_oxide: String::new(),
+            interview_packet: String::new(),
+            interviews: Default::default(),
+            interviews_started: None,
+            interviews_completed: None,
+            scorers: Default::default(),
+            scorers_completed: Default::default(),
+            scoring_form_id: String::new(),
+            scoring_form_url: String::new(),
+            scoring_form_responses_url: String::new(),
+            scoring_evaluations_count: 0,
+            scoring_enthusiastic_yes_count: 0,
+            scoring_yes_count: 0,
+            scoring_pass_count: 0,
+            scoring_no_count: 0,
+            scoring_not_applicable_count: 0,
+            scoring_insufficient_experience_count: 0,
+            scoring_inapplicable_experience_count: 0,
+            scoring_job_function_yet_needed_count: 0,
+            scoring_underwhelming_materials_count: 0,
+            criminal_background_check_status: String::new(),
+            motor_vehicle_background_check_status: String::new(),
+            start_date: None,
+            interested_in: Default::default(),
+            geocode_cache: String::new(),
+            docusign_envelope_id: String::new(),
+            docusign_envelope_status: String::new(),
+            offer_created: None,
+            offer_completed: None,
+            docusign_piia_envelope_id: String::new(),
+            docusign_piia_envelope_status: String::new(),
+            piia_envelope_created: None,
+            piia_envelope_completed: None,
+            link_to_reviews: Default::default(),
+            cio_company_id: 0,
+        }
+    }
+}
+
+/// Only the applicants that came in through a Google Sheet are synced this way, the
+/// applicants from the application form are updated as they are processed.
+#[async_trait]
+impl AirtableSyncable for Applicant {
+    // The hiring team moves applicants along in Airtable.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] = &[
+        ("status", ConflictPolicy::Airtable),
+        ("raw_status", ConflictPolicy::Airtable),
+        ("scorers", ConflictPolicy::Airtable),
+        ("interviews", ConflictPolicy::Airtable),
+        ("link_to_reviews", ConflictPolicy::Airtable),
+        ("start_date", ConflictPolicy::Airtable),
+    ];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::Hiring;
+
+    fn unique_key(fields: &NewApplicant) -> String {
+        format!("{}/{}", fields.sheet_id, fields.email)
+    }
+
+    async fn list_for_airtable(db: &Database, company: &Company) -> Result<Vec<Self>> {
+        Ok(applicants::dsl::applicants
+            .filter(applicants::dsl::cio_company_id.eq(company.id))
+            .filter(applicants::dsl::sheet_id.ne("".to_string()))
+            .order_by(applicants::dsl::id.asc())
+            .load_async::<Applicant>(db.pool())
+            .await?)
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewApplicant) {
+        match field {
+            "status" => self.status = airtable.status.to_string(),
+            "raw_status" => self.raw_status = airtable.raw_status.to_string(),
+            "scorers" => self.scorers = airtable.scorers.clone(),
+            "interviews" => self.interviews = airtable.interviews.clone(),
+            "link_to_reviews" => self.link_to_reviews = airtable.link_to_reviews.clone(),
+            "start_date" => self.start_date = airtable.start_date,
+            _ => (),
+        }
+    }
+}
+
 pub fn clean_interested_in(st: &str) -> String {
     let s = st.trim().to_lowercase();
 
@@ -264,6 +395,7 @@ pub fn clean_interested_in(st: &str) -> String {
     st.to_string()
 }
 
+#[cfg(feature = "slack")]
 impl NewApplicant {
     /// Get the human duration of time since the application was submitted.
     fn human_duration(&self) -> HumanTime {
@@ -276,6 +408,7 @@ impl NewApplicant {
     }
 }
 
+#[cfg(feature = "slack")]
 fn get_color_based_on_status(s: &str) -> String {
     let status = crate::applicant_status::Status::from_str(s).unwrap();
 
@@ -296,6 +429,7 @@ fn get_color_based_on_status(s: &str) -> String {
 }
 
 /// Convert the applicant into a Slack message.
+#[cfg(feature = "slack")]
 impl From<NewApplicant> for FormattedMessage {
     fn from(item: NewApplicant) -> Self {
         let time = item.human_duration();
@@ -440,6 +574,7 @@ impl From<NewApplicant> for FormattedMessage {
     }
 }
 
+#[cfg(feature = "slack")]
 impl From<Applicant> for FormattedMessage {
     fn from(item: Applicant) -> Self {
         let new: NewApplicant = item.into();
@@ -448,6 +583,7 @@ impl From<Applicant> for FormattedMessage {
 }
 
 impl Applicant {
+    #[cfg(feature = "github")]
     pub async fn refresh(
         &mut self,
         db: &Database,
@@ -567,7 +703,7 @@ impl Applicant {
 
         // Create the Airtable client.
         let company = Company::get_by_id(db, self.cio_company_id).await?;
-        let airtable = company.authenticate_airtable(&company.airtable_base_id_hiring);
+        let airtable = BaseRegistry::from_env()?.authenticate(Applicant::AIRTABLE_BASE, &company);
 
         // We need to capture the existing score count prior to mutations to ensure that
         // we can properly detect when we need to zero out onboarding and hired employees
@@ -914,6 +1050,7 @@ The applicants Airtable is at: https://airtable-applicants.corp.oxide.computer\
         username
     }
 
+    #[cfg(feature = "github")]
     pub async fn create_github_onboarding_issue(
         &self,
         db: &Database,
@@ -1072,6 +1209,7 @@ Notes:
         Ok(())
     }
 
+    #[cfg_attr(not(feature = "github"), allow(dead_code))]
     fn create_new_hire_issue_body(&self, username: &str, config: &NewHireIssue) -> String {
         let first_name = self.first_name();
         let last_name = self.last_name();
@@ -1329,7 +1467,13 @@ pub async fn refresh_docusign_for_applicants(db: &Database, company: &Company, c
     let applicants = Applicants::get_from_db(db, company.id).await?;
 
     // Iterate over the applicants and find any that have the status: giving offer.
+    let mut changed: Vec<Applicant> = Default::default();
     for mut applicant in applicants {
+        let statuses = (
+            applicant.docusign_envelope_status.to_string(),
+            applicant.docusign_piia_envelope_status.to_string(),
+        );
+
         let offer_letter = match applicant.role.as_str() {
             "Sales" => config.envelopes.create_sales_offer_letter(&applicant),
             _ => config.envelopes.create_offer_letter(&applicant),
@@ -1340,6 +1484,15 @@ pub async fn refresh_docusign_for_applicants(db: &Database, company: &Company, c
         applicant
             .do_docusign_piia(db, &ds, config.envelopes.create_piia_letter(&applicant))
             .await?;
+
+        if statuses.0 != applicant.docusign_envelope_status || statuses.1 != applicant.docusign_piia_envelope_status {
+            changed.push(applicant);
+        }
+    }
+
+    // Push the applicants whose envelopes moved, so the hiring team sees the new status.
+    if !changed.is_empty() {
+        sync_records_to_airtable(db, company, &AirtableCache::default(), changed, DryRun(false)).await?;
     }
 
     Ok(())
@@ -1972,6 +2125,7 @@ The applicants Airtable \
 
             // Let's create the envelope.
             let envelope = ds.create_envelope(new_envelope).await?;
+            crate::offer_envelopes::record_status_change(db, "offer", self, "", &envelope).await?;
 
             // Set the id of the envelope.
             self.docusign_envelope_id = envelope.envelope_id.to_string();
@@ -2003,6 +2157,10 @@ The applicants Airtable \
 
         let company = self.company(db).await?;
 
+        // Keep the history of the envelope before we overwrite its status.
+        crate::offer_envelopes::record_status_change(db, "offer", self, &self.docusign_envelope_status, &envelope)
+            .await?;
+
         // Set the status in the database and airtable.
         self.docusign_envelope_status = envelope.status.to_string();
         self.offer_created = envelope.created_date_time;
@@ -2177,6 +2335,7 @@ The applicants Airtable \
 
             // Let's create the envelope.
             let envelope = ds.create_envelope(new_envelope).await?;
+            crate::offer_envelopes::record_status_change(db, "piia", self, "", &envelope).await?;
 
             // Set the id of the envelope.
             self.docusign_piia_envelope_id = envelope.envelope_id.to_string();
@@ -2225,6 +2384,7 @@ The applicants Airtable \
 
             // Let's create the envelope.
             let envelope = ds.create_envelope(new_envelope).await?;
+            crate::offer_envelopes::record_status_change(db, "piia", self, "", &envelope).await?;
 
             // Set the id of the envelope.
             self.docusign_piia_envelope_id = envelope.envelope_id.to_string();
@@ -2284,6 +2444,10 @@ The applicants Airtable \
 
         let company = self.company(db).await?;
 
+        // Keep the history of the envelope before we overwrite its status.
+        crate::offer_envelopes::record_status_change(db, "piia", self, &self.docusign_piia_envelope_status, &envelope)
+            .await?;
+
         // Set the status in the database and airtable.
         self.docusign_piia_envelope_status = envelope.status.to_string();
         self.piia_envelope_created = envelope.created_date_time;
@@ -2356,6 +2520,7 @@ The applicants Airtable \
     }
 }
 
+#[cfg(feature = "github")]
 pub async fn refresh_new_applicants_and_reviews(
     db: &Database,
     company: &Company,
@@ -2550,6 +2715,7 @@ pub mod tests {
             link_to_reviews: vec![],
             cio_company_id: 0,
             airtable_record_id: String::default(),
+            airtable_record_url: String::default(),
         }
     }
 
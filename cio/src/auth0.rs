@@ -0,0 +1,1397 @@
+//! A small client for the Auth0 management API.
+//!
+//! Docs: https://auth0.com/docs/api/management/v2
+use std::{
+    collections::HashSet,
+    fmt,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use airtable_api::{new_request_id, ApiError, HttpTransport, RequestId, REQUEST_ID_HEADER};
+use anyhow::{anyhow, bail, Result};
+use chrono::{offset::Utc, DateTime, SecondsFormat, TimeZone};
+use futures::{stream, StreamExt, TryStreamExt};
+use log::{info, warn};
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::Instrument;
+
+use crate::{auth0_logs::LogEvent, metrics, secrets};
+
+/// Refresh the management token this long before Auth0 says it expires, so a request
+/// never goes out with a token that expires in flight.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The maximum number of results Auth0 returns in one page of a listing.
+const PAGE_SIZE: u32 = 100;
+
+/// The most results of a user search Auth0 lets us page through, later pages are
+/// rejected with a `400 Bad Request`.
+/// https://auth0.com/docs/manage-users/user-search/view-search-results-by-page#limitation
+pub const MAX_SEARCH_RESULTS: u32 = 1000;
+
+/// How many pages of users to fetch at once when listing them.
+const LIST_USERS_CONCURRENCY: usize = 4;
+
+/// The sort the users are listed in by default. The pages of a listing are fetched at
+/// once, which is only safe on a key that doesn't move while we page: sorted by last login,
+/// a user who logs in mid-listing jumps to a page already fetched and is missed.
+pub const STABLE_USERS_SORT: &str = "created_at:1";
+
+/// The maximum number of events Auth0 returns from the log stream in one request.
+const LOGS_PAGE_SIZE: u32 = 100;
+
+/// The most pages of a user's logs to fetch at once, so a user with a flood of events
+/// can't stall a sync. The events older than that are read from the tenant log stream.
+const MAX_USER_LOG_PAGES: u32 = 10;
+
+/// How many times to retry a request that was rejected for being over the rate limit.
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
+struct AccessToken {
+    secret: String,
+    expires_at: Instant,
+}
+
+/// The rate limit state advertised by the `x-ratelimit-*` headers on the last response.
+/// https://auth0.com/docs/troubleshoot/customer-support/operational-policies/rate-limit-policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The number of requests left in the current window.
+    pub remaining: u64,
+    /// When the window resets.
+    pub reset: DateTime<Utc>,
+}
+
+impl RateLimit {
+    /// Parse the rate limit headers from a response, if they are all present.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name)?.to_str().ok()?.parse::<i64>().ok();
+
+        Some(RateLimit {
+            remaining: header("x-ratelimit-remaining")?.max(0) as u64,
+            reset: Utc.timestamp_opt(header("x-ratelimit-reset")?, 0).single()?,
+        })
+    }
+
+    /// How long to wait before sending the next request. This is zero until we have
+    /// used up the current window, and then the time until the window resets.
+    pub fn delay(&self) -> std::time::Duration {
+        if self.remaining > 0 {
+            return std::time::Duration::ZERO;
+        }
+
+        (self.reset - Utc::now()).to_std().unwrap_or_default()
+    }
+}
+
+/// A client for the Auth0 management API of a single tenant.
+///
+/// The management token is cached and refreshed when it expires. Requests are paced
+/// by the rate limit headers Auth0 returns. Clones of the client share both.
+#[derive(Clone)]
+pub struct Auth0Client {
+    domain: String,
+    /// The URL of the tenant, `https://{domain}.auth0.com` unless it is pointed at a mock
+    /// server.
+    base_url: String,
+    client_id: String,
+    client_secret: String,
+    access_token: Arc<RwLock<Option<AccessToken>>>,
+    rate_limit: Arc<RwLock<Option<RateLimit>>>,
+    client: Client,
+    /// Sends the requests built with `client`.
+    transport: Arc<dyn HttpTransport>,
+}
+
+impl Auth0Client {
+    /// Create a new Auth0 client for the tenant `{domain}.auth0.com`.
+    pub fn new<D, I, S>(domain: D, client_id: I, client_secret: S) -> Self
+    where
+        D: ToString,
+        I: ToString,
+        S: ToString,
+    {
+        let client = Client::new();
+        Auth0Client {
+            base_url: format!("https://{}.auth0.com", domain.to_string()),
+            domain: domain.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            access_token: Arc::new(RwLock::new(None)),
+            rate_limit: Arc::new(RwLock::new(None)),
+            transport: Arc::new(client.clone()),
+            client,
+        }
+    }
+
+    /// Send the requests of the client to another URL than the tenant, for example a mock
+    /// server in a test. The domain is still used to tag the users and logins.
+    pub fn with_base_url<U: ToString>(mut self, base_url: U) -> Self {
+        self.base_url = base_url.to_string().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Send the requests of the client through the transport instead of `reqwest`, for
+    /// example to answer them with canned responses in a test.
+    pub fn with_transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Create a new Auth0 client for the tenant, reading the credentials of the
+    /// management API application from the `CIO_AUTH0_CLIENT_ID` and
+    /// `CIO_AUTH0_CLIENT_SECRET` secrets, see `secrets`.
+    pub fn new_from_env<D>(domain: D) -> Result<Self>
+    where
+        D: ToString,
+    {
+        Ok(Auth0Client::new(
+            domain,
+            secrets::get("CIO_AUTH0_CLIENT_ID")?,
+            secrets::get("CIO_AUTH0_CLIENT_SECRET")?,
+        ))
+    }
+
+    /// Returns the tenant the client is talking to.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Exchange the client credentials for a management API token and cache it.
+    async fn fetch_token(&self) -> Result<()> {
+        // Snapshot the time before the token request. We only get back an "expires_in"
+        // duration, so we want to be conservative about when our access will expire.
+        let now = Instant::now();
+
+        let mut map = std::collections::HashMap::new();
+        map.insert("client_id", self.client_id.to_string());
+        map.insert("client_secret", self.client_secret.to_string());
+        map.insert("audience", format!("https://{}.auth0.com/api/v2/", self.domain));
+        map.insert("grant_type", "client_credentials".to_string());
+
+        let request = self
+            .client
+            .post(&format!("{}/oauth/token", self.base_url()))
+            .json(&map)
+            .build()?;
+        let resp = self.transport.execute(request).await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => return Err(Auth0Error::from_response("getting auth0 token", resp).await.into()),
+        };
+
+        let token: Token = resp.json().await?;
+        let expires_in = Duration::from_secs(token.expires_in.max(0) as u64).saturating_sub(TOKEN_EXPIRY_MARGIN);
+
+        *self.access_token.write().unwrap() = Some(AccessToken {
+            secret: token.access_token,
+            expires_at: now + expires_in,
+        });
+
+        Ok(())
+    }
+
+    fn token_is_expired(&self) -> bool {
+        if let Ok(guard) = self.access_token.read() {
+            guard
+                .as_ref()
+                .map(|token| token.expires_at <= Instant::now())
+                .unwrap_or(true)
+        } else {
+            // If we do not have an access token then we consider it to be expired.
+            true
+        }
+    }
+
+    /// Returns a valid management API token, fetching a new one if the cached
+    /// token is missing or expired.
+    pub async fn get_token(&self) -> Result<String> {
+        if self.token_is_expired() {
+            self.fetch_token().await?;
+        }
+
+        match &*self.access_token.read().unwrap() {
+            Some(token) => Ok(token.secret.to_string()),
+            None => bail!("no auth0 token"),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(
+                method,
+                &format!("{}/api/v2/{}", self.base_url(), path.trim_start_matches('/')),
+            )
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json")
+    }
+
+    /// Send a request to the management API, authenticated with the cached token.
+    ///
+    /// If the last response told us we have used up the rate limit window, this waits
+    /// for the window to reset before sending the request. Requests that are rejected
+    /// with `429 Too Many Requests` are retried once the window resets.
+    ///
+    /// The request is sent with a generated id in the `x-request-id` header, the same one
+    /// for every retry, which the errors read from the response carry along.
+    pub async fn execute(&self, builder: RequestBuilder) -> Result<Response> {
+        let request_id = new_request_id();
+        let span = tracing::info_span!("auth0_request", request_id = %request_id, tenant = %self.domain);
+
+        let mut attempt = 0;
+        loop {
+            let token = self.get_token().await?;
+
+            let delay = self.rate_limit().map(|r| r.delay()).unwrap_or_default();
+            if !delay.is_zero() {
+                info!("auth0 rate limit reached, waiting {:?} for it to reset", delay);
+                metrics::rate_limit_wait("auth0", delay);
+                tokio::time::sleep(delay).await;
+            }
+
+            let request = builder
+                .try_clone()
+                .ok_or_else(|| anyhow!("auth0 request cannot be cloned to be sent"))?
+                .bearer_auth(token)
+                .header(REQUEST_ID_HEADER, &request_id)
+                .build()?;
+            let timer = metrics::time_api("auth0", request.method().as_str());
+            let mut resp = self.transport.execute(request).instrument(span.clone()).await?;
+            timer.observe_duration();
+            resp.extensions_mut().insert(RequestId(request_id.clone()));
+
+            let rate_limit = RateLimit::from_headers(resp.headers());
+            if resp.status() != StatusCode::TOO_MANY_REQUESTS {
+                if let Some(rate_limit) = rate_limit {
+                    *self.rate_limit.write().unwrap() = Some(rate_limit);
+                }
+
+                return Ok(resp);
+            }
+
+            if attempt >= MAX_RATE_LIMIT_RETRIES {
+                warn!("auth0 request was still rate limited after {} retries", attempt);
+                return Ok(resp);
+            }
+            attempt += 1;
+
+            // Make sure we wait for the window to reset before the retry, even if the
+            // headers are missing or claim there are requests remaining.
+            let reset = rate_limit
+                .map(|r| r.reset)
+                .filter(|reset| *reset > Utc::now())
+                .unwrap_or_else(|| Utc::now() + chrono::Duration::seconds(1 << attempt));
+            warn!(
+                "auth0 request was rate limited, retrying after {} (attempt {}/{})",
+                reset, attempt, MAX_RATE_LIMIT_RETRIES
+            );
+            *self.rate_limit.write().unwrap() = Some(RateLimit { remaining: 0, reset });
+        }
+    }
+
+    /// Returns the rate limit state from the last response, if any.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        *self.rate_limit.read().unwrap()
+    }
+
+    /// List all the users in the tenant matching the options.
+    ///
+    /// The query uses the Lucene syntax of the v3 user search engine, for example
+    /// `logins_count:>0 AND last_login:[2024-01-01 TO *]`. An empty query lists every user.
+    /// https://auth0.com/docs/manage-users/user-search/user-search-query-syntax
+    ///
+    /// The totals of the first page tell us how many pages there are, the rest are then
+    /// fetched `LIST_USERS_CONCURRENCY` at a time, or one at a time when the options sort
+    /// by something other than `STABLE_USERS_SORT`. They are still paced by the rate limit.
+    ///
+    /// Only the first `MAX_SEARCH_RESULTS` users of a search can be paged through. When
+    /// more users match, they are listed by creation, in windows that start at the
+    /// creation of the last user of the window before.
+    pub async fn list_users(&self, opts: &ListUsersOptions) -> Result<Vec<User>> {
+        let first = self.list_users_page(0, opts).await?;
+        if first.total <= MAX_SEARCH_RESULTS as i64 {
+            return self.list_users_pages(first, opts).await;
+        }
+
+        info!(
+            "{} auth0 users match `{}`, more than a search can page through, listing them by creation",
+            first.total, opts.q
+        );
+        let mut opts = ListUsersOptions {
+            sort: STABLE_USERS_SORT.to_string(),
+            ..opts.clone()
+        };
+        let mut seen: HashSet<String> = Default::default();
+        let mut users: Vec<User> = Default::default();
+        loop {
+            let first = self.list_users_page(0, &opts).await?;
+            let last_window = first.total <= MAX_SEARCH_RESULTS as i64;
+            let window = self.list_users_pages(first, &opts).await?;
+
+            let next = window.last().map(|u| u.created_at);
+            // The windows overlap on the users created at the same time as the last one.
+            users.extend(window.into_iter().filter(|u| seen.insert(u.user_id.to_string())));
+            if last_window {
+                break;
+            }
+
+            match next {
+                Some(next) if Some(next) != opts.created_since => opts.created_since = Some(next),
+                _ => bail!(
+                    "more than {} auth0 users matching `{}` were created at the same time, they can't be listed",
+                    MAX_SEARCH_RESULTS,
+                    opts.q
+                ),
+            }
+        }
+
+        Ok(users)
+    }
+
+    /// Returns the users of the listing the first page is of, up to the ones a search can
+    /// page through.
+    async fn list_users_pages(&self, first: UsersPage, opts: &ListUsersOptions) -> Result<Vec<User>> {
+        let pages = first.pages().min(opts.max_pages());
+        let mut users: Vec<User> = parse_each(first.users, "user");
+
+        let rest: Vec<UsersPage> = stream::iter(1..pages)
+            .map(|page| self.list_users_page(page, opts))
+            .buffered(opts.concurrency())
+            .try_collect()
+            .await?;
+        for p in rest {
+            users.append(&mut parse_each(p.users, "user"));
+        }
+
+        Ok(users)
+    }
+
+    /// List a page of the users in the tenant matching the options, along with the totals.
+    pub async fn list_users_page(&self, page: u32, opts: &ListUsersOptions) -> Result<UsersPage> {
+        let page = page.to_string();
+        let per_page = opts.per_page.clamp(1, PAGE_SIZE).to_string();
+        let mut query = vec![
+            ("per_page", per_page.as_str()),
+            ("page", page.as_str()),
+            ("include_totals", "true"),
+        ];
+        if !opts.sort.is_empty() {
+            query.push(("sort", opts.sort.as_str()));
+        }
+        let q = opts.query();
+        if !q.is_empty() {
+            query.push(("q", q.as_str()));
+            query.push(("search_engine", "v3"));
+        }
+
+        let resp = self.execute(self.request(Method::GET, "users").query(&query)).await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => return Err(Auth0Error::from_response("listing auth0 users", resp).await.into()),
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// Returns the number of users in the tenant matching the query.
+    pub async fn count_users(&self, q: &str) -> Result<i64> {
+        let opts = ListUsersOptions {
+            per_page: 1,
+            ..ListUsersOptions::search(q)
+        };
+
+        Ok(self.list_users_page(0, &opts).await?.total)
+    }
+
+    /// List all the connections in the tenant, for example `google-oauth2` or `github`.
+    pub async fn list_connections(&self) -> Result<Vec<Connection>> {
+        self.list_all("connections").await
+    }
+
+    /// Returns the number of users whose identities include the connection.
+    pub async fn count_connection_users(&self, connection: &str) -> Result<i64> {
+        self.count_users(&format!("identities.connection:\"{}\"", connection))
+            .await
+    }
+
+    /// List the most recent log events for a user.
+    pub async fn list_user_logs(&self, user_id: &str) -> Result<Vec<LogEvent>> {
+        self.list_user_logs_since(user_id, None).await
+    }
+
+    /// List the log events for a user at or after the event `since`, most recent first.
+    /// Without `since`, only the most recent page of events is listed.
+    ///
+    /// The endpoint can't filter by date, so the pages are fetched newest first until one
+    /// reaches `since`, up to `MAX_USER_LOG_PAGES` of them. The events older than the last
+    /// page are then read from the tenant log stream after `since`, so none are lost.
+    pub async fn list_user_logs_since(&self, user_id: &str, since: Option<&LogCheckpoint>) -> Result<Vec<LogEvent>> {
+        let per_page = PAGE_SIZE.to_string();
+        let mut logs: Vec<LogEvent> = Default::default();
+        for page in 0..MAX_USER_LOG_PAGES {
+            let page = page.to_string();
+            let resp = self
+                .execute(self.request(Method::GET, &format!("users/{}/logs", user_id)).query(&[
+                    ("per_page", per_page.as_str()),
+                    ("page", page.as_str()),
+                    ("sort", "date:-1"),
+                ]))
+                .await?;
+
+            match resp.status() {
+                StatusCode::OK => (),
+                _ => return Err(Auth0Error::from_response("getting auth0 user logs", resp).await.into()),
+            };
+
+            let events: Vec<serde_json::Value> = resp.json().await?;
+            let done = events.len() < PAGE_SIZE as usize;
+            let (mut events, reached) = events_since(parse_each(events, "log event"), since.map(|s| s.date));
+            logs.append(&mut events);
+
+            if done || reached {
+                return Ok(logs);
+            }
+        }
+
+        // Without `since` the first page is all we want, so we only get here with it.
+        if let Some(since) = since {
+            warn!(
+                "auth0 user `{}` has more than {} new log events, reading the rest from the tenant log stream",
+                user_id,
+                MAX_USER_LOG_PAGES * PAGE_SIZE
+            );
+            let seen: HashSet<String> = logs.iter().map(|e| e.log_id.to_string()).collect();
+            let mut rest: Vec<LogEvent> = self
+                .list_logs(&since.log_id)
+                .await?
+                .into_iter()
+                .filter(|e| e.user_id == user_id && e.date >= since.date && !seen.contains(&e.log_id))
+                .collect();
+            logs.append(&mut rest);
+            logs.sort_by(|a, b| b.date.cmp(&a.date));
+        }
+
+        Ok(logs)
+    }
+
+    /// Get a user by their id.
+    pub async fn get_user(&self, user_id: &str) -> Result<User> {
+        let resp = self
+            .execute(self.request(Method::GET, &format!("users/{}", user_id)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => {
+                return Err(
+                    Auth0Error::from_response(format!("getting auth0 user `{}`", user_id), resp)
+                        .await
+                        .into(),
+                )
+            }
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// List the users with the given email address. There can be more than one, since each
+    /// connection a person logs in with creates a separate user.
+    pub async fn list_users_by_email(&self, email: &str) -> Result<Vec<User>> {
+        let resp = self
+            .execute(self.request(Method::GET, "users-by-email").query(&[("email", email)]))
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => {
+                return Err(Auth0Error::from_response("listing auth0 users by email", resp)
+                    .await
+                    .into())
+            }
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// Create a user.
+    pub async fn create_user(&self, user: &NewUser) -> Result<User> {
+        let resp = self.execute(self.request(Method::POST, "users").json(user)).await?;
+
+        match resp.status() {
+            StatusCode::CREATED => (),
+            _ => return Err(Auth0Error::from_response("creating auth0 user", resp).await.into()),
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// Update a user. Only the fields that are set on the update are changed.
+    pub async fn update_user(&self, user_id: &str, update: &UpdateUser) -> Result<User> {
+        let resp = self
+            .execute(self.request(Method::PATCH, &format!("users/{}", user_id)).json(update))
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => {
+                return Err(
+                    Auth0Error::from_response(format!("updating auth0 user `{}`", user_id), resp)
+                        .await
+                        .into(),
+                )
+            }
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// Delete a user.
+    pub async fn delete_user(&self, user_id: &str) -> Result<()> {
+        let resp = self
+            .execute(self.request(Method::DELETE, &format!("users/{}", user_id)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::NO_CONTENT | StatusCode::OK => (),
+            _ => {
+                return Err(
+                    Auth0Error::from_response(format!("deleting auth0 user `{}`", user_id), resp)
+                        .await
+                        .into(),
+                )
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Block a user from logging in.
+    pub async fn block_user(&self, user_id: &str) -> Result<User> {
+        self.update_user(
+            user_id,
+            &UpdateUser {
+                blocked: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Allow a blocked user to log in again.
+    pub async fn unblock_user(&self, user_id: &str) -> Result<User> {
+        self.update_user(
+            user_id,
+            &UpdateUser {
+                blocked: Some(false),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Merge the given keys into the metadata of a user. Keys set to null are removed.
+    pub async fn update_user_metadata(&self, user_id: &str, user_metadata: serde_json::Value) -> Result<User> {
+        self.update_user(
+            user_id,
+            &UpdateUser {
+                user_metadata: Some(user_metadata),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Merge the given keys into the app metadata of a user. Keys set to null are removed.
+    pub async fn update_app_metadata(&self, user_id: &str, app_metadata: serde_json::Value) -> Result<User> {
+        self.update_user(
+            user_id,
+            &UpdateUser {
+                app_metadata: Some(app_metadata),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Block every user with the given email address, for example when someone offboards.
+    /// Returns the users that were blocked.
+    pub async fn block_users_by_email(&self, email: &str) -> Result<Vec<User>> {
+        let mut blocked: Vec<User> = Default::default();
+        for user in self.list_users_by_email(email).await? {
+            if user.blocked {
+                continue;
+            }
+
+            blocked.push(self.block_user(&user.user_id).await?);
+        }
+
+        Ok(blocked)
+    }
+
+    /// Revoke the grants of a user, so the applications they authorized have to ask again.
+    pub async fn revoke_grants(&self, user_id: &str) -> Result<()> {
+        let resp = self
+            .execute(self.request(Method::DELETE, "grants").query(&[("user_id", user_id)]))
+            .await?;
+
+        match resp.status() {
+            StatusCode::NO_CONTENT | StatusCode::OK => (),
+            _ => {
+                return Err(
+                    Auth0Error::from_response(format!("revoking the grants of auth0 user `{}`", user_id), resp)
+                        .await
+                        .into(),
+                )
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Revoke the refresh tokens of a user. Auth0 does this in the background.
+    pub async fn revoke_refresh_tokens(&self, user_id: &str) -> Result<()> {
+        let resp = self
+            .execute(self.request(Method::DELETE, &format!("users/{}/refresh-tokens", user_id)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::ACCEPTED | StatusCode::NO_CONTENT | StatusCode::OK => (),
+            _ => {
+                return Err(Auth0Error::from_response(
+                    format!("revoking the refresh tokens of auth0 user `{}`", user_id),
+                    resp,
+                )
+                .await
+                .into())
+            }
+        };
+
+        Ok(())
+    }
+
+    /// End every session of a user. Auth0 does this in the background.
+    pub async fn revoke_sessions(&self, user_id: &str) -> Result<()> {
+        let resp = self
+            .execute(self.request(Method::DELETE, &format!("users/{}/sessions", user_id)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::ACCEPTED | StatusCode::NO_CONTENT | StatusCode::OK => (),
+            _ => {
+                return Err(Auth0Error::from_response(
+                    format!("revoking the sessions of auth0 user `{}`", user_id),
+                    resp,
+                )
+                .await
+                .into())
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Revoke everything that lets a user stay logged in: their sessions, refresh tokens
+    /// and grants. This does not stop them logging in again, see `block_user`.
+    pub async fn revoke_access(&self, user_id: &str) -> Result<()> {
+        self.revoke_sessions(user_id).await?;
+        self.revoke_refresh_tokens(user_id).await?;
+        self.revoke_grants(user_id).await
+    }
+
+    /// Send the user an email to verify their email address. The email is sent by a job
+    /// in Auth0, which is returned.
+    /// https://auth0.com/docs/api/management/v2/jobs/post-verification-email
+    pub async fn send_verification_email(&self, user_id: &str) -> Result<Job> {
+        let resp = self
+            .execute(
+                self.request(Method::POST, "jobs/verification-email")
+                    .json(&serde_json::json!({ "user_id": user_id })),
+            )
+            .await?;
+
+        match resp.status() {
+            StatusCode::CREATED | StatusCode::OK => (),
+            _ => {
+                return Err(Auth0Error::from_response(
+                    format!("sending the verification email to auth0 user `{}`", user_id),
+                    resp,
+                )
+                .await
+                .into())
+            }
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// Link the account of the secondary user to the primary user, so the person can log in
+    /// with either and is a single user. Auth0 deletes the secondary user. Returns the
+    /// identities of the primary user.
+    /// https://auth0.com/docs/manage-users/user-accounts/user-account-linking
+    pub async fn link_user_account(&self, primary_user_id: &str, secondary_user_id: &str) -> Result<Vec<Identity>> {
+        let (provider, user_id) = secondary_user_id
+            .split_once('|')
+            .ok_or_else(|| anyhow!("auth0 user id `{}` has no provider", secondary_user_id))?;
+
+        let resp = self
+            .execute(
+                self.request(Method::POST, &format!("users/{}/identities", primary_user_id))
+                    .json(&serde_json::json!({ "provider": provider, "user_id": user_id })),
+            )
+            .await?;
+
+        match resp.status() {
+            StatusCode::CREATED | StatusCode::OK => (),
+            _ => {
+                return Err(Auth0Error::from_response(
+                    format!("linking auth0 user `{}` to `{}`", secondary_user_id, primary_user_id),
+                    resp,
+                )
+                .await
+                .into())
+            }
+        };
+
+        Ok(resp.json().await?)
+    }
+
+    /// List all the roles in the tenant.
+    pub async fn list_roles(&self) -> Result<Vec<Role>> {
+        self.list_all("roles").await
+    }
+
+    /// List all the users that hold a role.
+    pub async fn list_role_users(&self, role_id: &str) -> Result<Vec<RoleMember>> {
+        self.list_all(&format!("roles/{}/users", role_id)).await
+    }
+
+    /// List all the roles assigned to a user.
+    pub async fn list_user_roles(&self, user_id: &str) -> Result<Vec<Role>> {
+        self.list_all(&format!("users/{}/roles", user_id)).await
+    }
+
+    /// List all the permissions a user has, both directly and through their roles.
+    pub async fn list_user_permissions(&self, user_id: &str) -> Result<Vec<Permission>> {
+        self.list_all(&format!("users/{}/permissions", user_id)).await
+    }
+
+    /// Walk every page of a listing endpoint that uses page based pagination.
+    async fn list_all<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>> {
+        let mut all: Vec<T> = Default::default();
+
+        let per_page = PAGE_SIZE.to_string();
+        let mut page: i32 = 0;
+        loop {
+            let p = page.to_string();
+            let resp = self
+                .execute(
+                    self.request(Method::GET, path)
+                        .query(&[("per_page", per_page.as_str()), ("page", p.as_str())]),
+                )
+                .await?;
+
+            match resp.status() {
+                StatusCode::OK => (),
+                _ => {
+                    return Err(Auth0Error::from_response(format!("listing auth0 `{}`", path), resp)
+                        .await
+                        .into())
+                }
+            };
+
+            let mut results: Vec<T> = resp.json().await?;
+            let done = results.len() < PAGE_SIZE as usize;
+            all.append(&mut results);
+
+            if done {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(all)
+    }
+
+    /// List all the events in the tenant log stream after the checkpoint, oldest first.
+    /// An empty checkpoint starts at the oldest event Auth0 still retains.
+    /// https://auth0.com/docs/deploy-monitor/logs/retrieve-log-events-using-mgmt-api
+    pub async fn list_logs(&self, from: &str) -> Result<Vec<LogEvent>> {
+        let mut logs: Vec<LogEvent> = Default::default();
+
+        let mut from = from.to_string();
+        loop {
+            let mut l = self.list_logs_page(&from, LOGS_PAGE_SIZE).await?;
+            let done = l.len() < LOGS_PAGE_SIZE as usize;
+
+            match l.last() {
+                Some(last) => from = last.log_id.to_string(),
+                None => break,
+            }
+            logs.append(&mut l);
+
+            if done {
+                break;
+            }
+        }
+
+        Ok(logs)
+    }
+
+    /// List up to `take` events from the tenant log stream after the event with the id `from`.
+    pub async fn list_logs_page(&self, from: &str, take: u32) -> Result<Vec<LogEvent>> {
+        let take = take.to_string();
+        let mut query = vec![("take", take.as_str())];
+        if !from.is_empty() {
+            query.push(("from", from));
+        }
+
+        let resp = self.execute(self.request(Method::GET, "logs").query(&query)).await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            _ => return Err(Auth0Error::from_response("listing auth0 logs", resp).await.into()),
+        };
+
+        Ok(parse_each(resp.json().await?, "log event"))
+    }
+}
+
+/// The newest log event we have stored of a user, to list the ones after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogCheckpoint {
+    /// The id of the event, which the tenant log stream can be read from.
+    pub log_id: String,
+    pub date: DateTime<Utc>,
+}
+
+/// Deserialize each item of a listing on its own, skipping the ones that fail to parse
+/// so one malformed record does not fail the whole listing.
+pub(crate) fn parse_each<T: DeserializeOwned>(values: Vec<serde_json::Value>, what: &str) -> Vec<T> {
+    values
+        .into_iter()
+        .filter_map(|value| match serde_json::from_value(value.clone()) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("skipping auth0 {} that failed to parse: {} | {}", what, e, value);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the events, most recent first, that happened at or after `since`, and whether
+/// the page reached back past it, so there is no need for the next one. Without `since`,
+/// the first page is all we want.
+fn events_since(events: Vec<LogEvent>, since: Option<DateTime<Utc>>) -> (Vec<LogEvent>, bool) {
+    let since = match since {
+        Some(since) => since,
+        None => return (events, true),
+    };
+
+    let reached = events.iter().any(|e| e.date < since);
+    (events.into_iter().filter(|e| e.date >= since).collect(), reached)
+}
+
+/// An error response from the Auth0 management API.
+///
+/// This is returned wrapped in an [`anyhow::Error`]. Callers that want to decide whether
+/// to retry, skip or abort can get it back with `err.downcast_ref::<Auth0Error>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Auth0Error {
+    /// What we were trying to do, for example `listing auth0 users`.
+    pub action: String,
+    pub status: StatusCode,
+    pub body: String,
+    /// The id we sent the request with, see `Auth0Client::execute`.
+    pub request_id: Option<String>,
+    /// The id Auth0 returned for the request, if any.
+    pub trace_id: Option<String>,
+}
+
+impl Auth0Error {
+    /// Read the error from a response that was not successful.
+    pub async fn from_response<A: ToString>(action: A, resp: Response) -> Self {
+        let err = ApiError::from_response(resp).await;
+
+        Auth0Error {
+            action: action.to_string(),
+            status: err.status,
+            body: err.body,
+            request_id: err.request_id,
+            trace_id: err.trace_id,
+        }
+    }
+
+    /// Returns true if sending the same request again later might succeed.
+    pub fn is_retryable(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS || self.status.is_server_error()
+    }
+
+    /// Returns true if the resource does not exist, for example a user that was deleted
+    /// in between listing and fetching them.
+    pub fn is_not_found(&self) -> bool {
+        self.status == StatusCode::NOT_FOUND
+    }
+}
+
+impl fmt::Display for Auth0Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed, status: {} | resp: {}",
+            self.action, self.status, self.body
+        )?;
+        if let Some(id) = &self.request_id {
+            write!(f, " | request id: {}", id)?;
+        }
+        if let Some(id) = &self.trace_id {
+            write!(f, " | trace id: {}", id)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Auth0Error {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct Token {
+    access_token: String,
+    token_type: String,
+    #[serde(default)]
+    scope: String,
+    #[serde(default)]
+    expires_in: i64,
+}
+
+/// A user in Auth0.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct User {
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub nickname: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub username: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub picture: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub company: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub blog: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub phone_number: String,
+    #[serde(default)]
+    pub phone_verified: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub locale: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identities: Vec<Identity>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// This is not set for users that have never logged in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_login: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_ip: String,
+    #[serde(default)]
+    pub logins_count: i32,
+    #[serde(default)]
+    pub blocked: bool,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub user_metadata: serde_json::Value,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub app_metadata: serde_json::Value,
+}
+
+/// The fields used to create a user in Auth0.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NewUser {
+    /// The name of the connection to create the user in, for example
+    /// `Username-Password-Authentication`.
+    pub connection: String,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub password: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub nickname: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub verify_email: bool,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub user_metadata: serde_json::Value,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub app_metadata: serde_json::Value,
+}
+
+/// The fields to change on a user in Auth0. Fields that are not set are left as they are.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UpdateUser {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_metadata: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_metadata: Option<serde_json::Value>,
+}
+
+/// An identity linked to an Auth0 user, for example their GitHub or Google account.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Identity {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub provider: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub connection: String,
+    #[serde(default)]
+    pub user_id: serde_json::Value,
+    #[serde(default, rename = "isSocial")]
+    pub is_social: bool,
+}
+
+/// The options for listing users.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListUsersOptions {
+    /// A Lucene search query, empty lists every user.
+    pub q: String,
+    /// The number of users to get per request, at most 100.
+    pub per_page: u32,
+    /// The field to sort by and the direction, for example `last_login:-1`. Anything but
+    /// `STABLE_USERS_SORT` makes `Auth0Client::list_users` fetch the pages one at a time.
+    pub sort: String,
+    /// Only list the users created at or after this time.
+    pub created_since: Option<DateTime<Utc>>,
+}
+
+impl Default for ListUsersOptions {
+    fn default() -> Self {
+        ListUsersOptions {
+            q: String::new(),
+            per_page: PAGE_SIZE,
+            sort: STABLE_USERS_SORT.to_string(),
+            created_since: None,
+        }
+    }
+}
+
+impl ListUsersOptions {
+    /// The default options for the users matching the search query.
+    pub fn search(q: &str) -> Self {
+        ListUsersOptions {
+            q: q.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the search query sent, `q` narrowed to the users created since
+    /// `created_since`.
+    pub fn query(&self) -> String {
+        let since = match self.created_since {
+            Some(since) => since,
+            None => return self.q.to_string(),
+        };
+
+        let created = format!(
+            "created_at:[{} TO *]",
+            since.to_rfc3339_opts(SecondsFormat::Millis, true)
+        );
+        if self.q.is_empty() {
+            created
+        } else {
+            format!("({}) AND {}", self.q, created)
+        }
+    }
+
+    /// Returns how many pages of the listing can be fetched at once, see
+    /// `STABLE_USERS_SORT`.
+    pub fn concurrency(&self) -> usize {
+        if self.sort == STABLE_USERS_SORT {
+            LIST_USERS_CONCURRENCY
+        } else {
+            1
+        }
+    }
+
+    /// Returns the number of pages of a search that can be paged through, see
+    /// `MAX_SEARCH_RESULTS`.
+    pub fn max_pages(&self) -> u32 {
+        (MAX_SEARCH_RESULTS / self.per_page.clamp(1, PAGE_SIZE)).max(1)
+    }
+}
+
+/// A page of users returned when `include_totals` is set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UsersPage {
+    #[serde(default)]
+    pub start: i64,
+    #[serde(default)]
+    pub limit: i64,
+    #[serde(default)]
+    pub length: i64,
+    #[serde(default)]
+    pub total: i64,
+    /// The users are left unparsed, so one malformed user does not fail the whole page.
+    #[serde(default)]
+    pub users: Vec<serde_json::Value>,
+}
+
+impl UsersPage {
+    /// Returns the number of pages in the listing, going by the totals and the size of
+    /// the pages Auth0 returns.
+    pub fn pages(&self) -> u32 {
+        if self.length == 0 || self.limit <= 0 {
+            return 1;
+        }
+
+        let pages = self.total / self.limit + i64::from(self.total % self.limit != 0);
+        pages.max(1) as u32
+    }
+}
+
+/// A connection users can log in with in Auth0.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Connection {
+    pub id: String,
+    pub name: String,
+    /// The type of the connection, for example `google-oauth2`, `github` or `auth0` for a
+    /// database connection.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub strategy: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enabled_clients: Vec<String>,
+}
+
+/// A role in Auth0.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Role {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+/// A user that holds a role in Auth0.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RoleMember {
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub picture: String,
+}
+
+/// A job in Auth0, for example sending a verification email.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Job {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub status: String,
+    #[serde(default, rename = "type", skip_serializing_if = "String::is_empty")]
+    pub typev: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// A permission on an API in Auth0.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Permission {
+    pub permission_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub resource_server_identifier: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub resource_server_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let reset = Utc::now() + chrono::Duration::seconds(30);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("50"));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert(
+            "x-ratelimit-reset",
+            HeaderValue::from_str(&reset.timestamp().to_string()).unwrap(),
+        );
+
+        let rate_limit = RateLimit::from_headers(&headers).unwrap();
+        assert_eq!(rate_limit.remaining, 0);
+        assert!(rate_limit.delay() > std::time::Duration::from_secs(25));
+
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("10"));
+        let rate_limit = RateLimit::from_headers(&headers).unwrap();
+        assert_eq!(rate_limit.delay(), std::time::Duration::ZERO);
+
+        headers.remove("x-ratelimit-reset");
+        assert!(RateLimit::from_headers(&headers).is_none());
+    }
+
+    #[test]
+    fn test_users_page_count() {
+        let page = |limit, length, total| UsersPage {
+            start: 0,
+            limit,
+            length,
+            total,
+            users: vec![],
+        };
+
+        assert_eq!(page(100, 100, 1000).pages(), 10);
+        assert_eq!(page(100, 100, 1001).pages(), 11);
+        assert_eq!(page(100, 3, 3).pages(), 1);
+        assert_eq!(page(100, 0, 0).pages(), 1);
+        assert_eq!(page(0, 0, 50).pages(), 1);
+    }
+
+    #[test]
+    fn test_list_users_options_query() {
+        let since = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let opts = ListUsersOptions {
+            created_since: Some(since),
+            ..ListUsersOptions::search("logins_count:>0")
+        };
+        assert_eq!(
+            opts.query(),
+            "(logins_count:>0) AND created_at:[2024-01-02T03:04:05.000Z TO *]"
+        );
+        assert_eq!(opts.max_pages(), 10);
+
+        let opts = ListUsersOptions {
+            created_since: Some(since),
+            per_page: 30,
+            ..Default::default()
+        };
+        assert_eq!(opts.query(), "created_at:[2024-01-02T03:04:05.000Z TO *]");
+        assert_eq!(opts.max_pages(), 33);
+    }
+
+    #[test]
+    fn test_list_users_options_concurrency() {
+        let opts = ListUsersOptions::default();
+        assert_eq!(opts.sort, STABLE_USERS_SORT);
+        assert_eq!(opts.concurrency(), LIST_USERS_CONCURRENCY);
+
+        let opts = ListUsersOptions {
+            sort: "last_login:-1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(opts.concurrency(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_with_canned_responses() {
+        let transport = airtable_api::MockTransport::new()
+            .respond_json(
+                Method::POST,
+                "/oauth/token",
+                StatusCode::OK,
+                &serde_json::json!({"access_token": "token", "expires_in": 86400, "token_type": "Bearer"}),
+            )
+            .respond_json(
+                Method::GET,
+                "/api/v2/users",
+                StatusCode::OK,
+                &serde_json::json!({
+                    "start": 0,
+                    "limit": 1,
+                    "length": 1,
+                    "total": 2,
+                    "users": [{
+                        "user_id": "github|1",
+                        "created_at": "2021-01-01T00:00:00.000Z",
+                        "updated_at": "2021-01-02T00:00:00.000Z",
+                    }],
+                }),
+            )
+            .respond_json(
+                Method::GET,
+                "/api/v2/users",
+                StatusCode::OK,
+                &serde_json::json!({
+                    "start": 1,
+                    "limit": 1,
+                    "length": 1,
+                    "total": 2,
+                    "users": [{
+                        "user_id": "google-oauth2|2",
+                        "created_at": "2021-01-01T00:00:00.000Z",
+                        "updated_at": "2021-01-02T00:00:00.000Z",
+                    }],
+                }),
+            );
+        let transport = Arc::new(transport);
+        let auth0 = Auth0Client::new("oxide", "id", "secret").with_transport(transport.clone());
+
+        let users = auth0.list_users(&ListUsersOptions::default()).await.unwrap();
+        let ids: Vec<&str> = users.iter().map(|u| u.user_id.as_str()).collect();
+        assert_eq!(ids, vec!["github|1", "google-oauth2|2"]);
+
+        // The token is fetched once and reused for both pages.
+        let paths: Vec<String> = transport
+            .requests()
+            .iter()
+            .map(|(_, url)| url.path().to_string())
+            .collect();
+        assert_eq!(paths, vec!["/oauth/token", "/api/v2/users", "/api/v2/users"]);
+    }
+
+    #[test]
+    fn test_parse_each_skips_malformed_users() {
+        let users: Vec<User> = parse_each(
+            vec![
+                serde_json::json!({
+                    "user_id": "github|1",
+                    "created_at": "2021-01-01T00:00:00.000Z",
+                    "updated_at": "2021-01-02T00:00:00.000Z",
+                }),
+                serde_json::json!({"user_id": "github|2", "created_at": "not a date"}),
+            ],
+            "user",
+        );
+
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, "github|1");
+        assert_eq!(users[0].last_login, None);
+    }
+}
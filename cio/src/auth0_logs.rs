@@ -0,0 +1,480 @@
+//! The events in the Auth0 log stream, typed.
+//!
+//! Docs: https://auth0.com/docs/deploy-monitor/logs/log-event-type-codes
+use std::fmt;
+
+use chrono::{offset::Utc, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth_logins::NewAuthUserLogin, companies::Company};
+
+/// An event in the Auth0 logs, of a tenant or a user.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LogEvent {
+    pub date: DateTime<Utc>,
+    #[serde(rename = "type")]
+    pub event_type: LogEventType,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(flatten)]
+    pub client: LogClient,
+    #[serde(flatten)]
+    pub connection: LogConnection,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ip: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hostname: String,
+    /// Empty for the events that do not belong to a user, for example management API calls.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub audience: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub log_id: String,
+    #[serde(default, alias = "isMobile")]
+    pub is_mobile: bool,
+    /// The browser and OS, as Auth0 summarizes them, see `UserAgent`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_agent: String,
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub details: serde_json::Value,
+}
+
+/// The application an event came through.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LogClient {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub client_name: String,
+}
+
+/// The connection a user signed in with.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LogConnection {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub connection: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub connection_id: String,
+    /// For example `google-oauth2` or `auth0`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub strategy: String,
+    /// For example `social` or `database`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub strategy_type: String,
+}
+
+impl LogEvent {
+    /// Returns true if the event is a successful login to an application.
+    pub fn is_successful_login(&self) -> bool {
+        self.event_type == LogEventType::SuccessLogin && !self.client.client_name.is_empty()
+    }
+
+    /// Returns true if the event is a failed login: a wrong password, an unknown user or
+    /// any other failure.
+    pub fn is_failed_login(&self) -> bool {
+        self.event_type.is_failed_login()
+    }
+
+    /// Returns the browser, OS and device of the event.
+    pub fn user_agent(&self) -> UserAgent {
+        UserAgent::parse(&self.user_agent, self.is_mobile)
+    }
+
+    /// Convert the event to the login we store for it.
+    pub fn to_auth_user_login(&self, company: &Company, tenant: &str) -> NewAuthUserLogin {
+        let user_agent = self.user_agent();
+
+        NewAuthUserLogin {
+            date: self.date,
+            typev: self.event_type.code().to_string(),
+            description: self.description.to_string(),
+            connection: self.connection.connection.to_string(),
+            connection_id: self.connection.connection_id.to_string(),
+            client_id: self.client.client_id.to_string(),
+            client_name: self.client.client_name.to_string(),
+            ip: self.ip.to_string(),
+            // These are resolved from the ip by `geoip` after the sync.
+            country: Default::default(),
+            city: Default::default(),
+            hostname: self.hostname.to_string(),
+            user_id: self.user_id.to_string(),
+            user_name: self.user_name.to_string(),
+            email: self.email.to_string(),
+            audience: self.audience.to_string(),
+            scope: self.scope.to_string(),
+            strategy: self.connection.strategy.to_string(),
+            strategy_type: self.connection.strategy_type.to_string(),
+            log_id: self.log_id.to_string(),
+            is_mobile: self.is_mobile,
+            user_agent: self.user_agent.to_string(),
+            event: self.event_type.to_string(),
+            outcome: self.event_type.outcome().to_string(),
+            browser: user_agent.browser,
+            os: user_agent.os,
+            device: user_agent.device.to_string(),
+            link_to_auth_user: Default::default(),
+            tenant: tenant.to_string(),
+            cio_company_id: company.id,
+        }
+    }
+}
+
+/// The type of an event in the Auth0 logs, by its code. Only the codes we care about have
+/// a variant, the rest are kept as they are in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum LogEventType {
+    /// `s`
+    SuccessLogin,
+    /// `f`
+    FailedLogin,
+    /// `fp`
+    FailedLoginIncorrectPassword,
+    /// `fu`
+    FailedLoginInvalidUser,
+    /// `slo`
+    SuccessLogout,
+    /// `flo`
+    FailedLogout,
+    /// `ss`
+    SuccessSignup,
+    /// `fs`
+    FailedSignup,
+    /// `ssa`
+    SuccessSilentAuth,
+    /// `fsa`
+    FailedSilentAuth,
+    /// `seacft`
+    SuccessCodeExchange,
+    /// `feacft`
+    FailedCodeExchange,
+    /// `sertft`
+    SuccessRefreshTokenExchange,
+    /// `fertft`
+    FailedRefreshTokenExchange,
+    /// `scp`
+    SuccessChangePassword,
+    /// `fcp`
+    FailedChangePassword,
+    /// `sv`
+    SuccessVerificationEmail,
+    /// `fv`
+    FailedVerificationEmail,
+    /// `limit_wc`
+    BlockedAccount,
+    /// `limit_mu`
+    BlockedIp,
+    /// `pwd_leak`
+    BreachedPassword,
+    /// `sapi`
+    SuccessApiOperation,
+    /// `fapi`
+    FailedApiOperation,
+    /// `du`
+    DeletedUser,
+    /// Any other code.
+    Other(String),
+}
+
+impl LogEventType {
+    /// The codes of the types with a variant, in the order of the variants.
+    const CODES: &'static [(&'static str, LogEventType)] = &[
+        ("s", LogEventType::SuccessLogin),
+        ("f", LogEventType::FailedLogin),
+        ("fp", LogEventType::FailedLoginIncorrectPassword),
+        ("fu", LogEventType::FailedLoginInvalidUser),
+        ("slo", LogEventType::SuccessLogout),
+        ("flo", LogEventType::FailedLogout),
+        ("ss", LogEventType::SuccessSignup),
+        ("fs", LogEventType::FailedSignup),
+        ("ssa", LogEventType::SuccessSilentAuth),
+        ("fsa", LogEventType::FailedSilentAuth),
+        ("seacft", LogEventType::SuccessCodeExchange),
+        ("feacft", LogEventType::FailedCodeExchange),
+        ("sertft", LogEventType::SuccessRefreshTokenExchange),
+        ("fertft", LogEventType::FailedRefreshTokenExchange),
+        ("scp", LogEventType::SuccessChangePassword),
+        ("fcp", LogEventType::FailedChangePassword),
+        ("sv", LogEventType::SuccessVerificationEmail),
+        ("fv", LogEventType::FailedVerificationEmail),
+        ("limit_wc", LogEventType::BlockedAccount),
+        ("limit_mu", LogEventType::BlockedIp),
+        ("pwd_leak", LogEventType::BreachedPassword),
+        ("sapi", LogEventType::SuccessApiOperation),
+        ("fapi", LogEventType::FailedApiOperation),
+        ("du", LogEventType::DeletedUser),
+    ];
+
+    /// Returns the code Auth0 uses for the type.
+    pub fn code(&self) -> &str {
+        match self {
+            LogEventType::Other(code) => code,
+            t => LogEventType::CODES
+                .iter()
+                .find(|(_, known)| known == t)
+                .map(|(code, _)| *code)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns true if the event is a failed login: a wrong password, an unknown user or
+    /// any other failure.
+    pub fn is_failed_login(&self) -> bool {
+        matches!(
+            self,
+            LogEventType::FailedLogin
+                | LogEventType::FailedLoginIncorrectPassword
+                | LogEventType::FailedLoginInvalidUser
+        )
+    }
+
+    /// Returns true if the event is something a user or application did that worked.
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            LogEventType::SuccessLogin
+                | LogEventType::SuccessLogout
+                | LogEventType::SuccessSignup
+                | LogEventType::SuccessSilentAuth
+                | LogEventType::SuccessCodeExchange
+                | LogEventType::SuccessRefreshTokenExchange
+                | LogEventType::SuccessChangePassword
+                | LogEventType::SuccessVerificationEmail
+                | LogEventType::SuccessApiOperation
+        )
+    }
+
+    /// Returns true if the event is something a user or application did that failed, or
+    /// was blocked.
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            LogEventType::FailedLogin
+                | LogEventType::FailedLoginIncorrectPassword
+                | LogEventType::FailedLoginInvalidUser
+                | LogEventType::FailedLogout
+                | LogEventType::FailedSignup
+                | LogEventType::FailedSilentAuth
+                | LogEventType::FailedCodeExchange
+                | LogEventType::FailedRefreshTokenExchange
+                | LogEventType::FailedChangePassword
+                | LogEventType::FailedVerificationEmail
+                | LogEventType::BlockedAccount
+                | LogEventType::BlockedIp
+                | LogEventType::BreachedPassword
+                | LogEventType::FailedApiOperation
+        )
+    }
+
+    /// Returns `Success` or `Failure` for the outcome column, empty for the events that are
+    /// neither, like deleting a user or the codes we don't know.
+    pub fn outcome(&self) -> &'static str {
+        if self.is_success() {
+            "Success"
+        } else if self.is_failure() {
+            "Failure"
+        } else {
+            ""
+        }
+    }
+}
+
+impl From<String> for LogEventType {
+    fn from(code: String) -> Self {
+        LogEventType::CODES
+            .iter()
+            .find(|(known, _)| *known == code)
+            .map(|(_, t)| t.clone())
+            .unwrap_or(LogEventType::Other(code))
+    }
+}
+
+impl From<&str> for LogEventType {
+    fn from(code: &str) -> Self {
+        LogEventType::from(code.to_string())
+    }
+}
+
+impl From<LogEventType> for String {
+    fn from(t: LogEventType) -> Self {
+        t.code().to_string()
+    }
+}
+
+impl fmt::Display for LogEventType {
+    /// Writes the type for humans, for example `Failed login (incorrect password)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogEventType::SuccessLogin => "Successful login",
+            LogEventType::FailedLogin => "Failed login",
+            LogEventType::FailedLoginIncorrectPassword => "Failed login (incorrect password)",
+            LogEventType::FailedLoginInvalidUser => "Failed login (invalid email or username)",
+            LogEventType::SuccessLogout => "Successful logout",
+            LogEventType::FailedLogout => "Failed logout",
+            LogEventType::SuccessSignup => "Successful signup",
+            LogEventType::FailedSignup => "Failed signup",
+            LogEventType::SuccessSilentAuth => "Successful silent auth",
+            LogEventType::FailedSilentAuth => "Failed silent auth",
+            LogEventType::SuccessCodeExchange => "Successful code exchange",
+            LogEventType::FailedCodeExchange => "Failed code exchange",
+            LogEventType::SuccessRefreshTokenExchange => "Successful refresh token exchange",
+            LogEventType::FailedRefreshTokenExchange => "Failed refresh token exchange",
+            LogEventType::SuccessChangePassword => "Successful password change",
+            LogEventType::FailedChangePassword => "Failed password change",
+            LogEventType::SuccessVerificationEmail => "Successful email verification",
+            LogEventType::FailedVerificationEmail => "Failed email verification",
+            LogEventType::BlockedAccount => "Blocked account",
+            LogEventType::BlockedIp => "Blocked IP address",
+            LogEventType::BreachedPassword => "Breached password",
+            LogEventType::SuccessApiOperation => "Successful API operation",
+            LogEventType::FailedApiOperation => "Failed API operation",
+            LogEventType::DeletedUser => "Deleted user",
+            LogEventType::Other(code) => return write!(f, "Other ({})", code),
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// The kind of device an event came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    Desktop,
+    Mobile,
+    Unknown,
+}
+
+impl fmt::Display for Device {
+    /// Writes the device for the device column, empty if it is unknown.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Device::Desktop => write!(f, "Desktop"),
+            Device::Mobile => write!(f, "Mobile"),
+            Device::Unknown => Ok(()),
+        }
+    }
+}
+
+/// The browser and OS of an event, parsed from the user agent Auth0 logs, which it already
+/// summarizes as `<browser> <version> / <os> <version>`, for example
+/// `Chrome 120.0.0 / Mac OS X 10.15.7`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAgent {
+    /// The name of the browser, without the version.
+    pub browser: String,
+    /// The name of the OS, without the version.
+    pub os: String,
+    pub device: Device,
+}
+
+impl UserAgent {
+    /// Parse the user agent of an event. Auth0 flags the events from mobile devices, which
+    /// is trusted over the OS.
+    pub fn parse(user_agent: &str, is_mobile: bool) -> Self {
+        let (browser, os) = user_agent.split_once(" / ").unwrap_or((user_agent, ""));
+        let browser = strip_version(browser);
+        let os = strip_version(os);
+
+        let device = if is_mobile || browser.contains("Mobile") || matches!(os, "iOS" | "Android") {
+            Device::Mobile
+        } else if matches!(
+            os,
+            "Mac OS X" | "Windows" | "Linux" | "Ubuntu" | "Fedora" | "Debian" | "Chrome OS"
+        ) {
+            Device::Desktop
+        } else {
+            Device::Unknown
+        };
+
+        UserAgent {
+            browser: browser.to_string(),
+            os: os.to_string(),
+            device,
+        }
+    }
+}
+
+/// Returns the name in `<name> <version>`, where the version is optional.
+fn strip_version(s: &str) -> &str {
+    let s = s.trim();
+    match s.rsplit_once(' ') {
+        Some((name, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => name,
+        _ => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_event_type() {
+        assert_eq!(LogEventType::from("fp"), LogEventType::FailedLoginIncorrectPassword);
+        assert_eq!(LogEventType::from("fp").code(), "fp");
+        assert_eq!(LogEventType::from("s").outcome(), "Success");
+        assert_eq!(LogEventType::from("limit_wc").outcome(), "Failure");
+        assert!(LogEventType::from("fu").is_failed_login());
+
+        let other = LogEventType::from("gd_auth_succeed");
+        assert_eq!(other, LogEventType::Other("gd_auth_succeed".to_string()));
+        assert_eq!(other.code(), "gd_auth_succeed");
+        assert_eq!(other.outcome(), "");
+        assert_eq!(other.to_string(), "Other (gd_auth_succeed)");
+
+        for (code, t) in LogEventType::CODES {
+            assert_eq!(t.code(), *code);
+        }
+    }
+
+    #[test]
+    fn test_log_event() {
+        let event: LogEvent = serde_json::from_value(serde_json::json!({
+            "date": "2024-03-01T09:00:00.000Z",
+            "type": "s",
+            "client_id": "client1",
+            "client_name": "RFD",
+            "connection": "google-oauth2",
+            "strategy": "google-oauth2",
+            "strategy_type": "social",
+            "user_id": "google-oauth2|1",
+            "log_id": "900001",
+            "isMobile": false,
+            "user_agent": "Firefox 123.0.0 / Linux 0.0.0"
+        }))
+        .unwrap();
+
+        assert!(event.is_successful_login());
+        assert_eq!(event.client.client_name, "RFD");
+        assert_eq!(event.connection.strategy_type, "social");
+        assert_eq!(
+            event.user_agent(),
+            UserAgent {
+                browser: "Firefox".to_string(),
+                os: "Linux".to_string(),
+                device: Device::Desktop,
+            }
+        );
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let ua = UserAgent::parse("Mobile Safari 17.2.0 / iOS 17.2.1", false);
+        assert_eq!(ua.browser, "Mobile Safari");
+        assert_eq!(ua.os, "iOS");
+        assert_eq!(ua.device, Device::Mobile);
+
+        assert_eq!(
+            UserAgent::parse("Chrome 120.0.0 / Mac OS X 10.15.7", true).device,
+            Device::Mobile
+        );
+        assert_eq!(UserAgent::parse("node-fetch", false).device, Device::Unknown);
+        assert_eq!(UserAgent::parse("", false).device.to_string(), "");
+    }
+}
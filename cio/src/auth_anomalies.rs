@@ -0,0 +1,381 @@
+#![allow(clippy::from_over_into)]
+//! Logins that look unusual for the user: a login from a country they have not logged in
+//! from before, or a burst of failed logins.
+//!
+//! Each anomaly is recorded in the `auth_anomalies` table and posted to the Slack webhook
+//! set up in the `anomalies` section of the auth config. The logins of the last few days
+//! are checked on every sync, the table keeps an anomaly from being raised twice.
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use anyhow::{bail, Result};
+use async_bb8_diesel::AsyncRunQueryDsl;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
+use log::{error, info};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable_sync::SyncSummary,
+    auth_config::AnomalyConfig,
+    auth_logins::{AuthUserLogin, NewAuthUserLogin},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    schema::{auth_anomalies, auth_user_logins},
+};
+
+/// The kinds of anomaly we flag.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// A successful login from a country the user has not logged in from before.
+    NewCountry,
+    /// More failed logins for the user in a short time than the config allows.
+    FailedLogins,
+}
+
+impl AnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::NewCountry => "new_country",
+            AnomalyKind::FailedLogins => "failed_logins",
+        }
+    }
+}
+
+impl fmt::Display for AnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A login that looks unusual for the user.
+#[db {
+    new_struct_name = "AuthAnomaly",
+    match_on = {
+        "kind" = "String",
+        "log_id" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = auth_anomalies)]
+pub struct NewAuthAnomaly {
+    /// The kind of anomaly, see `AnomalyKind`.
+    pub kind: String,
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    /// The id of the log event of the login that raised the anomaly. For a burst of failed
+    /// logins this is the last one.
+    pub log_id: String,
+    pub date: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ip: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub country: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub city: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// The Auth0 tenant the login came from.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tenant: String,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl NewAuthAnomaly {
+    fn from_login(kind: AnomalyKind, login: &NewAuthUserLogin, description: String) -> Self {
+        NewAuthAnomaly {
+            kind: kind.to_string(),
+            user_id: login.user_id.to_string(),
+            email: login.email.to_string(),
+            log_id: login.log_id.to_string(),
+            date: login.date,
+            ip: login.ip.to_string(),
+            country: login.country.to_string(),
+            city: login.city.to_string(),
+            description,
+            tenant: login.tenant.to_string(),
+            cio_company_id: login.cio_company_id,
+        }
+    }
+
+    /// Returns the text of the Slack alert for the anomaly.
+    pub fn alert_text(&self) -> String {
+        let user = if self.email.is_empty() {
+            &self.user_id
+        } else {
+            &self.email
+        };
+        let mut text = format!(":rotating_light: *{}*: {}", user, self.description);
+        if !self.ip.is_empty() {
+            text += &format!(" | ip: `{}`", self.ip);
+        }
+
+        text
+    }
+}
+
+/// Returns the anomalies in `logins`, which are sorted by date.
+///
+/// `countries` are the countries each user logged in from before the first of `logins`.
+/// A user without any is not flagged for a new country, since every country is new on
+/// their first login. `known` are the anomalies raised before, so a burst of failed logins
+/// that was already flagged is not flagged again as it grows.
+pub fn detect_anomalies(
+    mut countries: HashMap<String, BTreeSet<String>>,
+    logins: &[NewAuthUserLogin],
+    known: &[NewAuthAnomaly],
+    config: &AnomalyConfig,
+) -> Vec<NewAuthAnomaly> {
+    let window = Duration::minutes(config.failed_login_window_minutes);
+    let mut last_alert: HashMap<&str, DateTime<Utc>> = Default::default();
+    for anomaly in known.iter().filter(|a| a.kind == AnomalyKind::FailedLogins.as_str()) {
+        let last = last_alert.entry(anomaly.user_id.as_str()).or_insert(anomaly.date);
+        *last = (*last).max(anomaly.date);
+    }
+
+    let mut anomalies: Vec<NewAuthAnomaly> = Default::default();
+    let mut failed: HashMap<&str, VecDeque<DateTime<Utc>>> = Default::default();
+    for login in logins {
+        if login.is_successful_login() && !login.country.is_empty() {
+            let seen = countries.entry(login.user_id.to_string()).or_default();
+            if !seen.is_empty() && !seen.contains(&login.country) {
+                let description = format!(
+                    "first login from {}, previously from {}",
+                    place(&login.country, &login.city),
+                    seen.iter().cloned().collect::<Vec<_>>().join(", ")
+                );
+                anomalies.push(NewAuthAnomaly::from_login(AnomalyKind::NewCountry, login, description));
+            }
+            seen.insert(login.country.to_string());
+        }
+
+        if login.is_failed_login() {
+            let recent = failed.entry(login.user_id.as_str()).or_default();
+            recent.push_back(login.date);
+            while recent.front().is_some_and(|d| login.date - *d > window) {
+                recent.pop_front();
+            }
+
+            let alerted = last_alert
+                .get(login.user_id.as_str())
+                .is_some_and(|d| login.date - *d < window);
+            if recent.len() >= config.failed_login_threshold && !alerted {
+                let description = format!(
+                    "{} failed logins in {} minutes",
+                    recent.len(),
+                    config.failed_login_window_minutes
+                );
+                anomalies.push(NewAuthAnomaly::from_login(
+                    AnomalyKind::FailedLogins,
+                    login,
+                    description,
+                ));
+                last_alert.insert(login.user_id.as_str(), login.date);
+                recent.clear();
+            }
+        }
+    }
+
+    // Drop the anomalies we raised on an earlier run.
+    let known: HashSet<(&str, &str)> = known.iter().map(|a| (a.kind.as_str(), a.log_id.as_str())).collect();
+    anomalies.retain(|a| !known.contains(&(a.kind.as_str(), a.log_id.as_str())));
+
+    anomalies
+}
+
+fn place(country: &str, city: &str) -> String {
+    if city.is_empty() {
+        country.to_string()
+    } else {
+        format!("{} ({})", country, city)
+    }
+}
+
+/// Post an alert to a Slack incoming webhook.
+async fn post_alert(client: &reqwest::Client, webhook_url: &str, anomaly: &NewAuthAnomaly) -> Result<()> {
+    let resp = client
+        .post(webhook_url)
+        .json(&serde_json::json!({ "text": anomaly.alert_text() }))
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        bail!("status code: {}, body: {}", resp.status(), resp.text().await?);
+    }
+
+    Ok(())
+}
+
+/// Check the recent logins of the company for anomalies, record the new ones and post an
+/// alert for each of them.
+///
+/// The locations of the logins come from `geoip`, so this should run after
+/// `refresh_ip_locations`.
+pub async fn refresh_auth_anomalies(
+    db: &Database,
+    company: &Company,
+    config: &AnomalyConfig,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let since = Utc::now() - Duration::days(config.lookback_days);
+
+    let history: Vec<(String, String)> = auth_user_logins::dsl::auth_user_logins
+        .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+        .filter(auth_user_logins::dsl::date.lt(since))
+        .filter(auth_user_logins::dsl::typev.eq("s"))
+        .filter(auth_user_logins::dsl::country.ne(""))
+        .select((auth_user_logins::dsl::user_id, auth_user_logins::dsl::country))
+        .distinct()
+        .load_async(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+    let mut countries: HashMap<String, BTreeSet<String>> = Default::default();
+    for (user_id, country) in history {
+        countries.entry(user_id).or_default().insert(country);
+    }
+
+    let logins: Vec<NewAuthUserLogin> = auth_user_logins::dsl::auth_user_logins
+        .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+        .filter(auth_user_logins::dsl::date.ge(since))
+        .order_by(auth_user_logins::dsl::date.asc())
+        .load_async::<AuthUserLogin>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    // Include the anomalies from just before the logins, so a burst that started before
+    // them is not flagged twice.
+    let known: Vec<NewAuthAnomaly> = auth_anomalies::dsl::auth_anomalies
+        .filter(auth_anomalies::dsl::cio_company_id.eq(company.id))
+        .filter(auth_anomalies::dsl::date.ge(since - Duration::minutes(config.failed_login_window_minutes)))
+        .load_async::<AuthAnomaly>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    let anomalies = detect_anomalies(countries, &logins, &known, config);
+    info!(
+        "found {} new anomalies in {} logins for company `{}`",
+        anomalies.len(),
+        logins.len(),
+        company.name
+    );
+
+    let client = reqwest::Client::new();
+    let mut summary = SyncSummary::default();
+    for anomaly in anomalies {
+        if dry_run.is_enabled() {
+            info!("[dry-run] would record and alert on: {}", anomaly.alert_text());
+            summary.created += 1;
+            continue;
+        }
+
+        if let Err(e) = anomaly.upsert(db).await {
+            error!(
+                "saving the {} anomaly of `{}` failed: {}",
+                anomaly.kind, anomaly.user_id, e
+            );
+            summary.errors += 1;
+            continue;
+        }
+        summary.created += 1;
+
+        if config.slack_webhook_url.is_empty() {
+            continue;
+        }
+        if let Err(e) = post_alert(&client, &config.slack_webhook_url, &anomaly).await {
+            error!(
+                "posting the {} anomaly of `{}` failed: {}",
+                anomaly.kind, anomaly.user_id, e
+            );
+            summary.errors += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn login(log_id: &str, user_id: &str, minutes: i64, typev: &str, country: &str) -> NewAuthUserLogin {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        NewAuthUserLogin {
+            date: start + Duration::minutes(minutes),
+            typev: typev.to_string(),
+            client_name: "Console".to_string(),
+            user_id: user_id.to_string(),
+            log_id: log_id.to_string(),
+            country: country.to_string(),
+            ..serde_json::from_str(r#"{"date": "2024-01-01T00:00:00Z"}"#).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_detect_new_country() {
+        let config = AnomalyConfig::default();
+        let mut countries: HashMap<String, BTreeSet<String>> = Default::default();
+        countries.insert("jess".to_string(), ["US".to_string()].into_iter().collect());
+
+        let logins = vec![
+            login("1", "jess", 0, "s", "US"),
+            login("2", "jess", 10, "s", "DE"),
+            login("3", "jess", 20, "s", "DE"),
+            // Every country is new on the first login of a user.
+            login("4", "sam", 30, "s", "GB"),
+            login("5", "sam", 40, "f", "FR"),
+        ];
+
+        let anomalies = detect_anomalies(countries, &logins, &[], &config);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, "new_country");
+        assert_eq!(anomalies[0].log_id, "2");
+        assert_eq!(anomalies[0].description, "first login from DE, previously from US");
+    }
+
+    #[test]
+    fn test_detect_failed_logins() {
+        let config = AnomalyConfig {
+            failed_login_threshold: 3,
+            failed_login_window_minutes: 10,
+            ..Default::default()
+        };
+
+        let logins = vec![
+            login("1", "jess", 0, "f", ""),
+            login("2", "jess", 5, "fp", ""),
+            // Too far apart from the first to make a burst with it.
+            login("3", "jess", 12, "fp", ""),
+            login("4", "jess", 13, "fu", ""),
+            // Part of the burst that was just flagged.
+            login("5", "jess", 14, "f", ""),
+            login("6", "sam", 14, "f", ""),
+        ];
+
+        let anomalies = detect_anomalies(Default::default(), &logins, &[], &config);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, "failed_logins");
+        assert_eq!(anomalies[0].log_id, "4");
+        assert_eq!(anomalies[0].description, "3 failed logins in 10 minutes");
+
+        // The burst is not flagged again on the next run.
+        assert!(detect_anomalies(Default::default(), &logins, &anomalies, &config).is_empty());
+    }
+}
@@ -0,0 +1,519 @@
+//! Settings for syncing auth users, read from a TOML file so the domains, tenants and
+//! company names are not baked into the code.
+//!
+//! The path to the file is read from the `CIO_AUTH_CONFIG` environment variable, for
+//! example:
+//!
+//! ```toml
+//! domains = ["oxidecomputer.com", "oxide.computer"]
+//! identity_provider = "auth0"
+//!
+//! [tenants.prod]
+//! domain = "oxide"
+//!
+//! [[company_rules]]
+//! email_domains = ["bench.com"]
+//! company = "@bench"
+//!
+//! [[company_rules]]
+//! companies = ["0xF9BA143B95FF6D82", "TBD"]
+//! company = ""
+//!
+//! [normalizer]
+//! drop = ["n/a", "none"]
+//!
+//! [normalizer.email_domains]
+//! "algolia.com" = "Algolia"
+//!
+//! [normalizer.aliases]
+//! "oxide computer company" = "Oxide Computer Company"
+//!
+//! [email_domain_aliases]
+//! "googlemail.com" = "gmail.com"
+//!
+//! [geoip]
+//! token = "ipinfo-token"
+//!
+//! [anomalies]
+//! slack_webhook_url = "https://hooks.slack.com/services/T000/B000/XXXX"
+//! failed_login_threshold = 5
+//!
+//! [verification_reminders]
+//! after_days = 3
+//!
+//! [duplicates]
+//! link_accounts = false
+//! ```
+//!
+//! More normalizer rules can be added to the `company_normalization_rules` table, so new
+//! rules don't need a deploy.
+#![allow(clippy::from_over_into)]
+use std::{collections::BTreeMap, env, fs};
+
+use anyhow::{anyhow, Result};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{companies::Company, configs::Auth0TenantConfig, db::Database, schema::company_normalization_rules};
+
+/// The settings for syncing auth users.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct AuthConfig {
+    /// The email domains of the people that work for the company. Users with an email
+    /// at one of these domains get the company name, regardless of their profile.
+    #[serde(default)]
+    pub domains: Vec<String>,
+
+    /// The identity provider the users log in with. Okta uses the credentials stored on
+    /// the company.
+    #[serde(default)]
+    pub identity_provider: IdentityProviderKind,
+
+    /// The Auth0 tenants to sync. If this is empty the tenants from the configs repo
+    /// are used.
+    #[serde(default)]
+    pub tenants: BTreeMap<String, Auth0TenantConfig>,
+
+    /// Rules to clean up the company users put in their profile. The first rule that
+    /// matches wins.
+    #[serde(default)]
+    pub company_rules: Vec<CompanyRule>,
+
+    /// Mappings to clean up the company of the users none of the rules match.
+    #[serde(default)]
+    pub normalizer: CompanyNormalizer,
+
+    /// Email domains that are aliases of another, used when matching users to people by
+    /// email. Our own `domains` are always aliases of each other.
+    #[serde(default)]
+    pub email_domain_aliases: BTreeMap<String, String>,
+
+    /// Where to look up the location of the IP addresses users log in from.
+    #[serde(default)]
+    pub geoip: GeoIpConfig,
+
+    /// When to flag a login as an anomaly, and where to send the alerts.
+    #[serde(default)]
+    pub anomalies: AnomalyConfig,
+
+    /// When to remind users to verify their email address.
+    #[serde(default)]
+    pub verification_reminders: VerificationReminderConfig,
+
+    /// What to do with users that have more than one account.
+    #[serde(default)]
+    pub duplicates: DuplicateConfig,
+}
+
+/// The API the locations of IP addresses are looked up with, see `geoip`.
+#[derive(Debug, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct GeoIpConfig {
+    /// The ipinfo.io access token. Locations are only looked up when this is set.
+    #[serde(default)]
+    pub token: String,
+    /// The URL of the API, for a service compatible with the ipinfo.io batch API.
+    #[serde(default = "default_geoip_endpoint")]
+    pub endpoint: String,
+}
+
+fn default_geoip_endpoint() -> String {
+    "https://ipinfo.io".to_string()
+}
+
+impl Default for GeoIpConfig {
+    fn default() -> Self {
+        GeoIpConfig {
+            token: String::new(),
+            endpoint: default_geoip_endpoint(),
+        }
+    }
+}
+
+/// The thresholds for flagging logins as anomalies, see `auth_anomalies`.
+#[derive(Debug, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct AnomalyConfig {
+    /// The Slack incoming webhook alerts are posted to. Anomalies are still recorded
+    /// when this is empty.
+    #[serde(default)]
+    pub slack_webhook_url: String,
+    /// The number of failed logins of a user within `failed_login_window_minutes` that is
+    /// flagged.
+    #[serde(default = "default_failed_login_threshold")]
+    pub failed_login_threshold: usize,
+    #[serde(default = "default_failed_login_window_minutes")]
+    pub failed_login_window_minutes: i64,
+    /// How far back to look for logins that have not been checked yet.
+    #[serde(default = "default_anomaly_lookback_days")]
+    pub lookback_days: i64,
+}
+
+fn default_failed_login_threshold() -> usize {
+    5
+}
+
+fn default_failed_login_window_minutes() -> i64 {
+    15
+}
+
+fn default_anomaly_lookback_days() -> i64 {
+    7
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        AnomalyConfig {
+            slack_webhook_url: String::new(),
+            failed_login_threshold: default_failed_login_threshold(),
+            failed_login_window_minutes: default_failed_login_window_minutes(),
+            lookback_days: default_anomaly_lookback_days(),
+        }
+    }
+}
+
+/// When to remind users to verify their email address, see `email_verification`.
+#[derive(Debug, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct VerificationReminderConfig {
+    /// The number of days after signing up a user is first reminded.
+    #[serde(default = "default_verification_reminder_after_days")]
+    pub after_days: i64,
+    /// The number of days between two reminders to the same user.
+    #[serde(default = "default_verification_reminder_interval_days")]
+    pub interval_days: i64,
+}
+
+fn default_verification_reminder_after_days() -> i64 {
+    3
+}
+
+fn default_verification_reminder_interval_days() -> i64 {
+    7
+}
+
+impl Default for VerificationReminderConfig {
+    fn default() -> Self {
+        VerificationReminderConfig {
+            after_days: default_verification_reminder_after_days(),
+            interval_days: default_verification_reminder_interval_days(),
+        }
+    }
+}
+
+/// What to do with users that have more than one account, see `auth_duplicates`.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct DuplicateConfig {
+    /// Link the duplicate accounts to the primary one in Auth0, not only in our database.
+    /// Auth0 deletes the duplicates, so this can not be undone.
+    #[serde(default)]
+    pub link_accounts: bool,
+}
+
+/// The kinds of identity provider we sync auth users from.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentityProviderKind {
+    #[default]
+    Auth0,
+    Okta,
+}
+
+impl std::fmt::Display for IdentityProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IdentityProviderKind::Auth0 => write!(f, "auth0"),
+            IdentityProviderKind::Okta => write!(f, "okta"),
+        }
+    }
+}
+
+/// A rule that sets the company of the users it matches.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct CompanyRule {
+    /// Match users with an email at one of these domains.
+    #[serde(default)]
+    pub email_domains: Vec<String>,
+    /// Match users whose company is one of these, ignoring case and whitespace.
+    #[serde(default)]
+    pub companies: Vec<String>,
+    /// The company to set, empty clears it.
+    #[serde(default)]
+    pub company: String,
+}
+
+impl CompanyRule {
+    /// Returns true if the rule matches the user.
+    pub fn matches(&self, email: &str, company: &str) -> bool {
+        let email = email.to_lowercase();
+        let company = company.trim();
+
+        self.email_domains
+            .iter()
+            .any(|d| email.ends_with(&format!("@{}", d.trim_start_matches('@').to_lowercase())))
+            || self.companies.iter().any(|c| c.trim().eq_ignore_ascii_case(company))
+    }
+}
+
+/// Cleans up the company users put in their profile, from mappings rather than ordered
+/// rules. The email domain mapping wins over the company the user gave.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct CompanyNormalizer {
+    /// The company of the users with an email at each domain.
+    #[serde(default)]
+    pub email_domains: BTreeMap<String, String>,
+    /// The canonical name of each company, by the name users give, ignoring case and
+    /// whitespace.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Companies to clear, ignoring case and whitespace.
+    #[serde(default)]
+    pub drop: Vec<String>,
+}
+
+impl CompanyNormalizer {
+    /// Add the rules of the company from the database to the mappings.
+    pub async fn load_from_db(&mut self, db: &Database, company: &Company) -> Result<()> {
+        for rule in CompanyNormalizationRules::get_from_db(db, company.id).await? {
+            self.add_rule(&rule.kind, &rule.pattern, &rule.company)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a rule of the given kind: `email_domain`, `alias` or `drop`.
+    pub fn add_rule(&mut self, kind: &str, pattern: &str, company: &str) -> Result<()> {
+        match kind {
+            "email_domain" => {
+                self.email_domains.insert(pattern.to_string(), company.to_string());
+            }
+            "alias" => {
+                self.aliases.insert(pattern.to_string(), company.to_string());
+            }
+            "drop" => self.drop.push(pattern.to_string()),
+            _ => return Err(anyhow!("unknown company normalization rule kind `{}`", kind)),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the cleaned up company, or `None` if no mapping matches.
+    pub fn normalize(&self, email: &str, company: &str) -> Option<String> {
+        let email = email.to_lowercase();
+        let company = company.trim();
+
+        if let Some((_, c)) = self
+            .email_domains
+            .iter()
+            .find(|(d, _)| email.ends_with(&format!("@{}", d.trim_start_matches('@').to_lowercase())))
+        {
+            return Some(c.to_string());
+        }
+
+        if self.drop.iter().any(|d| d.trim().eq_ignore_ascii_case(company)) {
+            return Some(String::new());
+        }
+
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias.trim().eq_ignore_ascii_case(company))
+            .map(|(_, c)| c.to_string())
+    }
+}
+
+/// A company normalization rule stored in the database.
+#[db {
+    new_struct_name = "CompanyNormalizationRule",
+    match_on = {
+        "kind" = "String",
+        "pattern" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = company_normalization_rules)]
+pub struct NewCompanyNormalizationRule {
+    /// `email_domain`, `alias` or `drop`.
+    pub kind: String,
+    /// The email domain, or the company as users give it.
+    pub pattern: String,
+    /// The company to set, empty for `drop` rules.
+    #[serde(default)]
+    pub company: String,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AuthConfig {
+    /// Read the config from the file at `CIO_AUTH_CONFIG`. If the variable is not set,
+    /// this falls back to the domain and name of the company.
+    pub fn from_env(company: &Company) -> Result<Self> {
+        match env::var("CIO_AUTH_CONFIG") {
+            Ok(path) => Self::from_file(&path),
+            Err(_) => Ok(Self::for_company(company)),
+        }
+    }
+
+    /// Read the config like `from_env`, adding the company normalization rules from the
+    /// database.
+    pub async fn load(db: &Database, company: &Company) -> Result<Self> {
+        let mut config = Self::from_env(company)?;
+        config.normalizer.load_from_db(db, company).await?;
+
+        Ok(config)
+    }
+
+    /// Read the config from a TOML file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| anyhow!("reading auth config `{}` failed: {}", path, e))?;
+
+        toml::from_str(&contents).map_err(|e| anyhow!("parsing auth config `{}` failed: {}", path, e))
+    }
+
+    /// The config used when there is no config file: people with an email at the
+    /// company's GSuite domain work for the company.
+    pub fn for_company(company: &Company) -> Self {
+        let mut config = AuthConfig::default();
+        if !company.gsuite_domain.is_empty() {
+            config.domains.push(company.gsuite_domain.to_string());
+        }
+
+        config
+    }
+
+    /// Returns the cleaned up company for a user, given the company of the cio company
+    /// we are syncing for.
+    pub fn normalize_company(&self, company: &Company, email: &str, user_company: &str) -> String {
+        let email = email.to_lowercase();
+        if self
+            .domains
+            .iter()
+            .any(|d| email.ends_with(&format!("@{}", d.trim_start_matches('@').to_lowercase())))
+        {
+            // Anyone with an email at our domain works for us, regardless of what
+            // they put in their profile.
+            return company.name.to_string();
+        }
+
+        if let Some(rule) = self.company_rules.iter().find(|r| r.matches(&email, user_company)) {
+            return rule.company.to_string();
+        }
+
+        self.normalizer
+            .normalize(&email, user_company)
+            .unwrap_or_else(|| user_company.trim().to_string())
+    }
+
+    /// Returns the email to match a user to a person on: lowercased, with aliased domains
+    /// replaced by the domain they alias.
+    pub fn canonical_email(&self, email: &str) -> String {
+        let email = email.trim().to_lowercase();
+        let (local, domain) = match email.rsplit_once('@') {
+            Some(parts) => parts,
+            None => return email,
+        };
+
+        let domain = if self
+            .domains
+            .iter()
+            .any(|d| d.trim_start_matches('@').eq_ignore_ascii_case(domain))
+        {
+            self.domains[0].trim_start_matches('@').to_lowercase()
+        } else {
+            self.email_domain_aliases
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(domain))
+                .map(|(_, d)| d.to_lowercase())
+                .unwrap_or_else(|| domain.to_string())
+        };
+
+        format!("{}@{}", local, domain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    #[test]
+    fn test_normalize_company() {
+        let company = mock_company();
+        let config: AuthConfig = toml::from_str(
+            r#"
+domains = ["oxidecomputer.com", "oxide.computer"]
+
+[[company_rules]]
+email_domains = ["bench.com"]
+company = "@bench"
+
+[[company_rules]]
+companies = ["0xF9BA143B95FF6D82", "tbd"]
+company = ""
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.normalize_company(&company, "jess@Oxide.Computer", "Acme"),
+            company.name
+        );
+        assert_eq!(config.normalize_company(&company, "sam@bench.com", "Bench"), "@bench");
+        assert_eq!(config.normalize_company(&company, "alex@example.com", " TBD "), "");
+        assert_eq!(config.normalize_company(&company, "alex@example.com", " Acme "), "Acme");
+    }
+
+    #[test]
+    fn test_canonical_email() {
+        let config: AuthConfig = toml::from_str(
+            r#"
+domains = ["oxidecomputer.com", "oxide.computer"]
+
+[email_domain_aliases]
+"googlemail.com" = "gmail.com"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.canonical_email(" Jess@Oxide.Computer"), "jess@oxidecomputer.com");
+        assert_eq!(
+            config.canonical_email("jess@oxidecomputer.com"),
+            "jess@oxidecomputer.com"
+        );
+        assert_eq!(config.canonical_email("sam@googlemail.com"), "sam@gmail.com");
+        assert_eq!(config.canonical_email("alex@example.com"), "alex@example.com");
+        assert_eq!(config.canonical_email("not-an-email"), "not-an-email");
+    }
+
+    #[test]
+    fn test_company_normalizer() {
+        let mut normalizer: CompanyNormalizer = toml::from_str(
+            r#"
+drop = ["n/a"]
+
+[email_domains]
+"algolia.com" = "Algolia"
+
+[aliases]
+"oxide computer company" = "Oxide Computer Company"
+"#,
+        )
+        .unwrap();
+        normalizer.add_rule("alias", "bench", "@bench").unwrap();
+        assert!(normalizer.add_rule("regex", ".*", "").is_err());
+
+        assert_eq!(
+            normalizer.normalize("sam@Algolia.com", "Acme"),
+            Some("Algolia".to_string())
+        );
+        assert_eq!(
+            normalizer.normalize("alex@example.com", " Oxide Computer company "),
+            Some("Oxide Computer Company".to_string())
+        );
+        assert_eq!(
+            normalizer.normalize("alex@example.com", "Bench"),
+            Some("@bench".to_string())
+        );
+        assert_eq!(normalizer.normalize("alex@example.com", "N/A"), Some(String::new()));
+        assert_eq!(normalizer.normalize("alex@example.com", "Acme"), None);
+    }
+}
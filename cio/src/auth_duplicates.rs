@@ -0,0 +1,199 @@
+//! People that have more than one account, for example because they logged in with Google
+//! once and with GitHub the next time, which Auth0 keeps as two users.
+//!
+//! The active users of a tenant with the same verified email are the same person. The
+//! oldest of their accounts is the primary one, and the others get its user id in
+//! `duplicate_of`. With `link_accounts` set in the `duplicates` section of the auth config,
+//! the duplicates are also linked to the primary account in Auth0, which turns them into
+//! identities of the primary user.
+use std::collections::HashMap;
+
+use log::{error, info};
+
+use crate::{
+    airtable_sync::SyncSummary,
+    auth_config::{AuthConfig, IdentityProviderKind},
+    auth_logins::{AuthUser, AuthUsers, NewAuthUser},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    identity::auth0_tenants,
+};
+
+/// Returns the user id of the primary account of each active user with a verified email,
+/// by tenant and user id. Users without duplicates, and primary accounts, have an empty one.
+///
+/// The primary account is the oldest, so it keeps the history of the person.
+pub fn find_duplicates(users: &[NewAuthUser], config: &AuthConfig) -> HashMap<(String, String), String> {
+    let mut duplicates: HashMap<(String, String), String> = Default::default();
+    let mut groups: HashMap<(&str, String), Vec<&NewAuthUser>> = Default::default();
+    for user in users {
+        if user.deleted_at.is_some() {
+            continue;
+        }
+        if !user.email_verified || user.email.is_empty() {
+            duplicates.insert((user.tenant.to_string(), user.user_id.to_string()), String::new());
+            continue;
+        }
+
+        groups
+            .entry((user.tenant.as_str(), config.canonical_email(&user.email)))
+            .or_default()
+            .push(user);
+    }
+
+    for mut group in groups.into_values() {
+        group.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.user_id.cmp(&b.user_id)));
+        let primary = group[0].user_id.to_string();
+        for (i, user) in group.into_iter().enumerate() {
+            let duplicate_of = if i == 0 { String::new() } else { primary.to_string() };
+            duplicates.insert((user.tenant.to_string(), user.user_id.to_string()), duplicate_of);
+        }
+    }
+
+    duplicates
+}
+
+/// Mark the auth users of the company that are duplicates of another account, and link
+/// them to it in Auth0 if the config says so.
+///
+/// Deleted users are left as they are, so a duplicate Auth0 deleted when it was linked
+/// still points at its primary account.
+pub async fn refresh_auth_duplicates(
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let mut auth_users: Vec<AuthUser> = AuthUsers::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .0;
+    let users: Vec<NewAuthUser> = auth_users.iter().cloned().map(Into::into).collect();
+    let duplicates = find_duplicates(&users, config);
+
+    let mut summary = SyncSummary::default();
+    for auth_user in auth_users.iter_mut() {
+        let duplicate_of = match duplicates.get(&(auth_user.tenant.to_string(), auth_user.user_id.to_string())) {
+            Some(duplicate_of) if *duplicate_of != auth_user.duplicate_of => duplicate_of,
+            _ => continue,
+        };
+
+        auth_user.duplicate_of = duplicate_of.to_string();
+        summary.updated += 1;
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would mark auth user `{}` as a duplicate of `{}`",
+                auth_user.user_id, auth_user.duplicate_of
+            );
+        } else if let Err(e) = auth_user.update(db).await {
+            error!(
+                "saving the duplicate of auth user `{}` failed: {}",
+                auth_user.user_id, e
+            );
+            summary.errors += 1;
+        }
+    }
+
+    if config.duplicates.link_accounts && config.identity_provider == IdentityProviderKind::Auth0 {
+        summary += link_duplicate_accounts(company, config, &auth_users, dry_run).await?;
+    }
+
+    Ok(summary)
+}
+
+/// Link the active duplicates to their primary account in Auth0.
+async fn link_duplicate_accounts(
+    company: &Company,
+    config: &AuthConfig,
+    auth_users: &[AuthUser],
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let mut summary = SyncSummary::default();
+    for (name, tenant) in auth0_tenants(company, config).await? {
+        let auth0 = match tenant.authenticate() {
+            Ok(auth0) => auth0,
+            Err(e) => {
+                error!("authenticating with auth0 tenant `{}` failed: {}", name, e);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        for auth_user in auth_users
+            .iter()
+            .filter(|u| u.tenant == auth0.domain() && u.deleted_at.is_none() && !u.duplicate_of.is_empty())
+        {
+            if dry_run.is_enabled() {
+                info!(
+                    "[dry-run] would link auth0 user `{}` to `{}`",
+                    auth_user.user_id, auth_user.duplicate_of
+                );
+                continue;
+            }
+
+            match auth0
+                .link_user_account(&auth_user.duplicate_of, &auth_user.user_id)
+                .await
+            {
+                Ok(_) => info!(
+                    "linked auth0 user `{}` to `{}`",
+                    auth_user.user_id, auth_user.duplicate_of
+                ),
+                Err(e) => {
+                    error!(
+                        "linking auth0 user `{}` to `{}` failed: {}",
+                        auth_user.user_id, auth_user.duplicate_of, e
+                    );
+                    summary.errors += 1;
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(user_id: &str, email: &str, email_verified: bool, created_at: &str) -> NewAuthUser {
+        serde_json::from_value(serde_json::json!({
+            "user_id": user_id,
+            "email": email,
+            "email_verified": email_verified,
+            "created_at": created_at,
+            "updated_at": created_at,
+            "last_login": created_at,
+            "logins_count": 1,
+            "tenant": "oxide",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let config: AuthConfig = toml::from_str(r#"domains = ["oxidecomputer.com", "oxide.computer"]"#).unwrap();
+        let users = vec![
+            user("github|2", "Jess@oxide.computer", true, "2024-02-01T00:00:00Z"),
+            user(
+                "google-oauth2|1",
+                "jess@oxidecomputer.com",
+                true,
+                "2024-01-01T00:00:00Z",
+            ),
+            // An unverified email could belong to anyone.
+            user("auth0|3", "jess@oxidecomputer.com", false, "2023-01-01T00:00:00Z"),
+            user("github|4", "sam@example.com", true, "2024-01-01T00:00:00Z"),
+        ];
+
+        let duplicates = find_duplicates(&users, &config);
+        let duplicate_of = |user_id: &str| duplicates[&("oxide".to_string(), user_id.to_string())].to_string();
+        assert_eq!(duplicate_of("github|2"), "google-oauth2|1");
+        assert_eq!(duplicate_of("google-oauth2|1"), "");
+        assert_eq!(duplicate_of("auth0|3"), "");
+        assert_eq!(duplicate_of("github|4"), "");
+    }
+}
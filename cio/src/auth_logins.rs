@@ -1,26 +1,69 @@
 #![allow(clippy::from_over_into)]
+use std::{
+    collections::{HashMap, HashSet},
+    ops::DerefMut,
+};
 
 use anyhow::Result;
-use async_bb8_diesel::AsyncRunQueryDsl;
+use async_bb8_diesel::{AsyncConnection, AsyncRunQueryDsl};
 use async_trait::async_trait;
-use chrono::{offset::Utc, DateTime};
+use chrono::{offset::Utc, DateTime, NaiveDate, SecondsFormat};
+use diesel::{ExpressionMethods, QueryDsl};
+use futures::{future, stream, StreamExt};
+use log::{error, info, warn};
 use macros::db;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    airtable::{AIRTABLE_AUTH_USERS_TABLE, AIRTABLE_AUTH_USER_LOGINS_TABLE},
-    core::UpdateAirtableRecord,
+    airtable::{
+        AIRTABLE_AUTH_CONNECTION_STATS_TABLE, AIRTABLE_AUTH_USERS_TABLE, AIRTABLE_AUTH_USER_LOGINS_TABLE,
+        AIRTABLE_AUTH_USER_ROLES_TABLE, AIRTABLE_PEOPLE_TABLE,
+    },
+    airtable_bases::{AirtableBase, BaseRegistry},
+    airtable_sync::{sync_to_airtable, AirtableCache, AirtableSyncable, ConflictPolicy, SyncSummary},
+    auth0::{
+        parse_each, Auth0Client, Auth0Error, ListUsersOptions, LogCheckpoint, User, MAX_SEARCH_RESULTS,
+        STABLE_USERS_SORT,
+    },
+    auth0_logs::LogEventType,
+    auth_anomalies::refresh_auth_anomalies,
+    auth_config::{AuthConfig, IdentityProviderKind},
+    auth_duplicates::refresh_auth_duplicates,
+    cancel::{shutdown, Cancellation},
+    companies::Company,
+    core::DryRun,
     db::Database,
-    schema::{auth_user_logins, auth_users},
+    error::CioError,
+    geoip::refresh_ip_locations,
+    identity::{auth0_tenants, identity_providers},
+    metrics::{self, Outcome},
+    progress::{self, ProgressUpdate},
+    schema::{auth_connection_stats, auth_user_logins, auth_user_roles, auth_user_sync_checkpoints, auth_users},
 };
 
+/// The number of users to fetch the Auth0 logs for at a time.
+pub const AUTH0_LOGS_CONCURRENCY: usize = 4;
+
+/// The number of days of logins the top applications of a user are counted over.
+pub const TOP_APPLICATIONS_DAYS: i64 = 30;
+
+/// The number of top applications kept for a user.
+pub const TOP_APPLICATIONS_COUNT: usize = 3;
+
+/// The number of rows to write in a single insert. Postgres allows at most 65535 bind
+/// parameters per statement, and each row binds one per column.
+const UPSERT_BATCH_SIZE: usize = 1000;
+
 /// The data type for an NewAuthUser.
 #[db {
     new_struct_name = "AuthUser",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_AUTH_USERS_TABLE",
     custom_partial_eq = true,
     match_on = {
         "user_id" = "String",
+        "tenant" = "String",
     },
 }]
 #[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
@@ -58,10 +101,20 @@ pub struct NewAuthUser {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub last_login: DateTime<Utc>,
+    /// The application of the user's most recent successful login.
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub last_application_accessed: String,
+    /// The applications the user logged into most over the last 30 days, most used first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_applications: Vec<String>,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub last_ip: String,
+    /// The country of `last_ip`, as an ISO 3166 code, see `geoip`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_ip_country: String,
+    /// The city of `last_ip`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_ip_city: String,
     pub logins_count: i32,
     /// link to another table in Airtable
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -72,6 +125,21 @@ pub struct NewAuthUser {
     /// link to another table in Airtable
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub link_to_page_views: Vec<String>,
+    /// When the user was deleted from Auth0. We keep deleted users around, so their
+    /// logins and page views still have someone to link to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The user id of the primary account of the person, if this is a duplicate of it,
+    /// see `auth_duplicates`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub duplicate_of: String,
+    /// When the row last changed in the database, leaving out the activity Auth0 updates on
+    /// every login. This is set by the database, it is not in Airtable.
+    #[serde(skip)]
+    pub modified_at: DateTime<Utc>,
+    /// The Auth0 tenant the record came from.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tenant: String,
     /// The CIO company ID.
     #[serde(default)]
     pub cio_company_id: i32,
@@ -84,13 +152,60 @@ impl PartialEq for AuthUser {
             && self.last_login == other.last_login
             && self.logins_count == other.logins_count
             && self.last_application_accessed == other.last_application_accessed
+            && self.top_applications == other.top_applications
             && self.company == other.company
+            && self.last_ip_country == other.last_ip_country
+            && self.last_ip_city == other.last_ip_city
+            && self.deleted_at == other.deleted_at
+            && self.duplicate_of == other.duplicate_of
+    }
+}
+
+/// The applications a user accessed, derived from their successful logins.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ApplicationUsage {
+    /// The application of the most recent successful login.
+    pub last_application_accessed: String,
+    /// The applications logged into most over the last `TOP_APPLICATIONS_DAYS` days, most
+    /// used first. Ties go to the application used most recently.
+    pub top_applications: Vec<String>,
+}
+
+impl ApplicationUsage {
+    /// Derive the usage from a user's logins, in any order. Failed logins and other events
+    /// are ignored, so a burst of failures doesn't change the application.
+    pub fn from_logins(logins: &[NewAuthUserLogin], now: DateTime<Utc>) -> Self {
+        let mut successful: Vec<&NewAuthUserLogin> = logins.iter().filter(|l| l.is_successful_login()).collect();
+        successful.sort_by(|a, b| b.date.cmp(&a.date));
+
+        let since = now - chrono::Duration::days(TOP_APPLICATIONS_DAYS);
+        // The count and most recent position of each application.
+        let mut counts: HashMap<&str, (usize, usize)> = Default::default();
+        for (i, login) in successful.iter().enumerate().filter(|(_, l)| l.date >= since) {
+            counts.entry(&login.client_name).or_insert((0, i)).0 += 1;
+        }
+        let mut top: Vec<(&str, (usize, usize))> = counts.into_iter().collect();
+        top.sort_by(|(_, (a, a_i)), (_, (b, b_i))| b.cmp(a).then(a_i.cmp(b_i)));
+
+        ApplicationUsage {
+            last_application_accessed: successful
+                .first()
+                .map(|l| l.client_name.to_string())
+                .unwrap_or_default(),
+            top_applications: top
+                .into_iter()
+                .take(TOP_APPLICATIONS_COUNT)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        }
     }
 }
 
 /// The data type for a NewAuthUserLogin.
 #[db {
     new_struct_name = "AuthUserLogin",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_AUTH_USER_LOGINS_TABLE",
     match_on = {
         "user_id" = "String",
         "date" = "DateTime<Utc>",
@@ -114,6 +229,12 @@ pub struct NewAuthUserLogin {
     pub client_name: String,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub ip: String,
+    /// The country of `ip`, as an ISO 3166 code, see `geoip`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub country: String,
+    /// The city of `ip`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub city: String,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub hostname: String,
     #[serde(default, skip_serializing_if = "String::is_empty")]
@@ -136,10 +257,1633 @@ pub struct NewAuthUserLogin {
     pub is_mobile: bool,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub user_agent: String,
+    /// The type of the event for humans, see `LogEventType`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub event: String,
+    /// `Success` or `Failure`, empty for the events that are neither.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub outcome: String,
+    /// The browser and OS of `user_agent`, without their versions.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub browser: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub os: String,
+    /// `Desktop` or `Mobile`, empty if we can't tell.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub device: String,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_auth_user: Vec<String>,
+    /// The Auth0 tenant the record came from.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tenant: String,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl NewAuthUserLogin {
+    /// Returns true if the event is a successful login to an application.
+    pub fn is_successful_login(&self) -> bool {
+        LogEventType::from(self.typev.as_str()) == LogEventType::SuccessLogin && !self.client_name.is_empty()
+    }
+
+    /// Returns true if the event is a failed login: a wrong password, an unknown user or
+    /// any other failure.
+    pub fn is_failed_login(&self) -> bool {
+        LogEventType::from(self.typev.as_str()).is_failed_login()
+    }
+}
+
+/// The progress of a sync of the users in an Auth0 tenant, so a sync that dies part way can
+/// be resumed. The checkpoint is removed when the sync finishes.
+#[db {
+    new_struct_name = "AuthUserSyncCheckpoint",
+    match_on = {
+        "tenant" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = auth_user_sync_checkpoints)]
+pub struct NewAuthUserSyncCheckpoint {
+    /// The Auth0 tenant being synced.
+    pub tenant: String,
+    /// The search query the sync started with.
+    #[serde(default)]
+    pub q: String,
+    /// The next page of users to fetch.
+    pub next_page: i32,
+    /// The last user saved.
+    #[serde(default)]
+    pub last_user_id: String,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+/// The data type for a NewAuthUserRole, a role held by a user in Auth0.
+#[db {
+    new_struct_name = "AuthUserRole",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_AUTH_USER_ROLES_TABLE",
+    match_on = {
+        "user_id" = "String",
+        "role_id" = "String",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = auth_user_roles)]
+pub struct NewAuthUserRole {
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    pub role_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub role_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub role_description: String,
+    /// The permissions the user has, both from this role and any others they hold.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<String>,
     /// link to another table in Airtable
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub link_to_auth_user: Vec<String>,
+    /// The Auth0 tenant the record came from.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tenant: String,
     /// The CIO company ID.
     #[serde(default)]
     pub cio_company_id: i32,
 }
+
+/// The data type for a NewAuthConnectionStat, the number of users of an Auth0 connection
+/// on a day. Keeping one per day lets us see how the login providers change over time.
+#[db {
+    new_struct_name = "AuthConnectionStat",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_AUTH_CONNECTION_STATS_TABLE",
+    match_on = {
+        "date" = "NaiveDate",
+        "tenant" = "String",
+        "connection_id" = "String",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = auth_connection_stats)]
+pub struct NewAuthConnectionStat {
+    pub date: NaiveDate,
+    pub connection_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub connection_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub strategy: String,
+    #[serde(default)]
+    pub user_count: i64,
+    /// The Auth0 tenant the record came from.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tenant: String,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for AuthUser {
+    // People fix the company by hand and make the links in Airtable. A company fixed in
+    // Airtable is kept unless it is changed in the database after.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] = &[
+        ("company", ConflictPolicy::NewestWins),
+        ("link_to_people", ConflictPolicy::Airtable),
+        ("link_to_auth_user_logins", ConflictPolicy::Airtable),
+        ("link_to_page_views", ConflictPolicy::Airtable),
+    ];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewAuthUser) -> String {
+        format!("{}/{}", fields.tenant, fields.user_id)
+    }
+
+    fn airtable_fields(&self) -> NewAuthUser {
+        // Airtable doesn't have it, leave it out of the comparison with the copy there.
+        NewAuthUser {
+            modified_at: Default::default(),
+            ..self.into()
+        }
+    }
+
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        Some(self.modified_at)
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewAuthUser) {
+        match field {
+            "company" => self.company = airtable.company.to_string(),
+            "link_to_people" => self.link_to_people = airtable.link_to_people.clone(),
+            "link_to_auth_user_logins" => self.link_to_auth_user_logins = airtable.link_to_auth_user_logins.clone(),
+            "link_to_page_views" => self.link_to_page_views = airtable.link_to_page_views.clone(),
+            _ => (),
+        }
+    }
+}
+
+impl AirtableSyncable for AuthUserLogin {
+    // The link to the user is made in Airtable.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] =
+        &[("link_to_auth_user", ConflictPolicy::Airtable)];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewAuthUserLogin) -> String {
+        fields.log_id.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewAuthUserLogin) {
+        if field == "link_to_auth_user" {
+            self.link_to_auth_user = airtable.link_to_auth_user.clone();
+        }
+    }
+}
+
+#[async_trait]
+impl AirtableSyncable for AuthUserRole {
+    const DELETE_STALE: bool = true;
+    // The link to the user is worked out from the users in the database.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] =
+        &[("link_to_auth_user", ConflictPolicy::Database)];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewAuthUserRole) -> String {
+        format!("{}/{}/{}", fields.tenant, fields.user_id, fields.role_id)
+    }
+
+    async fn list_for_airtable(db: &Database, company: &Company) -> Result<Vec<Self>> {
+        let mut auth_user_roles: Vec<AuthUserRole> = AuthUserRoles::get_from_db(db, company.id).await?.into();
+
+        // Link the roles to their users, if the users are in Airtable.
+        for auth_user_role in auth_user_roles.iter_mut() {
+            if let Some(auth_user) = AuthUser::get_from_db(
+                db,
+                auth_user_role.user_id.to_string(),
+                auth_user_role.tenant.to_string(),
+            )
+            .await
+            {
+                if !auth_user.airtable_record_id.is_empty() {
+                    auth_user_role.link_to_auth_user = vec![auth_user.airtable_record_id];
+                }
+            }
+        }
+
+        Ok(auth_user_roles)
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewAuthUserRole) {
+        if field == "link_to_auth_user" {
+            self.link_to_auth_user = airtable.link_to_auth_user.clone();
+        }
+    }
+}
+
+impl AirtableSyncable for AuthConnectionStat {
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewAuthConnectionStat) -> String {
+        format!("{}/{}/{}", fields.date, fields.tenant, fields.connection_id)
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewAuthConnectionStat) {}
+}
+
+impl User {
+    /// Convert an Auth0 user into the data type we store in the database.
+    pub fn to_auth_user(&self, company: &Company, tenant: &str, config: &AuthConfig) -> NewAuthUser {
+        let user_company = config.normalize_company(company, &self.email, &self.company);
+
+        NewAuthUser {
+            user_id: self.user_id.to_string(),
+            name: self.name.to_string(),
+            nickname: self.nickname.to_string(),
+            username: self.username.to_string(),
+            email: self.email.to_string(),
+            email_verified: self.email_verified,
+            picture: self.picture.to_string(),
+            company: user_company,
+            blog: self.blog.to_string(),
+            phone: self.phone_number.to_string(),
+            phone_verified: self.phone_verified,
+            locale: self.locale.to_string(),
+            login_provider: self
+                .identities
+                .first()
+                .map(|i| i.provider.to_string())
+                .unwrap_or_default(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            last_login: self.last_login.unwrap_or(self.created_at),
+            last_application_accessed: Default::default(),
+            top_applications: Default::default(),
+            last_ip: self.last_ip.to_string(),
+            last_ip_country: Default::default(),
+            last_ip_city: Default::default(),
+            logins_count: self.logins_count,
+            link_to_people: Default::default(),
+            link_to_auth_user_logins: Default::default(),
+            link_to_page_views: Default::default(),
+            deleted_at: None,
+            duplicate_of: Default::default(),
+            modified_at: Utc::now(),
+            tenant: tenant.to_string(),
+            cio_company_id: company.id,
+        }
+    }
+}
+
+/// Sync the users in the Auth0 tenant matching the search query with our database, along
+/// with their logins. An empty query syncs every user.
+///
+/// The users are saved a page at a time and the progress is checkpointed in the database.
+/// If a sync dies part way, the next one resumes after the last user saved, with the query
+/// it started with, rather than starting over. Dry runs neither read nor write checkpoints.
+///
+/// Once cancelled, the sync saves the users of the page it is on and stops, keeping the
+/// checkpoint at that page so the next sync starts it over.
+#[allow(clippy::too_many_arguments)]
+pub async fn sync_auth_users(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+    q: &str,
+    concurrency: usize,
+    cancel: &Cancellation,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let mut checkpoint = if dry_run.is_enabled() {
+        None
+    } else {
+        AuthUserSyncCheckpoint::get_from_db(db, auth0.domain().to_string(), company.id).await
+    };
+
+    let mut page = 0;
+    let q = match &checkpoint {
+        Some(c) => {
+            info!(
+                "resuming the sync of auth0 users at page {}, after user `{}`",
+                c.next_page, c.last_user_id
+            );
+            page = c.next_page.max(0) as u32;
+            c.q.to_string()
+        }
+        None => q.to_string(),
+    };
+
+    if checkpoint.is_none() && !dry_run.is_enabled() {
+        let new = NewAuthUserSyncCheckpoint {
+            tenant: auth0.domain().to_string(),
+            q: q.to_string(),
+            next_page: 0,
+            last_user_id: String::new(),
+            started_at: Utc::now(),
+            updated_at: Utc::now(),
+            cio_company_id: company.id,
+        };
+        checkpoint = match new.upsert(db).await {
+            Ok(c) => Some(c),
+            Err(e) => {
+                warn!(
+                    "saving the auth0 user sync checkpoint failed, the sync can't be resumed: {}",
+                    e
+                );
+                None
+            }
+        };
+    }
+
+    // Sort by creation so the pages don't shift under us while we go, or between a run
+    // and the one resuming it.
+    let mut opts = ListUsersOptions {
+        sort: STABLE_USERS_SORT.to_string(),
+        ..ListUsersOptions::search(&q)
+    };
+
+    // Resume after the creation of the last user saved rather than at the page, a search
+    // can only be paged through up to `MAX_SEARCH_RESULTS`.
+    if let Some(c) = checkpoint.as_ref().filter(|c| !c.last_user_id.is_empty()) {
+        match auth0.get_user(&c.last_user_id).await {
+            Ok(user) => opts.created_since = Some(user.created_at),
+            Err(e) => warn!(
+                "getting auth0 user `{}` to resume after failed, starting over: {}",
+                c.last_user_id, e
+            ),
+        }
+        page = 0;
+    }
+
+    let mut progress = progress::reporter().start(&format!("auth0 users of `{}`", auth0.domain()));
+    let mut pages = 0;
+    let mut summary = SyncSummary::default();
+    loop {
+        // The client paces the requests so we don't get rate limited.
+        let p = auth0.list_users_page(page, &opts).await.map_err(CioError::Auth0)?;
+        let fetched = p.start + p.length;
+        let users: Vec<User> = parse_each(p.users, "user");
+        let last_user_id = users.last().map(|u| u.user_id.to_string()).unwrap_or_default();
+        let last_created_at = users.last().map(|u| u.created_at);
+        metrics::record("auth0_users", Outcome::Fetched, users.len());
+
+        let auth_users = get_auth_users(auth0, db, company, config, users, concurrency, cancel, dry_run).await;
+        if dry_run.is_enabled() {
+            for auth_user in auth_users.iter() {
+                info!("[dry-run] would save auth0 user `{}`", auth_user.user_id);
+            }
+            summary.updated += auth_users.len();
+        } else {
+            let saved = upsert_auth_users(db, &auth_users).await;
+            summary.updated += saved;
+            summary.errors += auth_users.len().saturating_sub(saved);
+        }
+
+        pages += 1;
+        // The totals are of the whole query, so a resumed sync starts part way.
+        progress.update(ProgressUpdate {
+            pages,
+            records: fetched.max(0) as u64,
+            total: Some(p.total.max(0) as u64),
+        });
+
+        // The page may have been cut short, so leave the checkpoint before it.
+        if cancel.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        page += 1;
+
+        if let Some(c) = checkpoint.as_mut() {
+            c.next_page = page as i32;
+            c.last_user_id = last_user_id;
+            c.updated_at = Utc::now();
+            if let Err(e) = c.update(db).await {
+                warn!("saving the auth0 user sync checkpoint failed: {}", e);
+            }
+        }
+
+        // The totals tell us when we are done, rather than relying on an empty page.
+        if p.length == 0 || fetched >= p.total {
+            break;
+        }
+
+        // The search can't be paged through any further, go on with the users created
+        // since the last one. The users created at the same time are synced again.
+        if page >= opts.max_pages() {
+            match last_created_at {
+                Some(next) if Some(next) != opts.created_since => {
+                    opts.created_since = Some(next);
+                    page = 0;
+                }
+                _ => {
+                    return Err(CioError::Auth0(anyhow::anyhow!(
+                        "more than {} auth0 users of `{}` were created at the same time, they can't be synced",
+                        MAX_SEARCH_RESULTS,
+                        auth0.domain()
+                    )))
+                }
+            }
+        }
+    }
+
+    progress.finish();
+
+    if summary.cancelled {
+        info!(
+            "cancelled the sync of auth0 users, the next sync resumes at page {}",
+            page
+        );
+    } else if let Some(c) = checkpoint {
+        if let Err(e) = c.delete(db).await {
+            warn!("clearing the auth0 user sync checkpoint failed: {}", e);
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Returns the auth users for a page of Auth0 users, saving their logins to the database
+/// along the way.
+///
+/// Only the logins newer than the ones we have stored for a user are fetched, see
+/// `list_user_logs_since`. The applications they accessed are counted from the stored
+/// logins along with the new ones.
+///
+/// The logins of up to `concurrency` users are fetched at a time. The requests share the
+/// rate limit of the client, so raising this only helps while we have headroom.
+///
+/// Once cancelled, no more logins are fetched, and only the users whose logins were are
+/// returned.
+#[allow(clippy::too_many_arguments)]
+async fn get_auth_users(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+    users: Vec<User>,
+    concurrency: usize,
+    cancel: &Cancellation,
+    dry_run: DryRun,
+) -> Vec<NewAuthUser> {
+    let user_ids: Vec<String> = users.iter().map(|u| u.user_id.to_string()).collect();
+    // Without what we have stored, fetch the most recent logins of each user like the
+    // first sync does.
+    let mut stored = match StoredLogins::get(db, company, auth0.domain(), &user_ids).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            warn!("getting the stored logins of the auth0 users failed: {}", e);
+            StoredLogins::default()
+        }
+    };
+
+    // Get the logins for each user, which tell us the application they last accessed.
+    let newest = &stored.newest;
+    let results = stream::iter(users)
+        .take_while(|_| future::ready(!cancel.is_cancelled()))
+        .map(|user| async move {
+            let since = newest.get(&user.user_id);
+            let auth_user_logins = auth0.list_user_logs_since(&user.user_id, since).await;
+            (user, auth_user_logins)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut auth_users: Vec<NewAuthUser> = Default::default();
+    let mut logins: Vec<NewAuthUserLogin> = Default::default();
+    for (user, auth_user_logins) in results {
+        // Convert the user to an AuthUser.
+        let mut auth_user = user.to_auth_user(company, auth0.domain(), config);
+
+        let auth_user_logins: Vec<NewAuthUserLogin> = match auth_user_logins {
+            Ok(events) => events
+                .iter()
+                .map(|event| event.to_auth_user_login(company, auth0.domain()))
+                .collect(),
+            // The user was deleted after we listed them, there is nothing to sync.
+            Err(e)
+                if e.downcast_ref::<Auth0Error>()
+                    .map(|e| e.is_not_found())
+                    .unwrap_or(false) =>
+            {
+                warn!("skipping auth0 user `{}` that no longer exists: {}", user.user_id, e);
+                metrics::record("auth0_users", Outcome::Skipped, 1);
+                continue;
+            }
+            // Still sync the user, keeping the application we last saw them access.
+            Err(e) => {
+                error!("getting the logins for auth0 user `{}` failed: {}", user.user_id, e);
+                if let Some(existing) =
+                    AuthUser::get_from_db(db, user.user_id.to_string(), auth0.domain().to_string()).await
+                {
+                    auth_user.last_application_accessed = existing.last_application_accessed;
+                    auth_user.top_applications = existing.top_applications;
+                }
+                auth_users.push(auth_user);
+                continue;
+            }
+        };
+
+        let usage = ApplicationUsage::from_logins(&stored.history(&user.user_id, &auth_user_logins), Utc::now());
+        // The user has not logged in over the last `TOP_APPLICATIONS_DAYS` days, keep the
+        // application we last saw them access.
+        auth_user.last_application_accessed = if usage.last_application_accessed.is_empty() {
+            stored.last_application.remove(&user.user_id).unwrap_or_default()
+        } else {
+            usage.last_application_accessed
+        };
+        auth_user.top_applications = usage.top_applications;
+
+        auth_users.push(auth_user);
+
+        metrics::record("auth0_logins", Outcome::Fetched, auth_user_logins.len());
+        logins.extend(auth_user_logins);
+    }
+
+    // Save the logins to the database.
+    if dry_run.is_enabled() {
+        info!("[dry-run] would save {} auth0 logins", logins.len());
+    } else {
+        upsert_auth_user_logins(db, &logins).await;
+    }
+
+    auth_users
+}
+
+/// What we have stored of the logins of a page of Auth0 users.
+#[derive(Debug, Default)]
+struct StoredLogins {
+    /// The newest login of each user.
+    newest: HashMap<String, LogCheckpoint>,
+    /// The logins of each user over the last `TOP_APPLICATIONS_DAYS` days.
+    recent: HashMap<String, Vec<NewAuthUserLogin>>,
+    /// The application each user last accessed, as of the last sync.
+    last_application: HashMap<String, String>,
+}
+
+impl StoredLogins {
+    async fn get(db: &Database, company: &Company, tenant: &str, user_ids: &[String]) -> Result<Self> {
+        let newest: Vec<(String, String, DateTime<Utc>)> = auth_user_logins::dsl::auth_user_logins
+            .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+            .filter(auth_user_logins::dsl::tenant.eq(tenant.to_string()))
+            .filter(auth_user_logins::dsl::user_id.eq_any(user_ids.to_vec()))
+            .distinct_on(auth_user_logins::dsl::user_id)
+            .order_by((auth_user_logins::dsl::user_id, auth_user_logins::dsl::date.desc()))
+            .select((
+                auth_user_logins::dsl::user_id,
+                auth_user_logins::dsl::log_id,
+                auth_user_logins::dsl::date,
+            ))
+            .load_async(db.pool())
+            .await?;
+
+        let since = Utc::now() - chrono::Duration::days(TOP_APPLICATIONS_DAYS);
+        let recent: Vec<AuthUserLogin> = auth_user_logins::dsl::auth_user_logins
+            .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+            .filter(auth_user_logins::dsl::tenant.eq(tenant.to_string()))
+            .filter(auth_user_logins::dsl::user_id.eq_any(user_ids.to_vec()))
+            .filter(auth_user_logins::dsl::date.ge(since))
+            .load_async(db.pool())
+            .await?;
+
+        let last_application: Vec<(String, String)> = auth_users::dsl::auth_users
+            .filter(auth_users::dsl::cio_company_id.eq(company.id))
+            .filter(auth_users::dsl::tenant.eq(tenant.to_string()))
+            .filter(auth_users::dsl::user_id.eq_any(user_ids.to_vec()))
+            .select((auth_users::dsl::user_id, auth_users::dsl::last_application_accessed))
+            .load_async(db.pool())
+            .await?;
+
+        let mut stored = StoredLogins {
+            newest: newest
+                .into_iter()
+                .map(|(user_id, log_id, date)| (user_id, LogCheckpoint { log_id, date }))
+                .collect(),
+            last_application: last_application.into_iter().collect(),
+            ..Default::default()
+        };
+        for login in recent {
+            stored
+                .recent
+                .entry(login.user_id.to_string())
+                .or_default()
+                .push(login.into());
+        }
+
+        Ok(stored)
+    }
+
+    /// Returns the stored recent logins of the user along with the new ones.
+    fn history(&mut self, user_id: &str, new: &[NewAuthUserLogin]) -> Vec<NewAuthUserLogin> {
+        let new_ids: HashSet<&str> = new.iter().map(|l| l.log_id.as_str()).collect();
+
+        let mut history = self.recent.remove(user_id).unwrap_or_default();
+        history.retain(|l| !new_ids.contains(l.log_id.as_str()));
+        history.extend(new.iter().cloned());
+        history
+    }
+}
+
+/// Sync the users from each of the identity providers in the config with our database,
+/// along with their logins where the provider has them.
+///
+/// A provider that fails to sync does not stop the others, the first error is returned once
+/// they have all been tried.
+pub async fn refresh_db_auth(db: &Database, company: &Company, dry_run: DryRun) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let providers = identity_providers(company, &config).await?;
+    if providers.is_empty() {
+        info!(
+            "skipping `refresh_db_auth` for company `{}`, no {} tenants",
+            company.name, config.identity_provider
+        );
+
+        // Return early.
+        return Ok(SyncSummary::default());
+    }
+
+    let mut summary = SyncSummary::default();
+    let mut result = Ok(());
+    for (name, provider) in providers {
+        let synced = match provider {
+            Ok(provider) => {
+                info!("syncing {} tenant `{}` ({})", provider.kind(), name, provider.tenant());
+                provider.sync_users(db, company, &config, dry_run).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match synced {
+            Ok(synced) => summary += synced,
+            Err(e) => {
+                error!("syncing {} tenant `{}` failed: {}", config.identity_provider, name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+    }
+
+    // Linking is best effort, the users are synced either way.
+    match link_auth_users_to_people(db, company, &config, &AirtableCache::default(), dry_run).await {
+        Ok(linked) => summary.updated += linked,
+        Err(e) => error!("linking auth users to people failed: {}", e),
+    }
+
+    // So are the locations of the addresses they logged in from.
+    match refresh_ip_locations(db, company, &config.geoip, dry_run).await {
+        Ok(located) => summary += located,
+        Err(e) => error!("looking up the locations of auth users failed: {}", e),
+    }
+
+    // The anomalies need the locations of the logins, so they come last.
+    match refresh_auth_anomalies(db, company, &config.anomalies, dry_run).await {
+        Ok(flagged) => summary += flagged,
+        Err(e) => error!("checking the logins of auth users for anomalies failed: {}", e),
+    }
+
+    match refresh_auth_duplicates(db, company, &config, dry_run).await {
+        Ok(marked) => summary += marked,
+        Err(e) => error!("finding duplicate auth users failed: {}", e),
+    }
+
+    result.map(|_| summary)
+}
+
+/// Offboard a person from the applications they log in to with Auth0: block every user
+/// with their email in each tenant, revoke their sessions, refresh tokens and grants, and
+/// mark the users deleted in our database.
+///
+/// A user whose access we fail to revoke is not marked deleted, so running this again
+/// picks it up.
+pub async fn offboard_auth_user(
+    db: &Database,
+    company: &Company,
+    email: &str,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    if config.identity_provider != IdentityProviderKind::Auth0 {
+        return Err(CioError::Config(format!(
+            "offboarding is only supported for auth0, company `{}` uses {}",
+            company.name, config.identity_provider
+        )));
+    }
+
+    let mut summary = SyncSummary::default();
+    let now = Utc::now();
+    for (name, tenant) in auth0_tenants(company, &config).await? {
+        let auth0 = tenant.authenticate().map_err(|e| CioError::Config(e.to_string()))?;
+        let users = auth0.list_users_by_email(email).await.map_err(CioError::Auth0)?;
+        info!(
+            "offboarding {} auth0 users with email `{}` in `{}`",
+            users.len(),
+            email,
+            name
+        );
+
+        for user in users {
+            if dry_run.is_enabled() {
+                info!(
+                    "[dry-run] would block auth0 user `{}`, revoke their access and mark them deleted",
+                    user.user_id
+                );
+                summary.deleted += 1;
+                continue;
+            }
+
+            let revoked: Result<()> = async {
+                if !user.blocked {
+                    auth0.block_user(&user.user_id).await?;
+                }
+                auth0.revoke_access(&user.user_id).await
+            }
+            .await;
+            if let Err(e) = revoked {
+                error!("offboarding auth0 user `{}` failed: {}", user.user_id, e);
+                summary.errors += 1;
+                continue;
+            }
+
+            if let Some(mut auth_user) =
+                AuthUser::get_from_db(db, user.user_id.to_string(), auth0.domain().to_string()).await
+            {
+                if auth_user.deleted_at.is_none() {
+                    auth_user.deleted_at = Some(now);
+                    if let Err(e) = auth_user.update(db).await {
+                        error!("marking auth0 user `{}` as deleted failed: {}", user.user_id, e);
+                        summary.errors += 1;
+                        continue;
+                    }
+                }
+            }
+            summary.deleted += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Returns the Airtable record ids of the auth users of the company, by their canonical
+/// email, for linking records from other systems to them. Users that are not in Airtable
+/// yet are left out.
+pub async fn auth_user_records_by_email(
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+) -> Result<HashMap<String, Vec<String>>> {
+    let mut records: HashMap<String, Vec<String>> = Default::default();
+    for auth_user in AuthUsers::get_from_db(db, company.id).await? {
+        if auth_user.email.is_empty() || auth_user.airtable_record_id.is_empty() {
+            continue;
+        }
+
+        records
+            .entry(config.canonical_email(&auth_user.email))
+            .or_default()
+            .push(auth_user.airtable_record_id.to_string());
+    }
+
+    Ok(records)
+}
+
+/// Returns the ids of the records in the People table in Airtable, by their canonical email.
+pub async fn people_records_by_email(
+    company: &Company,
+    config: &AuthConfig,
+    cache: &AirtableCache,
+) -> Result<HashMap<String, String>, CioError> {
+    let bases = BaseRegistry::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+    let people = cache
+        .list(
+            &bases.authenticate(AirtableBase::CustomerLeads, company),
+            &bases.base_id(AirtableBase::CustomerLeads, company),
+            AIRTABLE_PEOPLE_TABLE,
+        )
+        .await
+        .map_err(CioError::Airtable)?;
+
+    Ok(people
+        .iter()
+        .filter_map(|p| {
+            let email = p.fields.get("Email")?.as_str()?;
+            (!email.is_empty()).then(|| (config.canonical_email(email), p.id.to_string()))
+        })
+        .collect())
+}
+
+/// Link the auth users that are not linked to a person yet to their record in the People
+/// table, by email. Employees that log in with the personal email they gave Gusto are
+/// linked through their Gusto record. Returns the number of users linked.
+///
+/// The link is set in Airtable as well as the database, since the column is edited by hand
+/// in Airtable and Airtable wins when they differ.
+pub async fn link_auth_users_to_people(
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+    cache: &AirtableCache,
+    dry_run: DryRun,
+) -> Result<usize, CioError> {
+    let unlinked: Vec<AuthUser> = AuthUsers::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .into_iter()
+        .filter(|u| u.link_to_people.is_empty() && !u.email.is_empty())
+        .collect();
+    if unlinked.is_empty() {
+        return Ok(0);
+    }
+
+    let mut people = people_records_by_email(company, config, cache).await?;
+    for (email, person) in crate::gusto::people_records_by_personal_email(db, company, config)
+        .await
+        .map_err(CioError::Database)?
+    {
+        people.entry(email).or_insert(person);
+    }
+
+    let mut linked = 0;
+    let mut records: Vec<airtable_api::Record<serde_json::Value>> = Default::default();
+    for mut auth_user in unlinked {
+        let person = match people.get(&config.canonical_email(&auth_user.email)) {
+            Some(person) => person,
+            None => continue,
+        };
+
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would link auth user `{}` to person `{}`",
+                auth_user.email, person
+            );
+            linked += 1;
+            continue;
+        }
+
+        auth_user.link_to_people = vec![person.to_string()];
+        if let Err(e) = auth_user.update(db).await {
+            error!(
+                "linking auth user `{}` to person `{}` failed: {}",
+                auth_user.email, person, e
+            );
+            continue;
+        }
+
+        if !auth_user.airtable_record_id.is_empty() {
+            records.push(airtable_api::Record {
+                id: auth_user.airtable_record_id.to_string(),
+                fields: serde_json::json!({ "link_to_people": [person] }),
+                created_time: None,
+            });
+        }
+        linked += 1;
+    }
+
+    if !records.is_empty() {
+        let bases = BaseRegistry::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+        cache.invalidate(
+            &bases.base_id(AuthUser::AIRTABLE_BASE, company),
+            AIRTABLE_AUTH_USERS_TABLE,
+        );
+        bases
+            .authenticate(AuthUser::AIRTABLE_BASE, company)
+            .update_records(AIRTABLE_AUTH_USERS_TABLE, records)
+            .await
+            .map_err(CioError::Airtable)?;
+    }
+
+    info!("linked {} auth users to people", linked);
+
+    Ok(linked)
+}
+
+/// Sync the users and logins from a single Auth0 tenant with our database.
+pub async fn refresh_db_auth_tenant(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    // Only fetch the users that changed since the last sync. The first sync has nothing to
+    // compare against, so it fetches everyone.
+    let q = match get_auth_users_updated_since(db, company, auth0.domain())
+        .await
+        .map_err(CioError::Database)?
+    {
+        Some(since) => {
+            info!("syncing auth0 users updated since {}", since);
+            updated_since_query(since)
+        }
+        None => String::new(),
+    };
+
+    let mut summary = sync_auth_users(
+        auth0,
+        db,
+        company,
+        config,
+        &q,
+        AUTH0_LOGS_CONCURRENCY,
+        shutdown(),
+        dry_run,
+    )
+    .await?;
+    // The rest compares against every user, which we don't have all of yet.
+    if summary.cancelled {
+        return Ok(summary);
+    }
+
+    summary.deleted = refresh_db_auth_deleted_users(auth0, db, company, dry_run).await?;
+
+    summary += refresh_db_auth_connection_stats(auth0, db, company, dry_run).await?;
+
+    Ok(summary)
+}
+
+/// Mark the users that were deleted from the tenant as deleted in our database, and bring
+/// back the users marked deleted that are in the tenant again and not blocked, like an
+/// offboarded user who returns.
+///
+/// The user search has no way to ask for deleted users, so this lists every user and
+/// looks for the ones we did not see. That is slow, so we only do it when we have more
+/// active users stored than the tenant has, or fewer than it has unblocked. Returns the
+/// number of users marked deleted.
+pub async fn refresh_db_auth_deleted_users(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<usize, CioError> {
+    let (active, deleted_before): (Vec<AuthUser>, Vec<AuthUser>) = AuthUsers::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .into_iter()
+        .filter(|u| u.tenant == auth0.domain())
+        .partition(|u| u.deleted_at.is_none());
+
+    let total = auth0.count_users("").await.map_err(CioError::Auth0)?;
+    let unblocked = auth0.count_users("NOT blocked:true").await.map_err(CioError::Auth0)?;
+    if active.len() as i64 <= total && active.len() as i64 >= unblocked {
+        return Ok(0);
+    }
+
+    info!(
+        "have {} active auth0 users stored for `{}`, the tenant has {} and {} unblocked, looking for changes",
+        active.len(),
+        auth0.domain(),
+        total,
+        unblocked
+    );
+
+    // Whether each user in the tenant is blocked, by id. Listed by creation: sorted by last
+    // login, a user logging in mid-listing would move to a page already fetched, be missed,
+    // and be marked deleted.
+    let opts = ListUsersOptions {
+        sort: STABLE_USERS_SORT.to_string(),
+        ..Default::default()
+    };
+    let seen: HashMap<String, bool> = auth0
+        .list_users(&opts)
+        .await
+        .map_err(CioError::Auth0)?
+        .into_iter()
+        .map(|u| (u.user_id, u.blocked))
+        .collect();
+
+    for mut auth_user in deleted_before {
+        if seen.get(&auth_user.user_id) != Some(&false) {
+            continue;
+        }
+
+        if dry_run.is_enabled() {
+            info!("[dry-run] would bring back auth0 user `{}`", auth_user.user_id);
+            continue;
+        }
+
+        info!(
+            "bringing back auth0 user `{}`, they are no longer blocked",
+            auth_user.user_id
+        );
+        auth_user.deleted_at = None;
+        if let Err(e) = auth_user.update(db).await {
+            error!("bringing back auth0 user `{}` failed: {}", auth_user.user_id, e);
+        }
+    }
+
+    let now = Utc::now();
+    let mut deleted = 0;
+    for mut auth_user in active {
+        if seen.contains_key(&auth_user.user_id) {
+            continue;
+        }
+
+        if dry_run.is_enabled() {
+            info!("[dry-run] would mark auth0 user `{}` as deleted", auth_user.user_id);
+            deleted += 1;
+            continue;
+        }
+
+        info!("marking auth0 user `{}` as deleted", auth_user.user_id);
+        auth_user.deleted_at = Some(now);
+        match auth_user.update(db).await {
+            Ok(_) => deleted += 1,
+            Err(e) => error!("marking auth0 user `{}` as deleted failed: {}", auth_user.user_id, e),
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Sync the tenant log stream with our database, starting after the most recent login we
+/// already have. This is a single pass over the stream rather than a request per user, and
+/// it captures failed logins too.
+pub async fn refresh_db_auth_logs(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let checkpoint = get_auth_user_logins_checkpoint(db, company, auth0.domain())
+        .await
+        .map_err(CioError::Database)?;
+
+    let logs = auth0.list_logs(&checkpoint).await.map_err(CioError::Auth0)?;
+    metrics::record("auth0_logins", Outcome::Fetched, logs.len());
+    info!(
+        "syncing {} auth0 log events after checkpoint `{}`",
+        logs.len(),
+        checkpoint
+    );
+
+    // The users that logged in, whose application usage needs updating.
+    let mut logged_in: HashSet<String> = Default::default();
+    let mut logins: Vec<NewAuthUserLogin> = Default::default();
+    for event in logs {
+        // Not every event in the stream belongs to a user, for example management API calls.
+        if event.user_id.is_empty() {
+            metrics::record("auth0_logins", Outcome::Skipped, 1);
+            continue;
+        }
+
+        if event.is_successful_login() {
+            logged_in.insert(event.user_id.to_string());
+        }
+
+        logins.push(event.to_auth_user_login(company, auth0.domain()));
+    }
+
+    if dry_run.is_enabled() {
+        info!("[dry-run] would save {} auth0 logins", logins.len());
+        for user_id in logged_in.iter() {
+            info!("[dry-run] would update the applications accessed by `{}`", user_id);
+        }
+
+        return Ok(SyncSummary {
+            updated: logins.len(),
+            ..Default::default()
+        });
+    }
+
+    let saved = upsert_auth_user_logins(db, &logins).await;
+    let mut summary = SyncSummary {
+        updated: saved,
+        errors: logins.len().saturating_sub(saved),
+        ..Default::default()
+    };
+
+    // Count the usage from the stored history rather than this batch, which may only hold
+    // a few of the user's logins.
+    let since = Utc::now() - chrono::Duration::days(TOP_APPLICATIONS_DAYS);
+    for user_id in logged_in {
+        let recent = match get_auth_user_logins_since(db, company, auth0.domain(), &user_id, since).await {
+            Ok(recent) => recent,
+            Err(e) => {
+                error!("getting the recent logins of auth0 user `{}` failed: {}", user_id, e);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        if let Some(mut auth_user) = AuthUser::get_from_db(db, user_id, auth0.domain().to_string()).await {
+            let usage = ApplicationUsage::from_logins(&recent, Utc::now());
+            if !usage.last_application_accessed.is_empty() {
+                auth_user.last_application_accessed = usage.last_application_accessed;
+            }
+            auth_user.top_applications = usage.top_applications;
+            if let Err(e) = auth_user.update(db).await {
+                error!("saving auth0 user `{}` failed: {}", auth_user.user_id, e);
+                summary.errors += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Sync the roles users hold in Auth0 with our database.
+///
+/// A role whose members we fail to list aborts the sync, since we would otherwise remove
+/// the roles of everyone in it.
+pub async fn refresh_db_auth_roles(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let roles = auth0.list_roles().await.map_err(CioError::Auth0)?;
+
+    let mut summary = SyncSummary::default();
+
+    // A user's permissions are the same for every role they hold, so only fetch them once.
+    let mut permissions: HashMap<String, Vec<String>> = Default::default();
+    let mut held: Vec<(String, String)> = Default::default();
+    for role in roles {
+        for member in auth0.list_role_users(&role.id).await.map_err(CioError::Auth0)? {
+            metrics::record("auth0_roles", Outcome::Fetched, 1);
+
+            // The user still holds the role even if we fail to save it below.
+            held.push((member.user_id.to_string(), role.id.to_string()));
+
+            if !permissions.contains_key(&member.user_id) {
+                match auth0.list_user_permissions(&member.user_id).await {
+                    Ok(p) => {
+                        permissions.insert(
+                            member.user_id.to_string(),
+                            p.into_iter().map(|p| p.permission_name).collect(),
+                        );
+                    }
+                    Err(e) => {
+                        error!(
+                            "getting the permissions for auth0 user `{}` failed: {}",
+                            member.user_id, e
+                        );
+                        metrics::record("auth0_roles", Outcome::Errored, 1);
+                        summary.errors += 1;
+                        continue;
+                    }
+                }
+            }
+
+            let auth_user_role = NewAuthUserRole {
+                user_id: member.user_id.to_string(),
+                email: member.email.to_string(),
+                role_id: role.id.to_string(),
+                role_name: role.name.to_string(),
+                role_description: role.description.to_string(),
+                permissions: permissions.get(&member.user_id).cloned().unwrap_or_default(),
+                link_to_auth_user: Default::default(),
+                tenant: auth0.domain().to_string(),
+                cio_company_id: company.id,
+            };
+            if dry_run.is_enabled() {
+                info!(
+                    "[dry-run] would save auth0 role `{}` for user `{}`",
+                    role.name, member.user_id
+                );
+                summary.updated += 1;
+            } else if let Err(e) = auth_user_role.upsert(db).await {
+                error!(
+                    "saving auth0 role `{}` for user `{}` failed: {}",
+                    role.name, member.user_id, e
+                );
+                metrics::record("auth0_roles", Outcome::Errored, 1);
+                summary.errors += 1;
+            } else {
+                metrics::record("auth0_roles", Outcome::Updated, 1);
+                summary.updated += 1;
+            }
+        }
+    }
+
+    // Remove the roles that users no longer hold.
+    for auth_user_role in AuthUserRoles::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+    {
+        if auth_user_role.tenant != auth0.domain() {
+            continue;
+        }
+
+        if !held.contains(&(auth_user_role.user_id.to_string(), auth_user_role.role_id.to_string())) {
+            if dry_run.is_enabled() {
+                info!(
+                    "[dry-run] would remove auth0 role `{}` from user `{}`",
+                    auth_user_role.role_name, auth_user_role.user_id
+                );
+                summary.deleted += 1;
+                continue;
+            }
+
+            info!(
+                "removing auth0 role `{}` from user `{}`",
+                auth_user_role.role_name, auth_user_role.user_id
+            );
+            match auth_user_role.delete(db).await {
+                Ok(_) => summary.deleted += 1,
+                Err(e) => {
+                    error!(
+                        "removing auth0 role `{}` from user `{}` failed: {}",
+                        auth_user_role.role_name, auth_user_role.user_id, e
+                    );
+                    summary.errors += 1;
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Sync the auth user roles in our database to Airtable. Roles users no longer hold are
+/// removed from Airtable too.
+pub async fn sync_auth_user_roles_to_airtable(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    sync_to_airtable::<AuthUserRole>(db, company, dry_run).await
+}
+
+/// Record how many users each connection in the tenant has today.
+pub async fn refresh_db_auth_connection_stats(
+    auth0: &Auth0Client,
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let today = Utc::now().date_naive();
+
+    let mut summary = SyncSummary::default();
+
+    for connection in auth0.list_connections().await.map_err(CioError::Auth0)? {
+        let user_count = match auth0.count_connection_users(&connection.name).await {
+            Ok(user_count) => user_count,
+            Err(e) => {
+                error!(
+                    "counting the users of auth0 connection `{}` failed: {}",
+                    connection.name, e
+                );
+                metrics::record("auth0_connection_stats", Outcome::Errored, 1);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        metrics::record("auth0_connection_stats", Outcome::Fetched, 1);
+
+        let stat = NewAuthConnectionStat {
+            date: today,
+            connection_id: connection.id,
+            connection_name: connection.name,
+            strategy: connection.strategy,
+            user_count,
+            tenant: auth0.domain().to_string(),
+            cio_company_id: company.id,
+        };
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would save {} users for auth0 connection `{}`",
+                stat.user_count, stat.connection_name
+            );
+            summary.updated += 1;
+        } else if let Err(e) = stat.upsert(db).await {
+            error!(
+                "saving the stats of auth0 connection `{}` failed: {}",
+                stat.connection_name, e
+            );
+            metrics::record("auth0_connection_stats", Outcome::Errored, 1);
+            summary.errors += 1;
+        } else {
+            metrics::record("auth0_connection_stats", Outcome::Updated, 1);
+            summary.updated += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Sync the auth connection stats in our database to Airtable.
+pub async fn sync_auth_connection_stats_to_airtable(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    sync_to_airtable::<AuthConnectionStat>(db, company, dry_run).await
+}
+
+/// Save the auth users to the database in batches, each in its own transaction. Users
+/// already in the database, by user id and tenant, are updated in place.
+///
+/// If a batch fails, its users are saved one at a time so a single bad row does not lose
+/// the rest. Returns the number of users saved.
+pub async fn upsert_auth_users(db: &Database, auth_users: &[NewAuthUser]) -> usize {
+    // Postgres refuses to update the same row twice in one statement, so keep the last
+    // copy of each user.
+    let mut unique: HashMap<(&str, &str), &NewAuthUser> = Default::default();
+    for auth_user in auth_users {
+        unique.insert((&auth_user.user_id, &auth_user.tenant), auth_user);
+    }
+    let auth_users: Vec<NewAuthUser> = unique.into_values().cloned().collect();
+
+    let mut saved = 0;
+    for chunk in auth_users.chunks(UPSERT_BATCH_SIZE) {
+        match save_auth_users(db, chunk.to_vec()).await {
+            Ok(n) => saved += n,
+            Err(e) => {
+                warn!(
+                    "saving a batch of {} auth users failed, saving them one at a time: {}",
+                    chunk.len(),
+                    e
+                );
+                for auth_user in chunk {
+                    match save_auth_users(db, vec![auth_user.clone()]).await {
+                        Ok(n) => saved += n,
+                        Err(e) => {
+                            error!("saving auth0 user `{}` failed: {}", auth_user.user_id, e);
+                            metrics::record("auth0_users", Outcome::Errored, 1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    metrics::record("auth0_users", Outcome::Updated, saved);
+
+    saved
+}
+
+/// Save the auth users in one transaction, updating the columns that come from Auth0 of
+/// the users we already have. The columns filled in by us or pulled from Airtable are
+/// kept.
+async fn save_auth_users(db: &Database, rows: Vec<NewAuthUser>) -> Result<usize> {
+    db.pool()
+        .transaction(move |mut conn| -> Result<usize> {
+            use crate::schema::auth_users::dsl::*;
+            use diesel::upsert::excluded;
+
+            Ok(diesel::insert_into(crate::schema::auth_users::table)
+                .values(&rows)
+                .on_conflict((user_id, tenant))
+                .do_update()
+                .set((
+                    name.eq(excluded(name)),
+                    nickname.eq(excluded(nickname)),
+                    username.eq(excluded(username)),
+                    email.eq(excluded(email)),
+                    email_verified.eq(excluded(email_verified)),
+                    picture.eq(excluded(picture)),
+                    // The company is fixed by hand in Airtable, `ConflictPolicy::NewestWins`
+                    // decides it.
+                    blog.eq(excluded(blog)),
+                    phone.eq(excluded(phone)),
+                    phone_verified.eq(excluded(phone_verified)),
+                    locale.eq(excluded(locale)),
+                    login_provider.eq(excluded(login_provider)),
+                    created_at.eq(excluded(created_at)),
+                    updated_at.eq(excluded(updated_at)),
+                    last_login.eq(excluded(last_login)),
+                    last_application_accessed.eq(excluded(last_application_accessed)),
+                    top_applications.eq(excluded(top_applications)),
+                    last_ip.eq(excluded(last_ip)),
+                    // The location of the ip is resolved by `geoip` after the sync, keep it.
+                    logins_count.eq(excluded(logins_count)),
+                    // Auth0 knows nothing of the links, keep the ones we resolved and
+                    // the ones pulled from Airtable.
+                    // Offboarded users are only blocked in Auth0, keep them deleted. Only
+                    // `refresh_db_auth_deleted_users` brings a user back.
+                    // Duplicates are found by `auth_duplicates` after the sync, keep them.
+                    cio_company_id.eq(excluded(cio_company_id)),
+                ))
+                .execute(conn.deref_mut())?)
+        })
+        .await
+}
+
+/// Save the auth user logins to the database in batches, each in its own transaction.
+/// Logins already in the database, by log id, are updated in place.
+///
+/// If a batch fails, its logins are saved one at a time so a single bad row does not lose
+/// the rest. Returns the number of logins saved.
+pub async fn upsert_auth_user_logins(db: &Database, auth_user_logins: &[NewAuthUserLogin]) -> usize {
+    // Postgres refuses to update the same row twice in one statement, so keep the last
+    // copy of each login.
+    let mut unique: HashMap<&str, &NewAuthUserLogin> = Default::default();
+    for auth_user_login in auth_user_logins {
+        unique.insert(&auth_user_login.log_id, auth_user_login);
+    }
+    let auth_user_logins: Vec<NewAuthUserLogin> = unique.into_values().cloned().collect();
+
+    let mut saved = 0;
+    for chunk in auth_user_logins.chunks(UPSERT_BATCH_SIZE) {
+        match save_auth_user_logins(db, chunk.to_vec()).await {
+            Ok(n) => saved += n,
+            Err(e) => {
+                warn!(
+                    "saving a batch of {} auth user logins failed, saving them one at a time: {}",
+                    chunk.len(),
+                    e
+                );
+                for auth_user_login in chunk {
+                    match save_auth_user_logins(db, vec![auth_user_login.clone()]).await {
+                        Ok(n) => saved += n,
+                        Err(e) => {
+                            error!("saving auth0 login `{}` failed: {}", auth_user_login.log_id, e);
+                            metrics::record("auth0_logins", Outcome::Errored, 1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    metrics::record("auth0_logins", Outcome::Updated, saved);
+
+    saved
+}
+
+/// Save the auth user logins in one transaction, updating the columns that come from
+/// Auth0 of the logins we already have. The columns filled in by us or pulled from
+/// Airtable are kept.
+async fn save_auth_user_logins(db: &Database, rows: Vec<NewAuthUserLogin>) -> Result<usize> {
+    db.pool()
+        .transaction(move |mut conn| -> Result<usize> {
+            use crate::schema::auth_user_logins::dsl::*;
+            use diesel::upsert::excluded;
+
+            Ok(diesel::insert_into(crate::schema::auth_user_logins::table)
+                .values(&rows)
+                .on_conflict(log_id)
+                .do_update()
+                .set((
+                    date.eq(excluded(date)),
+                    typev.eq(excluded(typev)),
+                    description.eq(excluded(description)),
+                    connection.eq(excluded(connection)),
+                    connection_id.eq(excluded(connection_id)),
+                    client_id.eq(excluded(client_id)),
+                    client_name.eq(excluded(client_name)),
+                    ip.eq(excluded(ip)),
+                    // The location of the ip is resolved by `geoip` after the sync, keep it.
+                    hostname.eq(excluded(hostname)),
+                    user_id.eq(excluded(user_id)),
+                    user_name.eq(excluded(user_name)),
+                    email.eq(excluded(email)),
+                    audience.eq(excluded(audience)),
+                    scope.eq(excluded(scope)),
+                    strategy.eq(excluded(strategy)),
+                    strategy_type.eq(excluded(strategy_type)),
+                    is_mobile.eq(excluded(is_mobile)),
+                    user_agent.eq(excluded(user_agent)),
+                    event.eq(excluded(event)),
+                    outcome.eq(excluded(outcome)),
+                    browser.eq(excluded(browser)),
+                    os.eq(excluded(os)),
+                    device.eq(excluded(device)),
+                    // The link is resolved by us and pulled from Airtable, keep it.
+                    tenant.eq(excluded(tenant)),
+                    cio_company_id.eq(excluded(cio_company_id)),
+                ))
+                .execute(conn.deref_mut())?)
+        })
+        .await
+}
+
+/// Returns the id of the most recent log event we have stored for the company from the
+/// tenant, to resume the tenant log stream from. This is empty if we have not stored any yet.
+pub async fn get_auth_user_logins_checkpoint(db: &Database, company: &Company, tenant: &str) -> Result<String> {
+    let log_ids: Vec<String> = auth_user_logins::dsl::auth_user_logins
+        .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+        .filter(auth_user_logins::dsl::tenant.eq(tenant.to_string()))
+        .filter(auth_user_logins::dsl::log_id.ne("".to_string()))
+        .order_by(auth_user_logins::dsl::date.desc())
+        .select(auth_user_logins::dsl::log_id)
+        .limit(1)
+        .load_async(db.pool())
+        .await?;
+
+    Ok(log_ids.into_iter().next().unwrap_or_default())
+}
+
+/// Returns the most recent `updated_at` of the auth users we have stored for the company
+/// from the tenant. This is the high-water mark of the last sync.
+pub async fn get_auth_users_updated_since(
+    db: &Database,
+    company: &Company,
+    tenant: &str,
+) -> Result<Option<DateTime<Utc>>> {
+    let updated_at: Option<DateTime<Utc>> = auth_users::dsl::auth_users
+        .filter(auth_users::dsl::cio_company_id.eq(company.id))
+        .filter(auth_users::dsl::tenant.eq(tenant.to_string()))
+        .select(diesel::dsl::max(auth_users::dsl::updated_at))
+        .first_async(db.pool())
+        .await?;
+
+    Ok(updated_at)
+}
+
+/// The logins of a user in a tenant at or after the given time, most recent first.
+pub async fn get_auth_user_logins_since(
+    db: &Database,
+    company: &Company,
+    tenant: &str,
+    user_id: &str,
+    since: DateTime<Utc>,
+) -> Result<Vec<NewAuthUserLogin>> {
+    let logins: Vec<AuthUserLogin> = auth_user_logins::dsl::auth_user_logins
+        .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+        .filter(auth_user_logins::dsl::tenant.eq(tenant.to_string()))
+        .filter(auth_user_logins::dsl::user_id.eq(user_id.to_string()))
+        .filter(auth_user_logins::dsl::date.ge(since))
+        .order_by(auth_user_logins::dsl::date.desc())
+        .load_async(db.pool())
+        .await?;
+
+    Ok(logins.into_iter().map(Into::into).collect())
+}
+
+/// Returns the search query for the users updated at or after the given time. The range is
+/// inclusive so we never miss a user updated in the same millisecond as the high-water mark,
+/// re-syncing that user is harmless.
+fn updated_since_query(since: DateTime<Utc>) -> String {
+    format!(
+        "updated_at:[{} TO *]",
+        since.to_rfc3339_opts(SecondsFormat::Millis, true)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_updated_since_query() {
+        let since = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(updated_since_query(since), "updated_at:[2024-01-02T03:04:05.000Z TO *]");
+    }
+
+    #[test]
+    fn test_application_usage() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let login = |days: i64, typev: &str, client_name: &str| NewAuthUserLogin {
+            date: now - chrono::Duration::days(days),
+            typev: typev.to_string(),
+            client_name: client_name.to_string(),
+            ..serde_json::from_str(r#"{"date": "2024-01-01T00:00:00Z"}"#).unwrap()
+        };
+
+        let usage = ApplicationUsage::from_logins(
+            &[
+                login(40, "s", "Old"),
+                login(40, "s", "Old"),
+                login(40, "s", "Old"),
+                login(5, "s", "Console"),
+                login(4, "s", "Docs"),
+                login(3, "s", "Console"),
+                login(2, "s", "Rfd"),
+                login(1, "s", "Docs"),
+                login(0, "f", "Failed"),
+            ],
+            now,
+        );
+
+        assert_eq!(usage.last_application_accessed, "Docs");
+        assert_eq!(usage.top_applications, vec!["Docs", "Console", "Rfd"]);
+        assert_eq!(ApplicationUsage::from_logins(&[], now), ApplicationUsage::default());
+    }
+    #[test]
+    fn test_stored_logins_history() {
+        let login = |log_id: &str, client_name: &str| NewAuthUserLogin {
+            log_id: log_id.to_string(),
+            client_name: client_name.to_string(),
+            ..serde_json::from_str(r#"{"date": "2024-01-01T00:00:00Z"}"#).unwrap()
+        };
+
+        let mut stored = StoredLogins::default();
+        stored
+            .recent
+            .insert("auth0|1".to_string(), vec![login("1", "Console"), login("2", "Docs")]);
+
+        // A login that is both stored and new is counted once, as the new copy.
+        let history = stored.history("auth0|1", &[login("2", "Rfd"), login("3", "Rfd")]);
+        let clients: Vec<&str> = history.iter().map(|l| l.client_name.as_str()).collect();
+        assert_eq!(clients, vec!["Console", "Rfd", "Rfd"]);
+
+        assert!(stored.history("auth0|2", &[]).is_empty());
+    }
+}
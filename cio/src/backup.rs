@@ -0,0 +1,271 @@
+//! Backing up database tables to JSON and restoring them, so we can keep a copy of the
+//! state the syncs built up, or seed a staging database, without going to the APIs the
+//! data came from.
+//!
+//! A backup is JSON lines, one record per line, with every column including the id and
+//! the Airtable record id. Restoring matches records on the same columns the syncs do,
+//! updating the ones that exist and creating the rest, so restoring twice is harmless.
+//! The ids are not restored, the database hands out its own.
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use async_bb8_diesel::AsyncRunQueryDsl;
+use async_trait::async_trait;
+use diesel::{ExpressionMethods, QueryDsl};
+use log::info;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "analytics")]
+use crate::analytics::{NewPageView, PageView};
+use crate::{
+    auth_logins::{AuthUser, AuthUserLogin, NewAuthUser, NewAuthUserLogin},
+    db::Database,
+    error::CioError,
+};
+
+/// The number of records read from the database at a time while exporting.
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+/// A table that can be backed up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupTable {
+    AuthUsers,
+    AuthUserLogins,
+    #[cfg(feature = "analytics")]
+    PageViews,
+}
+
+impl BackupTable {
+    /// Every table that can be backed up.
+    pub const ALL: &'static [BackupTable] = &[
+        BackupTable::AuthUsers,
+        BackupTable::AuthUserLogins,
+        #[cfg(feature = "analytics")]
+        BackupTable::PageViews,
+    ];
+
+    /// Returns the name of the table in the database.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackupTable::AuthUsers => "auth_users",
+            BackupTable::AuthUserLogins => "auth_user_logins",
+            #[cfg(feature = "analytics")]
+            BackupTable::PageViews => "page_views",
+        }
+    }
+}
+
+impl std::str::FromStr for BackupTable {
+    type Err = CioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BackupTable::ALL.iter().find(|t| t.name() == s).copied().ok_or_else(|| {
+            let names: Vec<&str> = BackupTable::ALL.iter().map(|t| t.name()).collect();
+            CioError::Config(format!("can't back up table `{}`, only {}", s, names.join(", ")))
+        })
+    }
+}
+
+/// A record of a table that can be backed up.
+#[async_trait]
+trait BackupRecord: Serialize + DeserializeOwned + Send + Sync + Sized {
+    /// Returns the id of the record in the database.
+    fn id(&self) -> i32;
+
+    /// Returns up to `limit` records with an id after `after`, in order of id.
+    async fn load_after(db: &Database, after: i32, limit: i64) -> Result<Vec<Self>>;
+
+    /// Save the record, updating the record it matches if there is one.
+    async fn restore(&self, db: &Database) -> Result<()>;
+}
+
+#[async_trait]
+impl BackupRecord for AuthUser {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn load_after(db: &Database, after: i32, limit: i64) -> Result<Vec<Self>> {
+        use crate::schema::auth_users::dsl;
+
+        Ok(dsl::auth_users
+            .filter(dsl::id.gt(after))
+            .order_by(dsl::id.asc())
+            .limit(limit)
+            .load_async::<AuthUser>(db.pool())
+            .await?)
+    }
+
+    async fn restore(&self, db: &Database) -> Result<()> {
+        let mut restored = NewAuthUser::from(self).upsert(db).await?;
+        if restored.airtable_record_id != self.airtable_record_id {
+            restored.airtable_record_id = self.airtable_record_id.to_string();
+            restored.update(db).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BackupRecord for AuthUserLogin {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn load_after(db: &Database, after: i32, limit: i64) -> Result<Vec<Self>> {
+        use crate::schema::auth_user_logins::dsl;
+
+        Ok(dsl::auth_user_logins
+            .filter(dsl::id.gt(after))
+            .order_by(dsl::id.asc())
+            .limit(limit)
+            .load_async::<AuthUserLogin>(db.pool())
+            .await?)
+    }
+
+    async fn restore(&self, db: &Database) -> Result<()> {
+        let mut restored = NewAuthUserLogin::from(self).upsert(db).await?;
+        if restored.airtable_record_id != self.airtable_record_id {
+            restored.airtable_record_id = self.airtable_record_id.to_string();
+            restored.update(db).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "analytics")]
+#[async_trait]
+impl BackupRecord for PageView {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn load_after(db: &Database, after: i32, limit: i64) -> Result<Vec<Self>> {
+        use crate::schema::page_views::dsl;
+
+        Ok(dsl::page_views
+            .filter(dsl::id.gt(after))
+            .order_by(dsl::id.asc())
+            .limit(limit)
+            .load_async::<PageView>(db.pool())
+            .await?)
+    }
+
+    async fn restore(&self, db: &Database) -> Result<()> {
+        let mut restored = NewPageView::from(self).upsert(db).await?;
+        if restored.airtable_record_id != self.airtable_record_id {
+            restored.airtable_record_id = self.airtable_record_id.to_string();
+            restored.update(db).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Write every record of the table to the writer as JSON lines, returning the number
+    /// of records written. See `BackupTable` for the tables that can be exported.
+    pub async fn export_table_json<W: Write + Send>(&self, name: &str, writer: W) -> Result<usize, CioError> {
+        match name.parse::<BackupTable>()? {
+            BackupTable::AuthUsers => export::<AuthUser, W>(self, writer).await,
+            BackupTable::AuthUserLogins => export::<AuthUserLogin, W>(self, writer).await,
+            #[cfg(feature = "analytics")]
+            BackupTable::PageViews => export::<PageView, W>(self, writer).await,
+        }
+    }
+
+    /// Restore the records of the table from JSON lines written by `export_table_json`,
+    /// returning the number of records restored.
+    ///
+    /// Stops at the first line that does not parse or fails to save. The records before it
+    /// stay restored, and importing the file again once it is fixed picks up the rest.
+    pub async fn import_table_json<R: BufRead + Send>(&self, name: &str, reader: R) -> Result<usize, CioError> {
+        match name.parse::<BackupTable>()? {
+            BackupTable::AuthUsers => import::<AuthUser, R>(self, reader).await,
+            BackupTable::AuthUserLogins => import::<AuthUserLogin, R>(self, reader).await,
+            #[cfg(feature = "analytics")]
+            BackupTable::PageViews => import::<PageView, R>(self, reader).await,
+        }
+    }
+}
+
+async fn export<T: BackupRecord, W: Write + Send>(db: &Database, mut writer: W) -> Result<usize, CioError> {
+    let mut written = 0;
+    let mut after = 0;
+    loop {
+        let records = T::load_after(db, after, EXPORT_BATCH_SIZE)
+            .await
+            .map_err(CioError::Database)?;
+        let last = match records.last() {
+            Some(last) => last.id(),
+            None => break,
+        };
+
+        for record in records.iter() {
+            write_line(&mut writer, record)?;
+        }
+        written += records.len();
+        after = last;
+    }
+    writer.flush()?;
+
+    info!("exported {} records", written);
+
+    Ok(written)
+}
+
+async fn import<T: BackupRecord, R: BufRead + Send>(db: &Database, reader: R) -> Result<usize, CioError> {
+    let mut restored = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: T = serde_json::from_str(&line).map_err(|e| anyhow!("parsing line {} failed: {}", i + 1, e))?;
+        record
+            .restore(db)
+            .await
+            .map_err(|e| CioError::Database(anyhow!("restoring the record on line {} failed: {}", i + 1, e)))?;
+        restored += 1;
+    }
+
+    info!("imported {} records", restored);
+
+    Ok(restored)
+}
+
+/// Write a record as a line of JSON.
+fn write_line<T: Serialize, W: Write>(writer: &mut W, record: &T) -> Result<(), CioError> {
+    serde_json::to_writer(&mut *writer, record).map_err(|e| anyhow!("serializing a record failed: {}", e))?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_line, BackupTable};
+
+    #[test]
+    fn test_backup_table_names() {
+        for table in BackupTable::ALL {
+            assert_eq!(table.name().parse::<BackupTable>().unwrap(), *table);
+        }
+        assert!("companys".parse::<BackupTable>().is_err());
+    }
+
+    #[test]
+    fn test_write_line() {
+        let mut buf = Vec::new();
+        write_line(&mut buf, &serde_json::json!({"user_id": "auth0|1"})).unwrap();
+        write_line(&mut buf, &serde_json::json!({"user_id": "auth0|2"})).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"user_id\":\"auth0|1\"}\n{\"user_id\":\"auth0|2\"}\n"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Stopping long running syncs part way, without losing the work they already did.
+//!
+//! A sync checks its `Cancellation` between pages and batches. Once it is cancelled, the
+//! sync writes out what it has so far, keeps its checkpoint and returns a summary marked
+//! as cancelled, so the next run carries on from there instead of starting over.
+//!
+//! The syncs started through the public entry points watch the `shutdown` cancellation,
+//! which the process cancels when it is asked to stop.
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use tokio::sync::watch;
+
+lazy_static! {
+    static ref SHUTDOWN: Cancellation = Cancellation::new();
+}
+
+/// Returns the cancellation of the process, cancelled when it is shutting down.
+pub fn shutdown() -> &'static Cancellation {
+    &SHUTDOWN
+}
+
+/// A flag that tells a sync to stop at its next checkpoint.
+///
+/// Clones share the flag, so cancelling one cancels all of them.
+#[derive(Debug, Clone)]
+pub struct Cancellation {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        let (sender, receiver) = watch::channel(false);
+
+        Cancellation {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+}
+
+impl Cancellation {
+    /// Create a cancellation that is not cancelled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancel the syncs watching this, and every clone of it.
+    pub fn cancel(&self) {
+        // We hold a receiver, so the send can't fail.
+        let _ = self.sender.send(true);
+    }
+
+    /// Returns true once `cancel` was called.
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Wait until `cancel` is called.
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow() {
+            // We hold the sender, so the channel can't close.
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Cancellation;
+
+    #[tokio::test]
+    async fn test_cancellation() {
+        let cancel = Cancellation::new();
+        let clone = cancel.clone();
+        assert!(!clone.is_cancelled());
+
+        let waiting = tokio::spawn(async move { clone.cancelled().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("the clone saw the cancellation")
+            .unwrap();
+        assert!(cancel.is_cancelled());
+    }
+}
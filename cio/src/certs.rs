@@ -16,6 +16,7 @@ use instant_acme::{
 };
 use macros::db;
 use mime::Mime;
+#[cfg(feature = "github")]
 use octorust::types::FullRepository;
 use openssl::x509::X509;
 use rcgen::{Certificate as GeneratedCertificate, CertificateParams, DistinguishedName};
@@ -27,6 +28,8 @@ use tokio::{
     time::sleep,
 };
 
+#[cfg(feature = "github")]
+use crate::utils::{create_or_update_file_in_github_repo, get_file_content_from_repo, SliceExt};
 use crate::{
     airtable::AIRTABLE_CERTIFICATES_TABLE,
     companies::Company,
@@ -34,7 +37,6 @@ use crate::{
     db::Database,
     dns_providers::{DNSProviderOps, DnsRecord, DnsRecordType, DnsUpdateMode},
     schema::certificates,
-    utils::{create_or_update_file_in_github_repo, get_file_content_from_repo, SliceExt},
 };
 
 /// A data type to hold the values of a let's encrypt certificate for a domain.
@@ -338,12 +340,14 @@ pub trait KeyStorage {
     async fn write_key(&self, domain: &str, data: &[u8]) -> Result<()>;
 }
 
+#[cfg(feature = "github")]
 pub struct GitHubBackend {
     client: octorust::Client,
     owner: String,
     repo: String,
 }
 
+#[cfg(feature = "github")]
 impl GitHubBackend {
     pub fn new(client: octorust::Client, owner: String, repo: String) -> Self {
         Self { client, owner, repo }
@@ -358,6 +362,7 @@ impl GitHubBackend {
     }
 }
 
+#[cfg(feature = "github")]
 #[async_trait]
 impl CertificateStorage for GitHubBackend {
     async fn read_cert(&self, domain: &str) -> Result<Vec<u8>> {
@@ -389,6 +394,7 @@ impl CertificateStorage for GitHubBackend {
     }
 }
 
+#[cfg(feature = "github")]
 #[async_trait]
 impl KeyStorage for GitHubBackend {
     async fn write_key(&self, domain: &str, data: &[u8]) -> Result<()> {
@@ -0,0 +1,214 @@
+//! Pushing the rows that change to Airtable within seconds, instead of at the next sync.
+//!
+//! Triggers on `auth_users` and `page_views` notify the `cio_changes` channel with the
+//! table and id of each row that is inserted or updated. The listener holds a connection
+//! that LISTENs on the channel, gathers the ids for a moment so a burst of changes is
+//! pushed together, and pushes only those rows with `push_records_to_airtable`.
+//!
+//! The cron syncs keep running, and pick up whatever changed while no listener was up.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    time::Duration,
+};
+
+use async_bb8_diesel::AsyncRunQueryDsl;
+use diesel::{ExpressionMethods, QueryDsl};
+use futures::{channel::mpsc, stream, StreamExt};
+use log::{error, info, warn};
+use openssl::ssl::{SslConnector, SslMethod};
+use postgres_openssl::MakeTlsConnector;
+use serde::{Deserialize, Serialize};
+use tokio::time::{timeout_at, Instant};
+use tokio_postgres::AsyncMessage;
+
+use crate::{
+    airtable_sync::{push_records_to_airtable, AirtableCache, AirtableSyncable, SyncSummary},
+    auth_logins::AuthUser,
+    cancel::Cancellation,
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    schema::auth_users,
+};
+
+/// The channel the triggers notify, see the `airtable_change_notify` migration.
+pub const CHANGES_CHANNEL: &str = "cio_changes";
+
+/// How long to gather changes after the first one before pushing them.
+const PUSH_WINDOW: Duration = Duration::from_secs(2);
+
+/// The payload of a notification on `cio_changes`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ChangeNotification {
+    pub table: String,
+    pub id: i32,
+}
+
+/// The ids of the rows that changed, by table.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PendingChanges {
+    tables: BTreeMap<String, BTreeSet<i32>>,
+}
+
+impl PendingChanges {
+    /// Add the row of a notification payload. Payloads that can't be read are logged and
+    /// dropped, the next sync picks the row up anyway.
+    pub fn push(&mut self, payload: &str) {
+        match serde_json::from_str::<ChangeNotification>(payload) {
+            Ok(change) => {
+                self.tables.entry(change.table).or_default().insert(change.id);
+            }
+            Err(e) => warn!("ignoring change notification `{}`: {}", payload, e),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Returns the tables that changed and the ids of their rows.
+    pub fn tables(&self) -> impl Iterator<Item = (&str, Vec<i32>)> {
+        self.tables
+            .iter()
+            .map(|(table, ids)| (table.as_str(), ids.iter().copied().collect()))
+    }
+}
+
+/// Listen for the rows that change and push them to Airtable, until the cancellation is
+/// cancelled. Returns an error if the connection to the database is lost, so the caller
+/// can start over.
+pub async fn listen_for_changes(db: &Database, cancel: &Cancellation, dry_run: DryRun) -> Result<(), CioError> {
+    let url = env::var("CIO_DATABASE_URL").map_err(|_| CioError::Config("CIO_DATABASE_URL must be set".to_string()))?;
+    let tls = SslConnector::builder(SslMethod::tls()).map_err(|e| CioError::Database(e.into()))?;
+    let (client, mut connection) = tokio_postgres::connect(&url, MakeTlsConnector::new(tls.build()))
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+
+    // The connection has to be polled for the notifications to arrive, hand them over
+    // until it closes.
+    let (sender, mut notifications) = mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(n)) => {
+                    if sender.unbounded_send(n.payload().to_string()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    error!("the connection listening for changes failed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    client
+        .batch_execute(&format!("LISTEN {}", CHANGES_CHANNEL))
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+    info!("listening for changes on `{}`", CHANGES_CHANNEL);
+
+    let cache = AirtableCache::default();
+    loop {
+        let first = tokio::select! {
+            payload = notifications.next() => payload,
+            _ = cancel.cancelled() => return Ok(()),
+        };
+        let first =
+            first.ok_or_else(|| CioError::Database(anyhow::anyhow!("the connection listening for changes closed")))?;
+
+        let mut pending = PendingChanges::default();
+        pending.push(&first);
+        let deadline = Instant::now() + PUSH_WINDOW;
+        while let Ok(Some(payload)) = timeout_at(deadline, notifications.next()).await {
+            pending.push(&payload);
+        }
+
+        if let Err(e) = push_changes(db, &cache, &pending, dry_run).await {
+            error!("pushing the changed rows to airtable failed: {}", e);
+        }
+    }
+}
+
+/// Push the rows that changed to Airtable.
+pub async fn push_changes(
+    db: &Database,
+    cache: &AirtableCache,
+    pending: &PendingChanges,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let mut summary = SyncSummary::default();
+    for (table, ids) in pending.tables() {
+        match table {
+            "auth_users" => {
+                let rows = auth_users::dsl::auth_users
+                    .filter(auth_users::dsl::id.eq_any(ids))
+                    .load_async::<AuthUser>(db.pool())
+                    .await
+                    .map_err(|e| CioError::Database(e.into()))?;
+                summary += push_by_company(db, cache, rows, |r| r.cio_company_id, dry_run).await?;
+            }
+            #[cfg(feature = "analytics")]
+            "page_views" => {
+                use crate::{analytics::PageView, schema::page_views};
+
+                let rows = page_views::dsl::page_views
+                    .filter(page_views::dsl::id.eq_any(ids))
+                    .load_async::<PageView>(db.pool())
+                    .await
+                    .map_err(|e| CioError::Database(e.into()))?;
+                summary += push_by_company(db, cache, rows, |r| r.cio_company_id, dry_run).await?;
+            }
+            _ => warn!("ignoring changes to `{}`, it is not pushed to airtable", table),
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Push the rows to the bases of the companies they belong to.
+async fn push_by_company<T: AirtableSyncable>(
+    db: &Database,
+    cache: &AirtableCache,
+    rows: Vec<T>,
+    company_id: impl Fn(&T) -> i32,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let mut by_company: BTreeMap<i32, Vec<T>> = Default::default();
+    for row in rows {
+        by_company.entry(company_id(&row)).or_default().push(row);
+    }
+
+    let mut summary = SyncSummary::default();
+    for (id, rows) in by_company {
+        let company = Company::get_by_id(db, id).await.map_err(CioError::Database)?;
+        summary += push_records_to_airtable(db, &company, cache, rows, dry_run).await?;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PendingChanges;
+
+    #[test]
+    fn test_pending_changes() {
+        let mut pending = PendingChanges::default();
+        assert!(pending.is_empty());
+
+        pending.push(r#"{"table":"page_views","id":7}"#);
+        pending.push(r#"{"table":"auth_users","id":2}"#);
+        pending.push(r#"{"table":"page_views","id":3}"#);
+        pending.push(r#"{"table":"page_views","id":7}"#);
+        pending.push("not json");
+
+        let tables: Vec<(&str, Vec<i32>)> = pending.tables().collect();
+        assert_eq!(tables, vec![("auth_users", vec![2]), ("page_views", vec![3, 7])]);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{convert::TryInto, env};
+use std::env;
 
 use airtable_api::Airtable;
 use anyhow::{anyhow, bail, Result};
@@ -21,11 +21,14 @@ use google_storage1::{
     hyper_rustls::{HttpsConnector, HttpsConnectorBuilder},
     Storage,
 };
+#[cfg(feature = "gsuite")]
 use gsuite_api::Client as GoogleAdmin;
 use gusto_api::Client as Gusto;
+#[cfg(feature = "shipments")]
 use log::{info, warn};
 use macros::db;
 use mailchimp_minimal_api::{AuthMode, MailChimp};
+#[cfg(feature = "github")]
 use octorust::{
     auth::{Credentials, InstallationTokenGenerator, JWTCredentials},
     http_cache::FileBasedCache,
@@ -33,6 +36,7 @@ use octorust::{
 use okta::Client as Okta;
 use quickbooks::QuickBooks;
 use ramp_minimal_api::RampClient as Ramp;
+#[cfg(feature = "github")]
 use rsa::{
     pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey},
     RsaPrivateKey,
@@ -41,25 +45,34 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sf_client::{AuthorizationServer, JwtAuthenticator, LoginClaims, SfClient};
 use sheets::Client as GoogleSheets;
+#[cfg(feature = "shipments")]
 use shipbob::Client as ShipBob;
+#[cfg(feature = "slack")]
 use slack_chat_api::Slack;
 use tailscale_api::Tailscale;
 use tripactions::Client as TripActions;
 use yup_oauth2::authenticator::Authenticator;
 use zoho_api::Zoho;
+#[cfg(feature = "zoom")]
 use zoom_api::Client as Zoom;
 
+#[cfg(feature = "github")]
+use crate::certs::GitHubBackend;
+#[cfg(feature = "shipments")]
+use crate::configs::{Building, Buildings};
 use crate::{
     airtable::{AIRTABLE_COMPANIES_TABLE, AIRTABLE_GRID_VIEW},
+    airtable_bases::airtable_client,
     api_tokens::{APIToken, NewAPIToken},
-    certs::{GcsBackend, GitHubBackend, SslCertificateStorage},
+    auth0::Auth0Client,
+    certs::{GcsBackend, SslCertificateStorage},
     cloud_dns::CloudDnsClient,
     cloudflare::CloudFlareClient,
-    configs::{Building, Buildings},
     core::UpdateAirtableRecord,
     db::Database,
     dns_proxy::DnsProviderProxy,
     schema::{api_tokens, companys},
+    secrets,
 };
 
 #[db {
@@ -167,6 +180,7 @@ pub struct NewCompany {
 impl Company {
     /// Returns the shippo data structure for the address at the office
     /// for the company.
+    #[cfg(feature = "shipments")]
     pub async fn hq_shipping_address(&self, db: &Database) -> Result<shippo::Address> {
         // Get the buildings from the company.
         let buildings: Vec<Building> = Buildings::get_from_db(db, self.cio_company_id).await?.into();
@@ -192,6 +206,7 @@ impl Company {
         })
     }
 
+    #[cfg(feature = "slack")]
     pub async fn post_to_slack_channel(&self, db: &Database, msg: &slack_chat_api::FormattedMessage) -> Result<()> {
         // Create the Slack client.
         let r = self.authenticate_slack(db).await;
@@ -340,10 +355,11 @@ impl Company {
 
     /// Authenticate with Airtable.
     pub fn authenticate_airtable(&self, base_id: &str) -> Airtable {
-        Airtable::new(&self.airtable_api_key, base_id, &self.airtable_enterprise_account_id)
+        airtable_client(&self.airtable_api_key, base_id, &self.airtable_enterprise_account_id)
     }
 
     /// Authenticate with ShipBob.
+    #[cfg(feature = "shipments")]
     pub async fn authenticate_shipbob(&self) -> Result<ShipBob> {
         if self.shipbob_pat.is_empty() {
             bail!("no shipbob personal access token");
@@ -353,6 +369,7 @@ impl Company {
     }
 
     /// Ensure the company has ShipBob webhooks setup.
+    #[cfg(feature = "shipments")]
     pub async fn ensure_shipbob_webhooks(&self) -> Result<()> {
         let shipbob_auth = self.authenticate_shipbob().await;
         if let Err(e) = shipbob_auth {
@@ -432,12 +449,13 @@ impl Company {
 
     /// Authenticate with MailChimp.
     pub async fn authenticate_mailchimp(&self) -> Result<MailChimp> {
-        let key = std::env::var("MAILCHIMP_API_KEY")?;
+        let key = secrets::get("MAILCHIMP_API_KEY")?;
 
         Ok(MailChimp::new(AuthMode::new_basic_auth(key)?))
     }
 
     /// Authenticate with Slack.
+    #[cfg(feature = "slack")]
     pub async fn authenticate_slack(&self, db: &Database) -> Result<Slack> {
         // Get the bot token and user token from the database.
         if let Ok(bot_token) = api_tokens::dsl::api_tokens
@@ -476,22 +494,33 @@ impl Company {
         bail!("no token");
     }
 
+    /// Authenticate with the Auth0 management API.
+    pub fn authenticate_auth0(&self) -> Result<Auth0Client> {
+        Ok(Auth0Client::new(
+            secrets::get("CIO_AUTH0_DOMAIN")?,
+            secrets::get("CIO_AUTH0_CLIENT_ID")?,
+            secrets::get("CIO_AUTH0_CLIENT_SECRET")?,
+        ))
+    }
+
     /// Authenticate with Ramp.
     pub fn authenticate_ramp(&self) -> Result<Ramp> {
         Ok(Ramp::new(
-            std::env::var("RAMP_CLIENT_ID")?,
-            std::env::var("RAMP_CLIENT_SECRET")?,
+            secrets::get("RAMP_CLIENT_ID")?,
+            secrets::get("RAMP_CLIENT_SECRET")?,
             vec![
                 "users:read".to_string(),
                 "users:write".to_string(),
                 "departments:read".to_string(),
                 "transactions:read".to_string(),
                 "reimbursements:read".to_string(),
+                "receipts:read".to_string(),
             ],
         ))
     }
 
     /// Authenticate with Zoom.
+    #[cfg(feature = "zoom")]
     pub async fn authenticate_zoom(&self, db: &Database) -> Result<Zoom> {
         // Get the APIToken from the database.
         if let Some(mut t) = APIToken::get_from_db(db, self.id, "zoom".to_string()).await {
@@ -759,6 +788,7 @@ impl Company {
     }
 
     /// Authenticate Google Admin.
+    #[cfg(feature = "gsuite")]
     pub async fn authenticate_google_admin(&self, db: &Database) -> Result<GoogleAdmin> {
         // Get the APIToken from the database.
         if let Some(mut t) = APIToken::get_from_db(db, self.id, "google".to_string()).await {
@@ -923,6 +953,33 @@ impl Company {
         Ok(token_string)
     }
 
+    /// Get a token for the Google Analytics Data API. This authenticates as the service
+    /// account itself, which needs to be a viewer of the property.
+    pub async fn get_google_analytics_token(&self) -> Result<String> {
+        if self.google_service_account.is_empty() {
+            bail!("no service account");
+        }
+
+        let client_secret = yup_oauth2::parse_service_account_key(&self.google_service_account)?;
+        let auth = yup_oauth2::ServiceAccountAuthenticator::builder(client_secret)
+            .build()
+            .await?;
+
+        let token = auth
+            .token(&["https://www.googleapis.com/auth/analytics.readonly"])
+            .await?;
+
+        let token_string = token
+            .token()
+            .ok_or_else(|| anyhow!("Failed to retrieve token"))?
+            .to_string();
+        if token_string.is_empty() {
+            bail!("empty token returned from authenticator");
+        }
+
+        Ok(token_string)
+    }
+
     /// Authenticate Google Sheets.
     pub async fn authenticate_google_sheets(&self, db: &Database) -> Result<GoogleSheets> {
         // Get the APIToken from the database.
@@ -995,12 +1052,13 @@ impl Company {
     }
 
     /// Authenticate GitHub with JSON web token credentials, for an application installation.
+    #[cfg(feature = "github")]
     pub fn authenticate_github(&self) -> Result<octorust::Client> {
         // Parse our env variables.
-        let app_id_str = env::var("GH_APP_ID")?;
+        let app_id_str = secrets::get("GH_APP_ID")?;
         let app_id = app_id_str.parse::<i64>()?;
 
-        let encoded_private_key = env::var("GH_PRIVATE_KEY")?;
+        let encoded_private_key = secrets::get("GH_PRIVATE_KEY")?;
         let private_key = String::from_utf8(base64::decode(encoded_private_key)?)?;
 
         let key = RsaPrivateKey::from_pkcs1_pem(private_key.as_str())?
@@ -1086,14 +1144,16 @@ impl Company {
             gcp_auth,
         );
 
-        Ok(vec![
-            Box::new(GcsBackend::new(gcs_storage, self.certs_gcs())),
-            Box::new(GitHubBackend::new(
-                self.authenticate_github()?,
-                self.github_org.clone(),
-                self.shorturl_repo(),
-            )),
-        ])
+        let mut storage: Vec<Box<dyn SslCertificateStorage>> =
+            vec![Box::new(GcsBackend::new(gcs_storage, self.certs_gcs()))];
+        #[cfg(feature = "github")]
+        storage.push(Box::new(GitHubBackend::new(
+            self.authenticate_github()?,
+            self.github_org.clone(),
+            self.shorturl_repo(),
+        )));
+
+        Ok(storage)
     }
 
     pub fn certs_gcs(&self) -> String {
@@ -1123,6 +1183,7 @@ impl Company {
 
     // Creates a minimal type for callers that need information about the RFD repo, but
     // do not want to parse data from the full API response
+    #[cfg(feature = "github")]
     pub async fn rfd_repo(&self) -> Result<RFDRepo> {
         Ok(self
             .authenticate_github()?
@@ -1158,6 +1219,7 @@ pub fn get_google_scopes() -> Vec<String> {
     ]
 }
 
+#[cfg(feature = "shipments")]
 pub fn get_shipbob_scopes() -> Vec<String> {
     vec![
         "channels_read".to_string(),
@@ -1,9 +1,7 @@
 #![allow(clippy::from_over_into)]
-use std::{
-    collections::{BTreeMap, HashMap},
-    fmt,
-    str::from_utf8,
-};
+#[cfg(all(feature = "github", feature = "gsuite", feature = "zoom"))]
+use std::collections::HashMap;
+use std::{collections::BTreeMap, fmt, str::from_utf8};
 
 use anyhow::{bail, Result};
 use async_bb8_diesel::AsyncRunQueryDsl;
@@ -17,17 +15,27 @@ use diesel::{
     FromSqlRow,
 };
 use google_calendar::types::{Event, EventAttendee, EventDateTime};
-use gsuite_api::types::{
-    Building as GSuiteBuilding, CalendarResource as GSuiteCalendarResource, Group as GSuiteGroup, User as GSuiteUser,
-};
+#[cfg(feature = "gsuite")]
+use gsuite_api::types::{Building as GSuiteBuilding, CalendarResource as GSuiteCalendarResource};
+#[cfg(all(feature = "github", feature = "gsuite", feature = "zoom"))]
+use gsuite_api::types::{Group as GSuiteGroup, User as GSuiteUser};
 use gusto_api::Client as Gusto;
-use log::{error, info, warn};
+#[cfg(feature = "github")]
+use log::error;
+use log::info;
+#[cfg(any(feature = "gsuite", feature = "shipments"))]
+use log::warn;
 use macros::db;
 use schemars::JsonSchema;
 use sendgrid_api::{traits::MailOps, Client as SendGrid};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zoom")]
 use zoom_api::Client as Zoom;
 
+#[cfg(feature = "gsuite")]
+use crate::gsuite::{update_gsuite_building, update_gsuite_calendar_resource};
+#[cfg(feature = "shipments")]
+use crate::shipments::NewOutboundShipment;
 use crate::{
     airtable::{
         AIRTABLE_BUILDINGS_TABLE, AIRTABLE_EMPLOYEES_TABLE, AIRTABLE_GROUPS_TABLE, AIRTABLE_LINKS_TABLE,
@@ -35,16 +43,20 @@ use crate::{
     },
     app_config::{AppConfig, OnboardingConfig},
     applicants::Applicant,
-    certs::{Certificate, Certificates, GitHubBackend, NewCertificate},
+    auth0::Auth0Client,
+    certs::NewCertificate,
     companies::Company,
     core::UpdateAirtableRecord,
     db::Database,
-    features::Features,
-    gsuite::{update_gsuite_building, update_gsuite_calendar_resource},
     providers::{ProviderReadOps, ProviderWriteOps},
     schema::{applicants, buildings, groups, links, resources, users},
-    shipments::NewOutboundShipment,
-    utils::{get_file_content_from_repo, get_github_user_public_ssh_keys},
+    utils::get_github_user_public_ssh_keys,
+};
+#[cfg(feature = "github")]
+use crate::{
+    certs::{Certificate, Certificates, GitHubBackend},
+    features::Features,
+    utils::get_file_content_from_repo,
 };
 
 /// The data type for our configuration files.
@@ -72,6 +84,9 @@ pub struct Config {
 
     #[serde(default)]
     pub certificates: BTreeMap<String, NewCertificate>,
+
+    #[serde(default)]
+    pub auth0_tenants: BTreeMap<String, Auth0TenantConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone, JsonSchema, Serialize, PartialEq, FromSqlRow, AsExpression)]
@@ -98,6 +113,7 @@ impl ExternalServices {
         }
     }
 
+    #[cfg_attr(not(any(feature = "gsuite", feature = "zoom")), allow(unused_variables))]
     pub async fn get_provider_writer(
         &self,
         db: &Database,
@@ -106,7 +122,9 @@ impl ExternalServices {
         Ok(match self {
             // We don't need a base id here since we are only using the enterprise api features.
             ExternalServices::Airtable => Box::new(company.authenticate_airtable("")),
+            #[cfg(feature = "github")]
             ExternalServices::GitHub => Box::new(company.authenticate_github()?),
+            #[cfg(feature = "gsuite")]
             ExternalServices::Google => Box::new(company.authenticate_google_admin(db).await?),
             ExternalServices::Okta => Box::new(
                 company
@@ -114,7 +132,10 @@ impl ExternalServices {
                     .ok_or_else(|| anyhow::anyhow!("Failed to instantiate Okta client"))?,
             ),
             ExternalServices::Ramp => Box::new(company.authenticate_ramp()?),
+            #[cfg(feature = "zoom")]
             ExternalServices::Zoom => Box::new(company.authenticate_zoom(db).await?),
+            #[cfg(not(all(feature = "github", feature = "gsuite", feature = "zoom")))]
+            _ => bail!("{} is not enabled in this build", self),
         })
     }
 }
@@ -368,6 +389,7 @@ pub mod null_date_format {
 
 impl UserConfig {
     /// Sync a user from the config file with the services.
+    #[cfg(all(feature = "github", feature = "gsuite", feature = "zoom"))]
     #[allow(clippy::too_many_arguments)]
     pub async fn sync(
         &mut self,
@@ -910,6 +932,7 @@ impl User {
     /// - Create a record in outgoing shipments.
     /// - Generate the shippo label.
     /// - Print said shippo label.
+    #[cfg(feature = "shipments")]
     pub async fn create_shipment_to_home_address(&self, db: &Database) -> Result<()> {
         // First let's check if the user even has an address.
         // If not we can return early.
@@ -1126,6 +1149,7 @@ xoxo,
         Ok(())
     }
 
+    #[cfg(feature = "zoom")]
     pub async fn update_zoom_vanity_name(
         &self,
         db: &Database,
@@ -1523,6 +1547,38 @@ pub struct LinkConfig {
     pub cio_company_id: i32,
 }
 
+/// The data type for an Auth0 tenant we sync users and logins from.
+#[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct Auth0TenantConfig {
+    /// The tenant, as in `{domain}.auth0.com`.
+    pub domain: String,
+    /// The secret holding the client id of the management API application, see `secrets`.
+    #[serde(default = "default_auth0_client_id_env")]
+    pub client_id_env: String,
+    /// The secret holding the client secret of the management API application.
+    #[serde(default = "default_auth0_client_secret_env")]
+    pub client_secret_env: String,
+}
+
+fn default_auth0_client_id_env() -> String {
+    "CIO_AUTH0_CLIENT_ID".to_string()
+}
+
+fn default_auth0_client_secret_env() -> String {
+    "CIO_AUTH0_CLIENT_SECRET".to_string()
+}
+
+impl Auth0TenantConfig {
+    /// Authenticate with the management API of the tenant.
+    pub fn authenticate(&self) -> Result<Auth0Client> {
+        Ok(Auth0Client::new(
+            &self.domain,
+            crate::secrets::get(&self.client_id_env)?,
+            crate::secrets::get(&self.client_secret_env)?,
+        ))
+    }
+}
+
 /// The data type for a huddle meeting that syncs with Airtable and notes in GitHub.
 #[derive(Debug, Default, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
 pub struct HuddleConfig {
@@ -1552,6 +1608,7 @@ impl HuddleConfig {
     }
 }
 /// Get the configs from the GitHub repository and parse them.
+#[cfg(feature = "github")]
 pub async fn get_configs_from_repo(github: &octorust::Client, company: &Company) -> Result<Config> {
     let owner = &company.github_org;
     let repo = "configs";
@@ -1591,6 +1648,7 @@ pub async fn get_configs_from_repo(github: &octorust::Client, company: &Company)
 }
 
 /// Sync our users with our database and then update Airtable from the database.
+#[cfg(all(feature = "github", feature = "gsuite", feature = "zoom"))]
 pub async fn sync_users(
     db: &Database,
     github: &octorust::Client,
@@ -1645,6 +1703,9 @@ pub async fn sync_users(
         ramp_departments.insert(r.name.to_string(), r);
     }
 
+    // Initialize the Auth0 client.
+    let auth0_auth = company.authenticate_auth0();
+
     // Initialize the Zoom client.
     let mut zoom_users: HashMap<String, zoom_api::types::UsersResponse> = HashMap::new();
     let mut zoom_users_pending: HashMap<String, zoom_api::types::UsersResponse> = HashMap::new();
@@ -1909,6 +1970,21 @@ pub async fn sync_users(
                 }
             }
 
+            // Block the user in Auth0. We block rather than delete so their login
+            // history stays around.
+            if let Ok(ref auth0) = auth0_auth {
+                match auth0.block_users_by_email(&user.email).await {
+                    Ok(blocked) => {
+                        info!("Blocked {} Auth0 users for {}", blocked.len(), username);
+                    }
+                    Err(err) => {
+                        warn!("Failed to block user {} in Auth0. err: {:?}", username, err);
+
+                        has_failures = true;
+                    }
+                }
+            }
+
             // Delete the user from Airtable.
             // Okta should take care of this if we are using Okta.
             // But let's do it anyway.
@@ -1956,6 +2032,7 @@ pub async fn sync_users(
 }
 
 /// Sync our buildings with our database and then update Airtable from the database.
+#[cfg(feature = "gsuite")]
 pub async fn sync_buildings(
     db: &Database,
     buildings: BTreeMap<String, BuildingConfig>,
@@ -2077,6 +2154,7 @@ pub async fn sync_buildings(
 }
 
 /// Sync our resources with our database and then update Airtable from the database.
+#[cfg(feature = "gsuite")]
 pub async fn sync_resources(
     db: &Database,
     resources: BTreeMap<String, NewResourceConfig>,
@@ -2189,6 +2267,7 @@ pub async fn sync_resources(
 }
 
 /// Sync our groups with our database and then update Airtable from the database.
+#[cfg(all(feature = "github", feature = "gsuite"))]
 pub async fn sync_groups(db: &Database, groups: BTreeMap<String, GroupConfig>, company: &Company) -> Result<()> {
     // Get everything we need to authenticate with GSuite.
     // Initialize the GSuite client.
@@ -2322,6 +2401,7 @@ pub async fn sync_links(
 }
 
 /// Sync our certificates with our database and then update Airtable from the database.
+#[cfg(feature = "github")]
 pub async fn sync_certificates(
     db: &Database,
     github: &octorust::Client,
@@ -1,7 +1,10 @@
 use airtable_api::User as AirtableUser;
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::{naive::NaiveDate, DateTime, Utc};
+use chrono::naive::NaiveDate;
+#[cfg(feature = "github")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "github")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +14,19 @@ pub trait UpdateAirtableRecord<T> {
     async fn update_airtable_record(&mut self, _: T) -> Result<()>;
 }
 
+/// Whether a sync job should only log the creates, updates and deletes it would make,
+/// instead of making them. Use this to check changes to the sync logic before pointing
+/// it at production.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DryRun(pub bool);
+
+impl DryRun {
+    /// Returns true if changes should only be logged.
+    pub fn is_enabled(&self) -> bool {
+        self.0
+    }
+}
+
 /// The data type for customer interactions.
 /// This is inline with our Airtable workspace.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -102,6 +118,7 @@ pub struct MeetingReminderEmailData {
 
 /// A GitHub pull request.
 /// FROM: https://docs.github.com/en/free-pro-team@latest/rest/reference/pulls#get-a-pull-request
+#[cfg(feature = "github")]
 #[derive(Debug, Default, Clone, PartialEq, JsonSchema, Deserialize, Serialize)]
 pub struct GitHubPullRequest {
     #[serde(default)]
@@ -206,6 +223,7 @@ pub struct GitHubPullRequest {
     pub labels: Vec<octorust::types::LabelsData>,
 }
 
+#[cfg(feature = "github")]
 impl From<octorust::types::PullRequestSimple> for GitHubPullRequest {
     fn from(item: octorust::types::PullRequestSimple) -> Self {
         GitHubPullRequest {
@@ -264,6 +282,7 @@ impl From<octorust::types::PullRequestSimple> for GitHubPullRequest {
 
 /// A GitHub commit.
 /// FROM: https://docs.github.com/en/free-pro-team@latest/developers/webhooks-and-events/webhook-events-and-payloads#push
+#[cfg(feature = "github")]
 #[derive(Debug, Clone, Default, PartialEq, JsonSchema, Deserialize, Serialize)]
 pub struct GitHubCommit {
     /// The SHA of the commit.
@@ -325,6 +344,7 @@ pub struct GitHubCommit {
     pub sha: String,
 }
 
+#[cfg(feature = "github")]
 impl GitHubCommit {
     /// Filter the files that were added, modified, or removed by their prefix
     /// including a specified directory or path.
@@ -347,6 +367,7 @@ impl GitHubCommit {
     }
 }
 
+#[cfg(feature = "github")]
 fn filter(files: &[String], dir: &str) -> Vec<String> {
     let mut in_dir: Vec<String> = Default::default();
     for file in files {
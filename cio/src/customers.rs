@@ -5,12 +5,17 @@ use anyhow::Result;
 
 use log::info;
 
-use crate::{companies::Company, core::CustomerInteraction, utils::get_file_content_from_repo};
+use crate::{
+    airtable_bases::{AirtableBase, BaseRegistry},
+    companies::Company,
+    core::CustomerInteraction,
+    utils::get_file_content_from_repo,
+};
 
 /// Sync meeting notes with the content from the notes.
 pub async fn sync_customer_meeting_notes(company: &Company) -> Result<()> {
     // Initialize the Airtable client.
-    let airtable = company.authenticate_airtable(&company.airtable_base_id_customer_leads);
+    let airtable = BaseRegistry::from_env()?.authenticate(AirtableBase::CustomerLeads, company);
 
     let github = company.authenticate_github()?;
 
@@ -1,12 +1,39 @@
-use std::{env, fmt};
+use std::{env, fmt, future::Future, time::Duration};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_bb8_diesel::ConnectionManager;
+#[cfg(all(feature = "github", feature = "slack"))]
 use async_trait::async_trait;
-use diesel::PgConnection;
+use diesel::{Connection, PgConnection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use log::{error, info, warn};
+use tokio::sync::OnceCell;
+
+use crate::{airtable_sync::SyncSummary, error::CioError};
 
 pub type DbConnection = PgConnection;
 
+/// The number of connections in the pool, if `CIO_DATABASE_POOL_SIZE` is not set.
+const DEFAULT_POOL_SIZE: u32 = 10;
+
+/// How long to wait for a connection from the pool before giving up.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The migrations in `cio/migrations`, built into the crate so a deploy can apply them.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// The pool shared by the callers that have no context to get a database from.
+static SHARED: OnceCell<Database> = OnceCell::const_new();
+
+/// A pool of connections to the database.
+///
+/// Cloning is cheap, the clones share the same pool. Pass a clone to anything that needs
+/// the database concurrently rather than calling `new` again.
+///
+/// Queries are async: the `*_async` methods from `async_bb8_diesel::AsyncRunQueryDsl` run
+/// them on tokio's blocking thread pool, so they never block the runtime. The synchronous
+/// diesel methods must only be used on the connection handed to a `transaction` closure,
+/// which runs on the blocking pool too.
 #[derive(Debug, Clone)]
 pub struct Database {
     pool: DB,
@@ -24,13 +51,48 @@ impl fmt::Debug for DB {
 
 impl Database {
     /// Establish a connection to the database.
+    ///
+    /// Panics if `CIO_DATABASE_URL` is not set, use `try_new` to handle that instead.
     pub async fn new() -> Self {
-        let database_url = env::var("CIO_DATABASE_URL").expect("CIO_DATABASE_URL must be set");
+        Self::try_new().await.unwrap()
+    }
+
+    /// Establish a connection to the database, returning an error if `CIO_DATABASE_URL`
+    /// is not set.
+    ///
+    /// The size of the pool is read from `CIO_DATABASE_POOL_SIZE`. If
+    /// `CIO_DATABASE_RUN_MIGRATIONS` is `true`, the pending migrations are applied first.
+    pub async fn try_new() -> Result<Self, CioError> {
+        let database_url =
+            env::var("CIO_DATABASE_URL").map_err(|_| CioError::Config("CIO_DATABASE_URL must be set".to_string()))?;
+
+        let pool_size = match env::var("CIO_DATABASE_POOL_SIZE") {
+            Ok(size) => size
+                .parse()
+                .map_err(|_| CioError::Config(format!("CIO_DATABASE_POOL_SIZE `{}` is not a number", size)))?,
+            Err(_) => DEFAULT_POOL_SIZE,
+        };
+
+        if env::var("CIO_DATABASE_RUN_MIGRATIONS")
+            .map(|v| v == "true")
+            .unwrap_or(false)
+        {
+            run_migrations(&database_url).await?;
+        }
 
         let manager = ConnectionManager::<DbConnection>::new(database_url);
-        let pool = bb8::Builder::new().build_unchecked(manager);
+        let pool = bb8::Builder::new()
+            .max_size(pool_size)
+            .connection_timeout(CONNECTION_TIMEOUT)
+            .build_unchecked(manager);
 
-        Database { pool: DB(pool) }
+        Ok(Database { pool: DB(pool) })
+    }
+
+    /// Returns a pool shared by the whole process, creating it the first time. Use this
+    /// where there is no context to take a database from, instead of opening a new pool.
+    pub async fn shared() -> Result<Self, CioError> {
+        SHARED.get_or_try_init(Self::try_new).await.map(Clone::clone)
     }
 
     /// Returns a reference to the underlying pool.
@@ -39,6 +101,78 @@ impl Database {
     }
 }
 
+/// Apply the embedded migrations that have not run against the database yet. Returns the
+/// versions that were applied.
+pub async fn run_migrations(database_url: &str) -> Result<Vec<String>, CioError> {
+    let database_url = database_url.to_string();
+
+    // Migrations need a synchronous connection, so keep them off the runtime.
+    tokio::task::spawn_blocking(move || {
+        let mut conn = PgConnection::establish(&database_url).map_err(|e| CioError::Database(e.into()))?;
+        let applied = conn
+            .run_pending_migrations(MIGRATIONS)
+            .map_err(|e| CioError::Database(anyhow!("running migrations failed: {}", e)))?;
+
+        let applied: Vec<String> = applied.into_iter().map(|v| v.to_string()).collect();
+        for version in applied.iter() {
+            info!("applied migration `{}`", version);
+        }
+
+        Ok(applied)
+    })
+    .await
+    .map_err(|e| CioError::Database(e.into()))?
+}
+
+/// Save the records listed by an upstream service one at a time, then prune the rows that
+/// are no longer listed. `key` names a record, `prune` is handed the keys of the listing.
+///
+/// The records that fail to save are logged and counted in the `errors` of the summary.
+/// Pruning is skipped when any record failed to save, so a failed write never drops a row,
+/// and when the listing is empty, which is far likelier a broken listing than an upstream
+/// with nothing left in it.
+pub async fn save_listing<'a, T, K, S, SF, R, P, PF>(
+    what: &str,
+    records: &'a [T],
+    key: K,
+    save: S,
+    prune: P,
+) -> Result<SyncSummary, CioError>
+where
+    K: Fn(&T) -> String,
+    S: Fn(&'a T) -> SF,
+    SF: Future<Output = Result<R>>,
+    P: FnOnce(Vec<String>) -> PF,
+    PF: Future<Output = Result<usize>>,
+{
+    let mut summary = SyncSummary::default();
+    for record in records {
+        if let Err(e) = save(record).await {
+            error!("saving {} `{}` failed: {}", what, key(record), e);
+            summary.errors += 1;
+        }
+    }
+
+    if summary.errors > 0 {
+        warn!(
+            "{} {}s failed to save, not pruning the ones no longer listed",
+            summary.errors, what
+        );
+    } else if records.is_empty() {
+        warn!("no {}s were listed, not pruning", what);
+    } else {
+        let deleted = prune(records.iter().map(&key).collect())
+            .await
+            .map_err(CioError::Database)?;
+        if deleted > 0 {
+            info!("deleted {} {}s that are no longer listed", deleted, what);
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(all(feature = "github", feature = "slack"))]
 #[async_trait]
 impl steno::SecStore for Database {
     async fn saga_create(&self, create_params: steno::SagaCreateParams) -> Result<()> {
@@ -48,14 +182,72 @@ impl steno::SecStore for Database {
     }
 
     async fn record_event(&self, event: steno::SagaNodeEvent) {
-        crate::functions::Function::from_saga_node_event(self, &event)
-            .await
-            .unwrap();
+        // The trait gives us no way to return the error, so log it rather than take down
+        // the saga executor.
+        if let Err(e) = crate::functions::Function::from_saga_node_event(self, &event).await {
+            error!("recording saga event failed: {}", e);
+        }
     }
 
     async fn saga_update(&self, id: steno::SagaId, update: steno::SagaCachedState) {
-        crate::functions::Function::from_saga_cached_state(self, &id, &update)
-            .await
-            .unwrap();
+        if let Err(e) = crate::functions::Function::from_saga_cached_state(self, &id, &update).await {
+            error!("updating saga `{}` failed: {}", id.0, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::bail;
+
+    use super::*;
+
+    /// Saves the records, failing the ones named `bad`, and returns the summary and the
+    /// keys handed to `prune`, if it ran.
+    async fn save(records: &[&str]) -> (SyncSummary, Option<Vec<String>>) {
+        let pruned: Mutex<Option<Vec<String>>> = Default::default();
+        let summary = save_listing(
+            "record",
+            records,
+            |r| r.to_string(),
+            |r| async move {
+                if *r == "bad" {
+                    bail!("failed");
+                }
+                Ok(())
+            },
+            |keys| {
+                let count = keys.len();
+                *pruned.lock().unwrap() = Some(keys);
+                async move { Ok(count) }
+            },
+        )
+        .await
+        .unwrap();
+
+        (summary, pruned.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_save_listing_prunes_the_rest() {
+        let (summary, pruned) = save(&["a", "b"]).await;
+        assert_eq!(summary, SyncSummary::default());
+        assert_eq!(pruned, Some(vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_save_listing_counts_errors_and_keeps_rows() {
+        let (summary, pruned) = save(&["a", "bad", "b", "bad"]).await;
+        assert_eq!(summary.errors, 2);
+        assert_eq!(pruned, None);
+    }
+
+    #[tokio::test]
+    async fn test_save_listing_keeps_rows_of_an_empty_listing() {
+        let (summary, pruned) = save(&[]).await;
+        assert_eq!(summary, SyncSummary::default());
+        assert_eq!(pruned, None);
     }
 }
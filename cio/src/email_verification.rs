@@ -0,0 +1,242 @@
+#![allow(clippy::from_over_into)]
+//! Reminding the users that signed up with an email and password to verify their email.
+//!
+//! Users whose email is still not verified some days after signing up get the Auth0
+//! verification email again. Every reminder is recorded in the
+//! `email_verification_reminders` table, so a user gets at most one every
+//! `interval_days`, as set in the `verification_reminders` section of the auth config.
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable_sync::SyncSummary,
+    auth_config::{AuthConfig, IdentityProviderKind, VerificationReminderConfig},
+    auth_logins::{AuthUsers, NewAuthUser},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    identity::auth0_tenants,
+    schema::email_verification_reminders,
+};
+
+/// The login provider of users that signed up with an email and password. Users of social
+/// connections have their email verified, or not, by the provider.
+const DATABASE_LOGIN_PROVIDER: &str = "auth0";
+
+/// The reminders sent to a user to verify their email.
+#[db {
+    new_struct_name = "EmailVerificationReminder",
+    match_on = {
+        "user_id" = "String",
+        "tenant" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = email_verification_reminders)]
+pub struct NewEmailVerificationReminder {
+    pub user_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    /// The id of the Auth0 job that sent the last reminder.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub job_id: String,
+    #[serde(default)]
+    pub reminders_sent: i32,
+    pub last_sent_at: DateTime<Utc>,
+    /// The Auth0 tenant of the user.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tenant: String,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+/// Returns true if the user should be reminded to verify their email now.
+fn reminder_due(
+    user: &NewAuthUser,
+    last_sent_at: Option<DateTime<Utc>>,
+    config: &VerificationReminderConfig,
+    now: DateTime<Utc>,
+) -> bool {
+    if user.email_verified
+        || user.email.is_empty()
+        || user.deleted_at.is_some()
+        || user.login_provider != DATABASE_LOGIN_PROVIDER
+    {
+        return false;
+    }
+
+    if now - user.created_at < Duration::days(config.after_days) {
+        return false;
+    }
+
+    match last_sent_at {
+        Some(sent) => now - sent >= Duration::days(config.interval_days),
+        None => true,
+    }
+}
+
+/// Send the Auth0 verification email to the users of the company that have not verified
+/// their email yet, and record the reminders.
+///
+/// The users come from the database, so this should run after the auth users are synced.
+/// Each user is checked in Auth0 before they are reminded, in case they verified their
+/// email since.
+pub async fn send_verification_reminders(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let mut summary = SyncSummary::default();
+    if config.identity_provider != IdentityProviderKind::Auth0 {
+        info!(
+            "skipping verification reminders for company `{}`, users are in {}",
+            company.name, config.identity_provider
+        );
+        return Ok(summary);
+    }
+
+    let reminders: HashMap<(String, String), EmailVerificationReminder> =
+        EmailVerificationReminders::get_from_db(db, company.id)
+            .await
+            .map_err(CioError::Database)?
+            .into_iter()
+            .map(|r| ((r.user_id.to_string(), r.tenant.to_string()), r))
+            .collect();
+
+    let now = Utc::now();
+    let users: Vec<NewAuthUser> = AuthUsers::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let due: Vec<NewAuthUser> = users
+        .into_iter()
+        .filter(|u| {
+            let last_sent_at = reminders
+                .get(&(u.user_id.to_string(), u.tenant.to_string()))
+                .map(|r| r.last_sent_at);
+            reminder_due(u, last_sent_at, &config.verification_reminders, now)
+        })
+        .collect();
+    info!("{} auth users are due a verification reminder", due.len());
+    if due.is_empty() {
+        return Ok(summary);
+    }
+
+    for (name, tenant) in auth0_tenants(company, &config).await? {
+        let auth0 = match tenant.authenticate() {
+            Ok(auth0) => auth0,
+            Err(e) => {
+                error!("authenticating with auth0 tenant `{}` failed: {}", name, e);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        for user in due.iter().filter(|u| u.tenant == auth0.domain()) {
+            match auth0.get_user(&user.user_id).await {
+                Ok(current) if current.email_verified => continue,
+                Ok(_) => (),
+                Err(e) => {
+                    error!("getting auth0 user `{}` failed: {}", user.user_id, e);
+                    summary.errors += 1;
+                    continue;
+                }
+            }
+
+            if dry_run.is_enabled() {
+                info!("[dry-run] would send a verification reminder to `{}`", user.email);
+                summary.created += 1;
+                continue;
+            }
+
+            let job = match auth0.send_verification_email(&user.user_id).await {
+                Ok(job) => job,
+                Err(e) => {
+                    error!("sending a verification reminder to `{}` failed: {}", user.email, e);
+                    summary.errors += 1;
+                    continue;
+                }
+            };
+            info!("sent a verification reminder to `{}`", user.email);
+
+            let reminders_sent = reminders
+                .get(&(user.user_id.to_string(), user.tenant.to_string()))
+                .map(|r| r.reminders_sent)
+                .unwrap_or_default();
+            let reminder = NewEmailVerificationReminder {
+                user_id: user.user_id.to_string(),
+                email: user.email.to_string(),
+                job_id: job.id,
+                reminders_sent: reminders_sent + 1,
+                last_sent_at: Utc::now(),
+                tenant: user.tenant.to_string(),
+                cio_company_id: company.id,
+            };
+            if let Err(e) = reminder.upsert(db).await {
+                error!("saving the verification reminder of `{}` failed: {}", user.email, e);
+                summary.errors += 1;
+                continue;
+            }
+            summary.created += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_reminder_due() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 0, 0, 0).unwrap();
+        let config = VerificationReminderConfig::default();
+        let user: NewAuthUser = serde_json::from_value(serde_json::json!({
+            "user_id": "auth0|1",
+            "email": "jess@example.com",
+            "login_provider": "auth0",
+            "created_at": "2024-03-01T00:00:00Z",
+            "updated_at": "2024-03-01T00:00:00Z",
+            "last_login": "2024-03-01T00:00:00Z",
+            "logins_count": 1,
+        }))
+        .unwrap();
+
+        assert!(reminder_due(&user, None, &config, now));
+        // At most one reminder a week.
+        assert!(!reminder_due(&user, Some(now - Duration::days(6)), &config, now));
+        assert!(reminder_due(&user, Some(now - Duration::days(7)), &config, now));
+
+        // Not before `after_days`.
+        assert!(!reminder_due(&user, None, &config, user.created_at + Duration::days(2)));
+
+        let verified = NewAuthUser {
+            email_verified: true,
+            ..user.clone()
+        };
+        assert!(!reminder_due(&verified, None, &config, now));
+
+        let social = NewAuthUser {
+            login_provider: "github".to_string(),
+            ..user
+        };
+        assert!(!reminder_due(&social, None, &config, now));
+    }
+}
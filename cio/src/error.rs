@@ -0,0 +1,83 @@
+//! The errors returned by the sync jobs.
+//!
+//! Most of the crate still returns `anyhow::Result`, so `CioError` converts from and into
+//! `anyhow::Error`. The variants tell the jobs which system failed, so they can decide
+//! whether to skip a record or give up on the whole run.
+use thiserror::Error;
+
+use crate::auth0::Auth0Error;
+
+/// An error from one of the systems we sync between.
+#[derive(Debug, Error)]
+pub enum CioError {
+    /// Reading from or writing to the database failed.
+    #[error("database error: {0}")]
+    Database(anyhow::Error),
+    /// A request to Airtable failed.
+    #[error("airtable error: {0}")]
+    Airtable(anyhow::Error),
+    /// A request to Auth0 failed.
+    #[error("auth0 error: {0}")]
+    Auth0(anyhow::Error),
+    /// A request to the IP geolocation API failed.
+    #[error("geoip error: {0}")]
+    GeoIp(anyhow::Error),
+    /// A request to GitHub failed.
+    #[error("github error: {0}")]
+    GitHub(anyhow::Error),
+    /// A request to the GSuite Admin SDK failed.
+    #[error("gsuite error: {0}")]
+    GSuite(anyhow::Error),
+    /// A request to Google Analytics failed.
+    #[error("google analytics error: {0}")]
+    GoogleAnalytics(anyhow::Error),
+    /// A request to Google Sheets failed.
+    #[error("google sheets error: {0}")]
+    GoogleSheets(anyhow::Error),
+    /// A request to Gusto failed.
+    #[error("gusto error: {0}")]
+    Gusto(anyhow::Error),
+    /// A request to Mailchimp failed.
+    #[error("mailchimp error: {0}")]
+    Mailchimp(anyhow::Error),
+    /// A request to Okta failed.
+    #[error("okta error: {0}")]
+    Okta(anyhow::Error),
+    /// A request to Ramp failed.
+    #[error("ramp error: {0}")]
+    Ramp(anyhow::Error),
+    /// A request to the Slack Web API failed.
+    #[error("slack error: {0}")]
+    Slack(anyhow::Error),
+    /// A request to Zoom failed.
+    #[error("zoom error: {0}")]
+    Zoom(anyhow::Error),
+    /// The config is missing or invalid.
+    #[error("config error: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl From<Auth0Error> for CioError {
+    fn from(e: Auth0Error) -> Self {
+        CioError::Auth0(e.into())
+    }
+}
+
+impl CioError {
+    /// Returns the Auth0 error this was caused by, if any.
+    pub fn auth0_error(&self) -> Option<&Auth0Error> {
+        match self {
+            CioError::Auth0(e) | CioError::Other(e) => e.downcast_ref::<Auth0Error>(),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the error is Auth0 telling us the resource does not exist.
+    pub fn is_not_found(&self) -> bool {
+        self.auth0_error().map(|e| e.is_not_found()).unwrap_or(false)
+    }
+}
@@ -2,12 +2,12 @@ use std::{collections::HashMap, env, fs::File};
 
 use anyhow::{bail, Result};
 use async_bb8_diesel::AsyncRunQueryDsl;
-use async_trait::async_trait;
 use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
 use log::{info, warn};
 use macros::db;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "slack")]
 use slack_chat_api::{
     FormattedMessage, MessageAttachment, MessageBlock, MessageBlockText, MessageBlockType, MessageType,
 };
@@ -15,15 +15,20 @@ use slack_chat_api::{
 use crate::{
     airtable::{
         AIRTABLE_ACCOUNTS_PAYABLE_TABLE, AIRTABLE_CREDIT_CARD_TRANSACTIONS_TABLE, AIRTABLE_EXPENSED_ITEMS_TABLE,
-        AIRTABLE_SOFTWARE_VENDORS_TABLE,
+        AIRTABLE_RAMP_EXPENSES_TABLE, AIRTABLE_SOFTWARE_VENDORS_TABLE,
     },
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable_cached, AirtableCache, AirtableSyncable, ConflictPolicy, SyncSummary},
     app_config::FinanceConfig,
+    auth_config::AuthConfig,
+    auth_logins::people_records_by_email,
     companies::Company,
     configs::{Group, User},
-    core::UpdateAirtableRecord,
-    db::Database,
+    core::{DryRun, UpdateAirtableRecord},
+    db::{save_listing, Database},
+    error::CioError,
     providers::ProviderReadOps,
-    schema::{accounts_payables, credit_card_transactions, expensed_items, software_vendors, users},
+    schema::{accounts_payables, credit_card_transactions, expensed_items, ramp_expenses, software_vendors, users},
 };
 
 #[db {
@@ -84,6 +89,7 @@ fn is_zero(num: &f32) -> bool {
 }
 
 /// Convert the vendor into a Slack message.
+#[cfg(feature = "slack")]
 impl From<NewSoftwareVendor> for FormattedMessage {
     fn from(item: NewSoftwareVendor) -> Self {
         FormattedMessage {
@@ -134,6 +140,7 @@ impl From<NewSoftwareVendor> for FormattedMessage {
     }
 }
 
+#[cfg(feature = "slack")]
 impl From<SoftwareVendor> for FormattedMessage {
     fn from(item: SoftwareVendor) -> Self {
         let new: NewSoftwareVendor = item.into();
@@ -142,6 +149,7 @@ impl From<SoftwareVendor> for FormattedMessage {
 }
 
 impl NewSoftwareVendor {
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification_if_price_changed(
         &mut self,
         db: &Database,
@@ -385,6 +393,289 @@ pub async fn refresh_ramp_reimbursements(db: &Database, company: &Company, confi
     Ok(())
 }
 
+/// A card transaction or reimbursement in Ramp, with its receipts, for expense reports.
+#[db {
+    new_struct_name = "RampExpense",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_RAMP_EXPENSES_TABLE",
+    match_on = {
+        "ramp_id" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = ramp_expenses)]
+pub struct NewRampExpense {
+    /// The id of the transaction or reimbursement in Ramp.
+    pub ramp_id: String,
+    /// `card` for a transaction on a corporate card, or `reimbursement`.
+    pub kind: String,
+    pub amount: f32,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub currency: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub merchant_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub category_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub state: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub memo: String,
+    /// The email of the card holder, or of the person reimbursed.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub employee_email: String,
+    pub time: DateTime<Utc>,
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "airtable_api::attachment_format_as_array_of_strings::deserialize",
+        serialize_with = "airtable_api::attachment_format_as_array_of_strings::serialize"
+    )]
+    pub receipts: Vec<String>,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_people: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for RampExpense {
+    const DELETE_STALE: bool = true;
+    // The memo is written by finance in Airtable when reviewing the reports.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] = &[("memo", ConflictPolicy::Airtable)];
+
+    // The people live in the customer leads base, and links can't cross bases.
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewRampExpense) -> String {
+        fields.ramp_id.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewRampExpense) {
+        if field == "memo" {
+            self.memo = airtable.memo.to_string();
+        }
+    }
+}
+
+impl NewRampExpense {
+    /// Convert a transaction on a corporate card. Transactions Ramp has no time for yet are
+    /// still pending and skipped.
+    fn from_transaction(
+        transaction: ramp_minimal_api::Transaction,
+        company: &Company,
+        employee_email: String,
+        link_to_people: Vec<String>,
+        receipts: Vec<String>,
+    ) -> Option<Self> {
+        Some(NewRampExpense {
+            ramp_id: transaction.id,
+            kind: "card".to_string(),
+            amount: transaction.amount as f32,
+            currency: "USD".to_string(),
+            merchant_name: transaction.merchant_name,
+            category_name: transaction.sk_category_name,
+            state: transaction.state,
+            memo: transaction.memo.unwrap_or_default(),
+            employee_email,
+            time: transaction.user_transaction_time?,
+            receipts,
+            link_to_people,
+            cio_company_id: company.id,
+        })
+    }
+
+    /// Convert a reimbursement, which is timed by the day of the expense when it has one.
+    fn from_reimbursement(
+        reimbursement: ramp_minimal_api::Reimbursement,
+        company: &Company,
+        employee_email: String,
+        link_to_people: Vec<String>,
+        receipts: Vec<String>,
+    ) -> Option<Self> {
+        let time = reimbursement
+            .transaction_date
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|t| DateTime::<Utc>::from_utc(t, Utc))
+            .or(reimbursement.created_at)?;
+
+        Some(NewRampExpense {
+            ramp_id: reimbursement.id,
+            kind: "reimbursement".to_string(),
+            amount: reimbursement.amount as f32,
+            currency: reimbursement.currency,
+            merchant_name: reimbursement.merchant.unwrap_or_default(),
+            category_name: String::new(),
+            state: "REIMBURSED".to_string(),
+            memo: String::new(),
+            employee_email,
+            time,
+            receipts,
+            link_to_people,
+            cio_company_id: company.id,
+        })
+    }
+}
+
+/// Returns the key card holders are matched to Ramp users by, since transactions only
+/// carry the name of the card holder.
+fn card_holder_key(first_name: &str, last_name: &str) -> String {
+    format!("{} {}", first_name.trim(), last_name.trim()).to_lowercase()
+}
+
+/// Returns the URLs of the receipts of an expense. Receipts are only fetched when the
+/// expense has new ones, the URLs we already have are kept otherwise.
+async fn ramp_receipt_urls(
+    ramp: &ramp_minimal_api::RampClient,
+    receipt_ids: &[String],
+    existing: Option<&Vec<String>>,
+) -> Result<Vec<String>, CioError> {
+    if let Some(existing) = existing {
+        if existing.len() == receipt_ids.len() {
+            return Ok(existing.clone());
+        }
+    }
+
+    let mut urls = Vec::new();
+    for receipt_id in receipt_ids {
+        let receipt = ramp
+            .receipts()
+            .get(receipt_id)
+            .await
+            .map_err(|e| CioError::Ramp(e.into()))?;
+        urls.push(receipt.receipt_url);
+    }
+
+    Ok(urls)
+}
+
+/// Sync the card transactions and reimbursements in Ramp, with their receipts, with our
+/// database and Airtable. Each expense is linked to the person with the email of the card
+/// holder.
+pub async fn refresh_ramp_expenses(db: &Database, company: &Company, dry_run: DryRun) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let ramp = company
+        .authenticate_ramp()
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let users = ramp.list_provider_users(company).await.map_err(CioError::Ramp)?;
+    let emails_by_id: HashMap<String, String> = users.iter().map(|u| (u.id.to_string(), u.email.to_string())).collect();
+    let emails_by_name: HashMap<String, String> = users
+        .iter()
+        .map(|u| (card_holder_key(&u.first_name, &u.last_name), u.email.to_string()))
+        .collect();
+
+    let cache = AirtableCache::default();
+    let people = people_records_by_email(company, &config, &cache).await?;
+    let link_to_people = |email: &str| {
+        people
+            .get(&config.canonical_email(email))
+            .map(|p| vec![p.to_string()])
+            .unwrap_or_default()
+    };
+
+    let existing: HashMap<String, Vec<String>> = RampExpenses::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .into_iter()
+        .map(|e| (e.ramp_id.to_string(), e.receipts.clone()))
+        .collect();
+
+    let mut expenses: Vec<NewRampExpense> = Default::default();
+
+    let mut page = ramp
+        .transactions()
+        .list(&ramp_minimal_api::ListTransactionsQuery::default())
+        .await
+        .map_err(|e| CioError::Ramp(e.into()))?;
+    loop {
+        let next = ramp.next_page(&page).await.map_err(|e| CioError::Ramp(e.into()))?;
+        for transaction in page.data {
+            let key = card_holder_key(&transaction.card_holder.first_name, &transaction.card_holder.last_name);
+            let email = emails_by_name.get(&key).cloned().unwrap_or_else(|| {
+                warn!(
+                    "no ramp user for card holder `{}` of transaction `{}`",
+                    key, transaction.id
+                );
+                String::new()
+            });
+            let receipts = ramp_receipt_urls(&ramp, &transaction.receipts, existing.get(&transaction.id)).await?;
+            let linked = link_to_people(&email);
+
+            expenses.extend(NewRampExpense::from_transaction(
+                transaction,
+                company,
+                email,
+                linked,
+                receipts,
+            ));
+        }
+
+        match next {
+            Some(next) => page = next,
+            None => break,
+        }
+    }
+
+    let mut page = ramp
+        .reimbursements()
+        .list()
+        .await
+        .map_err(|e| CioError::Ramp(e.into()))?;
+    loop {
+        let next = ramp.next_page(&page).await.map_err(|e| CioError::Ramp(e.into()))?;
+        for reimbursement in page.data {
+            let email = emails_by_id.get(&reimbursement.user_id).cloned().unwrap_or_default();
+            let receipts = ramp_receipt_urls(&ramp, &reimbursement.receipts, existing.get(&reimbursement.id)).await?;
+            let linked = link_to_people(&email);
+
+            expenses.extend(NewRampExpense::from_reimbursement(
+                reimbursement,
+                company,
+                email,
+                linked,
+                receipts,
+            ));
+        }
+
+        match next {
+            Some(next) => page = next,
+            None => break,
+        }
+    }
+
+    info!("syncing {} ramp expenses", expenses.len());
+
+    let mut summary = if dry_run.is_enabled() {
+        info!("[dry-run] would save {} ramp expenses", expenses.len());
+        SyncSummary::default()
+    } else {
+        save_listing(
+            "ramp expense",
+            &expenses,
+            |e| e.ramp_id.to_string(),
+            |e| e.upsert(db),
+            |ids| async move {
+                Ok(diesel::delete(
+                    ramp_expenses::dsl::ramp_expenses
+                        .filter(ramp_expenses::dsl::cio_company_id.eq(company.id))
+                        .filter(ramp_expenses::dsl::ramp_id.ne_all(ids)),
+                )
+                .execute_async(db.pool())
+                .await?)
+            },
+        )
+        .await?
+    };
+
+    summary += sync_to_airtable_cached::<RampExpense>(db, company, &cache, dry_run).await?;
+
+    Ok(summary)
+}
+
 // Changes the vendor name to one that matches our existing list.
 fn clean_vendor_name(vendor_name: &str, config: &FinanceConfig) -> String {
     if let Some(alias) = config.vendor_aliases.get(vendor_name) {
@@ -919,3 +1210,84 @@ pub async fn refresh_all_finance(db: &Database, company: &Company, config: &Fina
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    #[test]
+    fn test_ramp_expense_from_transaction() {
+        let transaction: ramp_minimal_api::Transaction = serde_json::from_value(serde_json::json!({
+            "accounting_categories": [],
+            "amount": 12.5,
+            "card_holder": {
+                "department_id": "d1",
+                "department_name": "Engineering",
+                "first_name": " Jess",
+                "last_name": "Doe ",
+                "location_id": "l1",
+                "location_name": "Emeryville",
+            },
+            "card_id": "c1",
+            "disputes": [],
+            "id": "t1",
+            "memo": "Team lunch",
+            "merchant_id": "m1",
+            "merchant_name": "Burrito Shop",
+            "policy_violations": [],
+            "receipts": ["r1"],
+            "sk_category_id": 3.0,
+            "sk_category_name": "Meals",
+            "state": "CLEARED",
+            "user_transaction_time": "2024-01-02T03:04:05Z",
+        }))
+        .unwrap();
+        assert_eq!(
+            card_holder_key(&transaction.card_holder.first_name, &transaction.card_holder.last_name),
+            card_holder_key("jess", "doe")
+        );
+
+        let mut pending = transaction.clone();
+        pending.user_transaction_time = None;
+        assert!(NewRampExpense::from_transaction(pending, &mock_company(), String::new(), vec![], vec![]).is_none());
+
+        let expense = NewRampExpense::from_transaction(
+            transaction,
+            &mock_company(),
+            "jess@example.com".to_string(),
+            vec!["recPerson".to_string()],
+            vec!["https://receipts.example.com/r1".to_string()],
+        )
+        .unwrap();
+        assert_eq!(expense.ramp_id, "t1");
+        assert_eq!(expense.kind, "card");
+        assert_eq!(expense.amount, 12.5);
+        assert_eq!(expense.memo, "Team lunch");
+        assert_eq!(expense.employee_email, "jess@example.com");
+        assert_eq!(expense.link_to_people, vec!["recPerson"]);
+        assert_eq!(expense.receipts.len(), 1);
+    }
+
+    #[test]
+    fn test_ramp_expense_from_reimbursement() {
+        let reimbursement: ramp_minimal_api::Reimbursement = serde_json::from_value(serde_json::json!({
+            "amount": 40.0,
+            "created_at": "2024-01-05T10:00:00Z",
+            "currency": "USD",
+            "id": "re1",
+            "merchant": null,
+            "receipts": [],
+            "transaction_date": "2024-01-03",
+            "user_id": "u1",
+        }))
+        .unwrap();
+
+        let expense =
+            NewRampExpense::from_reimbursement(reimbursement, &mock_company(), String::new(), vec![], vec![]).unwrap();
+        assert_eq!(expense.kind, "reimbursement");
+        assert_eq!(expense.merchant_name, "");
+        // The day of the expense is used over the day it was filed.
+        assert_eq!(expense.time.to_rfc3339(), "2024-01-03T00:00:00+00:00");
+    }
+}
@@ -0,0 +1,255 @@
+#![allow(clippy::from_over_into)]
+//! The locations of the IP addresses our users log in from, so the people reading the Auth
+//! Users and Auth User Logins tables see a country and city instead of a raw address.
+//!
+//! Addresses are looked up with the ipinfo.io batch API, set up in the `geoip` section of
+//! the auth config. Every address looked up is kept in the `ip_locations` table, so it is
+//! only looked up once, however many users and logins share it.
+use std::{collections::HashMap, net::IpAddr};
+
+use anyhow::{bail, Result};
+use async_bb8_diesel::AsyncRunQueryDsl;
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
+use log::{error, info};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable_sync::SyncSummary,
+    auth_config::GeoIpConfig,
+    auth_logins::{AuthUserLogin, AuthUsers},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    schema::{auth_user_logins, ip_locations},
+};
+
+/// The number of addresses to look up in a single request.
+const LOOKUP_BATCH_SIZE: usize = 100;
+
+/// The location of an IP address.
+#[db {
+    new_struct_name = "IpLocation",
+    match_on = {
+        "ip" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = ip_locations)]
+pub struct NewIpLocation {
+    pub ip: String,
+    /// The ISO 3166 code of the country. Empty for private addresses.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub country: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub region: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub city: String,
+    pub resolved_at: DateTime<Utc>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+/// The location of an address in a response of the ipinfo.io batch API.
+#[derive(Debug, Default, Deserialize)]
+struct IpInfo {
+    #[serde(default)]
+    country: String,
+    #[serde(default)]
+    region: String,
+    #[serde(default)]
+    city: String,
+}
+
+/// Returns the locations of the addresses in a response of the ipinfo.io batch API, by
+/// address. Addresses the API has no location for, like private ones, have an empty one.
+fn parse_batch(response: serde_json::Value, company_id: i32, now: DateTime<Utc>) -> Vec<NewIpLocation> {
+    let entries = match response {
+        serde_json::Value::Object(entries) => entries,
+        _ => return Default::default(),
+    };
+
+    entries
+        .into_iter()
+        .map(|(ip, info)| {
+            let info: IpInfo = serde_json::from_value(info).unwrap_or_default();
+            NewIpLocation {
+                ip,
+                country: info.country,
+                region: info.region,
+                city: info.city,
+                resolved_at: now,
+                cio_company_id: company_id,
+            }
+        })
+        .collect()
+}
+
+/// Look up the locations of a batch of addresses.
+async fn lookup(
+    client: &reqwest::Client,
+    config: &GeoIpConfig,
+    ips: &[&str],
+    company: &Company,
+) -> Result<Vec<NewIpLocation>> {
+    let resp = client
+        .post(format!("{}/batch", config.endpoint.trim_end_matches('/')))
+        .query(&[("token", &config.token)])
+        .json(ips)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        bail!("status code: {}, body: {}", resp.status(), resp.text().await?);
+    }
+
+    Ok(parse_batch(resp.json().await?, company.id, Utc::now()))
+}
+
+/// Look up the locations of the addresses of the auth users and logins of the company that
+/// we don't have yet, and fill them in on the users and logins.
+///
+/// The location of a user follows their `last_ip`, so it is updated when they log in from
+/// somewhere else. The users that changed are pushed to Airtable on the next sync.
+pub async fn refresh_ip_locations(
+    db: &Database,
+    company: &Company,
+    config: &GeoIpConfig,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let mut summary = SyncSummary::default();
+    if config.token.is_empty() {
+        info!("skipping ip locations for company `{}`, no geoip token", company.name);
+        return Ok(summary);
+    }
+
+    let mut locations: HashMap<String, NewIpLocation> = IpLocations::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .into_iter()
+        .map(|l| (l.ip.to_string(), l.into()))
+        .collect();
+
+    let mut auth_users = AuthUsers::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .0;
+    let mut logins: Vec<AuthUserLogin> = auth_user_logins::dsl::auth_user_logins
+        .filter(auth_user_logins::dsl::cio_company_id.eq(company.id))
+        .filter(auth_user_logins::dsl::ip.ne(""))
+        .filter(auth_user_logins::dsl::country.eq(""))
+        .load_async(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+
+    // Only look up valid addresses, once each.
+    let mut missing: Vec<&str> = auth_users
+        .iter()
+        .map(|u| u.last_ip.as_str())
+        .chain(logins.iter().map(|l| l.ip.as_str()))
+        .filter(|ip| !locations.contains_key(*ip) && ip.parse::<IpAddr>().is_ok())
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    info!("looking up the locations of {} ip addresses", missing.len());
+
+    let client = reqwest::Client::new();
+    let mut found: Vec<NewIpLocation> = Default::default();
+    for chunk in missing.chunks(LOOKUP_BATCH_SIZE) {
+        found.extend(lookup(&client, config, chunk, company).await.map_err(CioError::GeoIp)?);
+    }
+
+    for location in found {
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would save the location of `{}`: {} {}",
+                location.ip, location.country, location.city
+            );
+        } else if let Err(e) = location.upsert(db).await {
+            error!("saving the location of `{}` failed: {}", location.ip, e);
+            summary.errors += 1;
+        }
+        locations.insert(location.ip.to_string(), location);
+    }
+
+    for auth_user in auth_users.iter_mut() {
+        let (country, city) = match locations.get(&auth_user.last_ip) {
+            Some(l) => (l.country.as_str(), l.city.as_str()),
+            None => ("", ""),
+        };
+        if auth_user.last_ip_country == country && auth_user.last_ip_city == city {
+            continue;
+        }
+
+        auth_user.last_ip_country = country.to_string();
+        auth_user.last_ip_city = city.to_string();
+        summary.updated += 1;
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would set the location of auth user `{}` to {} {}",
+                auth_user.user_id, country, city
+            );
+        } else if let Err(e) = auth_user.update(db).await {
+            error!("saving the location of auth user `{}` failed: {}", auth_user.user_id, e);
+            summary.errors += 1;
+        }
+    }
+
+    for login in logins.iter_mut() {
+        let location = match locations.get(&login.ip) {
+            Some(l) if !l.country.is_empty() => l,
+            _ => continue,
+        };
+
+        login.country = location.country.to_string();
+        login.city = location.city.to_string();
+        summary.updated += 1;
+        if dry_run.is_enabled() {
+            info!(
+                "[dry-run] would set the location of login `{}` to {} {}",
+                login.log_id, login.country, login.city
+            );
+        } else if let Err(e) = login.update(db).await {
+            error!("saving the location of login `{}` failed: {}", login.log_id, e);
+            summary.errors += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_batch() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let response = serde_json::json!({
+            "8.8.8.8": {
+                "ip": "8.8.8.8",
+                "city": "Mountain View",
+                "region": "California",
+                "country": "US",
+            },
+            "10.0.0.1": { "ip": "10.0.0.1", "bogon": true },
+        });
+
+        let mut locations = parse_batch(response, 1, now);
+        locations.sort_by(|a, b| a.ip.cmp(&b.ip));
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[0].ip, "10.0.0.1");
+        assert_eq!(locations[0].country, "");
+        assert_eq!(locations[1].country, "US");
+        assert_eq!(locations[1].city, "Mountain View");
+        assert_eq!(locations[1].resolved_at, now);
+
+        assert!(parse_batch(serde_json::json!("error"), 1, now).is_empty());
+    }
+}
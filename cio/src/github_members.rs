@@ -0,0 +1,244 @@
+#![allow(clippy::from_over_into)]
+//! A mirror of the people with access to the GitHub organization, for security audits.
+//!
+//! The members and outside collaborators of the organization are pulled along with the
+//! teams they are on, and synced to Airtable. Each one is linked to the auth users that
+//! log in with the same GitHub account.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::Result;
+use async_bb8_diesel::AsyncRunQueryDsl;
+use log::info;
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable::AIRTABLE_GITHUB_MEMBERS_TABLE,
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable, AirtableSyncable, SyncSummary},
+    auth_logins::AuthUsers,
+    companies::Company,
+    core::DryRun,
+    db::{save_listing, Database},
+    error::CioError,
+    schema::github_org_members,
+};
+
+/// The role of a member of the organization.
+pub const ROLE_ADMIN: &str = "admin";
+pub const ROLE_MEMBER: &str = "member";
+pub const ROLE_OUTSIDE_COLLABORATOR: &str = "outside_collaborator";
+
+/// A person with access to the GitHub organization.
+#[db {
+    new_struct_name = "GitHubOrgMember",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_GITHUB_MEMBERS_TABLE",
+    match_on = {
+        "login" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = github_org_members)]
+pub struct NewGitHubOrgMember {
+    pub login: String,
+    /// The id of the account in GitHub.
+    pub github_id: i64,
+    /// `admin`, `member` or `outside_collaborator`.
+    pub role: String,
+    /// The names of the teams the member is on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<String>,
+    #[serde(default)]
+    pub site_admin: bool,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_auth_users: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for GitHubOrgMember {
+    const DELETE_STALE: bool = true;
+
+    // The auth users live in the customer leads base, and links can't cross bases.
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewGitHubOrgMember) -> String {
+        fields.login.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewGitHubOrgMember) {}
+}
+
+/// Sync the members, outside collaborators and team memberships of the GitHub organization
+/// with our database and Airtable.
+///
+/// People who lost access to the organization are removed from the database too, we only
+/// mirror who has access now.
+pub async fn refresh_github_members(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let github = company
+        .authenticate_github()
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let org = &company.github_org;
+
+    let admins: BTreeSet<String> = github
+        .orgs()
+        .list_all_members(
+            org,
+            octorust::types::OrgsListMembersFilter::All,
+            octorust::types::OrgsListMembersRole::Admin,
+        )
+        .await
+        .map_err(|e| CioError::GitHub(e.into()))?
+        .body
+        .into_iter()
+        .map(|u| u.login)
+        .collect();
+    let members = github
+        .orgs()
+        .list_all_members(
+            org,
+            octorust::types::OrgsListMembersFilter::All,
+            octorust::types::OrgsListMembersRole::All,
+        )
+        .await
+        .map_err(|e| CioError::GitHub(e.into()))?
+        .body;
+    let collaborators = github
+        .orgs()
+        .list_all_outside_collaborators(org, octorust::types::OrgsListOutsideCollaboratorsFilter::All)
+        .await
+        .map_err(|e| CioError::GitHub(e.into()))?
+        .body;
+
+    // The teams of each member, by login.
+    let mut teams_by_login: BTreeMap<String, BTreeSet<String>> = Default::default();
+    let teams = github
+        .teams()
+        .list_all(org)
+        .await
+        .map_err(|e| CioError::GitHub(e.into()))?
+        .body;
+    for team in teams {
+        let team_members = github
+            .teams()
+            .list_all_members_in_org(org, &team.slug, octorust::types::TeamsListMembersInOrgRole::All)
+            .await
+            .map_err(|e| CioError::GitHub(e.into()))?
+            .body;
+        for member in team_members {
+            teams_by_login
+                .entry(member.login)
+                .or_default()
+                .insert(team.name.to_string());
+        }
+    }
+    info!(
+        "syncing {} members and {} outside collaborators of github org `{}`",
+        members.len(),
+        collaborators.len(),
+        org
+    );
+
+    let auth_users = auth_user_records_by_github_id(db, company)
+        .await
+        .map_err(CioError::Database)?;
+    let new_members: Vec<NewGitHubOrgMember> = members
+        .into_iter()
+        .map(|u| (u, false))
+        .chain(collaborators.into_iter().map(|u| (u, true)))
+        .map(|(user, outside)| {
+            let role = if outside {
+                ROLE_OUTSIDE_COLLABORATOR
+            } else if admins.contains(&user.login) {
+                ROLE_ADMIN
+            } else {
+                ROLE_MEMBER
+            };
+
+            NewGitHubOrgMember {
+                teams: teams_by_login
+                    .get(&user.login)
+                    .map(|t| t.iter().cloned().collect())
+                    .unwrap_or_default(),
+                link_to_auth_users: auth_users.get(&user.id).cloned().unwrap_or_default(),
+                login: user.login,
+                github_id: user.id,
+                role: role.to_string(),
+                site_admin: user.site_admin,
+                cio_company_id: company.id,
+            }
+        })
+        .collect();
+
+    let mut summary = if dry_run.is_enabled() {
+        info!("[dry-run] would save {} github org members", new_members.len());
+        SyncSummary::default()
+    } else {
+        save_listing(
+            "github org member",
+            &new_members,
+            |m| m.login.to_string(),
+            |m| m.upsert(db),
+            |logins| async move {
+                Ok(diesel::delete(
+                    github_org_members::dsl::github_org_members
+                        .filter(github_org_members::dsl::cio_company_id.eq(company.id))
+                        .filter(github_org_members::dsl::login.ne_all(logins)),
+                )
+                .execute_async(db.pool())
+                .await?)
+            },
+        )
+        .await?
+    };
+
+    summary += sync_to_airtable::<GitHubOrgMember>(db, company, dry_run).await?;
+
+    Ok(summary)
+}
+
+/// Returns the Airtable record ids of the auth users that log in with GitHub, by the id of
+/// their GitHub account.
+async fn auth_user_records_by_github_id(db: &Database, company: &Company) -> Result<HashMap<i64, Vec<String>>> {
+    let mut records: HashMap<i64, Vec<String>> = Default::default();
+    for auth_user in AuthUsers::get_from_db(db, company.id).await? {
+        if auth_user.airtable_record_id.is_empty() {
+            continue;
+        }
+
+        if let Some(id) = github_id(&auth_user.user_id) {
+            records
+                .entry(id)
+                .or_default()
+                .push(auth_user.airtable_record_id.to_string());
+        }
+    }
+
+    Ok(records)
+}
+
+/// Returns the id of the GitHub account of an Auth0 user id, like `github|1234`.
+fn github_id(auth0_user_id: &str) -> Option<i64> {
+    auth0_user_id.strip_prefix("github|")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_id() {
+        assert_eq!(github_id("github|1234"), Some(1234));
+        assert_eq!(github_id("google-oauth2|1234"), None);
+        assert_eq!(github_id("github|not-a-number"), None);
+    }
+}
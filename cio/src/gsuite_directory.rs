@@ -0,0 +1,298 @@
+#![allow(clippy::from_over_into)]
+//! A mirror of the users and groups in the GSuite directory, so we can track employee
+//! accounts next to the Auth0 users they log into our apps with.
+//!
+//! The users and groups are pulled from the Admin SDK Directory API, along with their
+//! aliases and group memberships, and synced to Airtable. Each user is linked to the auth
+//! users with the same email, or one of its aliases.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::Result;
+use async_bb8_diesel::AsyncRunQueryDsl;
+use chrono::{DateTime, Utc};
+use log::info;
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable::{AIRTABLE_GSUITE_GROUPS_TABLE, AIRTABLE_GSUITE_USERS_TABLE},
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable, AirtableSyncable, SyncSummary},
+    auth_config::AuthConfig,
+    auth_logins::auth_user_records_by_email,
+    companies::Company,
+    core::DryRun,
+    db::{save_listing, Database},
+    error::CioError,
+    providers::ProviderReadOps,
+    schema::{gsuite_directory_groups, gsuite_directory_users},
+};
+
+/// A user in the GSuite directory.
+#[db {
+    new_struct_name = "GSuiteDirectoryUser",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_GSUITE_USERS_TABLE",
+    match_on = {
+        "primary_email" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = gsuite_directory_users)]
+pub struct NewGSuiteDirectoryUser {
+    /// The id of the user in GSuite.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub gsuite_id: String,
+    pub primary_email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub full_name: String,
+    /// The other addresses the user receives mail at.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub org_unit_path: String,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub suspended: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_login_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// The emails of the groups the user is a member of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_auth_users: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for GSuiteDirectoryUser {
+    const DELETE_STALE: bool = true;
+
+    // The auth users live in the customer leads base, and links can't cross bases.
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewGSuiteDirectoryUser) -> String {
+        fields.primary_email.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewGSuiteDirectoryUser) {}
+}
+
+/// A group in the GSuite directory.
+#[db {
+    new_struct_name = "GSuiteDirectoryGroup",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_GSUITE_GROUPS_TABLE",
+    match_on = {
+        "email" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = gsuite_directory_groups)]
+pub struct NewGSuiteDirectoryGroup {
+    /// The id of the group in GSuite.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub gsuite_id: String,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// The other addresses the group receives mail at.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// The emails of the members of the group.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for GSuiteDirectoryGroup {
+    const DELETE_STALE: bool = true;
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewGSuiteDirectoryGroup) -> String {
+        fields.email.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewGSuiteDirectoryGroup) {}
+}
+
+/// Sync the users and groups in the GSuite directory with our database and Airtable.
+///
+/// Users and groups that were removed from GSuite are removed from the database too, we
+/// only mirror the directory.
+pub async fn refresh_gsuite_directory(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let gsuite = company
+        .authenticate_google_admin(db)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let users = gsuite.list_provider_users(company).await.map_err(CioError::GSuite)?;
+    let groups = gsuite.list_provider_groups(company).await.map_err(CioError::GSuite)?;
+    info!(
+        "syncing {} users and {} groups from the gsuite directory",
+        users.len(),
+        groups.len()
+    );
+
+    // The groups of each user, by their email.
+    let mut groups_by_member: BTreeMap<String, BTreeSet<String>> = Default::default();
+    let mut new_groups: Vec<NewGSuiteDirectoryGroup> = Default::default();
+    for group in groups {
+        let members = gsuite
+            .members()
+            .list_all(&group.email, false, "")
+            .await
+            .map(|response| response.body)
+            .map_err(|e| CioError::GSuite(e.into()))?;
+        let members: Vec<String> = members.into_iter().map(|m| m.email.to_lowercase()).collect();
+        for member in members.iter() {
+            groups_by_member
+                .entry(member.to_string())
+                .or_default()
+                .insert(group.email.to_string());
+        }
+
+        new_groups.push(NewGSuiteDirectoryGroup {
+            gsuite_id: group.id.to_string(),
+            email: group.email.to_string(),
+            name: group.name.to_string(),
+            description: group.description.to_string(),
+            aliases: group.aliases.clone(),
+            members,
+            cio_company_id: company.id,
+        });
+    }
+
+    let auth_users = auth_user_records_by_email(db, company, &config)
+        .await
+        .map_err(CioError::Database)?;
+    let new_users: Vec<NewGSuiteDirectoryUser> = users
+        .iter()
+        .map(|user| {
+            let emails: Vec<&str> = std::iter::once(user.primary_email.as_str())
+                .chain(user.aliases.iter().map(|a| a.as_str()))
+                .collect();
+
+            NewGSuiteDirectoryUser {
+                gsuite_id: user.id.to_string(),
+                primary_email: user.primary_email.to_string(),
+                full_name: user.name.as_ref().map(|n| n.full_name.to_string()).unwrap_or_default(),
+                aliases: user.aliases.clone(),
+                org_unit_path: user.org_unit_path.to_string(),
+                is_admin: user.is_admin,
+                suspended: user.suspended,
+                last_login_at: user.last_login_time,
+                created_at: user.creation_time,
+                groups: groups_by_member
+                    .get(&user.primary_email.to_lowercase())
+                    .map(|g| g.iter().cloned().collect())
+                    .unwrap_or_default(),
+                link_to_auth_users: links_by_email(&emails, &config, &auth_users),
+                cio_company_id: company.id,
+            }
+        })
+        .collect();
+
+    let mut summary = SyncSummary::default();
+    if dry_run.is_enabled() {
+        info!(
+            "[dry-run] would save {} gsuite users and {} gsuite groups",
+            new_users.len(),
+            new_groups.len()
+        );
+    } else {
+        summary += save_listing(
+            "gsuite user",
+            &new_users,
+            |u| u.primary_email.to_string(),
+            |u| u.upsert(db),
+            |emails| async move {
+                Ok(diesel::delete(
+                    gsuite_directory_users::dsl::gsuite_directory_users
+                        .filter(gsuite_directory_users::dsl::cio_company_id.eq(company.id))
+                        .filter(gsuite_directory_users::dsl::primary_email.ne_all(emails)),
+                )
+                .execute_async(db.pool())
+                .await?)
+            },
+        )
+        .await?;
+        summary += save_listing(
+            "gsuite group",
+            &new_groups,
+            |g| g.email.to_string(),
+            |g| g.upsert(db),
+            |emails| async move {
+                Ok(diesel::delete(
+                    gsuite_directory_groups::dsl::gsuite_directory_groups
+                        .filter(gsuite_directory_groups::dsl::cio_company_id.eq(company.id))
+                        .filter(gsuite_directory_groups::dsl::email.ne_all(emails)),
+                )
+                .execute_async(db.pool())
+                .await?)
+            },
+        )
+        .await?;
+    }
+
+    summary += sync_to_airtable::<GSuiteDirectoryUser>(db, company, dry_run).await?;
+    summary += sync_to_airtable::<GSuiteDirectoryGroup>(db, company, dry_run).await?;
+
+    Ok(summary)
+}
+
+/// Returns the Airtable records to link to for any of the emails, without duplicates.
+fn links_by_email(emails: &[&str], config: &AuthConfig, records: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let links: BTreeSet<&String> = emails
+        .iter()
+        .filter_map(|email| records.get(&config.canonical_email(email)))
+        .flatten()
+        .collect();
+
+    links.into_iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_by_email() {
+        let config: AuthConfig = toml::from_str(r#"domains = ["oxidecomputer.com", "oxide.computer"]"#).unwrap();
+        let records: HashMap<String, Vec<String>> = [
+            ("jess@oxidecomputer.com".to_string(), vec!["rec1".to_string()]),
+            (
+                "j@oxidecomputer.com".to_string(),
+                vec!["rec1".to_string(), "rec2".to_string()],
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            links_by_email(&["Jess@oxide.computer", "j@oxidecomputer.com"], &config, &records),
+            vec!["rec1", "rec2"]
+        );
+        assert!(links_by_email(&["sam@example.com"], &config, &records).is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+#![allow(clippy::from_over_into)]
+//! A mirror of the employees in Gusto, our HR system.
+//!
+//! Gusto is the source of truth for the start date, title and department of everyone we
+//! employ, so those are filled in on their record in the People table. Each employee is
+//! matched to a user in the config by their Gusto id, which gives us their company email
+//! next to the personal email they signed up to Gusto with. The auth users that log in
+//! with the personal email are linked to the right person, where matching on email alone
+//! would miss them.
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_bb8_diesel::AsyncRunQueryDsl;
+use chrono::NaiveDate;
+use log::info;
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable::AIRTABLE_PEOPLE_TABLE,
+    airtable_bases::{AirtableBase, BaseRegistry},
+    airtable_sync::{AirtableCache, SyncSummary},
+    auth_config::AuthConfig,
+    companies::Company,
+    configs::Users,
+    core::DryRun,
+    db::{save_listing, Database},
+    error::CioError,
+    schema::gusto_employees,
+};
+
+/// An employee in Gusto.
+#[db {
+    new_struct_name = "GustoEmployee",
+    match_on = {
+        "gusto_id" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = gusto_employees)]
+pub struct NewGustoEmployee {
+    /// The id of the employee in Gusto.
+    pub gusto_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub first_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_name: String,
+    /// The personal email the employee signed up to Gusto with.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    /// The company email of the user with the same Gusto id in the config.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub work_email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub department: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub terminated: bool,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_people: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl NewGustoEmployee {
+    /// Convert an employee from Gusto. The title and start date come from their first job.
+    fn from_gusto(
+        employee: gusto_api::types::Employee,
+        company: &Company,
+        work_email: String,
+        link_to_people: Vec<String>,
+    ) -> Self {
+        let job = employee.jobs.first();
+
+        NewGustoEmployee {
+            gusto_id: employee.id.to_string(),
+            first_name: employee.first_name.to_string(),
+            last_name: employee.last_name.to_string(),
+            email: employee.email.to_lowercase(),
+            work_email,
+            title: job.map(|j| j.title.to_string()).unwrap_or_default(),
+            department: employee.department.to_string(),
+            start_date: job.and_then(|j| j.hire_date),
+            terminated: employee.terminated,
+            link_to_people,
+            cio_company_id: company.id,
+        }
+    }
+
+    /// Returns the fields to set on the record of the employee in the People table, or
+    /// `None` if the record is up to date. Fields Gusto has no value for are left alone.
+    fn people_fields(&self, person: &serde_json::Value) -> Option<serde_json::Value> {
+        let mut fields = serde_json::Map::new();
+        let start_date = self
+            .start_date
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        for (name, value) in [
+            ("Title", &self.title),
+            ("Department", &self.department),
+            ("Start Date", &start_date),
+        ] {
+            if !value.is_empty() && person.get(name).and_then(|v| v.as_str()) != Some(value.as_str()) {
+                fields.insert(name.to_string(), serde_json::Value::String(value.to_string()));
+            }
+        }
+
+        (!fields.is_empty()).then(|| serde_json::Value::Object(fields))
+    }
+}
+
+/// Sync the employees in Gusto with our database, and fill in their start date, title and
+/// department on their record in the People table in Airtable.
+pub async fn refresh_gusto_employees(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let (gusto, gusto_company_id) = company
+        .authenticate_gusto(db)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let employees = gusto
+        .employees()
+        .get_all_company(&gusto_company_id, false, &[])
+        .await
+        .map_err(|e| CioError::Gusto(e.into()))?
+        .body;
+    info!("syncing {} gusto employees", employees.len());
+
+    // The company email of each user, by their Gusto id.
+    let work_emails: HashMap<String, String> = Users::get_from_db(db, company.id)
+        .await
+        .map_err(CioError::Database)?
+        .into_iter()
+        .filter(|u| !u.gusto_id.is_empty())
+        .map(|u| (u.gusto_id.to_string(), u.email.to_string()))
+        .collect();
+
+    let bases = BaseRegistry::from_env().map_err(|e| CioError::Config(e.to_string()))?;
+    let airtable = bases.authenticate(AirtableBase::CustomerLeads, company);
+    let cache = AirtableCache::default();
+    let people = cache
+        .list(
+            &airtable,
+            &bases.base_id(AirtableBase::CustomerLeads, company),
+            AIRTABLE_PEOPLE_TABLE,
+        )
+        .await
+        .map_err(CioError::Airtable)?;
+    let people_by_email: HashMap<String, &airtable_api::Record<serde_json::Value>> = people
+        .iter()
+        .filter_map(|p| {
+            let email = p.fields.get("Email")?.as_str()?;
+            (!email.is_empty()).then(|| (config.canonical_email(email), p))
+        })
+        .collect();
+
+    let mut summary = SyncSummary::default();
+    let mut records: Vec<airtable_api::Record<serde_json::Value>> = Default::default();
+    let mut new_employees: Vec<NewGustoEmployee> = Default::default();
+    for employee in employees {
+        let work_email = work_emails.get(&employee.id).cloned().unwrap_or_default();
+        let person = [&work_email, &employee.email]
+            .iter()
+            .filter(|e| !e.is_empty())
+            .find_map(|e| people_by_email.get(&config.canonical_email(e)));
+        let link_to_people = person.map(|p| vec![p.id.to_string()]).unwrap_or_default();
+
+        let new_employee = NewGustoEmployee::from_gusto(employee, company, work_email, link_to_people);
+        if let Some(person) = person {
+            if let Some(fields) = new_employee.people_fields(&person.fields) {
+                records.push(airtable_api::Record {
+                    id: person.id.to_string(),
+                    fields,
+                    created_time: None,
+                });
+            }
+        }
+        new_employees.push(new_employee);
+    }
+
+    if dry_run.is_enabled() {
+        info!(
+            "[dry-run] would save {} gusto employees and update {} people",
+            new_employees.len(),
+            records.len()
+        );
+        return Ok(summary);
+    }
+
+    summary += save_listing(
+        "gusto employee",
+        &new_employees,
+        |e| e.gusto_id.to_string(),
+        |e| e.upsert(db),
+        |ids| async move {
+            Ok(diesel::delete(
+                gusto_employees::dsl::gusto_employees
+                    .filter(gusto_employees::dsl::cio_company_id.eq(company.id))
+                    .filter(gusto_employees::dsl::gusto_id.ne_all(ids)),
+            )
+            .execute_async(db.pool())
+            .await?)
+        },
+    )
+    .await?;
+
+    if !records.is_empty() {
+        summary.updated += records.len();
+        airtable
+            .update_records(AIRTABLE_PEOPLE_TABLE, records)
+            .await
+            .map_err(CioError::Airtable)?;
+    }
+
+    Ok(summary)
+}
+
+/// Returns the ids of the records in the People table, by the canonical personal email of
+/// the employees in Gusto. The auth users that log in with their personal email are linked
+/// to their person with these.
+pub async fn people_records_by_personal_email(
+    db: &Database,
+    company: &Company,
+    config: &AuthConfig,
+) -> Result<HashMap<String, String>> {
+    Ok(GustoEmployees::get_from_db(db, company.id)
+        .await?
+        .into_iter()
+        .filter(|e| !e.email.is_empty())
+        .filter_map(|e| Some((config.canonical_email(&e.email), e.link_to_people.first()?.to_string())))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    fn employee() -> NewGustoEmployee {
+        let employee: gusto_api::types::Employee = serde_json::from_value(serde_json::json!({
+            "id": "7757500908",
+            "first_name": "Jess",
+            "last_name": "Doe",
+            "email": "Jess@Example.com",
+            "department": "Engineering",
+            "jobs": [{ "title": "Software Engineer", "hire_date": "2020-01-02" }],
+        }))
+        .unwrap();
+
+        NewGustoEmployee::from_gusto(
+            employee,
+            &mock_company(),
+            "jess@oxide.computer".to_string(),
+            vec!["recPerson".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_from_gusto() {
+        let employee = employee();
+        assert_eq!(employee.gusto_id, "7757500908");
+        assert_eq!(employee.email, "jess@example.com");
+        assert_eq!(employee.work_email, "jess@oxide.computer");
+        assert_eq!(employee.title, "Software Engineer");
+        assert_eq!(employee.start_date, NaiveDate::from_ymd_opt(2020, 1, 2));
+        assert_eq!(employee.link_to_people, vec!["recPerson"]);
+    }
+
+    #[test]
+    fn test_people_fields() {
+        let employee = employee();
+
+        assert_eq!(
+            employee.people_fields(&serde_json::json!({ "Title": "Software Engineer" })),
+            Some(serde_json::json!({ "Department": "Engineering", "Start Date": "2020-01-02" }))
+        );
+        assert_eq!(
+            employee.people_fields(&serde_json::json!({
+                "Title": "Software Engineer",
+                "Department": "Engineering",
+                "Start Date": "2020-01-02",
+            })),
+            None
+        );
+
+        // Fields Gusto has no value for are left alone.
+        let mut employee = employee;
+        employee.department = String::new();
+        assert_eq!(
+            employee.people_fields(&serde_json::json!({
+                "Title": "Software Engineer",
+                "Department": "Hardware",
+                "Start Date": "2020-01-02",
+            })),
+            None
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use airtable_api::{Airtable, Record};
+use airtable_api::Record;
 use anyhow::Result;
 use chrono::{Duration, NaiveDate, Utc};
 use google_calendar::types::Event;
@@ -10,6 +10,7 @@ use sendgrid_api::{traits::MailOps, Client as SendGrid};
 
 use crate::{
     airtable::{AIRTABLE_DISCUSSION_TOPICS_TABLE, AIRTABLE_MEETING_SCHEDULE_TABLE},
+    airtable_bases::airtable_client,
     companies::Company,
     configs::{get_configs_from_repo, User},
     core::{DiscussionTopic, Meeting, MeetingReminderEmailData},
@@ -17,6 +18,9 @@ use crate::{
     utils::{create_or_update_file_in_github_repo, SliceExt},
 };
 
+/// How long the updates to the meeting schedule are held, to be sent to Airtable together.
+const MEETING_UPDATES_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Make sure if an event is moved in Google Calendar that Airtable is updated.
 pub async fn sync_changes_to_google_events(db: &Database, company: &Company) -> Result<()> {
     let github = company.authenticate_github()?;
@@ -32,147 +36,158 @@ pub async fn sync_changes_to_google_events(db: &Database, company: &Company) ->
     // Iterate over the huddle meetings.
     for (slug, huddle) in configs.huddles {
         // Initialize the Airtable client.
-        let airtable = Airtable::new(&company.airtable_api_key, huddle.airtable_base_id, "");
+        let airtable = airtable_client(&company.airtable_api_key, &huddle.airtable_base_id, "");
 
         // Get the meeting schedule table from airtable.
         let records: Vec<Record<Meeting>> = airtable
             .list_records(AIRTABLE_MEETING_SCHEDULE_TABLE, "All Meetings", vec![])
             .await?;
 
-        // Iterate over the airtable records and update the meeting notes where we have notes.
-        for mut record in records {
-            if record.fields.calendar_id.is_empty() || record.fields.calendar_event_id.is_empty() {
-                // We don't care we don't have the information we need.
-                continue;
-            }
-
-            // Get the event from Google Calendar.
-            if let Ok(event) = gcal
-                .events()
-                .get(
-                    &record.fields.calendar_id,
-                    &record.fields.calendar_event_id,
-                    0,  // max attendees, 0 to ignore
-                    "", // time_zone
-                )
-                .await
-                .map(|response| response.body)
-            {
-                // If the event is cancelled, we can just carry on our merry way.
-                if event.status.to_lowercase().trim() == "cancelled" {
-                    // Set the airtable record to cancelled.
-                    record.fields.cancelled = true;
+        // The meetings are updated one at a time, queue the updates so they are sent together.
+        let (queue, flusher) = airtable.write_queue(MEETING_UPDATES_WINDOW);
+        let sync = async {
+            // Iterate over the airtable records and update the meeting notes where we have notes.
+            for mut record in records {
+                if record.fields.calendar_id.is_empty() || record.fields.calendar_event_id.is_empty() {
+                    // We don't care we don't have the information we need.
+                    continue;
                 }
 
-                let date = event.start.unwrap().date_time.unwrap();
-                let pacific_time = date.with_timezone(&chrono_tz::US::Pacific);
-                // Update the date of the meeting based on the calendar event.
-                record.fields.date = pacific_time.date_naive();
-
-                // Clear out the fields that are functions since the API cannot take values for those.
-                record.fields.name = "".to_string();
-                record.fields.week = "".to_string();
-
-                // Update the Airtable
-                airtable
-                    .update_records(AIRTABLE_MEETING_SCHEDULE_TABLE, vec![record.clone()])
-                    .await?;
-
-                // Get the discussion topics for the meeting.
-                let mut discussion_topics = String::new();
-                for id in &record.fields.proposed_discussion {
-                    // Get the topic from Airtable.
-                    let topic: Record<DiscussionTopic> =
-                        airtable.get_record(AIRTABLE_DISCUSSION_TOPICS_TABLE, id).await?;
-
-                    if !topic.fields.topic.is_empty() {
-                        discussion_topics = format!(
-                            "{}\n- {} from {}",
-                            discussion_topics, topic.fields.topic, topic.fields.submitter.name
-                        );
+                // Get the event from Google Calendar.
+                if let Ok(event) = gcal
+                    .events()
+                    .get(
+                        &record.fields.calendar_id,
+                        &record.fields.calendar_event_id,
+                        0,  // max attendees, 0 to ignore
+                        "", // time_zone
+                    )
+                    .await
+                    .map(|response| response.body)
+                {
+                    // If the event is cancelled, we can just carry on our merry way.
+                    if event.status.to_lowercase().trim() == "cancelled" {
+                        // Set the airtable record to cancelled.
+                        record.fields.cancelled = true;
                     }
-                }
-                discussion_topics = discussion_topics.trim().to_string();
-                if !discussion_topics.is_empty() {
-                    discussion_topics = format!("Discussion topics:\n{}", discussion_topics);
-                }
-
-                let notes = if !huddle.link_to_notes.is_empty() {
-                    format!("Notes Doc: {}\n", huddle.link_to_notes)
-                } else {
-                    String::new()
-                };
 
-                // Update the event description.
-                let description = format!(
-                    r#"This is the event for {} huddles.
+                    let date = event.start.unwrap().date_time.unwrap();
+                    let pacific_time = date.with_timezone(&chrono_tz::US::Pacific);
+                    // Update the date of the meeting based on the calendar event.
+                    record.fields.date = pacific_time.date_naive();
 
-You can submit topics at: https://{}-huddle-form.corp.{}
+                    // Clear out the fields that are functions since the API cannot take values for those.
+                    record.fields.name = "".to_string();
+                    record.fields.week = "".to_string();
 
-The Airtable workspace lives at: https://{}-huddle.corp.{}
+                    // Queue the update, the queue sends the meetings to Airtable in batches.
+                    queue.update(AIRTABLE_MEETING_SCHEDULE_TABLE, &record.id, &record.fields)?;
 
-{}
-{}"#,
-                    slug.replace('-', " "),
-                    slug,
-                    company.domain,
-                    slug,
-                    company.domain,
-                    notes,
-                    discussion_topics
-                );
+                    // Get the discussion topics for the meeting.
+                    let mut discussion_topics = String::new();
+                    for id in &record.fields.proposed_discussion {
+                        // Get the topic from Airtable.
+                        let topic: Record<DiscussionTopic> =
+                            airtable.get_record(AIRTABLE_DISCUSSION_TOPICS_TABLE, id).await?;
 
-                if event.recurring_event_id != event.id {
-                    let organizer_email = event.organizer.unwrap().email.to_string();
-                    // Update the calendar event with the new description.
-                    // Get the event under the right user.
-                    if let Ok(mut event) = gcal
-                        .events()
-                        .get(
-                            &organizer_email,
-                            &event.id,
-                            0,  // max attendees, 0 to ignore
-                            "", // time_zone
-                        )
-                        .await
-                        .map(|response| response.body)
-                    {
-                        // Modify the properties of the event so we can update it.
-                        event.description = description.trim().to_string();
-                        if !event.recurring_event_id.is_empty() {
-                            // Individual instances are similar to single events. Unlike their parent recurring events, instances do not have the recurrence field set.
-                            // FROM: https://developers.google.com/calendar/recurringevents#ruby_1
-                            event.recurrence = vec![];
+                        if !topic.fields.topic.is_empty() {
+                            discussion_topics = format!(
+                                "{}\n- {} from {}",
+                                discussion_topics, topic.fields.topic, topic.fields.submitter.name
+                            );
                         }
+                    }
+                    discussion_topics = discussion_topics.trim().to_string();
+                    if !discussion_topics.is_empty() {
+                        discussion_topics = format!("Discussion topics:\n{}", discussion_topics);
+                    }
 
-                        match gcal
+                    let notes = if !huddle.link_to_notes.is_empty() {
+                        format!("Notes Doc: {}\n", huddle.link_to_notes)
+                    } else {
+                        String::new()
+                    };
+
+                    // Update the event description.
+                    let description = format!(
+                        r#"This is the event for {} huddles.
+
+    You can submit topics at: https://{}-huddle-form.corp.{}
+
+    The Airtable workspace lives at: https://{}-huddle.corp.{}
+
+    {}
+    {}"#,
+                        slug.replace('-', " "),
+                        slug,
+                        company.domain,
+                        slug,
+                        company.domain,
+                        notes,
+                        discussion_topics
+                    );
+
+                    if event.recurring_event_id != event.id {
+                        let organizer_email = event.organizer.unwrap().email.to_string();
+                        // Update the calendar event with the new description.
+                        // Get the event under the right user.
+                        if let Ok(mut event) = gcal
                             .events()
-                            .update(
+                            .get(
                                 &organizer_email,
                                 &event.id,
-                                0,     // conference data version
-                                0,     // max attendees, 0 to ignore
-                                false, // send notifications
-                                google_calendar::types::SendUpdates::Noop,
-                                true, // supports_attachments
-                                &event,
+                                0,  // max attendees, 0 to ignore
+                                "", // time_zone
                             )
                             .await
                             .map(|response| response.body)
                         {
-                            Ok(_) => (),
-                            Err(err) => debug!(
-                                "could not update event description {}: {}",
-                                serde_json::to_string_pretty(&json!(event))?.to_string(),
-                                err
-                            ),
+                            // Modify the properties of the event so we can update it.
+                            event.description = description.trim().to_string();
+                            if !event.recurring_event_id.is_empty() {
+                                // Individual instances are similar to single events. Unlike their parent recurring events, instances do not have the recurrence field set.
+                                // FROM: https://developers.google.com/calendar/recurringevents#ruby_1
+                                event.recurrence = vec![];
+                            }
+
+                            match gcal
+                                .events()
+                                .update(
+                                    &organizer_email,
+                                    &event.id,
+                                    0,     // conference data version
+                                    0,     // max attendees, 0 to ignore
+                                    false, // send notifications
+                                    google_calendar::types::SendUpdates::Noop,
+                                    true, // supports_attachments
+                                    &event,
+                                )
+                                .await
+                                .map(|response| response.body)
+                            {
+                                Ok(_) => (),
+                                Err(err) => debug!(
+                                    "could not update event description {}: {}",
+                                    serde_json::to_string_pretty(&json!(event))?.to_string(),
+                                    err
+                                ),
+                            }
                         }
                     }
-                }
 
-                info!("updated {} huddle meeting {} in Airtable", slug, pacific_time);
+                    info!("updated {} huddle meeting {} in Airtable", slug, pacific_time);
+                }
             }
-        }
+
+            // Let the queue flush what it holds and finish.
+            drop(queue);
+            Ok::<_, anyhow::Error>(())
+        };
+        let (_, summary) = futures::try_join!(sync, flusher)?;
+        debug!(
+            "sent the {} huddle meeting updates in {} requests",
+            slug, summary.requests
+        );
     }
 
     Ok(())
@@ -198,7 +213,7 @@ pub async fn send_huddle_reminders(db: &Database, company: &Company) -> Result<(
         let mut email_data: MeetingReminderEmailData = Default::default();
 
         // Initialize the Airtable client.
-        let airtable = Airtable::new(&company.airtable_api_key, huddle.airtable_base_id, "");
+        let airtable = airtable_client(&company.airtable_api_key, &huddle.airtable_base_id, "");
 
         // Get the meeting schedule table from airtable.
         let records: Vec<Record<Meeting>> = airtable
@@ -439,7 +454,7 @@ pub async fn sync_huddle_meeting_notes(company: &Company) -> Result<()> {
     // Iterate over the huddle meetings.
     for (name, huddle) in configs.huddles {
         // Initialize the Airtable client.
-        let airtable = Airtable::new(&company.airtable_api_key, huddle.airtable_base_id, "");
+        let airtable = airtable_client(&company.airtable_api_key, &huddle.airtable_base_id, "");
 
         // Get the meeting schedule table from airtable.
         let records: Vec<Record<Meeting>> = airtable
@@ -588,7 +603,7 @@ pub async fn sync_huddles(db: &Database, company: &Company) -> Result<()> {
         );
 
         // Now let's get the Airtable records.
-        let airtable = Airtable::new(&company.airtable_api_key, huddle.airtable_base_id.to_string(), "");
+        let airtable = airtable_client(&company.airtable_api_key, &huddle.airtable_base_id, "");
         let records: Vec<Record<Meeting>> = airtable
             .list_records(AIRTABLE_MEETING_SCHEDULE_TABLE, "All Meetings", vec![])
             .await?;
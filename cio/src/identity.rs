@@ -0,0 +1,328 @@
+//! The identity providers the users of our applications log in with.
+//!
+//! Each provider turns its users into `NewAuthUser`s, so everything after the sync (the
+//! database, linking users to people, Airtable) works the same whether the company uses
+//! Auth0 or Okta. Which one is used is set by `identity_provider` in the auth config.
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{offset::Utc, DateTime, SecondsFormat};
+use log::info;
+
+#[cfg(feature = "github")]
+use crate::configs::get_configs_from_repo;
+use crate::{
+    airtable_sync::SyncSummary,
+    auth0::Auth0Client,
+    auth_config::{AuthConfig, IdentityProviderKind},
+    auth_logins::{get_auth_users_updated_since, refresh_db_auth_tenant, upsert_auth_users, NewAuthUser},
+    companies::Company,
+    configs::Auth0TenantConfig,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+};
+
+/// A system that keeps the users that log in to our applications.
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// The kind of provider, for logs.
+    fn kind(&self) -> IdentityProviderKind;
+
+    /// The tenant the users are stored under in the database.
+    fn tenant(&self) -> &str;
+
+    /// Sync the users of the provider with our database.
+    async fn sync_users(
+        &self,
+        db: &Database,
+        company: &Company,
+        config: &AuthConfig,
+        dry_run: DryRun,
+    ) -> Result<SyncSummary, CioError>;
+}
+
+#[async_trait]
+impl IdentityProvider for Auth0Client {
+    fn kind(&self) -> IdentityProviderKind {
+        IdentityProviderKind::Auth0
+    }
+
+    fn tenant(&self) -> &str {
+        self.domain()
+    }
+
+    async fn sync_users(
+        &self,
+        db: &Database,
+        company: &Company,
+        config: &AuthConfig,
+        dry_run: DryRun,
+    ) -> Result<SyncSummary, CioError> {
+        refresh_db_auth_tenant(self, db, company, config, dry_run).await
+    }
+}
+
+/// The Okta org of a company.
+pub struct OktaProvider {
+    client: okta::Client,
+    domain: String,
+}
+
+impl OktaProvider {
+    /// Returns the Okta org of the company, if it has the credentials for one.
+    pub fn new(company: &Company) -> Option<Self> {
+        Some(OktaProvider {
+            client: company.authenticate_okta()?,
+            domain: company.okta_domain.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for OktaProvider {
+    fn kind(&self) -> IdentityProviderKind {
+        IdentityProviderKind::Okta
+    }
+
+    fn tenant(&self) -> &str {
+        &self.domain
+    }
+
+    /// Okta has no log of the applications each user accessed that we sync, so only the
+    /// users themselves are synced.
+    async fn sync_users(
+        &self,
+        db: &Database,
+        company: &Company,
+        config: &AuthConfig,
+        dry_run: DryRun,
+    ) -> Result<SyncSummary, CioError> {
+        // Only fetch the users that changed since the last sync, like we do for Auth0.
+        let filter = match get_auth_users_updated_since(db, company, &self.domain)
+            .await
+            .map_err(CioError::Database)?
+        {
+            Some(since) => {
+                info!("syncing okta users updated since {}", since);
+                updated_since_filter(since)
+            }
+            None => String::new(),
+        };
+
+        let users = self
+            .client
+            .users()
+            .list_all(
+                "",      // query
+                &filter, // filter
+                "",      // search
+                "",      // sort by
+                "",      // sort order
+            )
+            .await
+            .map_err(|e| CioError::Okta(e.into()))?
+            .body;
+
+        let auth_users: Vec<NewAuthUser> = users
+            .iter()
+            .filter_map(|u| okta_auth_user(u, company, &self.domain, config))
+            .collect();
+
+        let mut summary = SyncSummary::default();
+        if dry_run.is_enabled() {
+            for auth_user in auth_users.iter() {
+                info!("[dry-run] would save okta user `{}`", auth_user.user_id);
+            }
+            summary.updated += auth_users.len();
+        } else {
+            let saved = upsert_auth_users(db, &auth_users).await;
+            summary.updated += saved;
+            summary.errors += auth_users.len().saturating_sub(saved);
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Convert an Okta user into the data type we store in the database. Users without a
+/// profile are skipped, there is nothing to sync for them.
+fn okta_auth_user(
+    user: &okta::types::User,
+    company: &Company,
+    tenant: &str,
+    config: &AuthConfig,
+) -> Option<NewAuthUser> {
+    let profile = user.profile.as_ref()?;
+    let created_at = user.created.unwrap_or_else(Utc::now);
+    let name = if profile.display_name.is_empty() {
+        format!("{} {}", profile.first_name, profile.last_name)
+            .trim()
+            .to_string()
+    } else {
+        profile.display_name.to_string()
+    };
+    let deleted_at = match user.status {
+        Some(okta::types::UserStatus::Deprovisioned) => Some(user.status_changed.unwrap_or_else(Utc::now)),
+        _ => None,
+    };
+
+    Some(NewAuthUser {
+        user_id: user.id.to_string(),
+        name,
+        nickname: profile.nick_name.to_string(),
+        username: profile.login.to_string(),
+        email: profile.email.to_string(),
+        email_verified: false,
+        picture: Default::default(),
+        company: config.normalize_company(company, &profile.email, &profile.organization),
+        blog: Default::default(),
+        phone: profile.mobile_phone.to_string(),
+        phone_verified: false,
+        locale: profile.locale.to_string(),
+        login_provider: "okta".to_string(),
+        created_at,
+        updated_at: user.last_updated.unwrap_or(created_at),
+        last_login: user.last_login.unwrap_or(created_at),
+        last_application_accessed: Default::default(),
+        top_applications: Default::default(),
+        last_ip: Default::default(),
+        last_ip_country: Default::default(),
+        last_ip_city: Default::default(),
+        logins_count: 0,
+        link_to_people: Default::default(),
+        link_to_auth_user_logins: Default::default(),
+        link_to_page_views: Default::default(),
+        deleted_at,
+        duplicate_of: Default::default(),
+        modified_at: Utc::now(),
+        tenant: tenant.to_string(),
+        cio_company_id: company.id,
+    })
+}
+
+/// Returns the filter for the Okta users updated at or after the given time.
+fn updated_since_filter(since: DateTime<Utc>) -> String {
+    format!(
+        "lastUpdated ge \"{}\"",
+        since.to_rfc3339_opts(SecondsFormat::Millis, true)
+    )
+}
+
+/// Returns the identity providers to sync for the company, by name, as set in the config.
+///
+/// A provider that fails to authenticate is returned as an error, so it does not stop the
+/// others from syncing.
+pub async fn identity_providers(
+    company: &Company,
+    config: &AuthConfig,
+) -> Result<Vec<(String, Result<Box<dyn IdentityProvider>, CioError>)>, CioError> {
+    match config.identity_provider {
+        IdentityProviderKind::Okta => {
+            let provider: Result<Box<dyn IdentityProvider>, CioError> = match OktaProvider::new(company) {
+                Some(okta) => Ok(Box::new(okta)),
+                None => Err(CioError::Config(format!(
+                    "company `{}` has no okta credentials",
+                    company.name
+                ))),
+            };
+
+            Ok(vec![(company.okta_domain.to_string(), provider)])
+        }
+        IdentityProviderKind::Auth0 => {
+            let tenants = auth0_tenants(company, config).await?;
+
+            Ok(tenants
+                .into_iter()
+                .map(|(name, tenant)| {
+                    let provider: Result<Box<dyn IdentityProvider>, CioError> = tenant
+                        .authenticate()
+                        .map(|auth0| Box::new(auth0) as Box<dyn IdentityProvider>)
+                        .map_err(|e| CioError::Config(e.to_string()));
+                    (name, provider)
+                })
+                .collect())
+        }
+    }
+}
+
+/// Returns the Auth0 tenants of the company, by name: the ones in the config, or the ones
+/// in the configs repo if the config has none. Without the `github` feature there is no
+/// configs repo to fall back to, so the config has to list them.
+#[cfg_attr(not(feature = "github"), allow(unused_variables))]
+pub async fn auth0_tenants(
+    company: &Company,
+    config: &AuthConfig,
+) -> Result<BTreeMap<String, Auth0TenantConfig>, CioError> {
+    if !config.tenants.is_empty() {
+        return Ok(config.tenants.clone());
+    }
+
+    #[cfg(feature = "github")]
+    {
+        let github = company.authenticate_github()?;
+        Ok(get_configs_from_repo(&github, company)
+            .await
+            .map_err(|e| CioError::Config(e.to_string()))?
+            .auth0_tenants)
+    }
+
+    #[cfg(not(feature = "github"))]
+    {
+        Err(CioError::Config("the auth config lists no auth0 tenants".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    #[test]
+    fn test_updated_since_filter() {
+        let since = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            updated_since_filter(since),
+            r#"lastUpdated ge "2024-01-02T03:04:05.000Z""#
+        );
+    }
+
+    #[test]
+    fn test_okta_auth_user() {
+        let company = mock_company();
+        let config: AuthConfig = toml::from_str(
+            r#"
+identity_provider = "okta"
+domains = ["oxidecomputer.com"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.identity_provider, IdentityProviderKind::Okta);
+
+        let user: okta::types::User = serde_json::from_value(serde_json::json!({
+            "id": "00u1",
+            "status": "ACTIVE",
+            "created": "2024-01-02T03:04:05.000Z",
+            "lastUpdated": "2024-02-02T03:04:05.000Z",
+            "profile": {
+                "login": "jess@oxidecomputer.com",
+                "email": "jess@oxidecomputer.com",
+                "firstName": "Jess",
+                "lastName": "Doe",
+            },
+        }))
+        .unwrap();
+
+        let auth_user = okta_auth_user(&user, &company, "oxide", &config).unwrap();
+        assert_eq!(auth_user.user_id, "00u1");
+        assert_eq!(auth_user.name, "Jess Doe");
+        assert_eq!(auth_user.company, company.name);
+        assert_eq!(auth_user.login_provider, "okta");
+        assert_eq!(auth_user.tenant, "oxide");
+        assert_eq!(auth_user.last_login, auth_user.created_at);
+        assert!(auth_user.deleted_at.is_none());
+    }
+}
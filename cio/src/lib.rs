@@ -3,61 +3,113 @@
 #![allow(clippy::nonstandard_macro_braces)]
 
 pub mod airtable;
+pub mod airtable_bases;
+pub mod airtable_fields;
+pub mod airtable_offsets;
+pub mod airtable_replication;
+pub mod airtable_sync;
+#[cfg(feature = "analytics")]
 pub mod analytics;
 pub mod api_tokens;
 pub mod app_config;
 pub mod applicant_reviews;
+pub mod applicant_sheets;
 pub mod applicant_status;
 pub mod applicant_uploads;
 pub mod applicants;
 pub mod application_form;
 pub mod asset_inventory;
+pub mod auth0;
+pub mod auth0_logs;
+pub mod auth_anomalies;
+pub mod auth_config;
+pub mod auth_duplicates;
 pub mod auth_logins;
+pub mod backup;
+pub mod cancel;
 pub mod certs;
+pub mod change_listener;
 pub mod cloud_dns;
 pub mod cloudflare;
 pub mod colors;
 pub mod companies;
 pub mod configs;
 pub mod core;
+#[cfg(feature = "github")]
 pub mod customers;
 pub mod db;
 pub mod dns_providers;
 pub mod dns_proxy;
+pub mod email_verification;
 #[macro_use]
 pub mod enclose;
+pub mod error;
 pub mod features;
 pub mod finance;
+#[cfg(all(feature = "github", feature = "slack"))]
 pub mod functions;
+pub mod geoip;
+#[cfg(feature = "github")]
 pub mod github_commits;
+#[cfg(feature = "github")]
+pub mod github_members;
+#[cfg(feature = "github")]
 pub mod github_prs;
+#[cfg(feature = "gsuite")]
 pub mod gsuite;
+#[cfg(feature = "gsuite")]
+pub mod gsuite_directory;
+pub mod gusto;
 pub mod health;
+#[cfg(feature = "github")]
 pub mod huddles;
+pub mod identity;
 pub mod interviews;
+#[cfg(all(feature = "github", feature = "slack"))]
 pub mod journal_clubs;
 pub mod mailerlite;
 pub mod mailing_list;
+pub mod metrics;
+#[cfg(feature = "github")]
 pub mod octorust_utils;
+pub mod offer_envelopes;
 pub mod printer;
+pub mod progress;
 pub mod providers;
 pub mod rack_line;
+#[cfg(all(feature = "slack", feature = "zoom"))]
 pub mod recorded_meetings;
+#[cfg(feature = "github")]
 pub mod repos;
+#[cfg(feature = "github")]
 pub mod rfd;
 pub mod schema;
+pub mod secrets;
 pub mod sf;
+#[cfg(feature = "shipments")]
 pub mod shipment_status;
+#[cfg(feature = "shipments")]
 pub mod shipments;
+#[cfg(feature = "github")]
 pub mod shorturls;
+#[cfg(feature = "slack")]
+pub mod slack_users;
 pub mod states;
 pub mod swag_inventory;
+#[cfg(feature = "shipments")]
 pub mod swag_store;
+pub mod sync_plan;
+pub mod sync_runs;
 pub mod tailscale;
+#[cfg(feature = "github")]
 pub mod templates;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod travel;
 pub mod utils;
 pub mod zoho;
+#[cfg(feature = "zoom")]
+pub mod zoom;
 
 #[macro_use]
 extern crate diesel;
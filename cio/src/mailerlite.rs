@@ -35,7 +35,7 @@ impl Mailerlite<chrono_tz::Tz> {
             .map_err(|err| anyhow::anyhow!("Failed to parse mailerlite time zone from environment: {}", err))?;
 
         Ok(Self {
-            client: MailerliteClient::new(std::env::var("MAILERLITE_API_KEY")?, tz),
+            client: MailerliteClient::new(crate::secrets::get("MAILERLITE_API_KEY")?, tz),
             segments: MailerliteSegments::new()?,
         })
     }
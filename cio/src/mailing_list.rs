@@ -1,22 +1,35 @@
+use std::collections::BTreeMap;
+
 use anyhow::Result;
 use async_bb8_diesel::AsyncRunQueryDsl;
-use async_trait::async_trait;
 use chrono::{offset::Utc, DateTime};
 use chrono_humanize::HumanTime;
+use log::{error, info};
 use macros::db;
 use mailerlite::SubscriberFieldValue;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "slack")]
 use slack_chat_api::{FormattedMessage, MessageBlock, MessageBlockText, MessageBlockType, MessageType};
 
 use crate::{
-    airtable::AIRTABLE_MAILING_LIST_SIGNUPS_TABLE, companies::Company, core::UpdateAirtableRecord, db::Database,
+    airtable::AIRTABLE_MAILING_LIST_SIGNUPS_TABLE,
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable_cached, AirtableCache, AirtableSyncable, SyncSummary},
+    auth_config::AuthConfig,
+    auth_logins::people_records_by_email,
+    companies::Company,
+    core::{DryRun, UpdateAirtableRecord},
+    db::Database,
+    error::CioError,
     schema::mailing_list_subscribers,
 };
 
 /// The data type for a MailingListSubscriber.
 #[db {
     new_struct_name = "MailingListSubscriber",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_MAILING_LIST_SIGNUPS_TABLE",
     match_on = {
         "email" = "String",
     },
@@ -78,6 +91,7 @@ pub struct NewMailingListSubscriber {
 }
 
 impl NewMailingListSubscriber {
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification(&self, db: &Database, company: &Company) -> Result<()> {
         let mut msg: FormattedMessage = self.clone().into();
         // Set the channel.
@@ -100,6 +114,7 @@ impl NewMailingListSubscriber {
 }
 
 impl MailingListSubscriber {
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification(&self, db: &Database, company: &Company) -> Result<()> {
         let n: NewMailingListSubscriber = self.into();
         n.send_slack_notification(db, company).await
@@ -107,6 +122,7 @@ impl MailingListSubscriber {
 }
 
 /// Convert the mailing list signup into a Slack message.
+#[cfg(feature = "slack")]
 impl From<NewMailingListSubscriber> for FormattedMessage {
     fn from(item: NewMailingListSubscriber) -> Self {
         let time = item.human_duration();
@@ -222,6 +238,49 @@ impl Default for NewMailingListSubscriber {
     }
 }
 
+impl From<mailchimp_minimal_api::Member> for NewMailingListSubscriber {
+    fn from(member: mailchimp_minimal_api::Member) -> Self {
+        let address: mailchimp_minimal_api::Address =
+            serde_json::from_value(member.merge_fields.address.clone()).unwrap_or_default();
+        let parse_timestamp = |t: &str| {
+            DateTime::parse_from_rfc3339(t)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or(member.last_changed)
+        };
+
+        let mut name = member.merge_fields.name.to_string();
+        if name.is_empty() {
+            name = format!("{} {}", member.merge_fields.first_name, member.merge_fields.last_name)
+                .trim()
+                .to_string();
+        }
+
+        NewMailingListSubscriber {
+            email: member.email_address.trim().to_lowercase(),
+            first_name: member.merge_fields.first_name,
+            last_name: member.merge_fields.last_name,
+            name,
+            company: member.merge_fields.company,
+            interest: member.merge_fields.interest,
+            date_added: parse_timestamp(&member.timestamp_signup),
+            date_optin: parse_timestamp(&member.timestamp_opt),
+            date_last_changed: member.last_changed,
+            notes: member.merge_fields.notes,
+            source: member.source,
+            revenue: member.stats.ecommerce_data.total_revenue,
+            street_1: address.addr1,
+            street_2: address.addr2,
+            city: address.city,
+            state: address.state,
+            zipcode: address.zip,
+            country: address.country,
+            phone: member.merge_fields.phone,
+            tags: member.tags.into_iter().map(|t| t.name).collect(),
+            ..Default::default()
+        }
+    }
+}
+
 impl From<mailerlite::Subscriber> for NewMailingListSubscriber {
     fn from(subscriber: mailerlite::Subscriber) -> Self {
         let mut new_sub = NewMailingListSubscriber::default();
@@ -277,3 +336,169 @@ impl From<mailerlite::Subscriber> for NewMailingListSubscriber {
         new_sub
     }
 }
+
+impl AirtableSyncable for MailingListSubscriber {
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewMailingListSubscriber) -> String {
+        fields.email.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewMailingListSubscriber) {}
+}
+
+/// Keep one subscriber per person, by canonical email. The earliest signup is kept, with
+/// the tags of the others merged in.
+fn dedup_subscribers(subscribers: Vec<NewMailingListSubscriber>, config: &AuthConfig) -> Vec<NewMailingListSubscriber> {
+    let mut by_email: BTreeMap<String, NewMailingListSubscriber> = Default::default();
+    for subscriber in subscribers {
+        match by_email.entry(config.canonical_email(&subscriber.email)) {
+            std::collections::btree_map::Entry::Vacant(e) => {
+                e.insert(subscriber);
+            }
+            std::collections::btree_map::Entry::Occupied(mut e) => {
+                let (mut kept, other) = if subscriber.date_added < e.get().date_added {
+                    (subscriber, e.get().clone())
+                } else {
+                    (e.get().clone(), subscriber)
+                };
+                for tag in other.tags {
+                    if !kept.tags.contains(&tag) {
+                        kept.tags.push(tag);
+                    }
+                }
+                e.insert(kept);
+            }
+        }
+    }
+
+    by_email.into_values().collect()
+}
+
+/// Sync the subscribers of the company's Mailchimp list with our database and Airtable.
+///
+/// Subscribers are linked to the record in the People table with the same email, so a
+/// person who signs up twice, or is already in People, does not show up as a new lead.
+pub async fn refresh_mailchimp_subscribers(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    if company.mailchimp_list_id.is_empty() {
+        info!("company {} has no mailchimp list, skipping", company.name);
+        return Ok(SyncSummary::default());
+    }
+
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let mailchimp = company
+        .authenticate_mailchimp()
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let members = mailchimp
+        .get_subscribers(&company.mailchimp_list_id)
+        .await
+        .map_err(|e| CioError::Mailchimp(e.into()))?;
+    let subscribers: Vec<NewMailingListSubscriber> = members
+        .into_iter()
+        .filter(|m| m.status == "subscribed")
+        .map(|m| NewMailingListSubscriber {
+            cio_company_id: company.id,
+            ..m.into()
+        })
+        .collect();
+    let subscribers = dedup_subscribers(subscribers, &config);
+    info!("syncing {} mailchimp subscribers", subscribers.len());
+
+    let cache = AirtableCache::default();
+    let people = people_records_by_email(company, &config, &cache).await?;
+
+    if dry_run.is_enabled() {
+        info!("[dry-run] would save {} mailing list subscribers", subscribers.len());
+    } else {
+        for mut subscriber in subscribers {
+            // Keep the link someone made by hand in Airtable if we can't find the person.
+            let existing = MailingListSubscriber::get_from_db(db, subscriber.email.to_string()).await;
+            subscriber.link_to_people = match people.get(&config.canonical_email(&subscriber.email)) {
+                Some(person) => vec![person.to_string()],
+                None => existing.map(|e| e.link_to_people).unwrap_or_default(),
+            };
+
+            if let Err(e) = subscriber.upsert(db).await {
+                error!("saving mailing list subscriber `{}` failed: {}", subscriber.email, e);
+            }
+        }
+    }
+
+    sync_to_airtable_cached::<MailingListSubscriber>(db, company, &cache, dry_run).await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_from_mailchimp_member() {
+        let member: mailchimp_minimal_api::Member = serde_json::from_value(serde_json::json!({
+            "email_address": "Jess@Example.com",
+            "status": "subscribed",
+            "merge_fields": {
+                "FNAME": "Jess",
+                "LNAME": "Doe",
+                "ADDRESS": {"addr1": "1 Main St", "city": "Oakland", "zip": "94612"},
+            },
+            "timestamp_signup": "2024-01-02T03:04:05+00:00",
+            "last_changed": "2024-02-01T00:00:00+00:00",
+            "tags": [{"id": 1, "name": "website"}],
+        }))
+        .unwrap();
+
+        let subscriber: NewMailingListSubscriber = member.into();
+        assert_eq!(subscriber.email, "jess@example.com");
+        assert_eq!(subscriber.name, "Jess Doe");
+        assert_eq!(subscriber.city, "Oakland");
+        assert_eq!(subscriber.tags, vec!["website"]);
+        assert_eq!(
+            subscriber.date_added,
+            Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap()
+        );
+        // No opt-in time, fall back to the last change.
+        assert_eq!(
+            subscriber.date_optin,
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dedup_subscribers() {
+        let config: AuthConfig = toml::from_str(
+            r#"
+[email_domain_aliases]
+"googlemail.com" = "gmail.com"
+"#,
+        )
+        .unwrap();
+        let subscriber = |email: &str, day: u32, tag: &str| NewMailingListSubscriber {
+            email: email.to_string(),
+            date_added: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+            tags: vec![tag.to_string()],
+            ..Default::default()
+        };
+
+        let subscribers = dedup_subscribers(
+            vec![
+                subscriber("sam@gmail.com", 2, "website"),
+                subscriber("sam@googlemail.com", 1, "podcast"),
+                subscriber("jess@example.com", 3, "website"),
+            ],
+            &config,
+        );
+        assert_eq!(subscribers.len(), 2);
+        assert_eq!(subscribers[1].email, "sam@googlemail.com");
+        assert_eq!(subscribers[1].tags, vec!["podcast", "website"]);
+    }
+}
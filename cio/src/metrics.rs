@@ -0,0 +1,124 @@
+//! Prometheus metrics for the sync jobs.
+//!
+//! The metrics live in their own registry, which the server exposes in the text format
+//! with [`gather`].
+use anyhow::Result;
+use lazy_static::lazy_static;
+use prometheus::{
+    exponential_buckets, CounterVec, Encoder, GaugeVec, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec,
+    Opts, Registry, TextEncoder,
+};
+
+lazy_static! {
+    /// The registry holding the metrics of the sync jobs.
+    pub static ref REGISTRY: Registry = Registry::new_custom(Some("cio".to_string()), None).unwrap();
+
+    /// The records seen by the sync jobs, by job and outcome.
+    pub static ref SYNC_RECORDS: IntCounterVec = register(IntCounterVec::new(
+        Opts::new("sync_records_total", "Records seen by the sync jobs, by outcome."),
+        &["job", "outcome"],
+    ));
+
+    /// How long the requests to third party APIs take.
+    pub static ref API_REQUEST_DURATION: HistogramVec = register(HistogramVec::new(
+        HistogramOpts::new("api_request_duration_seconds", "Time taken by requests to third party APIs.")
+            .buckets(exponential_buckets(0.01, 2.0, 12).unwrap()),
+        &["api", "operation"],
+    ));
+
+    /// How long the sync jobs waited on the rate limits of third party APIs.
+    pub static ref RATE_LIMIT_WAIT: CounterVec = register(CounterVec::new(
+        Opts::new("rate_limit_wait_seconds_total", "Time spent waiting for API rate limits to reset."),
+        &["api"],
+    ));
+
+    /// When each sync job last succeeded, to alert on data that has not been refreshed.
+    pub static ref SYNC_LAST_SUCCESS: GaugeVec = register(GaugeVec::new(
+        Opts::new("sync_last_success_timestamp_seconds", "When the sync job last succeeded, as a unix timestamp."),
+        &["job"],
+    ));
+}
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: prometheus::Result<T>) -> T {
+    let metric = metric.unwrap();
+    REGISTRY.register(Box::new(metric.clone())).unwrap();
+    metric
+}
+
+/// What happened to a record during a sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The record was read from the source.
+    Fetched,
+    /// The record was created or updated at the destination.
+    Updated,
+    /// The record was left alone, because it could not be read or had not changed.
+    Skipped,
+    /// Saving the record failed.
+    Errored,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Fetched => "fetched",
+            Outcome::Updated => "updated",
+            Outcome::Skipped => "skipped",
+            Outcome::Errored => "errored",
+        }
+    }
+}
+
+/// Count `n` records of the job with the outcome.
+pub fn record(job: &str, outcome: Outcome, n: usize) {
+    SYNC_RECORDS
+        .with_label_values(&[job, outcome.as_str()])
+        .inc_by(n as u64);
+}
+
+/// Start timing a request to a third party API. The time is observed when the returned
+/// timer is dropped.
+pub fn time_api(api: &str, operation: &str) -> HistogramTimer {
+    API_REQUEST_DURATION.with_label_values(&[api, operation]).start_timer()
+}
+
+/// Count the time spent waiting for the rate limit of an API to reset.
+pub fn rate_limit_wait(api: &str, wait: std::time::Duration) {
+    RATE_LIMIT_WAIT.with_label_values(&[api]).inc_by(wait.as_secs_f64());
+}
+
+/// Record that the job succeeded at the time.
+pub fn sync_succeeded(job: &str, at: chrono::DateTime<chrono::Utc>) {
+    SYNC_LAST_SUCCESS
+        .with_label_values(&[job])
+        .set(at.timestamp_millis() as f64 / 1000.0);
+}
+
+/// Returns the metrics in the Prometheus text format.
+pub fn gather() -> Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+
+    Ok(String::from_utf8(buffer)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_gather() {
+        record("test", Outcome::Updated, 3);
+        rate_limit_wait("test", std::time::Duration::from_millis(500));
+        drop(time_api("test", "list"));
+        sync_succeeded("test", chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+
+        let text = gather().unwrap();
+        assert!(text.contains(r#"cio_sync_records_total{job="test",outcome="updated"} 3"#));
+        assert!(text.contains(r#"cio_rate_limit_wait_seconds_total{api="test"} 0.5"#));
+        assert!(text.contains(r#"cio_api_request_duration_seconds_count{api="test",operation="list"} 1"#));
+        assert!(text.contains(r#"cio_sync_last_success_timestamp_seconds{job="test"} 1700000000"#));
+    }
+}
@@ -0,0 +1,149 @@
+#![allow(clippy::from_over_into)]
+//! The history of the DocuSign envelopes we send applicants: their offer letter and their
+//! PIIA.
+//!
+//! DocuSign only tells us the status an envelope is in now, so every time we see it move
+//! (sent, viewed, signed, ...) the change is stored. Changes are recorded the same way
+//! whether we learned about them from the DocuSign Connect webhook or from polling the
+//! envelopes on the applications sync.
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::info;
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{applicants::Applicant, db::Database, schema::envelope_status_changes};
+
+/// A change in the status of the DocuSign envelope of an applicant.
+#[db {
+    new_struct_name = "EnvelopeStatusChange",
+    match_on = {
+        "envelope_id" = "String",
+        "to_status" = "String",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = envelope_status_changes)]
+pub struct NewEnvelopeStatusChange {
+    pub envelope_id: String,
+    /// `offer` or `piia`.
+    pub kind: String,
+    pub applicant_email: String,
+    /// The status before the change, empty when the envelope was just sent.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub from_status: String,
+    /// The status DocuSign reported: `sent`, `delivered` once the applicant viewed it,
+    /// `completed` once everyone signed, `declined` or `voided`.
+    pub to_status: String,
+    pub changed_at: DateTime<Utc>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl NewEnvelopeStatusChange {
+    /// Returns the change between the status we had for the envelope and the one DocuSign
+    /// reported, if the status changed.
+    fn between(
+        kind: &str,
+        applicant_email: &str,
+        cio_company_id: i32,
+        from_status: &str,
+        envelope: &docusign::Envelope,
+        now: DateTime<Utc>,
+    ) -> Option<Self> {
+        if envelope.envelope_id.is_empty() || envelope.status.is_empty() || from_status == envelope.status {
+            return None;
+        }
+
+        Some(NewEnvelopeStatusChange {
+            envelope_id: envelope.envelope_id.to_string(),
+            kind: kind.to_string(),
+            applicant_email: applicant_email.to_string(),
+            from_status: from_status.to_string(),
+            to_status: envelope.status.to_string(),
+            changed_at: status_time(envelope).unwrap_or(now),
+            cio_company_id,
+        })
+    }
+}
+
+/// Returns when the envelope got to its status, for the statuses DocuSign keeps the time of.
+fn status_time(envelope: &docusign::Envelope) -> Option<DateTime<Utc>> {
+    match envelope.status.as_str() {
+        "created" => envelope.created_date_time,
+        "delivered" => envelope.delivered_date_time,
+        "completed" => envelope.completed_date_time,
+        "declined" => envelope.declined_date_time,
+        _ => None,
+    }
+}
+
+/// Store the change in the status of an envelope of the applicant, if the status changed.
+pub async fn record_status_change(
+    db: &Database,
+    kind: &str,
+    applicant: &Applicant,
+    from_status: &str,
+    envelope: &docusign::Envelope,
+) -> Result<()> {
+    let change = match NewEnvelopeStatusChange::between(
+        kind,
+        &applicant.email,
+        applicant.cio_company_id,
+        from_status,
+        envelope,
+        Utc::now(),
+    ) {
+        Some(change) => change,
+        None => return Ok(()),
+    };
+
+    info!(
+        "{} envelope `{}` of applicant `{}` went from `{}` to `{}`",
+        change.kind, change.envelope_id, change.applicant_email, change.from_status, change.to_status
+    );
+    change.upsert(db).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_status_change_between() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 3, 0, 0, 0).unwrap();
+        let viewed = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let envelope = docusign::Envelope {
+            envelope_id: "e1".to_string(),
+            status: "delivered".to_string(),
+            delivered_date_time: Some(viewed),
+            ..Default::default()
+        };
+
+        let change = NewEnvelopeStatusChange::between("offer", "jess@example.com", 1, "sent", &envelope, now).unwrap();
+        assert_eq!(change.from_status, "sent");
+        assert_eq!(change.to_status, "delivered");
+        assert_eq!(change.applicant_email, "jess@example.com");
+        assert_eq!(change.changed_at, viewed);
+
+        // No change, nothing to record.
+        assert!(
+            NewEnvelopeStatusChange::between("offer", "jess@example.com", 1, "delivered", &envelope, now).is_none()
+        );
+
+        // DocuSign does not keep the time it sent the envelope.
+        let sent = docusign::Envelope {
+            status: "sent".to_string(),
+            ..envelope
+        };
+        let change = NewEnvelopeStatusChange::between("piia", "jess@example.com", 1, "", &sent, now).unwrap();
+        assert_eq!(change.from_status, "");
+        assert_eq!(change.changed_at, now);
+    }
+}
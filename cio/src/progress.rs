@@ -0,0 +1,147 @@
+//! Reporting how far along the long running syncs are.
+//!
+//! The Auth0 and Airtable syncs report each page or batch they finish to the reporter of
+//! the process. By default that logs a line every few seconds, which is what we want from
+//! cron.
+//! A CLI can set a reporter that draws progress bars instead, see `set_reporter`.
+use std::time::{Duration, Instant};
+
+use log::info;
+use tokio::sync::OnceCell;
+
+/// The reporter set for the process, if any.
+static REPORTER: OnceCell<Box<dyn ProgressReporter>> = OnceCell::const_new();
+
+/// How often the log reporter writes a line for a sync, at most.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Set the reporter the syncs of the process report to.
+///
+/// Only the first reporter set is used, returns false if one was already set.
+pub fn set_reporter<R: ProgressReporter + 'static>(reporter: R) -> bool {
+    REPORTER.set(Box::new(reporter)).is_ok()
+}
+
+/// Returns the reporter of the process, which logs unless another one was set.
+pub fn reporter() -> &'static dyn ProgressReporter {
+    match REPORTER.get() {
+        Some(reporter) => reporter.as_ref(),
+        None => &LogReporter,
+    }
+}
+
+/// How far along a sync is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    /// The pages or batches done so far.
+    pub pages: u64,
+    /// The records done so far.
+    pub records: u64,
+    /// The records the sync expects to do in all, if it knows, for example from the totals
+    /// of a listing.
+    pub total: Option<u64>,
+}
+
+impl ProgressUpdate {
+    /// Returns the share of the total done, from 0 to 100, if the total is known.
+    pub fn percent(&self) -> Option<u64> {
+        match self.total {
+            Some(0) => Some(100),
+            Some(total) => Some((self.records * 100 / total).min(100)),
+            None => None,
+        }
+    }
+}
+
+/// Starts tracking the progress of each sync.
+pub trait ProgressReporter: Send + Sync {
+    /// Returns the progress of a sync that is starting, named for humans.
+    fn start(&self, sync: &str) -> Box<dyn SyncProgress>;
+}
+
+/// The progress of a single sync.
+pub trait SyncProgress: Send {
+    /// The sync finished another page or batch.
+    fn update(&mut self, update: ProgressUpdate);
+
+    /// The sync is done, or stopped.
+    fn finish(&mut self);
+}
+
+/// Logs the progress of each sync, at most every ten seconds, and once it is done.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogReporter;
+
+impl ProgressReporter for LogReporter {
+    fn start(&self, sync: &str) -> Box<dyn SyncProgress> {
+        Box::new(LogProgress {
+            sync: sync.to_string(),
+            last: ProgressUpdate::default(),
+            logged_at: Instant::now(),
+        })
+    }
+}
+
+struct LogProgress {
+    sync: String,
+    last: ProgressUpdate,
+    logged_at: Instant,
+}
+
+impl LogProgress {
+    fn log(&mut self) {
+        self.logged_at = Instant::now();
+        match (self.last.total, self.last.percent()) {
+            (Some(total), Some(percent)) => info!(
+                "{}: {} pages, {} of {} records ({}%)",
+                self.sync, self.last.pages, self.last.records, total, percent
+            ),
+            _ => info!(
+                "{}: {} pages, {} records",
+                self.sync, self.last.pages, self.last.records
+            ),
+        }
+    }
+}
+
+impl SyncProgress for LogProgress {
+    fn update(&mut self, update: ProgressUpdate) {
+        self.last = update;
+        if self.logged_at.elapsed() >= LOG_INTERVAL {
+            self.log();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.log();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressUpdate;
+
+    #[test]
+    fn test_progress_update_percent() {
+        let update = ProgressUpdate {
+            pages: 2,
+            records: 150,
+            total: Some(600),
+        };
+        assert_eq!(update.percent(), Some(25));
+
+        // The totals of a listing are estimates, so the count can run past them.
+        let update = ProgressUpdate { records: 700, ..update };
+        assert_eq!(update.percent(), Some(100));
+
+        assert_eq!(
+            ProgressUpdate {
+                total: Some(0),
+                ..update
+            }
+            .percent(),
+            Some(100)
+        );
+        assert_eq!(ProgressUpdate { total: None, ..update }.percent(), None);
+    }
+}
@@ -4,12 +4,13 @@ use log::{info, warn};
 use sodiumoxide::{base64, crypto::hash};
 use std::convert::TryInto;
 
+#[cfg(feature = "github")]
+use crate::octorust_utils::{into_octorust_error, OctorustErrorKind};
 use crate::{
     app_config::AppConfig,
     companies::Company,
     configs::{ExternalServices, Group, User},
     db::Database,
-    octorust_utils::{into_octorust_error, OctorustErrorKind},
 };
 
 /// This trait defines how to implement a provider for a vendor that manages users
@@ -202,6 +203,7 @@ impl ProviderReadOps for ramp_minimal_api::RampClient {
     }
 }
 
+#[cfg(feature = "github")]
 #[async_trait]
 impl ProviderWriteOps for octorust::Client {
     async fn ensure_user(&self, _db: &Database, company: &Company, user: &User, _config: &AppConfig) -> Result<String> {
@@ -525,6 +527,7 @@ impl ProviderWriteOps for octorust::Client {
     }
 }
 
+#[cfg(feature = "github")]
 #[async_trait]
 impl ProviderReadOps for octorust::Client {
     type ProviderUser = octorust::types::SimpleUser;
@@ -553,6 +556,7 @@ impl ProviderReadOps for octorust::Client {
     }
 }
 
+#[cfg(feature = "gsuite")]
 #[async_trait]
 impl ProviderWriteOps for gsuite_api::Client {
     async fn ensure_user(&self, db: &Database, company: &Company, user: &User, config: &AppConfig) -> Result<String> {
@@ -848,6 +852,7 @@ impl ProviderWriteOps for gsuite_api::Client {
     }
 }
 
+#[cfg(feature = "gsuite")]
 #[async_trait]
 impl ProviderReadOps for gsuite_api::Client {
     type ProviderUser = gsuite_api::types::User;
@@ -1307,6 +1312,7 @@ impl ProviderReadOps for okta::Client {
     }
 }
 
+#[cfg(feature = "zoom")]
 #[async_trait]
 impl ProviderWriteOps for zoom_api::Client {
     async fn ensure_user(&self, db: &Database, _company: &Company, user: &User, _config: &AppConfig) -> Result<String> {
@@ -1491,6 +1497,7 @@ impl ProviderWriteOps for zoom_api::Client {
     }
 }
 
+#[cfg(feature = "zoom")]
 #[async_trait]
 impl ProviderReadOps for zoom_api::Client {
     type ProviderUser = zoom_api::types::UsersResponse;
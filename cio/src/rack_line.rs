@@ -1,3 +1,4 @@
+#[cfg(feature = "slack")]
 use anyhow::Result;
 use async_bb8_diesel::AsyncRunQueryDsl;
 use async_trait::async_trait;
@@ -7,12 +8,12 @@ use macros::db;
 use mailerlite::SubscriberFieldValue;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "slack")]
 use slack_chat_api::{FormattedMessage, MessageBlock, MessageBlockText, MessageBlockType, MessageType};
 
-use crate::{
-    airtable::AIRTABLE_RACK_LINE_SIGNUPS_TABLE, companies::Company, core::UpdateAirtableRecord, db::Database,
-    schema::rack_line_subscribers,
-};
+use crate::{airtable::AIRTABLE_RACK_LINE_SIGNUPS_TABLE, core::UpdateAirtableRecord, schema::rack_line_subscribers};
+#[cfg(feature = "slack")]
+use crate::{companies::Company, db::Database};
 
 /// The data type for a RackLineSubscriber.
 #[db {
@@ -67,6 +68,7 @@ impl NewRackLineSubscriber {
         HumanTime::from(dur)
     }
 
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification(&self, db: &Database, company: &Company) -> Result<()> {
         let mut msg: FormattedMessage = self.clone().into();
         // Set the channel.
@@ -79,6 +81,7 @@ impl NewRackLineSubscriber {
 }
 
 impl RackLineSubscriber {
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification(&self, db: &Database, company: &Company) -> Result<()> {
         let n: NewRackLineSubscriber = self.into();
         n.send_slack_notification(db, company).await
@@ -86,6 +89,7 @@ impl RackLineSubscriber {
 }
 
 /// Convert the mailing list signup into Slack message.
+#[cfg(feature = "slack")]
 impl From<NewRackLineSubscriber> for FormattedMessage {
     fn from(item: NewRackLineSubscriber) -> Self {
         let time = item.human_duration();
@@ -2,7 +2,6 @@
 
 use anyhow::{anyhow, bail, Result};
 use async_bb8_diesel::AsyncRunQueryDsl;
-use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use macros::db;
 use partial_struct::partial;
@@ -11,6 +10,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     airtable::AIRTABLE_RFD_TABLE,
+    airtable_bases::AirtableBase,
+    airtable_sync::{AirtableSyncable, ConflictPolicy},
     companies::Company,
     core::UpdateAirtableRecord,
     rfd::{GitHubRFDBranch, GitHubRFDReadmeLocation, GitHubRFDRepo, GitHubRFDUpdate, RFDContent},
@@ -25,6 +26,8 @@ use crate::{
 #[db {
     target_struct = "NewRFD",
     new_struct_name = "RFD",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_RFD_TABLE",
     match_on = {
         "number" = "i32",
     }
@@ -91,6 +94,36 @@ pub struct NewRFD {
     pub cio_company_id: i32,
 }
 
+impl AirtableSyncable for RFD {
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] = &[
+        ("milestones", ConflictPolicy::Airtable),
+        ("relevant_components", ConflictPolicy::Airtable),
+    ];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::Roadmap;
+
+    fn unique_key(fields: &NewRFD) -> String {
+        fields.number.to_string()
+    }
+
+    /// The table links to the rendered RFD, the contents are too large for an Airtable cell.
+    fn airtable_fields(&self) -> NewRFD {
+        let mut fields: NewRFD = self.into();
+        fields.html = String::new();
+        fields.content = String::new();
+
+        fields
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewRFD) {
+        match field {
+            "milestones" => self.milestones = airtable.milestones.clone(),
+            "relevant_components" => self.relevant_components = airtable.relevant_components.clone(),
+            _ => (),
+        }
+    }
+}
+
 pub struct RemoteRFD {
     pub rfd: NewRFD,
     pub location: GitHubRFDReadmeLocation,
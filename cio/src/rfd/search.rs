@@ -89,7 +89,7 @@ impl RFDSearchIndex {
     pub fn default_client() -> Result<MeiliClient> {
         Ok(MeiliClient::new(
             std::env::var("MEILI_URL")?,
-            std::env::var("MEILI_KEY")?,
+            crate::secrets::get("MEILI_KEY")?,
         ))
     }
 
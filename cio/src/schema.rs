@@ -17,6 +17,35 @@ table! {
     }
 }
 
+table! {
+    airtable_list_offsets (id) {
+        id -> Int4,
+        base_id -> Varchar,
+        table_name -> Varchar,
+        view -> Varchar,
+        formula -> Varchar,
+        next_offset -> Varchar,
+        updated_at -> Timestamptz,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
+table! {
+    airtable_sync_conflicts (id) {
+        id -> Int4,
+        table_name -> Varchar,
+        record_key -> Varchar,
+        field -> Varchar,
+        database_value -> Varchar,
+        airtable_value -> Varchar,
+        resolution -> Varchar,
+        detected_at -> Timestamptz,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
 table! {
     api_tokens (id) {
         id -> Int4,
@@ -169,6 +198,7 @@ table! {
         link_to_reviews -> Array<Text>,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
     }
 }
 
@@ -208,6 +238,39 @@ table! {
     }
 }
 
+table! {
+    auth_anomalies (id) {
+        id -> Int4,
+        kind -> Varchar,
+        user_id -> Varchar,
+        email -> Varchar,
+        log_id -> Varchar,
+        date -> Timestamptz,
+        ip -> Varchar,
+        country -> Varchar,
+        city -> Varchar,
+        description -> Varchar,
+        tenant -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
+table! {
+    auth_connection_stats (id) {
+        id -> Int4,
+        date -> Date,
+        connection_id -> Varchar,
+        connection_name -> Varchar,
+        strategy -> Varchar,
+        user_count -> Int8,
+        tenant -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
 table! {
     auth_user_logins (id) {
         id -> Int4,
@@ -219,6 +282,8 @@ table! {
         client_id -> Varchar,
         client_name -> Varchar,
         ip -> Varchar,
+        country -> Varchar,
+        city -> Varchar,
         hostname -> Varchar,
         user_id -> Varchar,
         user_name -> Varchar,
@@ -230,7 +295,45 @@ table! {
         log_id -> Varchar,
         is_mobile -> Bool,
         user_agent -> Varchar,
+        event -> Varchar,
+        outcome -> Varchar,
+        browser -> Varchar,
+        os -> Varchar,
+        device -> Varchar,
         link_to_auth_user -> Array<Text>,
+        tenant -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
+table! {
+    auth_user_roles (id) {
+        id -> Int4,
+        user_id -> Varchar,
+        email -> Varchar,
+        role_id -> Varchar,
+        role_name -> Varchar,
+        role_description -> Varchar,
+        permissions -> Array<Text>,
+        link_to_auth_user -> Array<Text>,
+        tenant -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
+table! {
+    auth_user_sync_checkpoints (id) {
+        id -> Int4,
+        tenant -> Varchar,
+        q -> Varchar,
+        next_page -> Int4,
+        last_user_id -> Varchar,
+        started_at -> Timestamptz,
+        updated_at -> Timestamptz,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
     }
@@ -256,13 +359,21 @@ table! {
         updated_at -> Timestamptz,
         last_login -> Timestamptz,
         last_application_accessed -> Varchar,
+        top_applications -> Array<Text>,
         last_ip -> Varchar,
+        last_ip_country -> Varchar,
+        last_ip_city -> Varchar,
         logins_count -> Int4,
         link_to_people -> Array<Text>,
         link_to_auth_user_logins -> Array<Text>,
         link_to_page_views -> Array<Text>,
+        deleted_at -> Nullable<Timestamptz>,
+        duplicate_of -> Varchar,
+        modified_at -> Timestamptz,
+        tenant -> Varchar,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
     }
 }
 
@@ -353,6 +464,17 @@ table! {
     }
 }
 
+table! {
+    company_normalization_rules (id) {
+        id -> Int4,
+        kind -> Varchar,
+        pattern -> Varchar,
+        company -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
 table! {
     companys (id) {
         id -> Int4,
@@ -442,6 +564,34 @@ table! {
     }
 }
 
+table! {
+    email_verification_reminders (id) {
+        id -> Int4,
+        user_id -> Varchar,
+        email -> Varchar,
+        job_id -> Varchar,
+        reminders_sent -> Int4,
+        last_sent_at -> Timestamptz,
+        tenant -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
+table! {
+    envelope_status_changes (id) {
+        id -> Int4,
+        envelope_id -> Varchar,
+        kind -> Varchar,
+        applicant_email -> Varchar,
+        from_status -> Varchar,
+        to_status -> Varchar,
+        changed_at -> Timestamptz,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
 table! {
     expensed_items (id) {
         id -> Int4,
@@ -479,6 +629,21 @@ table! {
     }
 }
 
+table! {
+    github_org_members (id) {
+        id -> Int4,
+        login -> Varchar,
+        github_id -> Int8,
+        role -> Varchar,
+        teams -> Array<Text>,
+        site_admin -> Bool,
+        link_to_auth_users -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
 table! {
     github_repos (id) {
         id -> Int4,
@@ -578,6 +743,59 @@ table! {
     }
 }
 
+table! {
+    gsuite_directory_groups (id) {
+        id -> Int4,
+        gsuite_id -> Varchar,
+        email -> Varchar,
+        name -> Varchar,
+        description -> Varchar,
+        aliases -> Array<Text>,
+        members -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
+table! {
+    gsuite_directory_users (id) {
+        id -> Int4,
+        gsuite_id -> Varchar,
+        primary_email -> Varchar,
+        full_name -> Varchar,
+        aliases -> Array<Text>,
+        org_unit_path -> Varchar,
+        is_admin -> Bool,
+        suspended -> Bool,
+        last_login_at -> Nullable<Timestamptz>,
+        created_at -> Nullable<Timestamptz>,
+        groups -> Array<Text>,
+        link_to_auth_users -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
+table! {
+    gusto_employees (id) {
+        id -> Int4,
+        gusto_id -> Varchar,
+        first_name -> Varchar,
+        last_name -> Varchar,
+        email -> Varchar,
+        work_email -> Varchar,
+        title -> Varchar,
+        department -> Varchar,
+        start_date -> Nullable<Date>,
+        terminated -> Bool,
+        link_to_people -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
 table! {
     inbound_shipments (id) {
         id -> Int4,
@@ -595,6 +813,20 @@ table! {
         notes -> Varchar,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
+table! {
+    ip_locations (id) {
+        id -> Int4,
+        ip -> Varchar,
+        country -> Varchar,
+        region -> Varchar,
+        city -> Varchar,
+        resolved_at -> Timestamptz,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
     }
 }
 
@@ -669,6 +901,7 @@ table! {
         link_to_people -> Array<Text>,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
     }
 }
 
@@ -710,6 +943,7 @@ table! {
         link_to_package_pickup -> Array<Text>,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
     }
 }
 
@@ -734,6 +968,21 @@ table! {
     }
 }
 
+table! {
+    page_view_stats (id) {
+        id -> Int4,
+        date -> Date,
+        domain -> Varchar,
+        path -> Varchar,
+        user_email -> Varchar,
+        page_link -> Varchar,
+        views -> Int8,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
 table! {
     page_views (id) {
         id -> Int4,
@@ -745,6 +994,7 @@ table! {
         link_to_auth_user -> Array<Text>,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
     }
 }
 
@@ -771,6 +1021,27 @@ table! {
     }
 }
 
+table! {
+    ramp_expenses (id) {
+        id -> Int4,
+        ramp_id -> Varchar,
+        kind -> Varchar,
+        amount -> Float4,
+        currency -> Varchar,
+        merchant_name -> Varchar,
+        category_name -> Varchar,
+        state -> Varchar,
+        memo -> Varchar,
+        employee_email -> Varchar,
+        time -> Timestamptz,
+        receipts -> Array<Text>,
+        link_to_people -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
 table! {
     recorded_meetings (id) {
         id -> Int4,
@@ -817,6 +1088,45 @@ table! {
         labels -> Array<Text>,
         cio_company_id -> Int4,
         airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
+table! {
+    shipment_status_changes (id) {
+        id -> Int4,
+        direction -> Varchar,
+        carrier -> Varchar,
+        tracking_number -> Varchar,
+        from_status -> Varchar,
+        to_status -> Varchar,
+        status_details -> Varchar,
+        changed_at -> Timestamptz,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+    }
+}
+
+table! {
+    slack_users (id) {
+        id -> Int4,
+        slack_id -> Varchar,
+        name -> Varchar,
+        real_name -> Varchar,
+        display_name -> Varchar,
+        email -> Varchar,
+        title -> Varchar,
+        is_admin -> Bool,
+        is_owner -> Bool,
+        is_guest -> Bool,
+        deleted -> Bool,
+        has_2fa -> Bool,
+        tz -> Varchar,
+        link_to_auth_users -> Array<Text>,
+        link_to_people -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
     }
 }
 
@@ -881,6 +1191,24 @@ table! {
     }
 }
 
+table! {
+    sync_runs (id) {
+        id -> Int4,
+        job -> Varchar,
+        status -> Varchar,
+        started_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+        records_created -> Int4,
+        records_updated -> Int4,
+        records_deleted -> Int4,
+        errors -> Int4,
+        error -> Varchar,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
 table! {
     users (id) {
         id -> Int4,
@@ -941,75 +1269,134 @@ table! {
     }
 }
 
+table! {
+    zoom_users (id) {
+        id -> Int4,
+        zoom_id -> Varchar,
+        email -> Varchar,
+        first_name -> Varchar,
+        last_name -> Varchar,
+        department -> Varchar,
+        license_type -> Varchar,
+        status -> Varchar,
+        last_login_at -> Nullable<Timestamptz>,
+        inactive_license -> Bool,
+        link_to_auth_users -> Array<Text>,
+        cio_company_id -> Int4,
+        airtable_record_id -> Varchar,
+        airtable_record_url -> Varchar,
+    }
+}
+
 joinable!(accounts_payables -> companys (cio_company_id));
+joinable!(airtable_list_offsets -> companys (cio_company_id));
+joinable!(airtable_sync_conflicts -> companys (cio_company_id));
 joinable!(api_tokens -> companys (auth_company_id));
 joinable!(applicant_interviews -> companys (cio_company_id));
 joinable!(applicant_reviewers -> companys (cio_company_id));
 joinable!(applicant_reviews -> companys (cio_company_id));
 joinable!(applicants -> companys (cio_company_id));
 joinable!(asset_items -> companys (cio_company_id));
+joinable!(auth_anomalies -> companys (cio_company_id));
+joinable!(auth_connection_stats -> companys (cio_company_id));
 joinable!(auth_user_logins -> companys (cio_company_id));
+joinable!(auth_user_roles -> companys (cio_company_id));
+joinable!(auth_user_sync_checkpoints -> companys (cio_company_id));
 joinable!(auth_users -> companys (cio_company_id));
 joinable!(barcode_scans -> companys (cio_company_id));
 joinable!(bookings -> companys (cio_company_id));
 joinable!(buildings -> companys (cio_company_id));
 joinable!(certificates -> companys (cio_company_id));
+joinable!(company_normalization_rules -> companys (cio_company_id));
 joinable!(credit_card_transactions -> companys (cio_company_id));
+joinable!(email_verification_reminders -> companys (cio_company_id));
+joinable!(envelope_status_changes -> companys (cio_company_id));
 joinable!(expensed_items -> companys (cio_company_id));
 joinable!(functions -> companys (cio_company_id));
+joinable!(github_org_members -> companys (cio_company_id));
 joinable!(github_repos -> companys (cio_company_id));
 joinable!(groups -> companys (cio_company_id));
+joinable!(gsuite_directory_groups -> companys (cio_company_id));
+joinable!(gsuite_directory_users -> companys (cio_company_id));
+joinable!(gusto_employees -> companys (cio_company_id));
 joinable!(inbound_shipments -> companys (cio_company_id));
+joinable!(ip_locations -> companys (cio_company_id));
 joinable!(journal_club_meetings -> companys (cio_company_id));
 joinable!(journal_club_papers -> companys (cio_company_id));
 joinable!(links -> companys (cio_company_id));
 joinable!(mailing_list_subscribers -> companys (cio_company_id));
 joinable!(outbound_shipments -> companys (cio_company_id));
 joinable!(package_pickups -> companys (cio_company_id));
+joinable!(page_view_stats -> companys (cio_company_id));
 joinable!(page_views -> companys (cio_company_id));
 joinable!(rack_line_subscribers -> companys (cio_company_id));
+joinable!(ramp_expenses -> companys (cio_company_id));
 joinable!(recorded_meetings -> companys (cio_company_id));
 joinable!(resources -> companys (cio_company_id));
 joinable!(rfds -> companys (cio_company_id));
+joinable!(shipment_status_changes -> companys (cio_company_id));
+joinable!(slack_users -> companys (cio_company_id));
 joinable!(software_vendors -> companys (cio_company_id));
 joinable!(swag_inventory_items -> companys (cio_company_id));
 joinable!(swag_items -> companys (cio_company_id));
+joinable!(sync_runs -> companys (cio_company_id));
 joinable!(users -> companys (cio_company_id));
+joinable!(zoom_users -> companys (cio_company_id));
 
 allow_tables_to_appear_in_same_query!(
     accounts_payables,
+    airtable_list_offsets,
+    airtable_sync_conflicts,
     api_tokens,
     applicant_interviews,
     applicant_reviewers,
     applicant_reviews,
     applicants,
     asset_items,
+    auth_anomalies,
+    auth_connection_stats,
     auth_user_logins,
+    auth_user_roles,
+    auth_user_sync_checkpoints,
     auth_users,
     barcode_scans,
     bookings,
     buildings,
     certificates,
+    company_normalization_rules,
     companys,
     credit_card_transactions,
+    email_verification_reminders,
+    envelope_status_changes,
     expensed_items,
     functions,
+    github_org_members,
     github_repos,
     groups,
+    gsuite_directory_groups,
+    gsuite_directory_users,
+    gusto_employees,
     inbound_shipments,
+    ip_locations,
     journal_club_meetings,
     journal_club_papers,
     links,
     mailing_list_subscribers,
     outbound_shipments,
     package_pickups,
+    page_view_stats,
     page_views,
     rack_line_subscribers,
+    ramp_expenses,
     recorded_meetings,
     resources,
     rfds,
+    shipment_status_changes,
+    slack_users,
     software_vendors,
     swag_inventory_items,
     swag_items,
+    sync_runs,
     users,
+    zoom_users,
 );
@@ -0,0 +1,361 @@
+//! The credentials of the APIs we sync with, read from a secret manager.
+//!
+//! The secrets are resolved once at startup by `init`, from the provider named by
+//! `CIO_SECRETS_PROVIDER`:
+//!
+//! - `env`, the default, reads them from the environment.
+//! - `gcp` reads them from GCP Secret Manager, in the project `CIO_SECRETS_GCP_PROJECT`,
+//!   authenticated as the instance.
+//! - `vault` reads them from the KV v2 secret `CIO_SECRETS_VAULT_PATH` (for example
+//!   `secret/cio`) of the Vault at `VAULT_ADDR`, with the token in `VAULT_TOKEN`.
+//!
+//! The code that needs a credential calls `get` with its name, like `CIO_AUTH0_CLIENT_ID`.
+//! Besides `SECRET_NAMES`, the comma separated names in `CIO_SECRET_NAMES` are resolved,
+//! for example the credentials of additional Auth0 tenants.
+//! Secrets that were not resolved, or everything when `init` was never called (in tests
+//! and one off commands), fall back to the environment.
+use std::{collections::BTreeMap, env, fmt};
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use log::{info, warn};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+use crate::error::CioError;
+
+/// The secrets resolved at startup.
+pub const SECRET_NAMES: &[&str] = &[
+    "CIO_AUTH0_CLIENT_ID",
+    "CIO_AUTH0_CLIENT_SECRET",
+    "CIO_AUTH0_DOMAIN",
+    "GH_APP_ID",
+    "GH_PRIVATE_KEY",
+    "MAILCHIMP_API_KEY",
+    "MAILERLITE_API_KEY",
+    "MEILI_KEY",
+    "RAMP_CLIENT_ID",
+    "RAMP_CLIENT_SECRET",
+];
+
+/// The OAuth scope for reading from GCP Secret Manager.
+const GCP_SCOPES: &[&str] = &["https://www.googleapis.com/auth/cloud-platform"];
+
+static SECRETS: OnceCell<Secrets> = OnceCell::const_new();
+
+/// Where the secrets are read from.
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// The name of the provider, for the logs.
+    fn name(&self) -> &str;
+
+    /// Returns the value of the secret, `None` if the provider does not have it.
+    async fn get(&self, name: &str) -> Result<Option<String>>;
+
+    /// Returns the values of the secrets the provider has, by name.
+    async fn get_all(&self, names: &[&str]) -> Result<BTreeMap<String, String>> {
+        let mut values: BTreeMap<String, String> = Default::default();
+        for name in names {
+            if let Some(value) = self.get(name).await? {
+                values.insert(name.to_string(), value);
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// Reads the secrets from the environment.
+#[derive(Debug, Default, Clone)]
+pub struct EnvSecrets;
+
+#[async_trait]
+impl SecretProvider for EnvSecrets {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(env::var(name).ok())
+    }
+}
+
+/// Reads the latest version of each secret from GCP Secret Manager.
+#[derive(Debug, Clone)]
+pub struct GcpSecretManager {
+    project: String,
+    client: Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessSecretVersionResponse {
+    payload: SecretPayload,
+}
+
+#[derive(Debug, Deserialize)]
+struct SecretPayload {
+    /// The value, base64 encoded.
+    data: String,
+}
+
+impl GcpSecretManager {
+    pub fn new<P: ToString>(project: P) -> Self {
+        GcpSecretManager {
+            project: project.to_string(),
+            client: Client::new(),
+        }
+    }
+
+    async fn token(&self) -> Result<String> {
+        use yup_oauth2::authenticator::ApplicationDefaultCredentialsTypes;
+
+        let opts = yup_oauth2::ApplicationDefaultCredentialsFlowOpts::default();
+        let token = match yup_oauth2::ApplicationDefaultCredentialsAuthenticator::builder(opts).await {
+            ApplicationDefaultCredentialsTypes::InstanceMetadata(auth) => auth.build().await?.token(GCP_SCOPES).await?,
+            ApplicationDefaultCredentialsTypes::ServiceAccount(auth) => auth.build().await?.token(GCP_SCOPES).await?,
+        };
+
+        Ok(token
+            .token()
+            .ok_or_else(|| anyhow!("no token for GCP Secret Manager"))?
+            .to_string())
+    }
+
+    async fn access(&self, token: &str, name: &str) -> Result<Option<String>> {
+        let resp = self
+            .client
+            .get(format!(
+                "https://secretmanager.googleapis.com/v1/projects/{}/secrets/{}/versions/latest:access",
+                self.project, name
+            ))
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            StatusCode::NOT_FOUND => return Ok(None),
+            s => bail!("reading secret `{}` failed, status: {}", name, s),
+        }
+
+        let value: AccessSecretVersionResponse = resp.json().await?;
+        Ok(Some(String::from_utf8(base64::decode(value.payload.data)?)?))
+    }
+}
+
+#[async_trait]
+impl SecretProvider for GcpSecretManager {
+    fn name(&self) -> &str {
+        "gcp"
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        self.access(&self.token().await?, name).await
+    }
+
+    async fn get_all(&self, names: &[&str]) -> Result<BTreeMap<String, String>> {
+        // One token for every secret.
+        let token = self.token().await?;
+
+        let mut values: BTreeMap<String, String> = Default::default();
+        for name in names {
+            if let Some(value) = self.access(&token, name).await? {
+                values.insert(name.to_string(), value);
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// Reads the secrets from the keys of a KV v2 secret in Vault.
+#[derive(Clone)]
+pub struct VaultSecrets {
+    addr: String,
+    token: String,
+    /// The mount and path of the secret, for example `secret/cio`.
+    path: String,
+    client: Client,
+}
+
+impl fmt::Debug for VaultSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultSecrets")
+            .field("addr", &self.addr)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultResponse {
+    data: VaultData,
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultData {
+    data: BTreeMap<String, String>,
+}
+
+impl VaultSecrets {
+    pub fn new<A, T, P>(addr: A, token: T, path: P) -> Self
+    where
+        A: ToString,
+        T: ToString,
+        P: ToString,
+    {
+        VaultSecrets {
+            addr: addr.to_string().trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            path: path.to_string().trim_matches('/').to_string(),
+            client: Client::new(),
+        }
+    }
+
+    /// Returns the URL of the secret. KV v2 puts `data` between the mount and the path.
+    fn url(&self) -> String {
+        match self.path.split_once('/') {
+            Some((mount, path)) => format!("{}/v1/{}/data/{}", self.addr, mount, path),
+            None => format!("{}/v1/{}/data", self.addr, self.path),
+        }
+    }
+
+    async fn read(&self) -> Result<BTreeMap<String, String>> {
+        let resp = self
+            .client
+            .get(self.url())
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => (),
+            StatusCode::NOT_FOUND => return Ok(Default::default()),
+            s => bail!("reading vault secret `{}` failed, status: {}", self.path, s),
+        }
+
+        let secret: VaultResponse = resp.json().await?;
+        Ok(secret.data.data)
+    }
+}
+
+#[async_trait]
+impl SecretProvider for VaultSecrets {
+    fn name(&self) -> &str {
+        "vault"
+    }
+
+    async fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.read().await?.remove(name))
+    }
+
+    async fn get_all(&self, names: &[&str]) -> Result<BTreeMap<String, String>> {
+        // The keys all live in the one secret, read it once.
+        let mut values = self.read().await?;
+        values.retain(|name, _| names.contains(&name.as_str()));
+
+        Ok(values)
+    }
+}
+
+/// Returns the provider named by `CIO_SECRETS_PROVIDER`.
+pub fn provider_from_env() -> Result<Box<dyn SecretProvider>, CioError> {
+    let var = |name: &str| env::var(name).map_err(|_| CioError::Config(format!("{} must be set", name)));
+
+    match env::var("CIO_SECRETS_PROVIDER").unwrap_or_default().as_str() {
+        "" | "env" => Ok(Box::new(EnvSecrets)),
+        "gcp" => Ok(Box::new(GcpSecretManager::new(var("CIO_SECRETS_GCP_PROJECT")?))),
+        "vault" => Ok(Box::new(VaultSecrets::new(
+            var("VAULT_ADDR")?,
+            var("VAULT_TOKEN")?,
+            var("CIO_SECRETS_VAULT_PATH")?,
+        ))),
+        other => Err(CioError::Config(format!(
+            "CIO_SECRETS_PROVIDER `{}` is not one of `env`, `gcp` or `vault`",
+            other
+        ))),
+    }
+}
+
+/// The secrets resolved from a provider, by name.
+#[derive(Default, Clone)]
+pub struct Secrets {
+    values: BTreeMap<String, String>,
+}
+
+// Only the names, the values must never end up in a log.
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.values.keys()).finish()
+    }
+}
+
+impl Secrets {
+    /// Read the secrets from the provider. Secrets the provider does not have are left
+    /// out, `get` falls back to the environment for them.
+    pub async fn resolve(provider: &dyn SecretProvider, names: &[&str]) -> Result<Self, CioError> {
+        let values = provider
+            .get_all(names)
+            .await
+            .map_err(|e| CioError::Config(format!("reading the secrets from `{}` failed: {}", provider.name(), e)))?;
+
+        for name in names {
+            if !values.contains_key(*name) {
+                warn!("secret `{}` is not in `{}`", name, provider.name());
+            }
+        }
+
+        Ok(Secrets { values })
+    }
+
+    /// Returns the value of the secret, from the provider or else the environment.
+    pub fn get(&self, name: &str) -> Result<String> {
+        match self.values.get(name) {
+            Some(value) => Ok(value.to_string()),
+            None => env::var(name).map_err(|_| anyhow!("secret `{}` is not set", name)),
+        }
+    }
+}
+
+/// Resolve the secrets from the provider named by `CIO_SECRETS_PROVIDER`. Call this once
+/// at startup, before anything reads a secret. Calling it again does nothing.
+pub async fn init() -> Result<(), CioError> {
+    SECRETS
+        .get_or_try_init(|| async {
+            let extra = env::var("CIO_SECRET_NAMES").unwrap_or_default();
+            let mut names = SECRET_NAMES.to_vec();
+            names.extend(extra.split(',').map(str::trim).filter(|n| !n.is_empty()));
+
+            let provider = provider_from_env()?;
+            let secrets = Secrets::resolve(provider.as_ref(), &names).await?;
+            info!("resolved {} secrets from `{}`", secrets.values.len(), provider.name());
+
+            Ok::<_, CioError>(secrets)
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the value of a secret, see the module docs.
+pub fn get(name: &str) -> Result<String> {
+    match SECRETS.get() {
+        Some(secrets) => secrets.get(name),
+        None => Secrets::default().get(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VaultSecrets;
+
+    #[test]
+    fn test_vault_url() {
+        let vault = VaultSecrets::new("https://vault.internal:8200/", "token", "secret/cio/prod");
+        assert_eq!(vault.url(), "https://vault.internal:8200/v1/secret/data/cio/prod");
+
+        let vault = VaultSecrets::new("https://vault.internal:8200", "token", "/kv/");
+        assert_eq!(vault.url(), "https://vault.internal:8200/v1/kv/data");
+    }
+}
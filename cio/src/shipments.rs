@@ -3,11 +3,10 @@ use std::convert::From;
 
 use anyhow::{bail, Result};
 use async_bb8_diesel::AsyncRunQueryDsl;
-use async_trait::async_trait;
 use chrono::{naive::NaiveDate, offset::Utc, DateTime, Duration, NaiveTime, TimeZone};
 use chrono_humanize::HumanTime;
 use google_geocode::Geocode;
-use log::{info, warn};
+use log::{error, info, warn};
 use macros::db;
 use reqwest::StatusCode;
 use schemars::JsonSchema;
@@ -20,17 +19,22 @@ use slack_chat_api::{
 
 use crate::{
     airtable::{AIRTABLE_INBOUND_TABLE, AIRTABLE_OUTBOUND_TABLE, AIRTABLE_PACKAGE_PICKUPS_TABLE},
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable, AirtableSyncable, ConflictPolicy, SyncSummary},
     companies::Company,
     configs::User,
-    core::UpdateAirtableRecord,
+    core::{DryRun, UpdateAirtableRecord},
     db::Database,
+    error::CioError,
     printer::Printer,
-    schema::{inbound_shipments, outbound_shipments, package_pickups},
+    schema::{inbound_shipments, outbound_shipments, package_pickups, shipment_status_changes},
 };
 
 /// The data type for an inbound shipment.
 #[db {
     new_struct_name = "InboundShipment",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_INBOUND_TABLE",
     match_on = {
         "carrier" = "String",
         "tracking_number" = "String",
@@ -167,11 +171,30 @@ impl InboundShipment {
     pub async fn expand(&mut self, db: &Database) -> Result<()> {
         let mut ns: NewInboundShipment = self.clone().into();
         ns.expand().await?;
-        ns.upsert(db).await?;
+        *self = ns.upsert(db).await?;
         Ok(())
     }
 }
 
+impl AirtableSyncable for InboundShipment {
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] =
+        &[("name", ConflictPolicy::Airtable), ("notes", ConflictPolicy::Airtable)];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::Shipments;
+
+    fn unique_key(fields: &NewInboundShipment) -> String {
+        format!("{}/{}", fields.carrier, fields.tracking_number)
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewInboundShipment) {
+        match field {
+            "name" => self.name = airtable.name.to_string(),
+            "notes" => self.notes = airtable.notes.to_string(),
+            _ => (),
+        }
+    }
+}
+
 fn get_color_based_on_tracking_status(s: &str) -> String {
     let status = s.to_lowercase().trim().to_string();
 
@@ -326,6 +349,8 @@ impl InboundShipment {
 /// The data type for an outbound shipment.
 #[db {
     new_struct_name = "OutboundShipment",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_OUTBOUND_TABLE",
     match_on = {
         "carrier" = "String",
         "tracking_number" = "String",
@@ -666,6 +691,30 @@ impl From<OutboundShipment> for FormattedMessage {
     }
 }
 
+impl AirtableSyncable for OutboundShipment {
+    // The notes, local pickups and links to the package pickups are kept in Airtable.
+    const AIRTABLE_FIELD_POLICIES: &'static [(&'static str, ConflictPolicy)] = &[
+        ("notes", ConflictPolicy::Airtable),
+        ("local_pickup", ConflictPolicy::Airtable),
+        ("link_to_package_pickup", ConflictPolicy::Airtable),
+    ];
+
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::Shipments;
+
+    fn unique_key(fields: &NewOutboundShipment) -> String {
+        format!("{}/{}", fields.carrier, fields.tracking_number)
+    }
+
+    fn pull_airtable_field(&mut self, field: &str, airtable: &NewOutboundShipment) {
+        match field {
+            "notes" => self.notes = airtable.notes.to_string(),
+            "local_pickup" => self.local_pickup = airtable.local_pickup,
+            "link_to_package_pickup" => self.link_to_package_pickup = airtable.link_to_package_pickup.clone(),
+            _ => (),
+        }
+    }
+}
+
 /// The data type for a shipment pickup.
 #[db {
     new_struct_name = "PackagePickup",
@@ -1557,6 +1606,178 @@ pub async fn refresh_outbound_shipments(db: &Database, company: &Company) -> Res
     Ok(())
 }
 
+/// A change of the tracking status of a shipment, as reported by the carrier.
+#[db {
+    new_struct_name = "ShipmentStatusChange",
+    match_on = {
+        "tracking_number" = "String",
+        "changed_at" = "DateTime<Utc>",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = shipment_status_changes)]
+pub struct NewShipmentStatusChange {
+    /// `inbound` or `outbound`.
+    pub direction: String,
+    pub carrier: String,
+    pub tracking_number: String,
+    /// The tracking status before the change, empty for the first status we saw.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub from_status: String,
+    pub to_status: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub status_details: String,
+    pub changed_at: DateTime<Utc>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl NewShipmentStatusChange {
+    /// Returns the change between the tracking status we had and the one we got from the
+    /// carrier, if the status changed.
+    #[allow(clippy::too_many_arguments)]
+    fn between(
+        direction: &str,
+        carrier: &str,
+        tracking_number: &str,
+        from_status: &str,
+        to_status: &str,
+        status_details: &str,
+        changed_at: DateTime<Utc>,
+        cio_company_id: i32,
+    ) -> Option<Self> {
+        if to_status.is_empty() || from_status == to_status {
+            return None;
+        }
+
+        Some(NewShipmentStatusChange {
+            direction: direction.to_string(),
+            carrier: carrier.to_string(),
+            tracking_number: tracking_number.to_string(),
+            from_status: from_status.to_string(),
+            to_status: to_status.to_string(),
+            status_details: status_details.to_string(),
+            changed_at,
+            cio_company_id,
+        })
+    }
+}
+
+/// Refresh the tracking status of the shipments that are not delivered yet, record the
+/// status changes, and sync the shipments to Airtable.
+///
+/// This runs on every sync, so the statuses stay fresh even when a tracking webhook
+/// from Shippo is missed.
+pub async fn refresh_shipment_tracking(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    if company.airtable_base_id_shipments.is_empty() {
+        return Ok(SyncSummary::default());
+    }
+
+    let outbound = outbound_shipments::dsl::outbound_shipments
+        .filter(
+            outbound_shipments::dsl::cio_company_id
+                .eq(company.id)
+                .and(outbound_shipments::dsl::tracking_status.ne("DELIVERED".to_string()))
+                .and(outbound_shipments::dsl::tracking_number.ne("".to_string())),
+        )
+        .load_async::<OutboundShipment>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+    let inbound = inbound_shipments::dsl::inbound_shipments
+        .filter(
+            inbound_shipments::dsl::cio_company_id
+                .eq(company.id)
+                .and(inbound_shipments::dsl::tracking_status.ne("DELIVERED".to_string()))
+                .and(inbound_shipments::dsl::tracking_number.ne("".to_string())),
+        )
+        .load_async::<InboundShipment>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+    info!(
+        "refreshing the tracking status of {} outbound and {} inbound shipments",
+        outbound.len(),
+        inbound.len()
+    );
+
+    let mut summary = SyncSummary::default();
+    if dry_run.is_enabled() {
+        info!(
+            "[dry-run] would refresh the tracking status of {} shipments",
+            outbound.len() + inbound.len()
+        );
+    } else {
+        let mut changes: Vec<NewShipmentStatusChange> = Default::default();
+        for mut shipment in outbound {
+            let from_status = shipment.tracking_status.to_string();
+            if let Err(e) = shipment.expand(db).await {
+                error!(
+                    "refreshing the tracking of outbound shipment `{}` failed: {}",
+                    shipment.tracking_number, e
+                );
+                summary.errors += 1;
+                continue;
+            }
+
+            changes.extend(NewShipmentStatusChange::between(
+                "outbound",
+                &shipment.carrier,
+                &shipment.tracking_number,
+                &from_status,
+                &shipment.tracking_status,
+                &shipment.messages,
+                Utc::now(),
+                company.id,
+            ));
+        }
+        for mut shipment in inbound {
+            let from_status = shipment.tracking_status.to_string();
+            if let Err(e) = shipment.expand(db).await {
+                error!(
+                    "refreshing the tracking of inbound shipment `{}` failed: {}",
+                    shipment.tracking_number, e
+                );
+                summary.errors += 1;
+                continue;
+            }
+
+            changes.extend(NewShipmentStatusChange::between(
+                "inbound",
+                &shipment.carrier,
+                &shipment.tracking_number,
+                &from_status,
+                &shipment.tracking_status,
+                &shipment.messages,
+                Utc::now(),
+                company.id,
+            ));
+        }
+
+        for change in changes {
+            info!(
+                "shipment `{}` went from `{}` to `{}`",
+                change.tracking_number, change.from_status, change.to_status
+            );
+            if let Err(e) = change.upsert(db).await {
+                error!(
+                    "saving the status change of shipment `{}` failed: {}",
+                    change.tracking_number, e
+                );
+                summary.errors += 1;
+            }
+        }
+    }
+
+    summary += sync_to_airtable::<OutboundShipment>(db, company, dry_run).await?;
+    summary += sync_to_airtable::<InboundShipment>(db, company, dry_run).await?;
+
+    Ok(summary)
+}
+
 pub fn clean_carrier_name(s: &str) -> String {
     let l = s.to_lowercase();
     if l == "ups" || l.starts_with("ups") {
@@ -1668,3 +1889,30 @@ pub fn clean_address_string(s: &str) -> String {
 
     s.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_change_between() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+
+        let change =
+            NewShipmentStatusChange::between("outbound", "USPS", "9400", "PRE_TRANSIT", "TRANSIT", "Accepted", now, 1)
+                .unwrap();
+        assert_eq!(change.from_status, "PRE_TRANSIT");
+        assert_eq!(change.to_status, "TRANSIT");
+        assert_eq!(change.status_details, "Accepted");
+        assert_eq!(change.changed_at, now);
+
+        // The first status we see is a change too.
+        assert!(NewShipmentStatusChange::between("inbound", "UPS", "1Z", "", "TRANSIT", "", now, 1).is_some());
+
+        // No change, or no status from the carrier.
+        assert!(
+            NewShipmentStatusChange::between("outbound", "USPS", "9400", "TRANSIT", "TRANSIT", "", now, 1).is_none()
+        );
+        assert!(NewShipmentStatusChange::between("outbound", "USPS", "9400", "TRANSIT", "", "", now, 1).is_none());
+    }
+}
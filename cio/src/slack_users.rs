@@ -0,0 +1,212 @@
+#![allow(clippy::from_over_into)]
+//! A mirror of the members of the Slack workspace.
+//!
+//! Each member is linked to the auth users and the people with the same email, so the
+//! People table in Airtable has the Slack ids to send notifications to.
+use anyhow::Result;
+use async_bb8_diesel::AsyncRunQueryDsl;
+use log::info;
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable::AIRTABLE_SLACK_USERS_TABLE,
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable_cached, AirtableCache, AirtableSyncable, SyncSummary},
+    auth_config::AuthConfig,
+    auth_logins::{auth_user_records_by_email, people_records_by_email},
+    companies::Company,
+    core::DryRun,
+    db::{save_listing, Database},
+    error::CioError,
+    schema::slack_users,
+};
+
+/// A member of the Slack workspace.
+#[db {
+    new_struct_name = "SlackUser",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_SLACK_USERS_TABLE",
+    match_on = {
+        "slack_id" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = slack_users)]
+pub struct NewSlackUser {
+    /// The id of the member in Slack, for example `U012AB3CD`.
+    pub slack_id: String,
+    /// The handle of the member.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub real_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub is_owner: bool,
+    /// Whether the member is a guest of the workspace.
+    #[serde(default)]
+    pub is_guest: bool,
+    /// Whether the member was deactivated.
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub has_2fa: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tz: String,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_auth_users: Vec<String>,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_people: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for SlackUser {
+    const DELETE_STALE: bool = true;
+
+    // The people and auth users live in the customer leads base, and links can't cross bases.
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewSlackUser) -> String {
+        fields.slack_id.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewSlackUser) {}
+}
+
+impl NewSlackUser {
+    /// Convert a member of the workspace, linking them to the records with their email.
+    fn from_slack(
+        user: slack_chat_api::User,
+        company: &Company,
+        link_to_auth_users: Vec<String>,
+        link_to_people: Vec<String>,
+    ) -> Self {
+        let email = if user.profile.email.is_empty() {
+            user.email
+        } else {
+            user.profile.email
+        };
+
+        NewSlackUser {
+            slack_id: user.id,
+            name: user.name,
+            real_name: user.real_name,
+            display_name: user.profile.display_name,
+            email,
+            title: user.profile.title,
+            is_admin: user.is_admin,
+            is_owner: user.is_owner,
+            is_guest: user.is_restricted || user.is_ultra_restricted,
+            deleted: user.deleted,
+            has_2fa: user.has_2fa,
+            tz: user.tz,
+            link_to_auth_users,
+            link_to_people,
+            cio_company_id: company.id,
+        }
+    }
+}
+
+/// Sync the members of the Slack workspace with our database and Airtable. Bots are left
+/// out, members who left the workspace are kept and marked deleted.
+pub async fn refresh_slack_users(db: &Database, company: &Company, dry_run: DryRun) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let slack = company
+        .authenticate_slack(db)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let users = slack.list_users().await.map_err(CioError::Slack)?;
+    info!("syncing {} slack users", users.len());
+
+    let cache = AirtableCache::default();
+    let auth_users = auth_user_records_by_email(db, company, &config)
+        .await
+        .map_err(CioError::Database)?;
+    let people = people_records_by_email(company, &config, &cache).await?;
+
+    let new_users: Vec<NewSlackUser> = users
+        .into_iter()
+        .filter(|u| !u.is_bot && !u.is_app_user && u.id != "USLACKBOT")
+        .map(|u| {
+            let email = config.canonical_email(if u.profile.email.is_empty() {
+                &u.email
+            } else {
+                &u.profile.email
+            });
+            let link_to_auth_users = auth_users.get(&email).cloned().unwrap_or_default();
+            let link_to_people = people.get(&email).map(|p| vec![p.to_string()]).unwrap_or_default();
+
+            NewSlackUser::from_slack(u, company, link_to_auth_users, link_to_people)
+        })
+        .collect();
+
+    let mut summary = if dry_run.is_enabled() {
+        info!("[dry-run] would save {} slack users", new_users.len());
+        SyncSummary::default()
+    } else {
+        save_listing(
+            "slack user",
+            &new_users,
+            |u| u.slack_id.to_string(),
+            |u| u.upsert(db),
+            |ids| async move {
+                Ok(diesel::delete(
+                    slack_users::dsl::slack_users
+                        .filter(slack_users::dsl::cio_company_id.eq(company.id))
+                        .filter(slack_users::dsl::slack_id.ne_all(ids)),
+                )
+                .execute_async(db.pool())
+                .await?)
+            },
+        )
+        .await?
+    };
+
+    summary += sync_to_airtable_cached::<SlackUser>(db, company, &cache, dry_run).await?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::companies::tests::mock_company;
+
+    #[test]
+    fn test_from_slack() {
+        let user: slack_chat_api::User = serde_json::from_value(serde_json::json!({
+            "id": "U012AB3CD",
+            "name": "jess",
+            "is_restricted": true,
+            "profile": {
+                "email": "jess@example.com",
+                "display_name": "Jess",
+            },
+        }))
+        .unwrap();
+
+        let user = NewSlackUser::from_slack(user, &mock_company(), vec![], vec!["recPerson".to_string()]);
+        assert_eq!(user.slack_id, "U012AB3CD");
+        assert_eq!(user.email, "jess@example.com");
+        assert_eq!(user.display_name, "Jess");
+        assert!(user.is_guest);
+        assert_eq!(user.link_to_people, vec!["recPerson"]);
+    }
+}
@@ -18,14 +18,16 @@ use printpdf::{Image as PdfImage, Mm, PdfDocument, Pt};
 use reqwest::StatusCode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "slack")]
 use slack_chat_api::{
     FormattedMessage, MessageAttachment, MessageBlock, MessageBlockAccessory, MessageBlockText, MessageBlockType,
     MessageType,
 };
 
+#[cfg(feature = "slack")]
+use crate::companies::Company;
 use crate::{
     airtable::{AIRTABLE_BARCODE_SCANS_TABLE, AIRTABLE_SWAG_INVENTORY_ITEMS_TABLE, AIRTABLE_SWAG_ITEMS_TABLE},
-    companies::Company,
     core::UpdateAirtableRecord,
     db::Database,
     printer::Printer,
@@ -132,6 +134,7 @@ pub struct NewSwagInventoryItem {
 }
 
 impl NewSwagInventoryItem {
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification(&self, db: &Database, company: &Company) -> Result<()> {
         let mut msg: FormattedMessage = self.clone().into();
         // Set the channel.
@@ -273,6 +276,7 @@ impl NewSwagInventoryItem {
 }
 
 /// Convert the swag inventory item into a Slack message.
+#[cfg(feature = "slack")]
 impl From<NewSwagInventoryItem> for FormattedMessage {
     fn from(item: NewSwagInventoryItem) -> Self {
         let text = format!("*{}*\n | current stock: {}", item.name, item.current_stock);
@@ -325,6 +329,7 @@ impl From<NewSwagInventoryItem> for FormattedMessage {
     }
 }
 
+#[cfg(feature = "slack")]
 impl From<SwagInventoryItem> for FormattedMessage {
     fn from(item: SwagInventoryItem) -> Self {
         let new: NewSwagInventoryItem = item.into();
@@ -333,6 +338,7 @@ impl From<SwagInventoryItem> for FormattedMessage {
 }
 
 impl SwagInventoryItem {
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification(&self, db: &Database, company: &Company) -> Result<()> {
         let n: NewSwagInventoryItem = self.into();
         n.send_slack_notification(db, company).await
@@ -492,6 +498,7 @@ impl SwagInventoryItem {
         SwagItem::get_from_db(db, self.item.to_string()).await
     }
 
+    #[cfg(feature = "slack")]
     pub async fn send_slack_notification_if_inventory_changed(
         &mut self,
         db: &Database,
@@ -0,0 +1,235 @@
+//! Running sync jobs that depend on each other in order.
+//!
+//! Some syncs read what others wrote. The page views link to the Airtable records of the
+//! auth users, so those have to be pushed first, and they can only be pushed once they
+//! are synced from Auth0. A `SyncPlan` holds the jobs along with the ones each depends
+//! on, and runs them in stages: every job of a stage at once, and a stage only after the
+//! ones before it are done. A job that fails skips the jobs that depend on it, the rest
+//! of the plan goes on.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+};
+
+use futures::future::{self, BoxFuture, FutureExt};
+use log::{error, info, warn};
+
+use crate::{
+    airtable_sync::SyncSummary, companies::Company, core::DryRun, db::Database, error::CioError,
+    sync_runs::record_sync_run,
+};
+
+/// How a job of a plan went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobOutcome {
+    Succeeded(SyncSummary),
+    Failed(String),
+    /// The job did not run because a job it depends on did not finish.
+    Skipped {
+        dependency: String,
+    },
+}
+
+impl JobOutcome {
+    /// Returns true if the job ran to the end, so the jobs that depend on it can run.
+    pub fn is_done(&self) -> bool {
+        matches!(self, JobOutcome::Succeeded(summary) if !summary.cancelled)
+    }
+}
+
+/// How each job of a plan went, by name.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncPlanReport {
+    pub outcomes: BTreeMap<String, JobOutcome>,
+}
+
+impl SyncPlanReport {
+    /// Returns the records changed by the jobs that succeeded, or an error naming the
+    /// jobs that failed.
+    pub fn into_result(self) -> Result<SyncSummary, CioError> {
+        let mut summary = SyncSummary::default();
+        let mut failed: Vec<String> = Default::default();
+        for (name, outcome) in self.outcomes {
+            match outcome {
+                JobOutcome::Succeeded(s) => summary += s,
+                JobOutcome::Failed(e) => failed.push(format!("`{}`: {}", name, e)),
+                JobOutcome::Skipped { .. } => (),
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(anyhow::anyhow!("sync jobs failed: {}", failed.join("; ")).into());
+        }
+
+        Ok(summary)
+    }
+}
+
+struct PlannedJob<'a> {
+    depends_on: Vec<String>,
+    sync: BoxFuture<'a, Result<SyncSummary, CioError>>,
+}
+
+/// Sync jobs and the jobs they depend on.
+#[derive(Default)]
+pub struct SyncPlan<'a> {
+    jobs: BTreeMap<String, PlannedJob<'a>>,
+}
+
+impl<'a> SyncPlan<'a> {
+    /// Create a plan with no jobs.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a job that runs once the jobs it depends on are done. The name is the one the
+    /// run is recorded under, see `record_sync_run`.
+    pub fn job<F>(mut self, name: &str, depends_on: &[&str], sync: F) -> Self
+    where
+        F: Future<Output = Result<SyncSummary, CioError>> + Send + 'a,
+    {
+        self.jobs.insert(
+            name.to_string(),
+            PlannedJob {
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                sync: sync.boxed(),
+            },
+        );
+        self
+    }
+
+    /// Returns the names of the jobs in the order they run, the jobs of each stage run at
+    /// once. Returns an error if a job depends on one that is not in the plan, or the jobs
+    /// depend on each other in a cycle.
+    pub fn stages(&self) -> Result<Vec<Vec<String>>, CioError> {
+        let mut waiting: BTreeMap<&str, BTreeSet<&str>> = Default::default();
+        for (name, job) in &self.jobs {
+            if let Some(missing) = job.depends_on.iter().find(|d| !self.jobs.contains_key(*d)) {
+                return Err(CioError::Config(format!(
+                    "sync job `{}` depends on `{}`, which is not in the plan",
+                    name, missing
+                )));
+            }
+            waiting.insert(name.as_str(), job.depends_on.iter().map(|d| d.as_str()).collect());
+        }
+
+        let mut stages: Vec<Vec<String>> = Default::default();
+        while !waiting.is_empty() {
+            let ready: Vec<&str> = waiting
+                .iter()
+                .filter(|(_, depends_on)| depends_on.is_empty())
+                .map(|(name, _)| *name)
+                .collect();
+            if ready.is_empty() {
+                let names: Vec<String> = waiting.keys().map(|n| format!("`{}`", n)).collect();
+                return Err(CioError::Config(format!(
+                    "the sync jobs {} depend on each other in a cycle",
+                    names.join(", ")
+                )));
+            }
+
+            for name in &ready {
+                waiting.remove(name);
+            }
+            for depends_on in waiting.values_mut() {
+                depends_on.retain(|d| !ready.contains(d));
+            }
+
+            stages.push(ready.into_iter().map(|n| n.to_string()).collect());
+        }
+
+        Ok(stages)
+    }
+
+    /// Run the jobs, each recorded as a sync run. Returns an error only if the plan is
+    /// invalid, see `stages`, how each job went is in the report.
+    pub async fn run(mut self, db: &Database, company: &Company, dry_run: DryRun) -> Result<SyncPlanReport, CioError> {
+        let mut report = SyncPlanReport::default();
+        for stage in self.stages()? {
+            let mut running = Vec::new();
+            for name in stage {
+                let job = self.jobs.remove(&name).expect("the staged jobs are in the plan");
+
+                let blocked = job
+                    .depends_on
+                    .iter()
+                    .find(|d| !report.outcomes.get(*d).map(JobOutcome::is_done).unwrap_or_default());
+                if let Some(dependency) = blocked {
+                    warn!("skipping sync job `{}`, `{}` did not finish", name, dependency);
+                    report.outcomes.insert(
+                        name,
+                        JobOutcome::Skipped {
+                            dependency: dependency.to_string(),
+                        },
+                    );
+                    continue;
+                }
+
+                running.push(async move {
+                    info!("running sync job `{}`", name);
+                    let result = record_sync_run(db, company, &name, dry_run, job.sync).await;
+                    (name, result)
+                });
+            }
+
+            for (name, result) in future::join_all(running).await {
+                let outcome = match result {
+                    Ok(summary) => JobOutcome::Succeeded(summary),
+                    Err(e) => {
+                        error!("sync job `{}` failed: {}", name, e);
+                        JobOutcome::Failed(e.to_string())
+                    }
+                };
+                report.outcomes.insert(name, outcome);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::{self, Ready};
+
+    use super::SyncPlan;
+    use crate::{airtable_sync::SyncSummary, error::CioError};
+
+    fn done() -> Ready<Result<SyncSummary, CioError>> {
+        future::ready(Ok(SyncSummary::default()))
+    }
+
+    #[test]
+    fn test_sync_plan_stages() {
+        let plan = SyncPlan::new()
+            .job("page-views", &["auth-users-to-airtable"], done())
+            .job("auth-users", &[], done())
+            .job("auth-users-to-airtable", &["auth-users"], done())
+            .job("auth-logins-to-airtable", &["auth-users"], done())
+            .job("github-members", &[], done());
+
+        assert_eq!(
+            plan.stages().unwrap(),
+            vec![
+                vec!["auth-users".to_string(), "github-members".to_string()],
+                vec![
+                    "auth-logins-to-airtable".to_string(),
+                    "auth-users-to-airtable".to_string()
+                ],
+                vec!["page-views".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sync_plan_rejects_cycles_and_missing_jobs() {
+        let cycle = SyncPlan::new()
+            .job("a", &["b"], done())
+            .job("b", &["a"], done())
+            .job("c", &[], done());
+        assert!(cycle.stages().unwrap_err().to_string().contains("`a`, `b`"));
+
+        let missing = SyncPlan::new().job("a", &["b"], done());
+        assert!(missing.stages().is_err());
+    }
+}
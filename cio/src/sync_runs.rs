@@ -0,0 +1,290 @@
+#![allow(clippy::from_over_into)]
+//! A log of the sync jobs that ran, so we can tell when the data was last refreshed.
+use std::{collections::BTreeMap, future::Future};
+
+use anyhow::Result;
+use async_bb8_diesel::AsyncRunQueryDsl;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
+use log::{error, info};
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable::AIRTABLE_SYNC_RUNS_TABLE,
+    airtable_bases::AirtableBase,
+    airtable_sync::{AirtableSyncable, SyncSummary},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    metrics,
+    schema::sync_runs,
+};
+
+/// A run of a sync job.
+#[db {
+    new_struct_name = "SyncRun",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_SYNC_RUNS_TABLE",
+    match_on = {
+        "job" = "String",
+        "started_at" = "DateTime<Utc>",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = sync_runs)]
+pub struct NewSyncRun {
+    pub job: String,
+    /// `running`, `succeeded`, `cancelled` or `failed`.
+    pub status: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub records_created: i32,
+    #[serde(default)]
+    pub records_updated: i32,
+    #[serde(default)]
+    pub records_deleted: i32,
+    /// The number of records that failed to save.
+    #[serde(default)]
+    pub errors: i32,
+    /// The error that failed the run.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub error: String,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for SyncRun {
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::Misc;
+
+    fn unique_key(fields: &NewSyncRun) -> String {
+        format!("{}/{}", fields.job, fields.started_at.to_rfc3339())
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewSyncRun) {}
+}
+
+/// Run a sync job and record the run in the database. Dry runs are not recorded.
+///
+/// Failing to record the run is logged, it never fails the job.
+pub async fn record_sync_run<F>(
+    db: &Database,
+    company: &Company,
+    job: &str,
+    dry_run: DryRun,
+    sync: F,
+) -> Result<SyncSummary, CioError>
+where
+    F: Future<Output = Result<SyncSummary, CioError>>,
+{
+    if dry_run.is_enabled() {
+        return sync.await;
+    }
+
+    let run = NewSyncRun {
+        job: job.to_string(),
+        status: "running".to_string(),
+        started_at: Utc::now(),
+        finished_at: None,
+        records_created: 0,
+        records_updated: 0,
+        records_deleted: 0,
+        errors: 0,
+        error: String::new(),
+        cio_company_id: company.id,
+    };
+    let run = match run.create(db).await {
+        Ok(run) => Some(run),
+        Err(e) => {
+            error!("recording the start of sync `{}` failed: {}", job, e);
+            None
+        }
+    };
+
+    let result = sync.await;
+
+    if let Ok(summary) = &result {
+        if !summary.cancelled {
+            metrics::sync_succeeded(job, Utc::now());
+        }
+    }
+
+    if let Some(mut run) = run {
+        run.finished_at = Some(Utc::now());
+        match &result {
+            Ok(summary) => {
+                run.status = if summary.cancelled { "cancelled" } else { "succeeded" }.to_string();
+                run.records_created = summary.created as i32;
+                run.records_updated = summary.updated as i32;
+                run.records_deleted = summary.deleted as i32;
+                run.errors = summary.errors as i32;
+            }
+            Err(e) => {
+                run.status = "failed".to_string();
+                run.error = e.to_string();
+            }
+        }
+
+        info!("sync `{}` {}", job, run.status);
+        if let Err(e) = run.update(db).await {
+            error!("recording the end of sync `{}` failed: {}", job, e);
+        }
+    }
+
+    result
+}
+
+/// How fresh the data of a sync job is, going by its recorded runs.
+#[derive(Debug, Clone, PartialEq, Eq, JsonSchema, Deserialize, Serialize)]
+pub struct SyncStatus {
+    pub job: String,
+    /// The status of the last run, `running`, `succeeded`, `cancelled` or `failed`.
+    pub last_status: String,
+    pub last_started_at: DateTime<Utc>,
+    /// The error that failed the last run.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_error: String,
+    /// When the last successful run finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// How long the last successful run took, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_success_duration_secs: Option<i64>,
+    /// The records changed by the last successful run.
+    #[serde(default)]
+    pub records_created: i32,
+    #[serde(default)]
+    pub records_updated: i32,
+    #[serde(default)]
+    pub records_deleted: i32,
+    /// The records that failed to save in the last successful run.
+    #[serde(default)]
+    pub errors: i32,
+    /// Whether the job has not succeeded within the max age asked for, or ever.
+    #[serde(default)]
+    pub stale: bool,
+}
+
+/// Returns the status of each sync job of the company, by job. With a max age, the jobs
+/// that have not succeeded within it are marked stale.
+pub async fn get_sync_status(
+    db: &Database,
+    company: &Company,
+    max_age: Option<Duration>,
+) -> Result<Vec<SyncStatus>, CioError> {
+    let latest = sync_runs::dsl::sync_runs
+        .filter(sync_runs::dsl::cio_company_id.eq(company.id))
+        .distinct_on(sync_runs::dsl::job)
+        .order_by((sync_runs::dsl::job, sync_runs::dsl::started_at.desc()))
+        .load_async::<SyncRun>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+
+    let succeeded = sync_runs::dsl::sync_runs
+        .filter(sync_runs::dsl::cio_company_id.eq(company.id))
+        .filter(sync_runs::dsl::status.eq("succeeded"))
+        .distinct_on(sync_runs::dsl::job)
+        .order_by((sync_runs::dsl::job, sync_runs::dsl::started_at.desc()))
+        .load_async::<SyncRun>(db.pool())
+        .await
+        .map_err(|e| CioError::Database(e.into()))?;
+
+    Ok(sync_statuses(latest, succeeded, max_age, Utc::now()))
+}
+
+/// Combine the last run of each job with its last successful one.
+fn sync_statuses(
+    latest: Vec<SyncRun>,
+    succeeded: Vec<SyncRun>,
+    max_age: Option<Duration>,
+    now: DateTime<Utc>,
+) -> Vec<SyncStatus> {
+    let mut succeeded: BTreeMap<String, SyncRun> = succeeded.into_iter().map(|r| (r.job.to_string(), r)).collect();
+
+    latest
+        .into_iter()
+        .map(|last| {
+            let success = succeeded.remove(&last.job);
+            let last_success_at = success.as_ref().and_then(|s| s.finished_at);
+            let stale = match max_age {
+                Some(max_age) => last_success_at.map(|at| now - at > max_age).unwrap_or(true),
+                None => false,
+            };
+
+            SyncStatus {
+                job: last.job,
+                last_status: last.status,
+                last_started_at: last.started_at,
+                last_error: last.error,
+                last_success_at,
+                last_success_duration_secs: success
+                    .as_ref()
+                    .and_then(|s| Some((s.finished_at? - s.started_at).num_seconds())),
+                records_created: success.as_ref().map(|s| s.records_created).unwrap_or_default(),
+                records_updated: success.as_ref().map(|s| s.records_updated).unwrap_or_default(),
+                records_deleted: success.as_ref().map(|s| s.records_deleted).unwrap_or_default(),
+                errors: success.as_ref().map(|s| s.errors).unwrap_or_default(),
+                stale,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::{sync_statuses, SyncRun};
+
+    fn run(job: &str, status: &str, started_hour: u32, minutes: i64) -> SyncRun {
+        let started_at = Utc.with_ymd_and_hms(2026, 10, 15, started_hour, 0, 0).unwrap();
+        SyncRun {
+            id: 1,
+            job: job.to_string(),
+            status: status.to_string(),
+            started_at,
+            finished_at: Some(started_at + Duration::minutes(minutes)),
+            records_created: 1,
+            records_updated: 20,
+            records_deleted: 0,
+            errors: 0,
+            error: String::new(),
+            cio_company_id: 1,
+            airtable_record_id: String::new(),
+            airtable_record_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_sync_statuses() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+        let latest = vec![
+            run("sync-auth-users", "failed", 11, 1),
+            run("sync-page-views", "succeeded", 10, 5),
+            run("sync-gusto", "failed", 9, 1),
+        ];
+        let succeeded = vec![
+            run("sync-auth-users", "succeeded", 2, 10),
+            run("sync-page-views", "succeeded", 10, 5),
+        ];
+
+        let statuses = sync_statuses(latest, succeeded, Some(Duration::hours(6)), now);
+
+        assert_eq!(statuses[0].job, "sync-auth-users");
+        assert_eq!(statuses[0].last_status, "failed");
+        assert_eq!(statuses[0].last_success_duration_secs, Some(600));
+        assert_eq!(statuses[0].records_updated, 20);
+        assert!(statuses[0].stale);
+
+        assert!(!statuses[1].stale);
+
+        // Never succeeded.
+        assert_eq!(statuses[2].last_success_at, None);
+        assert!(statuses[2].stale);
+    }
+}
@@ -0,0 +1,239 @@
+//! Recorded responses of the Auth0 management API, and a mock server that serves them, so
+//! the auth syncs can be tested without a tenant.
+//!
+//! Enabled with the `testing` feature, which also enables the mock Airtable API of
+//! `airtable_api::testing`. The fixtures are a tenant of three users, listed in two pages,
+//! the logins of the first one, and the errors Auth0 returns for rate limits and users that
+//! do not exist.
+use std::collections::HashMap;
+
+use airtable_api::testing::json;
+pub use airtable_api::testing::MockAirtable;
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Utc};
+use wiremock::{
+    matchers::{method, path, path_regex, query_param},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+use crate::auth0::{Auth0Client, MAX_SEARCH_RESULTS};
+
+/// The recorded response bodies.
+pub mod fixtures {
+    /// The response to the client credentials exchange.
+    pub const TOKEN: &str = include_str!("../fixtures/auth0/token.json");
+    /// The first page of users, with the totals.
+    pub const USERS_PAGE_1: &str = include_str!("../fixtures/auth0/users_page_1.json");
+    /// The last page of users.
+    pub const USERS_PAGE_2: &str = include_str!("../fixtures/auth0/users_page_2.json");
+    /// The log events of the first user, a login and a failed login.
+    pub const USER_LOGS: &str = include_str!("../fixtures/auth0/user_logs.json");
+    /// The body of a `429 Too Many Requests`.
+    pub const RATE_LIMITED: &str = include_str!("../fixtures/auth0/rate_limited.json");
+    /// The body of a `404 Not Found` for a user.
+    pub const USER_NOT_FOUND: &str = include_str!("../fixtures/auth0/user_not_found.json");
+}
+
+/// The number of users in the tenant of the fixtures.
+pub const TENANT_USERS: usize = 3;
+
+/// A mock Auth0 tenant.
+pub struct MockAuth0 {
+    server: MockServer,
+    domain: String,
+}
+
+impl MockAuth0 {
+    /// Start a mock server for the tenant that hands out tokens, with nothing else mounted
+    /// yet.
+    pub async fn start(domain: &str) -> Self {
+        let auth0 = MockAuth0 {
+            server: MockServer::start().await,
+            domain: domain.to_string(),
+        };
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(json(200, fixtures::TOKEN))
+            .mount(&auth0.server)
+            .await;
+
+        auth0
+    }
+
+    /// Returns the server, to mount other responses or look at the requests it got.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Returns a client for the tenant that talks to the mock server.
+    pub fn client(&self) -> Auth0Client {
+        Auth0Client::new(&self.domain, "clientTest", "secretTest").with_base_url(self.server.uri())
+    }
+
+    /// Serve the two pages of users of the fixtures.
+    pub async fn mount_users(&self) {
+        Mock::given(method("GET"))
+            .and(path("/api/v2/users"))
+            .and(query_param("page", "1"))
+            .respond_with(json(200, fixtures::USERS_PAGE_2))
+            .with_priority(2)
+            .mount(&self.server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/users"))
+            .respond_with(json(200, fixtures::USERS_PAGE_1))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serve a tenant of `count` users created a minute apart, searched the way Auth0
+    /// does: a `created_at` lower bound in the query is honored, and the pages past
+    /// `MAX_SEARCH_RESULTS` are rejected.
+    pub async fn mount_many_users(&self, count: usize) {
+        Mock::given(method("GET"))
+            .and(path("/api/v2/users"))
+            .respond_with(move |request: &Request| many_users_page(request, count))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serve the log events of the fixtures for every user.
+    pub async fn mount_user_logs(&self) {
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/api/v2/users/[^/]+/logs$"))
+            .respond_with(json(200, fixtures::USER_LOGS))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serve `count` logins of `MANY_LOGS_USER` a minute apart, see `many_logs_event`. They
+    /// are served newest first in pages for the user, and oldest first after the `from`
+    /// checkpoint from the tenant log stream.
+    pub async fn mount_many_user_logs(&self, count: usize) {
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/api/v2/users/[^/]+/logs$"))
+            .respond_with(move |request: &Request| many_user_logs_page(request, count))
+            .mount(&self.server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/logs"))
+            .respond_with(move |request: &Request| many_logs_page(request, count))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer requests for the logs of users with a `404 Not Found`, as if they were
+    /// deleted, before any other response.
+    pub async fn mount_user_logs_not_found(&self) {
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/api/v2/users/[^/]+/logs$"))
+            .respond_with(json(404, fixtures::USER_NOT_FOUND))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answer the next `times` requests to the management API path with a
+    /// `429 Too Many Requests`, before any other response. The rate limit resets a second
+    /// later.
+    pub async fn mount_rate_limited(&self, api_path: &str, times: u64) {
+        let reset = chrono::Utc::now().timestamp() + 1;
+        Mock::given(path(format!("/api/v2/{}", api_path.trim_start_matches('/'))))
+            .respond_with(
+                json(429, fixtures::RATE_LIMITED)
+                    .insert_header("x-ratelimit-limit", "50")
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("x-ratelimit-reset", reset.to_string().as_str()),
+            )
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+}
+
+/// The user the events of `mount_many_user_logs` belong to.
+pub const MANY_LOGS_USER: &str = "auth0|65f000000000000000000001";
+
+/// Returns the `i`th event of `mount_many_user_logs`, counting from the oldest.
+pub fn many_logs_event(i: usize) -> serde_json::Value {
+    let date = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::minutes(i as i64);
+    serde_json::json!({
+        "date": date.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "type": "s",
+        "client_name": "RFD",
+        "user_id": MANY_LOGS_USER,
+        "log_id": format!("{:056}", i),
+    })
+}
+
+/// Returns the page of the events of `mount_many_user_logs` a request for the logs of the
+/// user asks for.
+fn many_user_logs_page(request: &Request, count: usize) -> ResponseTemplate {
+    let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+    let param = |name: &str| query.get(name).and_then(|v| v.parse::<usize>().ok());
+    let page = param("page").unwrap_or(0);
+    let per_page = param("per_page").unwrap_or(50);
+
+    let events: Vec<serde_json::Value> = (0..count)
+        .rev()
+        .skip(page * per_page)
+        .take(per_page)
+        .map(many_logs_event)
+        .collect();
+    ResponseTemplate::new(200).set_body_json(events)
+}
+
+/// Returns the events of `mount_many_user_logs` a request to the tenant log stream asks
+/// for.
+fn many_logs_page(request: &Request, count: usize) -> ResponseTemplate {
+    let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+    let from = query.get("from").and_then(|v| v.parse::<usize>().ok()).map(|i| i + 1);
+    let take = query.get("take").and_then(|v| v.parse::<usize>().ok()).unwrap_or(50);
+
+    let events: Vec<serde_json::Value> = (from.unwrap_or(0)..count).take(take).map(many_logs_event).collect();
+    ResponseTemplate::new(200).set_body_json(events)
+}
+
+/// Returns the page of the users of `mount_many_users` the request asks for.
+fn many_users_page(request: &Request, count: usize) -> ResponseTemplate {
+    let query: HashMap<String, String> = request.url.query_pairs().into_owned().collect();
+    let param = |name: &str| query.get(name).and_then(|v| v.parse::<usize>().ok());
+    let page = param("page").unwrap_or(0);
+    let per_page = param("per_page").unwrap_or(50);
+    if (page + 1) * per_page > MAX_SEARCH_RESULTS as usize {
+        return json(
+            400,
+            r#"{"statusCode":400,"error":"Bad Request","message":"You can only page through the first 1000 records."}"#,
+        );
+    }
+
+    let since = query
+        .get("q")
+        .and_then(|q| q.split("created_at:[").nth(1))
+        .and_then(|range| range.split(" TO").next())
+        .and_then(|since| DateTime::parse_from_rfc3339(since).ok())
+        .map(|since| since.with_timezone(&Utc));
+    let first = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let users: Vec<serde_json::Value> = (0..count)
+        .map(|i| (i, first + Duration::minutes(i as i64)))
+        .filter(|(_, created_at)| since.map(|since| *created_at >= since).unwrap_or(true))
+        .map(|(i, created_at)| {
+            let created_at = created_at.to_rfc3339_opts(SecondsFormat::Millis, true);
+            serde_json::json!({
+                "user_id": format!("auth0|{}", i),
+                "created_at": created_at,
+                "updated_at": created_at,
+            })
+        })
+        .collect();
+
+    let total = users.len();
+    let users: Vec<serde_json::Value> = users.into_iter().skip(page * per_page).take(per_page).collect();
+    ResponseTemplate::new(200).set_body_json(serde_json::json!({
+        "start": page * per_page,
+        "limit": per_page,
+        "length": users.len(),
+        "total": total,
+        "users": users,
+    }))
+}
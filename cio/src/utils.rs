@@ -1,18 +1,20 @@
-use anyhow::{anyhow, bail, Result};
+use anyhow::Result;
+#[cfg(feature = "github")]
+use anyhow::{anyhow, bail};
 use log::info;
+#[cfg(feature = "github")]
 use octorust::Client as GitHub;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use reqwest::get;
 use serde::{Deserialize, Deserializer};
 use serde_json::Value;
-use std::{
-    collections::{BTreeMap, HashMap},
-    path::Path,
-    str::from_utf8,
-};
+#[cfg(feature = "github")]
+use std::collections::BTreeMap;
+use std::{collections::HashMap, path::Path, str::from_utf8};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
+#[cfg(feature = "github")]
 use crate::companies::Company;
 
 /// Write a file.
@@ -36,6 +38,7 @@ pub fn is_image(file: &str) -> bool {
 
 /// Create a comment on a commit for a repo.
 /// We use this a lot if a webhook was a success or errored.
+#[cfg(feature = "github")]
 pub async fn add_comment_to_commit(
     github: &GitHub,
     owner: &str,
@@ -63,6 +66,7 @@ pub async fn add_comment_to_commit(
 }
 
 /// Check if a GitHub issue already exists.
+#[cfg(feature = "github")]
 pub fn check_if_github_issue_exists(
     issues: &[octorust::types::IssueSimple],
     search: &str,
@@ -98,6 +102,7 @@ pub async fn get_github_user_public_ssh_keys(handle: &str) -> Result<Vec<String>
 
 /// Get a files content from a repo.
 /// It returns a tuple of the bytes of the file content and the sha of the file.
+#[cfg(feature = "github")]
 pub async fn get_file_content_from_repo(
     github: &octorust::Client,
     owner: &str,
@@ -132,6 +137,7 @@ pub async fn get_file_content_from_repo(
 /// Create or update a file in a GitHub repository.
 /// If the file does not exist, it will be created.
 /// If the file exists, it will be updated _only if_ the content of the file has changed.
+#[cfg(feature = "github")]
 pub async fn create_or_update_file_in_github_repo(
     github: &octorust::Client,
     owner: &str,
@@ -310,6 +316,7 @@ pub fn decode_base64_to_string(c: &str) -> String {
     from_utf8(&decoded).unwrap().trim().to_string()
 }
 
+#[cfg(feature = "github")]
 pub async fn encrypt_github_secrets(
     github: &octorust::Client,
     company: &Company,
@@ -364,6 +371,7 @@ pub fn setup_logger() {
     log::set_max_level(log::LevelFilter::Info);
 }
 
+#[cfg(feature = "github")]
 pub async fn get_github_entry_contents(
     github: &octorust::Client,
     owner: &str,
@@ -374,6 +382,7 @@ pub async fn get_github_entry_contents(
     get_github_file(github, owner, repo, branch, &file.path).await
 }
 
+#[cfg(feature = "github")]
 pub async fn get_github_file(
     github: &octorust::Client,
     owner: &str,
@@ -411,7 +420,7 @@ where
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "github"))]
 mod tests {
     use std::collections::BTreeMap;
 
@@ -0,0 +1,238 @@
+#![allow(clippy::from_over_into)]
+//! A mirror of the Zoom users and their licenses.
+//!
+//! Licensed accounts nobody logged in to for a while are flagged, so the licenses can be
+//! taken back.
+use anyhow::Result;
+use async_bb8_diesel::AsyncRunQueryDsl;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use macros::db;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    airtable::AIRTABLE_ZOOM_USERS_TABLE,
+    airtable_bases::AirtableBase,
+    airtable_sync::{sync_to_airtable, AirtableSyncable, SyncSummary},
+    auth_config::AuthConfig,
+    auth_logins::auth_user_records_by_email,
+    companies::Company,
+    core::DryRun,
+    db::{save_listing, Database},
+    error::CioError,
+    schema::zoom_users,
+};
+
+/// The number of days without a login after which a licensed account is flagged inactive.
+pub const INACTIVE_LICENSE_DAYS: i64 = 90;
+
+/// A user of the Zoom account.
+#[db {
+    new_struct_name = "ZoomUser",
+    airtable_record_url = true,
+    airtable_table = "AIRTABLE_ZOOM_USERS_TABLE",
+    match_on = {
+        "zoom_id" = "String",
+        "cio_company_id" = "i32",
+    },
+}]
+#[derive(Debug, Insertable, AsChangeset, PartialEq, Clone, JsonSchema, Deserialize, Serialize)]
+#[diesel(table_name = zoom_users)]
+pub struct NewZoomUser {
+    /// The id of the user in Zoom.
+    pub zoom_id: String,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub first_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub department: String,
+    /// `basic`, `licensed`, `on_prem` or `none`.
+    pub license_type: String,
+    /// `active`, or `pending` until the user accepts the invite.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_login_at: Option<DateTime<Utc>>,
+    /// Whether the account holds a license but nobody logged in to it for
+    /// `INACTIVE_LICENSE_DAYS` days.
+    #[serde(default)]
+    pub inactive_license: bool,
+    /// link to another table in Airtable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_to_auth_users: Vec<String>,
+    /// The CIO company ID.
+    #[serde(default)]
+    pub cio_company_id: i32,
+}
+
+impl AirtableSyncable for ZoomUser {
+    const DELETE_STALE: bool = true;
+
+    // The auth users live in the customer leads base, and links can't cross bases.
+    const AIRTABLE_BASE: AirtableBase = AirtableBase::CustomerLeads;
+
+    fn unique_key(fields: &NewZoomUser) -> String {
+        fields.zoom_id.to_string()
+    }
+
+    fn pull_airtable_field(&mut self, _field: &str, _airtable: &NewZoomUser) {}
+}
+
+/// Returns the name of a Zoom user type.
+/// FROM: https://marketplace.zoom.us/docs/api-reference/zoom-api/users/users
+fn license_type(type_: i64) -> &'static str {
+    match type_ {
+        1 => "basic",
+        2 => "licensed",
+        3 => "on_prem",
+        _ => "none",
+    }
+}
+
+impl NewZoomUser {
+    /// Returns whether the account holds a license and nobody logged in to it in the last
+    /// `INACTIVE_LICENSE_DAYS` days. Accounts that never logged in count from when they
+    /// were created, so new invites are not flagged right away.
+    pub fn is_inactive_license(&self, created_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        if self.license_type != "licensed" {
+            return false;
+        }
+
+        match self.last_login_at.or(created_at) {
+            Some(at) => now - at > Duration::days(INACTIVE_LICENSE_DAYS),
+            None => false,
+        }
+    }
+}
+
+/// Sync the Zoom users and their licenses with our database and Airtable.
+pub async fn refresh_zoom_users(db: &Database, company: &Company, dry_run: DryRun) -> Result<SyncSummary, CioError> {
+    let config = AuthConfig::load(db, company)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+    let zoom = company
+        .authenticate_zoom(db)
+        .await
+        .map_err(|e| CioError::Config(e.to_string()))?;
+
+    let auth_users = auth_user_records_by_email(db, company, &config)
+        .await
+        .map_err(CioError::Database)?;
+
+    let now = Utc::now();
+    let mut new_users: Vec<NewZoomUser> = Default::default();
+    for (status, name) in [
+        (zoom_api::types::UsersStatus::Active, "active"),
+        (zoom_api::types::UsersStatus::Pending, "pending"),
+    ] {
+        let users = zoom
+            .users()
+            .get_all(status, "", zoom_api::types::UsersIncludeFields::Noop)
+            .await
+            .map_err(|e| CioError::Zoom(e.into()))?
+            .body;
+
+        for user in users {
+            let mut new_user = NewZoomUser {
+                zoom_id: user.id,
+                link_to_auth_users: auth_users
+                    .get(&config.canonical_email(&user.email))
+                    .cloned()
+                    .unwrap_or_default(),
+                email: user.email,
+                first_name: user.first_name,
+                last_name: user.last_name,
+                department: user.dept,
+                license_type: license_type(user.type_).to_string(),
+                status: name.to_string(),
+                last_login_at: user.last_login_time,
+                inactive_license: false,
+                cio_company_id: company.id,
+            };
+            new_user.inactive_license = new_user.is_inactive_license(user.created_at, now);
+            new_users.push(new_user);
+        }
+    }
+
+    let inactive = new_users.iter().filter(|u| u.inactive_license).count();
+    info!(
+        "syncing {} zoom users, {} licensed users with no login in {} days",
+        new_users.len(),
+        inactive,
+        INACTIVE_LICENSE_DAYS
+    );
+
+    let mut summary = if dry_run.is_enabled() {
+        info!("[dry-run] would save {} zoom users", new_users.len());
+        SyncSummary::default()
+    } else {
+        save_listing(
+            "zoom user",
+            &new_users,
+            |u| u.zoom_id.to_string(),
+            |u| u.upsert(db),
+            |ids| async move {
+                Ok(diesel::delete(
+                    zoom_users::dsl::zoom_users
+                        .filter(zoom_users::dsl::cio_company_id.eq(company.id))
+                        .filter(zoom_users::dsl::zoom_id.ne_all(ids)),
+                )
+                .execute_async(db.pool())
+                .await?)
+            },
+        )
+        .await?
+    };
+
+    summary += sync_to_airtable::<ZoomUser>(db, company, dry_run).await?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn user(license_type: &str, last_login_at: Option<DateTime<Utc>>) -> NewZoomUser {
+        NewZoomUser {
+            zoom_id: "z1".to_string(),
+            email: "jess@example.com".to_string(),
+            first_name: String::new(),
+            last_name: String::new(),
+            department: String::new(),
+            license_type: license_type.to_string(),
+            status: "active".to_string(),
+            last_login_at,
+            inactive_license: false,
+            link_to_auth_users: vec![],
+            cio_company_id: 1,
+        }
+    }
+
+    #[test]
+    fn test_is_inactive_license() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let recent = Some(now - Duration::days(10));
+        let old = Some(now - Duration::days(INACTIVE_LICENSE_DAYS + 1));
+
+        assert!(!user("licensed", recent).is_inactive_license(None, now));
+        assert!(user("licensed", old).is_inactive_license(None, now));
+        assert!(!user("basic", old).is_inactive_license(None, now));
+
+        // Never logged in, count from when the account was created.
+        assert!(!user("licensed", None).is_inactive_license(recent, now));
+        assert!(user("licensed", None).is_inactive_license(old, now));
+        assert!(!user("licensed", None).is_inactive_license(None, now));
+    }
+
+    #[test]
+    fn test_license_type() {
+        assert_eq!(license_type(1), "basic");
+        assert_eq!(license_type(2), "licensed");
+        assert_eq!(license_type(99), "none");
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+
+use chrono::{TimeZone, Utc};
+use cio_api::{
+    auth0::{Auth0Client, Auth0Error, ListUsersOptions, LogCheckpoint, STABLE_USERS_SORT},
+    auth0_logs::LogEvent,
+    auth_config::AuthConfig,
+    auth_logins::{upsert_auth_user_logins, upsert_auth_users, AuthUser, AuthUserLogin, NewAuthUser, NewAuthUserLogin},
+    companies::Company,
+    db::Database,
+    testing::{many_logs_event, MockAuth0, MANY_LOGS_USER, TENANT_USERS},
+};
+
+#[tokio::test]
+async fn test_list_users_follows_pages() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_users().await;
+
+    let users = auth0.client().list_users(&ListUsersOptions::default()).await.unwrap();
+
+    assert_eq!(users.len(), TENANT_USERS);
+    assert_eq!(users[0].email, "jess@oxide.computer");
+    assert_eq!(users[2].user_id, "auth0|65f000000000000000000003");
+    assert_eq!(users[2].last_login, None);
+}
+
+#[tokio::test]
+async fn test_list_users_pages_by_creation() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_users().await;
+
+    auth0.client().list_users(&ListUsersOptions::default()).await.unwrap();
+
+    // The pages are fetched at once, which is only safe on a sort that doesn't move.
+    let requests = auth0.server().received_requests().await.unwrap();
+    let pages: Vec<_> = requests.iter().filter(|r| r.url.path() == "/api/v2/users").collect();
+    assert_eq!(pages.len(), 2);
+    for page in pages {
+        assert!(page
+            .url
+            .query_pairs()
+            .any(|(k, v)| k == "sort" && v == STABLE_USERS_SORT));
+    }
+}
+
+#[tokio::test]
+async fn test_list_users_past_the_search_limit() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_many_users(2500).await;
+
+    let users = auth0.client().list_users(&ListUsersOptions::default()).await.unwrap();
+
+    assert_eq!(users.len(), 2500);
+    let ids: HashSet<&str> = users.iter().map(|u| u.user_id.as_str()).collect();
+    assert_eq!(ids.len(), 2500);
+}
+
+#[tokio::test]
+async fn test_list_user_logs() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_user_logs().await;
+
+    let logins = auth0
+        .client()
+        .list_user_logs("google-oauth2|100000000000000000001")
+        .await
+        .unwrap();
+
+    assert_eq!(logins.len(), 2);
+    assert!(logins[0].is_successful_login());
+    assert!(logins[1].is_failed_login());
+}
+
+#[tokio::test]
+async fn test_list_user_logs_since() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_user_logs().await;
+
+    let since = LogCheckpoint {
+        log_id: "90020240229000000000000000000000000000000000000000000000".to_string(),
+        date: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+    };
+    let logins = auth0
+        .client()
+        .list_user_logs_since("google-oauth2|100000000000000000001", Some(&since))
+        .await
+        .unwrap();
+
+    assert_eq!(logins.len(), 1);
+    assert!(logins[0].is_successful_login());
+    // The token and the one page of logs.
+    assert_eq!(auth0.server().received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_list_user_logs_past_the_page_limit() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_many_user_logs(1500).await;
+
+    let checkpoint: LogEvent = serde_json::from_value(many_logs_event(100)).unwrap();
+    let since = LogCheckpoint {
+        log_id: checkpoint.log_id,
+        date: checkpoint.date,
+    };
+    let logins = auth0
+        .client()
+        .list_user_logs_since(MANY_LOGS_USER, Some(&since))
+        .await
+        .unwrap();
+
+    // The pages of the user hold the newest 1000, the rest come from the tenant log stream
+    // after the checkpoint.
+    assert_eq!(logins.len(), 1399);
+    let ids: HashSet<&str> = logins.iter().map(|l| l.log_id.as_str()).collect();
+    assert_eq!(ids.len(), 1399);
+    assert!(logins.windows(2).all(|w| w[0].date >= w[1].date));
+    assert!(logins.iter().all(|l| l.date > since.date));
+}
+
+#[tokio::test]
+async fn test_list_user_logs_of_deleted_user() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_user_logs().await;
+    auth0.mount_user_logs_not_found().await;
+
+    let err = auth0.client().list_user_logs("github|1000002").await.unwrap_err();
+
+    assert!(err.downcast_ref::<Auth0Error>().unwrap().is_not_found());
+}
+
+#[tokio::test]
+async fn test_rate_limited_request_is_retried() {
+    let auth0 = MockAuth0::start("oxide").await;
+    auth0.mount_users().await;
+    auth0.mount_rate_limited("users", 1).await;
+
+    let client = auth0.client();
+    let users = client.list_users(&ListUsersOptions::default()).await.unwrap();
+
+    assert_eq!(users.len(), TENANT_USERS);
+    // The token, the rate limited page and the two pages.
+    let requests = auth0.server().received_requests().await.unwrap();
+    assert_eq!(requests.len(), 4);
+}
+
+/// Save the users of the tenant to the database, like a full sync does.
+async fn sync_users(auth0: &Auth0Client, db: &Database, company: &Company) {
+    let users: Vec<NewAuthUser> = auth0
+        .list_users(&ListUsersOptions::default())
+        .await
+        .unwrap()
+        .iter()
+        .map(|u| u.to_auth_user(company, auth0.domain(), &AuthConfig::default()))
+        .collect();
+    assert_eq!(upsert_auth_users(db, &users).await, TENANT_USERS);
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_resync_keeps_offboarded_user_deleted() {
+    let auth0 = MockAuth0::start("offboard-test").await;
+    auth0.mount_users().await;
+    let client = auth0.client();
+
+    let db = Database::new().await;
+    let company = Company::get_from_domain(&db, "oxide.computer").await.unwrap();
+    sync_users(&client, &db, &company).await;
+
+    // Offboard the first user, which blocks them in Auth0 and marks them deleted.
+    let user_id = "google-oauth2|100000000000000000001".to_string();
+    let mut auth_user = AuthUser::get_from_db(&db, user_id.to_string(), client.domain().to_string())
+        .await
+        .unwrap();
+    auth_user.deleted_at = Some(Utc::now());
+    auth_user.update(&db).await.unwrap();
+
+    // Blocked users are still listed, the next sync must not bring them back.
+    sync_users(&client, &db, &company).await;
+
+    let auth_user = AuthUser::get_from_db(&db, user_id, client.domain().to_string())
+        .await
+        .unwrap();
+    assert!(auth_user.deleted_at.is_some());
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_resync_keeps_hand_fixed_company() {
+    let auth0 = MockAuth0::start("company-fix-test").await;
+    auth0.mount_users().await;
+    let client = auth0.client();
+
+    let db = Database::new().await;
+    let company = Company::get_from_domain(&db, "oxide.computer").await.unwrap();
+    sync_users(&client, &db, &company).await;
+
+    // Fix the company by hand, like a pull from Airtable does.
+    let user_id = "google-oauth2|100000000000000000001".to_string();
+    let mut auth_user = AuthUser::get_from_db(&db, user_id.to_string(), client.domain().to_string())
+        .await
+        .unwrap();
+    auth_user.company = "Oxide Computer Company".to_string();
+    let fixed = auth_user.update(&db).await.unwrap();
+
+    sync_users(&client, &db, &company).await;
+
+    let auth_user = AuthUser::get_from_db(&db, user_id, client.domain().to_string())
+        .await
+        .unwrap();
+    assert_eq!(auth_user.company, "Oxide Computer Company");
+    // Nothing changed, so a company fixed in Airtable since still wins.
+    assert_eq!(auth_user.modified_at, fixed.modified_at);
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_resync_keeps_login_links_and_location() {
+    let auth0 = MockAuth0::start("login-resync-test").await;
+    auth0.mount_user_logs().await;
+    let client = auth0.client();
+
+    let db = Database::new().await;
+    let company = Company::get_from_domain(&db, "oxide.computer").await.unwrap();
+    let user_id = "google-oauth2|100000000000000000001";
+    let logins: Vec<NewAuthUserLogin> = client
+        .list_user_logs(user_id)
+        .await
+        .unwrap()
+        .iter()
+        .map(|event| event.to_auth_user_login(&company, client.domain()))
+        .collect();
+    assert_eq!(upsert_auth_user_logins(&db, &logins).await, 2);
+
+    // Link the login to its user and resolve its location, like Airtable and `geoip` do.
+    let mut login = AuthUserLogin::get_from_db(&db, user_id.to_string(), logins[0].date)
+        .await
+        .unwrap();
+    login.link_to_auth_user = vec!["recAuthUser".to_string()];
+    login.country = "US".to_string();
+    login.city = "Emeryville".to_string();
+    login.update(&db).await.unwrap();
+
+    // The logs are listed from the newest stored login on, so it is saved again.
+    assert_eq!(upsert_auth_user_logins(&db, &logins).await, 2);
+
+    let login = AuthUserLogin::get_from_db(&db, user_id.to_string(), logins[0].date)
+        .await
+        .unwrap();
+    assert_eq!(login.link_to_auth_user, vec!["recAuthUser".to_string()]);
+    assert_eq!(login.country, "US");
+    assert_eq!(login.city, "Emeryville");
+}
@@ -24,6 +24,13 @@ struct Params {
     /// If so, we will not add the derive method PartialEq to the new struct.
     #[serde(default)]
     custom_partial_eq: bool,
+    /// A boolean representing if the new struct keeps the link to its record in Airtable,
+    /// in an `airtable_record_url` field after `airtable_record_id`.
+    #[serde(default)]
+    airtable_record_url: bool,
+    /// The constant with the name of the table in Airtable the records are synced to. If
+    /// set, the new struct implements `AirtableRow`, which needs `airtable_record_url`.
+    airtable_table: Option<String>,
     /// The struct item and type that we will filter on to find unique database entries.
     match_on: BTreeMap<String, String>,
 }
@@ -237,12 +244,65 @@ fn do_db(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
         };
 
+        // Is this struct synced to Airtable?
+        let mut airtable_row_impl = quote!();
+        if let Some(table) = &params.airtable_table {
+            let table: syn::Path = syn::parse_str(table).unwrap();
+            airtable_row_impl = quote! {
+                #[async_trait::async_trait]
+                impl crate::airtable_sync::AirtableRow for #new_struct_name {
+                    type Fields = #og_struct_name;
+
+                    const AIRTABLE_TABLE: &'static str = #table;
+
+                    fn airtable_record_id(&self) -> &str {
+                        &self.airtable_record_id
+                    }
+
+                    fn set_airtable_record_id(&mut self, id: String) {
+                        self.airtable_record_id = id;
+                    }
+
+                    fn airtable_record_url(&self) -> &str {
+                        &self.airtable_record_url
+                    }
+
+                    fn set_airtable_record_url(&mut self, url: String) {
+                        self.airtable_record_url = url;
+                    }
+
+                    fn database_id(&self) -> i32 {
+                        self.id
+                    }
+
+                    async fn save(&self, db: &crate::db::Database) -> anyhow::Result<()> {
+                        self.update(db).await?;
+
+                        Ok(())
+                    }
+
+                    async fn list_from_db(db: &crate::db::Database, company: &crate::companies::Company) -> anyhow::Result<Vec<Self>> {
+                        Ok(#new_struct_name_plural::get_from_db(db, company.id).await?.into())
+                    }
+                }
+            };
+        }
+
         // Does this struct have a custom PartialEq function?
         let mut partial_eq_text = Default::default();
         if !params.custom_partial_eq {
             partial_eq_text = quote!(PartialEq,);
         }
 
+        // Does this struct keep the link to its record in Airtable?
+        let mut airtable_record_url_field = Default::default();
+        if params.airtable_record_url {
+            airtable_record_url_field = quote!(
+                #[serde(default, skip_serializing_if = "String::is_empty")]
+                pub airtable_record_url: String,
+            );
+        }
+
         let new_struct = quote!(
             #item
 
@@ -265,9 +325,13 @@ fn do_db(attr: TokenStream, item: TokenStream) -> TokenStream {
                 // This has to be the last field, due to the schemas.
                 #[serde(default, skip_serializing_if = "String::is_empty")]
                 pub airtable_record_id: String,
+                // This follows `airtable_record_id` in the schemas that have it.
+                #airtable_record_url_field
             }
 
             #db_impl
+
+            #airtable_row_impl
         );
 
         new_struct
@@ -5,7 +5,7 @@ use oauth2::{
     AuthUrl, ClientId, ClientSecret, Scope, StandardErrorResponse, TokenResponse, TokenUrl,
 };
 use reqwest::{header::HeaderValue, Client, Method, RequestBuilder, Response, StatusCode};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use std::{
     sync::{Arc, RwLock},
@@ -369,6 +369,20 @@ impl RampClient {
         }
     }
 
+    /// Fetch the page of a list that comes after the given one, if there is one.
+    pub async fn next_page<T: DeserializeOwned>(
+        &self,
+        list: &ResponseList<T>,
+    ) -> Result<Option<ResponseList<T>>, Error> {
+        match &list.page.next {
+            Some(next) => {
+                let req = self.client.request(Method::GET, next);
+                Ok(Some(self.execute(req).await?.json().await?))
+            }
+            None => Ok(None),
+        }
+    }
+
     fn request(&self, method: Method, path: &str) -> RequestBuilder {
         self.client
             .request(method, format!("https://api.ramp.com/developer/v1/{path}"))
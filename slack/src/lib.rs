@@ -203,27 +203,42 @@ impl Slack {
     /// List users on a workspace.
     /// FROM: https://api.slack.com/methods/users.list
     pub async fn list_users(&self) -> Result<Vec<User>> {
-        // Build the request.
-        // TODO: paginate.
-        let request = self.request(
-            &self.token,
-            Method::GET,
-            "users.list",
-            (),
-            Some(vec![("limit", "100".to_string())]),
-        )?;
+        let mut users: Vec<User> = Default::default();
+        let mut cursor = String::new();
+        loop {
+            // Build the request.
+            let mut query = vec![("limit", "200".to_string())];
+            if !cursor.is_empty() {
+                query.push(("cursor", cursor.to_string()));
+            }
+            let request = self.request(&self.token, Method::GET, "users.list", (), Some(query))?;
 
-        let resp = self.client.execute(request).await?;
-        match resp.status() {
-            StatusCode::OK => (),
-            s => {
-                bail!("status code: {}, body: {}", s, resp.text().await?);
+            let resp = self.client.execute(request).await?;
+            match resp.status() {
+                StatusCode::OK => (),
+                s => {
+                    bail!("status code: {}, body: {}", s, resp.text().await?);
+                }
+            };
+
+            let mut r: APIResponse = resp.json().await?;
+            if !r.ok {
+                bail!(
+                    "status code: {}, body: {}",
+                    StatusCode::OK,
+                    serde_json::json!(r).to_string()
+                );
             }
-        };
+            users.append(&mut r.users);
 
-        let r: APIResponse = resp.json().await?;
+            // Paginate.
+            cursor = r.response_metadata.next_cursor;
+            if cursor.is_empty() {
+                break;
+            }
+        }
 
-        Ok(r.users)
+        Ok(users)
     }
 
     /// Get the current user's identity.
@@ -1104,6 +1119,9 @@ pub struct APIResponse {
 
     #[serde(default, skip_serializing_if = "Vec::is_empty", alias = "members")]
     pub users: Vec<User>,
+
+    #[serde(default)]
+    pub response_metadata: ResponseMetadata,
 }
 
 /// The data type for a User.
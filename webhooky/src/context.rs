@@ -8,12 +8,17 @@ use cio_api::{
 };
 use std::sync::{Arc, RwLock};
 
-use crate::sagas::{create_registry, Saga};
+use crate::{
+    sagas::{create_registry, Saga},
+    scheduler::Jobs,
+};
 
 #[derive(Clone, Debug)]
 pub struct ServerContext {
     pub sec: Arc<steno::SecClient>,
     pub exec_registry: Arc<steno::ActionRegistry<Saga>>,
+    /// The cron jobs, registered by the scheduler when the server runs them.
+    pub jobs: Jobs,
     pub app: Context,
 }
 
@@ -24,6 +29,7 @@ impl ServerContext {
         Ok(Self {
             sec: Arc::new(steno::sec(logger, Arc::new(context.db.clone()))),
             exec_registry: Arc::new(create_registry()),
+            jobs: Default::default(),
             app: context,
         })
     }
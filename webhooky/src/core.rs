@@ -6,7 +6,7 @@ use clap::Parser;
 #[clap(version = clap::crate_version!(), author = clap::crate_authors!("\n"))]
 pub struct Opts {
     /// Print debug info
-    #[clap(short, long)]
+    #[clap(short, long, visible_alias = "verbose")]
     pub debug: bool,
 
     /// Print logs as json
@@ -22,21 +22,34 @@ pub enum SubCommand {
     Server(Server),
 
     CreateServerSpec(SpecOut),
+    AirtablePush(AirtablePush),
+    ListenAirtableChanges(ListenAirtableChanges),
+    OffboardAuthUser(OffboardAuthUser),
     SendRFDChangelog(SendRFDChangelog),
+    SendVerificationReminders(SendVerificationReminders),
+    SyncAirtableReplications(SyncAirtableReplications),
     SyncAnalytics(SyncAnalytics),
     #[clap(name = "sync-api-tokens")]
     SyncAPITokens(SyncAPITokens),
     SyncApplications(SyncApplications),
     SyncAssetInventory(SyncAssetInventory),
+    SyncAuthAndPageViews(SyncAuthAndPageViews),
+    SyncAuthUsers(SyncAuthUsers),
     SyncCompanies(SyncCompanies),
     SyncConfigs(SyncConfigs),
     SyncFinance(SyncFinance),
     SyncFunctions(SyncFunctions),
+    #[clap(name = "sync-github-members")]
+    SyncGitHubMembers(SyncGitHubMembers),
+    #[clap(name = "sync-gsuite-directory")]
+    SyncGSuiteDirectory(SyncGSuiteDirectory),
+    SyncGusto(SyncGusto),
     SyncHuddles(SyncHuddles),
     SyncInterviews(SyncInterviews),
     SyncJournalClubs(SyncJournalClubs),
     SyncMailingLists(SyncMailingLists),
     SyncOther(SyncOther),
+    SyncPageViews(SyncPageViews),
     SyncRecordedMeetings(SyncRecordedMeetings),
     SyncRepos(SyncRepos),
     #[clap(name = "sync-rfds")]
@@ -44,9 +57,11 @@ pub enum SubCommand {
     SyncSalesForce(SyncSalesForce),
     SyncShipments(SyncShipments),
     SyncShorturls(SyncShorturls),
+    SyncSlackUsers(SyncSlackUsers),
     SyncSwagInventory(SyncSwagInventory),
     SyncTravel(SyncTravel),
     SyncZoho(SyncZoho),
+    SyncZoomUsers(SyncZoomUsers),
 }
 
 /// A subcommand for running the server.
@@ -69,10 +84,80 @@ pub struct SpecOut {
     pub spec_file: std::path::PathBuf,
 }
 
+/// A subcommand for pushing a table from the database to Airtable.
+#[derive(Parser, Clone, Debug)]
+pub struct AirtablePush {
+    /// The table to push
+    #[clap(value_enum)]
+    pub table: AirtablePushTable,
+
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// The tables that can be pushed to Airtable.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AirtablePushTable {
+    AuthUsers,
+    AuthUserLogins,
+    AuthUserRoles,
+    AuthConnectionStats,
+    PageViews,
+    PageViewStats,
+    SyncRuns,
+    GsuiteUsers,
+    GsuiteGroups,
+    GithubMembers,
+    SlackUsers,
+    ZoomUsers,
+    MailingListSubscribers,
+    Rfds,
+    OutboundShipments,
+    InboundShipments,
+    RampExpenses,
+}
+
+/// A subcommand for pushing the auth users and page views to Airtable as they change,
+/// until the process is stopped.
+#[derive(Parser, Debug, Clone)]
+pub struct ListenAirtableChanges {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// A subcommand for offboarding a person from the applications they log in to with Auth0.
+#[derive(Parser, Debug, Clone)]
+pub struct OffboardAuthUser {
+    /// The email of the person to offboard
+    pub email: String,
+
+    /// Log the users that would be offboarded instead of offboarding them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 /// A subcommand for sending the RFD changelog.
 #[derive(Parser, Clone, Debug)]
 pub struct SendRFDChangelog {}
 
+/// A subcommand for reminding auth users to verify their email.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SendVerificationReminders {
+    /// Log the users that would be reminded instead of reminding them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// A subcommand for running the background job of replicating Airtable tables into other bases.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncAirtableReplications {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 /// A subcommand for running the background job of syncing analytics.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncAnalytics {}
@@ -82,13 +167,34 @@ pub struct SyncAnalytics {}
 pub struct SyncAPITokens {}
 
 /// A subcommand for running the background job of syncing applications.
-#[derive(Parser, Debug, Clone)]
-pub struct SyncApplications {}
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncApplications {
+    /// Log the changes to the applicants from Google Sheets instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
 
 /// A subcommand for running the background job of syncing asset inventory.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncAssetInventory {}
 
+/// A subcommand for running the background job of syncing auth users from Auth0, pushing
+/// them and their logins to Airtable, and then syncing the page views that link to them.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncAuthAndPageViews {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// A subcommand for running the background job of syncing auth users from Auth0.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncAuthUsers {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 /// A subcommand for running the background job of syncing companies.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncCompanies {}
@@ -98,13 +204,41 @@ pub struct SyncCompanies {}
 pub struct SyncConfigs {}
 
 /// A subcommand for running the background job of syncing finance data.
-#[derive(Parser, Debug, Clone)]
-pub struct SyncFinance {}
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncFinance {
+    /// Log the Ramp expenses that would change instead of changing them
+    #[clap(long)]
+    pub dry_run: bool,
+}
 
 /// A subcommand for running the background job of syncing functions.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncFunctions {}
 
+/// A subcommand for running the background job of syncing the GitHub organization members.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncGitHubMembers {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// A subcommand for running the background job of syncing the GSuite directory.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncGSuiteDirectory {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// A subcommand for running the background job of syncing the employees in Gusto.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncGusto {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 /// A subcommand for running the background job of syncing interviews.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncInterviews {}
@@ -118,13 +252,33 @@ pub struct SyncHuddles {}
 pub struct SyncJournalClubs {}
 
 /// A subcommand for running the background job of syncing mailing lists.
-#[derive(Parser, Debug, Clone)]
-pub struct SyncMailingLists {}
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncMailingLists {
+    /// Log the changes to the Mailchimp subscribers instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
 
 /// A subcommand for running the background job of syncing other things.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncOther {}
 
+/// A subcommand for running the background job of syncing page views to Airtable.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncPageViews {
+    /// Only sync the page views from this date on, for example 2024-01-01
+    #[clap(long)]
+    pub since: Option<chrono::NaiveDate>,
+
+    /// Only sync the daily rollups to Airtable, not every page view
+    #[clap(long)]
+    pub rollups_only: bool,
+
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 /// A subcommand for running the background job of syncing recorded_meetings.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncRecordedMeetings {}
@@ -134,21 +288,37 @@ pub struct SyncRecordedMeetings {}
 pub struct SyncRepos {}
 
 /// A subcommand for running the background job of syncing RFDs.
-#[derive(Parser, Debug, Clone)]
-pub struct SyncRFDs {}
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncRFDs {
+    /// Log the changes to the RFDs table in Airtable instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
 
 /// A subcommand for running the background job of syncing SalesForce leads.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncSalesForce {}
 
 /// A subcommand for running the background job of syncing shipments.
-#[derive(Parser, Debug, Clone)]
-pub struct SyncShipments {}
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncShipments {
+    /// Log the tracking refreshes and the changes to Airtable instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
 
 /// A subcommand for running the background job of syncing shorturls.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncShorturls {}
 
+/// A subcommand for running the background job of syncing the Slack workspace members.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncSlackUsers {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 /// A subcommand for running the background job of syncing swag inventory.
 #[derive(Parser, Debug, Clone)]
 pub struct SyncSwagInventory {}
@@ -161,31 +331,51 @@ pub struct SyncTravel {}
 #[derive(Parser, Debug, Clone)]
 pub struct SyncZoho {}
 
+/// A subcommand for running the background job of syncing Zoom users and their licenses.
+#[derive(Parser, Debug, Clone, Default)]
+pub struct SyncZoomUsers {
+    /// Log the changes instead of making them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 pub fn into_job_command(cmd: &str) -> Option<SubCommand> {
     match cmd {
         "send-rfd-changelog" => Some(SubCommand::SendRFDChangelog(SendRFDChangelog {})),
+        "send-verification-reminders" => Some(SubCommand::SendVerificationReminders(
+            SendVerificationReminders::default(),
+        )),
+        "sync-airtable-replications" => Some(SubCommand::SyncAirtableReplications(SyncAirtableReplications::default())),
         "sync-analytics" => Some(SubCommand::SyncAnalytics(SyncAnalytics {})),
         "sync-api-tokens" => Some(SubCommand::SyncAPITokens(SyncAPITokens {})),
-        "sync-applications" => Some(SubCommand::SyncApplications(SyncApplications {})),
+        "sync-applications" => Some(SubCommand::SyncApplications(SyncApplications::default())),
         "sync-asset-inventory" => Some(SubCommand::SyncAssetInventory(SyncAssetInventory {})),
+        "sync-auth-and-page-views" => Some(SubCommand::SyncAuthAndPageViews(SyncAuthAndPageViews::default())),
+        "sync-auth-users" => Some(SubCommand::SyncAuthUsers(SyncAuthUsers::default())),
         "sync-companies" => Some(SubCommand::SyncCompanies(SyncCompanies {})),
         "sync-configs" => Some(SubCommand::SyncConfigs(SyncConfigs {})),
-        "sync-finance" => Some(SubCommand::SyncFinance(SyncFinance {})),
+        "sync-finance" => Some(SubCommand::SyncFinance(SyncFinance::default())),
         "sync-functions" => Some(SubCommand::SyncFunctions(SyncFunctions {})),
+        "sync-github-members" => Some(SubCommand::SyncGitHubMembers(SyncGitHubMembers::default())),
+        "sync-gsuite-directory" => Some(SubCommand::SyncGSuiteDirectory(SyncGSuiteDirectory::default())),
+        "sync-gusto" => Some(SubCommand::SyncGusto(SyncGusto::default())),
         "sync-huddles" => Some(SubCommand::SyncHuddles(SyncHuddles {})),
         "sync-interviews" => Some(SubCommand::SyncInterviews(SyncInterviews {})),
         "sync-journal-clubs" => Some(SubCommand::SyncJournalClubs(SyncJournalClubs {})),
-        "sync-mailing-lists" => Some(SubCommand::SyncMailingLists(SyncMailingLists {})),
+        "sync-mailing-lists" => Some(SubCommand::SyncMailingLists(SyncMailingLists::default())),
         "sync-other" => Some(SubCommand::SyncOther(SyncOther {})),
+        "sync-page-views" => Some(SubCommand::SyncPageViews(SyncPageViews::default())),
         "sync-recorded-meetings" => Some(SubCommand::SyncRecordedMeetings(SyncRecordedMeetings {})),
         "sync-repos" => Some(SubCommand::SyncRepos(SyncRepos {})),
-        "sync-rfds" => Some(SubCommand::SyncRFDs(SyncRFDs {})),
+        "sync-rfds" => Some(SubCommand::SyncRFDs(SyncRFDs::default())),
         "sync-salesforce" => Some(SubCommand::SyncSalesForce(SyncSalesForce {})),
-        "sync-shipments" => Some(SubCommand::SyncShipments(SyncShipments {})),
+        "sync-shipments" => Some(SubCommand::SyncShipments(SyncShipments::default())),
         "sync-shorturls" => Some(SubCommand::SyncShorturls(SyncShorturls {})),
+        "sync-slack-users" => Some(SubCommand::SyncSlackUsers(SyncSlackUsers::default())),
         "sync-swag-inventory" => Some(SubCommand::SyncSwagInventory(SyncSwagInventory {})),
         "sync-travel" => Some(SubCommand::SyncTravel(SyncTravel {})),
         "sync-zoho" => Some(SubCommand::SyncZoho(SyncZoho {})),
+        "sync-zoom-users" => Some(SubCommand::SyncZoomUsers(SyncZoomUsers::default())),
         _ => None,
     }
 }
@@ -3,13 +3,16 @@ use async_bb8_diesel::AsyncRunQueryDsl;
 use chrono::{TimeZone, Utc};
 use chrono_humanize::HumanTime;
 use cio_api::{
+    airtable_sync::{sync_records_to_airtable, AirtableCache},
     analytics::NewPageView,
     applicants::Applicant,
     asset_inventory::AssetItem,
     certs::Certificate,
     companies::Company,
     configs::User,
+    core::DryRun,
     journal_clubs::JournalClubMeeting,
+    metrics::{self, Outcome},
     rfd::RFD,
     schema::{applicants, inbound_shipments, journal_club_meetings, outbound_shipments},
     shipments::{InboundShipment, NewInboundShipment, OutboundShipment, OutboundShipments},
@@ -922,6 +925,20 @@ pub async fn handle_shippo_tracking_update(
     Ok(())
 }
 
+pub async fn handle_airtable_webhook_notification(
+    _rqctx: &RequestContext<ServerContext>,
+    notification: airtable_api::WebhookNotification,
+) -> Result<()> {
+    // The notification only says that something changed, the changes are in the payloads
+    // of the webhook, which whoever consumes them lists from their own cursor.
+    info!(
+        "airtable webhook {} notified of changes to base {} at {:?}",
+        notification.webhook.id, notification.base.id, notification.timestamp
+    );
+
+    Ok(())
+}
+
 pub async fn handle_checkr_background_update(
     rqctx: &RequestContext<ServerContext>,
     event: checkr::WebhookEvent,
@@ -1003,6 +1020,9 @@ pub async fn handle_docusign_envelope_update(
                 applicant
                     .update_applicant_from_docusign_offer_envelope(db, &ds, event.clone())
                     .await?;
+                // Show the new status of the offer to the hiring team right away.
+                sync_records_to_airtable(db, &company, &AirtableCache::default(), vec![applicant], DryRun(false))
+                    .await?;
             }
 
             // Since we got the ID, then return early here.
@@ -1033,6 +1053,8 @@ pub async fn handle_docusign_envelope_update(
                 applicant
                     .update_applicant_from_docusign_piia_envelope(db, &ds, event)
                     .await?;
+                sync_records_to_airtable(db, &company, &AirtableCache::default(), vec![applicant], DryRun(false))
+                    .await?;
             }
 
             // Since we got the ID, then return early here.
@@ -1053,12 +1075,18 @@ pub async fn handle_analytics_page_view(rqctx: &RequestContext<ServerContext>, m
     let api_context = rqctx.context();
     let db = &api_context.app.db;
 
+    metrics::record("page_views", Outcome::Fetched, 1);
+
     // Expand the page_view.
     event.set_page_link();
     event.set_company_id(db).await.unwrap();
 
     // Add the page_view to the database and Airttable.
-    let pv = event.create(db).await?;
+    let pv = event.create(db).await.map_err(|e| {
+        metrics::record("page_views", Outcome::Errored, 1);
+        e
+    })?;
+    metrics::record("page_views", Outcome::Updated, 1);
 
     info!("page_view `{} | {}` created successfully", pv.page_link, pv.user_email);
     Ok(())
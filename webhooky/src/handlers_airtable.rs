@@ -0,0 +1,42 @@
+use airtable_api::{parse_webhook_mac, WEBHOOK_MAC_HEADER};
+use anyhow::Result;
+use async_trait::async_trait;
+use dropshot::{RequestContext, ServerContext};
+use dropshot_verify_request::sig::HmacSignatureVerifier;
+use hmac::Hmac;
+use log::info;
+use sha2::Sha256;
+
+use crate::http::Headers;
+
+#[derive(Debug)]
+pub struct AirtableWebhookVerification;
+
+#[async_trait]
+impl HmacSignatureVerifier for AirtableWebhookVerification {
+    type Algo = Hmac<Sha256>;
+
+    async fn key<Context: ServerContext>(_: &RequestContext<Context>) -> Result<Vec<u8>> {
+        // The macSecretBase64 Airtable returned when the webhook was created.
+        let secret = std::env::var("AIRTABLE_WH_MAC_SECRET")
+            .map_err(|_| anyhow::anyhow!("Failed to find the MAC secret of the Airtable webhook"))?;
+
+        Ok(base64::decode(secret)?)
+    }
+
+    async fn signature<Context: ServerContext>(rqctx: &RequestContext<Context>) -> Result<Vec<u8>> {
+        let headers = Headers::from_request(rqctx).await?;
+        let signature = headers
+            .0
+            .get(WEBHOOK_MAC_HEADER)
+            .ok_or_else(|| anyhow::anyhow!("Airtable webhook is missing signature"))
+            .and_then(|header_value| Ok(header_value.to_str()?))
+            .and_then(parse_webhook_mac)
+            .map_err(|err| {
+                info!("Airtable webhook is missing a well-formed signature: {}", err);
+                err
+            })?;
+
+        Ok(signature)
+    }
+}
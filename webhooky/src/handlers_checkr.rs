@@ -20,9 +20,9 @@ impl HmacSignatureVerifier for CheckrWebhookVerification {
         match std::env::var("CHECKR_API_KEY") {
             Ok(key) => Ok(key.into_bytes()),
             Err(_) => {
-                // We only have a generic context here so we can not take values out. Instead construct a
-                // new db connection in the meantime
-                let db = Database::new().await;
+                // We only have a generic context here so we can not take values out. Instead use the
+                // pool shared by the process
+                let db = Database::shared().await?;
 
                 Ok(Company::get_from_db(&db, "Oxide".to_string())
                     .await
@@ -1,14 +1,47 @@
+use std::collections::BTreeMap;
+
 use anyhow::Result;
 use async_bb8_diesel::AsyncRunQueryDsl;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use chrono_humanize::HumanTime;
-use cio_api::{functions::Function, schema::functions};
+use cio_api::{
+    functions::Function,
+    schema::functions,
+    sync_runs::{get_sync_status, SyncStatus},
+};
 use diesel::{ExpressionMethods, QueryDsl};
 use log::info;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
 
-use crate::context::ServerContext;
+use crate::{context::ServerContext, scheduler::JobStatus};
 
 pub async fn run_subcmd_job(server_context: &ServerContext, cmd_name: &str) -> Result<uuid::Uuid> {
+    Ok(start_subcmd_job(server_context, cmd_name).await?.0)
+}
+
+/// Run the job and wait for its saga to complete. A run of the job that is already in
+/// progress is left to finish on its own, there is nothing of ours to wait on.
+pub async fn run_subcmd_job_to_completion(server_context: &ServerContext, cmd_name: &str) -> Result<()> {
+    match start_subcmd_job(server_context, cmd_name).await? {
+        (_, Some(completion)) => completion.await?,
+        (id, None) => {
+            info!(
+                "job `{}` is already running as saga `{}`, not waiting on it",
+                cmd_name, id
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Start the saga of the job, unless a run of it is already in progress. Returns the id of
+/// the saga, and the handle of its completion if we started it.
+async fn start_subcmd_job(
+    server_context: &ServerContext,
+    cmd_name: &str,
+) -> Result<(uuid::Uuid, Option<JoinHandle<Result<()>>>)> {
     let db = &server_context.app.db;
 
     // Check if we already have an in-progress run for this job.
@@ -34,14 +67,14 @@ pub async fn run_subcmd_job(server_context: &ServerContext, cmd_name: &str) -> R
             );
             // TODO: a better way to be to check if we know about the saga.
             // Return that uuid versus starting another.
-            return Ok(u);
+            return Ok((u, None));
         }
     }
 
     let id = uuid::Uuid::new_v4();
 
     // Run the saga.
-    crate::sagas::run_cmd(
+    let completion = crate::sagas::run_cmd(
         db,
         &server_context.sec,
         server_context.exec_registry.clone(),
@@ -50,5 +83,79 @@ pub async fn run_subcmd_job(server_context: &ServerContext, cmd_name: &str) -> R
     )
     .await?;
 
-    Ok(id)
+    Ok((id, Some(completion)))
+}
+
+/// The last run of a job.
+#[derive(Debug, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct JobRunStatus {
+    pub name: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub conclusion: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    pub saga_id: String,
+}
+
+impl From<Function> for JobRunStatus {
+    fn from(f: Function) -> Self {
+        JobRunStatus {
+            name: f.name,
+            status: f.status,
+            conclusion: f.conclusion,
+            started_at: f.created_at,
+            completed_at: f.completed_at,
+            saga_id: f.saga_id,
+        }
+    }
+}
+
+/// Returns the last run of each job, by name.
+pub async fn get_job_status(server_context: &ServerContext) -> Result<Vec<JobRunStatus>> {
+    let db = &server_context.app.db;
+
+    let latest = functions::dsl::functions
+        .filter(functions::dsl::cio_company_id.eq(server_context.app.company.id))
+        .distinct_on(functions::dsl::name)
+        .order_by((functions::dsl::name, functions::dsl::created_at.desc()))
+        .load_async::<Function>(db.pool())
+        .await?;
+
+    Ok(latest.into_iter().map(JobRunStatus::from).collect())
+}
+
+/// The query of the status endpoint.
+#[derive(Debug, Clone, Default, JsonSchema, Deserialize, Serialize)]
+pub struct StatusQuery {
+    /// Mark the syncs that have not succeeded within this many hours as stale.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_hours: Option<i64>,
+}
+
+/// The last run of each job, and how fresh the data of each sync is.
+#[derive(Debug, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct ServerStatus {
+    pub jobs: Vec<JobRunStatus>,
+    pub syncs: Vec<SyncStatus>,
+    /// The cron jobs of the scheduler, by name. Empty unless the server runs them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub schedule: BTreeMap<String, JobStatus>,
+}
+
+pub async fn get_status(server_context: &ServerContext, query: StatusQuery) -> Result<ServerStatus> {
+    let jobs = get_job_status(server_context).await?;
+    let syncs = get_sync_status(
+        &server_context.app.db,
+        &server_context.app.company,
+        query.max_age_hours.map(chrono::Duration::hours),
+    )
+    .await?;
+
+    Ok(ServerStatus {
+        jobs,
+        syncs,
+        schedule: server_context.jobs.status(),
+    })
 }
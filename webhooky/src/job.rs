@@ -1,13 +1,122 @@
-use crate::context::Context;
+use crate::{context::Context, core::AirtablePushTable};
 use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use cio_api::{
+    airtable_sync::{sync_to_airtable, AirtableCache, AirtableSyncable, SyncSummary},
+    analytics::{PageView, PageViewStat},
+    app_config::AnalyticsConfig,
+    auth_logins::{AuthConnectionStat, AuthUser, AuthUserLogin, AuthUserRole},
+    companies::Company,
+    core::DryRun,
+    db::Database,
+    error::CioError,
+    finance::RampExpense,
+    github_members::GitHubOrgMember,
+    gsuite_directory::{GSuiteDirectoryGroup, GSuiteDirectoryUser},
+    mailing_list::MailingListSubscriber,
+    rfd::RFD,
+    shipments::{InboundShipment, OutboundShipment},
+    slack_users::SlackUser,
+    sync_plan::SyncPlan,
+    sync_runs::{record_sync_run, SyncRun},
+    zoom::ZoomUser,
+};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+
+/// Cancel the syncs of a job on the first SIGINT or SIGTERM, so they stop at their next
+/// checkpoint and save what they have. A second signal exits right away.
+pub fn cancel_on_signal() -> Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+
+    std::thread::spawn(move || {
+        let mut received = signals.forever();
+        if let Some(sig) = received.next() {
+            log::info!(
+                "received signal: {:?}, stopping the syncs at their next checkpoint",
+                sig
+            );
+            cio_api::cancel::shutdown().cancel();
+        }
+        if let Some(sig) = received.next() {
+            log::info!("received signal: {:?} again, exiting", sig);
+            std::process::exit(1);
+        }
+    });
+
+    Ok(())
+}
 
 pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Result<()> {
     match cmd {
+        crate::core::SubCommand::AirtablePush(push) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(push.dry_run);
+            let summary = match push.table {
+                AirtablePushTable::AuthUsers => airtable_push::<AuthUser>(&db, &company, dry_run).await?,
+                AirtablePushTable::AuthUserLogins => airtable_push::<AuthUserLogin>(&db, &company, dry_run).await?,
+                AirtablePushTable::AuthUserRoles => airtable_push::<AuthUserRole>(&db, &company, dry_run).await?,
+                AirtablePushTable::AuthConnectionStats => {
+                    airtable_push::<AuthConnectionStat>(&db, &company, dry_run).await?
+                }
+                AirtablePushTable::PageViews => airtable_push::<PageView>(&db, &company, dry_run).await?,
+                AirtablePushTable::PageViewStats => airtable_push::<PageViewStat>(&db, &company, dry_run).await?,
+                AirtablePushTable::SyncRuns => airtable_push::<SyncRun>(&db, &company, dry_run).await?,
+                AirtablePushTable::GsuiteUsers => airtable_push::<GSuiteDirectoryUser>(&db, &company, dry_run).await?,
+                AirtablePushTable::GsuiteGroups => {
+                    airtable_push::<GSuiteDirectoryGroup>(&db, &company, dry_run).await?
+                }
+                AirtablePushTable::GithubMembers => airtable_push::<GitHubOrgMember>(&db, &company, dry_run).await?,
+                AirtablePushTable::SlackUsers => airtable_push::<SlackUser>(&db, &company, dry_run).await?,
+                AirtablePushTable::ZoomUsers => airtable_push::<ZoomUser>(&db, &company, dry_run).await?,
+                AirtablePushTable::MailingListSubscribers => {
+                    airtable_push::<MailingListSubscriber>(&db, &company, dry_run).await?
+                }
+                AirtablePushTable::Rfds => airtable_push::<RFD>(&db, &company, dry_run).await?,
+                AirtablePushTable::OutboundShipments => {
+                    airtable_push::<OutboundShipment>(&db, &company, dry_run).await?
+                }
+                AirtablePushTable::InboundShipments => airtable_push::<InboundShipment>(&db, &company, dry_run).await?,
+                AirtablePushTable::RampExpenses => airtable_push::<RampExpense>(&db, &company, dry_run).await?,
+            };
+            log::info!("pushed {:?} to airtable: {:?}", push.table, summary);
+        }
+        crate::core::SubCommand::ListenAirtableChanges(listen) => {
+            let Context { db, .. } = context;
+            cio_api::change_listener::listen_for_changes(&db, cio_api::cancel::shutdown(), DryRun(listen.dry_run))
+                .await?;
+        }
+        crate::core::SubCommand::OffboardAuthUser(offboard) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(offboard.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "offboard-auth-user",
+                dry_run,
+                cio_api::auth_logins::offboard_auth_user(&db, &company, &offboard.email, dry_run),
+            )
+            .await?;
+        }
         crate::core::SubCommand::SendRFDChangelog(_) => {
             let Context { db, company, .. } = context;
             cio_api::rfd::send_rfd_changelog(&db, &company).await?;
         }
-        crate::core::SubCommand::SyncApplications(_) => {
+        crate::core::SubCommand::SendVerificationReminders(send) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(send.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "send-verification-reminders",
+                dry_run,
+                cio_api::email_verification::send_verification_reminders(&db, &company, dry_run),
+            )
+            .await?;
+        }
+        crate::core::SubCommand::SyncApplications(sync) => {
             let Context {
                 app_config,
                 db,
@@ -15,13 +124,84 @@ pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Resu
                 ..
             } = context;
 
-            // Do the new applicants.
+            // Pull in the applicants from the application sheets.
             let app_config = app_config.read().unwrap().clone();
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-applicant-sheets",
+                dry_run,
+                cio_api::applicant_sheets::refresh_sheet_applicants(&db, &company, &app_config, dry_run),
+            )
+            .await?;
+
+            // Do the new applicants.
             cio_api::applicants::refresh_new_applicants_and_reviews(&db, &company, &app_config).await?;
 
             // Refresh DocuSign for the applicants.
             cio_api::applicants::refresh_docusign_for_applicants(&db, &company, &app_config).await?;
         }
+        crate::core::SubCommand::SyncAirtableReplications(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-airtable-replications",
+                dry_run,
+                cio_api::airtable_replication::refresh_airtable_replications(&company, dry_run),
+            )
+            .await?;
+        }
+        crate::core::SubCommand::SyncAuthAndPageViews(sync) => {
+            let Context {
+                db,
+                company,
+                app_config,
+                ..
+            } = context;
+            let app_config = app_config.read().unwrap().clone();
+            let dry_run = DryRun(sync.dry_run);
+            // The logins and page views link to the Airtable records of the auth users, so
+            // those have to be pushed first.
+            SyncPlan::new()
+                .job(
+                    "sync-auth-users",
+                    &[],
+                    cio_api::auth_logins::refresh_db_auth(&db, &company, dry_run),
+                )
+                .job(
+                    "sync-auth-users-to-airtable",
+                    &["sync-auth-users"],
+                    sync_to_airtable::<AuthUser>(&db, &company, dry_run),
+                )
+                .job(
+                    "sync-auth-user-logins-to-airtable",
+                    &["sync-auth-users-to-airtable"],
+                    sync_to_airtable::<AuthUserLogin>(&db, &company, dry_run),
+                )
+                .job(
+                    "sync-page-views",
+                    &["sync-auth-users-to-airtable"],
+                    sync_page_views(&db, &company, &app_config.analytics, None, false, dry_run),
+                )
+                .run(&db, &company, dry_run)
+                .await?
+                .into_result()?;
+        }
+        crate::core::SubCommand::SyncAuthUsers(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-auth-users",
+                dry_run,
+                cio_api::auth_logins::refresh_db_auth(&db, &company, dry_run),
+            )
+            .await?;
+        }
         crate::core::SubCommand::SyncConfigs(_) => {
             let Context {
                 app_config,
@@ -32,7 +212,7 @@ pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Resu
             let config = app_config.read().unwrap().clone();
             // cio_api::configs::refresh_db_configs_and_airtable(&db, &company, &config).await?;
         }
-        crate::core::SubCommand::SyncFinance(_) => {
+        crate::core::SubCommand::SyncFinance(sync) => {
             let Context {
                 app_config,
                 db,
@@ -40,12 +220,59 @@ pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Resu
                 ..
             } = context;
             let app_config = app_config.read().unwrap().clone();
-            cio_api::finance::refresh_all_finance(&db, &company, &app_config.finance).await?;
+            let dry_run = DryRun(sync.dry_run);
+            if !dry_run.is_enabled() {
+                cio_api::finance::refresh_all_finance(&db, &company, &app_config.finance).await?;
+            }
+            record_sync_run(
+                &db,
+                &company,
+                "sync-ramp-expenses",
+                dry_run,
+                cio_api::finance::refresh_ramp_expenses(&db, &company, dry_run),
+            )
+            .await?;
         }
         crate::core::SubCommand::SyncFunctions(_) => {
             let Context { db, company, .. } = context;
             cio_api::functions::refresh_functions(&db, &company).await?;
         }
+        crate::core::SubCommand::SyncGitHubMembers(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-github-members",
+                dry_run,
+                cio_api::github_members::refresh_github_members(&db, &company, dry_run),
+            )
+            .await?;
+        }
+        crate::core::SubCommand::SyncGSuiteDirectory(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-gsuite-directory",
+                dry_run,
+                cio_api::gsuite_directory::refresh_gsuite_directory(&db, &company, dry_run),
+            )
+            .await?;
+        }
+        crate::core::SubCommand::SyncGusto(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-gusto",
+                dry_run,
+                cio_api::gusto::refresh_gusto_employees(&db, &company, dry_run),
+            )
+            .await?;
+        }
         crate::core::SubCommand::SyncHuddles(_) => {
             let Context { db, company, .. } = context;
             cio_api::huddles::sync_changes_to_google_events(&db, &company).await?;
@@ -62,16 +289,47 @@ pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Resu
             let Context { db, company, .. } = context;
             cio_api::journal_clubs::refresh_db_journal_club_meetings(&db, &company).await?;
         }
-        crate::core::SubCommand::SyncMailingLists(_) => {
+        crate::core::SubCommand::SyncMailingLists(sync) => {
+            let Context { db, company, .. } = context;
+
             if std::env::var("MAILERLITE_ENABLED")
                 .map(|v| v == "true")
                 .unwrap_or(false)
             {
-                let Context { db, .. } = context;
-
                 crate::mailing_lists::sync_pending_mailing_list_subscribers(&db).await?;
                 crate::mailing_lists::sync_pending_wait_list_subscribers(&db).await?;
             }
+
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-mailing-lists",
+                dry_run,
+                cio_api::mailing_list::refresh_mailchimp_subscribers(&db, &company, dry_run),
+            )
+            .await?;
+        }
+        crate::core::SubCommand::SyncPageViews(sync) => {
+            let Context {
+                db,
+                company,
+                app_config,
+                ..
+            } = context;
+            let app_config = app_config.read().unwrap().clone();
+            let since = sync
+                .since
+                .map(|since| chrono::Utc.from_utc_datetime(&since.and_hms_opt(0, 0, 0).unwrap()));
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-page-views",
+                dry_run,
+                sync_page_views(&db, &company, &app_config.analytics, since, sync.rollups_only, dry_run),
+            )
+            .await?;
         }
         crate::core::SubCommand::SyncRecordedMeetings(_) => {
             let Context { db, company, .. } = context;
@@ -100,10 +358,20 @@ pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Resu
             sync_result?;
             refresh_result?;
         }
-        crate::core::SubCommand::SyncRFDs(_) => {
+        crate::core::SubCommand::SyncRFDs(sync) => {
             let Context { db, company, .. } = &context;
             crate::handlers_rfd::refresh_db_rfds(&context).await?;
             cio_api::rfd::drive::cleanup_rfd_pdfs(db, company).await?;
+
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                db,
+                company,
+                "sync-rfds",
+                dry_run,
+                sync_to_airtable::<RFD>(db, company, dry_run),
+            )
+            .await?;
         }
         crate::core::SubCommand::SyncOther(_) => {
             let Context { company, .. } = context;
@@ -115,12 +383,85 @@ pub async fn run_job_cmd(cmd: crate::core::SubCommand, context: Context) -> Resu
             let Context { db, company, .. } = context;
             cio_api::sf::refresh_sf_leads(&db, &company).await?;
         }
+        crate::core::SubCommand::SyncShipments(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+
+            // Creating the labels in Shippo can't be previewed, so it is skipped in a dry run.
+            if !dry_run.is_enabled() {
+                cio_api::shipments::refresh_outbound_shipments(&db, &company).await?;
+            }
+
+            record_sync_run(
+                &db,
+                &company,
+                "sync-shipments",
+                dry_run,
+                cio_api::shipments::refresh_shipment_tracking(&db, &company, dry_run),
+            )
+            .await?;
+        }
         crate::core::SubCommand::SyncShorturls(_) => {
             let Context { db, company, .. } = context;
             cio_api::shorturls::refresh_shorturls(&db, &company).await?;
         }
+        crate::core::SubCommand::SyncSlackUsers(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-slack-users",
+                dry_run,
+                cio_api::slack_users::refresh_slack_users(&db, &company, dry_run),
+            )
+            .await?;
+        }
+        crate::core::SubCommand::SyncZoomUsers(sync) => {
+            let Context { db, company, .. } = context;
+            let dry_run = DryRun(sync.dry_run);
+            record_sync_run(
+                &db,
+                &company,
+                "sync-zoom-users",
+                dry_run,
+                cio_api::zoom::refresh_zoom_users(&db, &company, dry_run),
+            )
+            .await?;
+        }
         other => anyhow::bail!("Non-job subcommand passed to job runner {:?}", other),
     }
 
     Ok(())
 }
+
+/// Sync the page views from Google Analytics, roll them up by day and push both to
+/// Airtable. With `rollups_only` only the daily rollups are pushed.
+async fn sync_page_views(
+    db: &Database,
+    company: &Company,
+    analytics: &AnalyticsConfig,
+    since: Option<DateTime<Utc>>,
+    rollups_only: bool,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let cache = AirtableCache::default();
+    let mut summary = cio_api::analytics::refresh_ga4_page_views(db, company, analytics, since, dry_run).await?;
+    summary += cio_api::analytics::refresh_page_view_stats(db, company, since, dry_run).await?;
+    summary += cio_api::analytics::sync_page_view_stats_to_airtable(db, company, since, &cache, dry_run).await?;
+    if !rollups_only {
+        summary += cio_api::analytics::sync_page_views_to_airtable(db, company, since, &cache, dry_run).await?;
+    }
+
+    Ok(summary)
+}
+
+/// Push a table to Airtable, recording the run.
+async fn airtable_push<T: AirtableSyncable>(
+    db: &Database,
+    company: &Company,
+    dry_run: DryRun,
+) -> Result<SyncSummary, CioError> {
+    let job = format!("airtable-push {}", T::AIRTABLE_TABLE);
+    record_sync_run(db, company, &job, dry_run, sync_to_airtable::<T>(db, company, dry_run)).await
+}
@@ -7,6 +7,7 @@ mod cors;
 mod event_types;
 pub mod github_types;
 mod handlers;
+pub mod handlers_airtable;
 pub mod handlers_auth;
 pub mod handlers_checkr;
 pub mod handlers_cron;
@@ -22,6 +23,7 @@ mod job;
 mod mailing_lists;
 mod repos;
 mod sagas;
+pub mod scheduler;
 pub mod server;
 mod slack_commands;
 // mod tracking_numbers;
@@ -7,6 +7,7 @@ mod cors;
 mod event_types;
 mod github_types;
 mod handlers;
+mod handlers_airtable;
 mod handlers_auth;
 mod handlers_checkr;
 mod handlers_cron;
@@ -20,8 +21,10 @@ mod health;
 mod http;
 mod job;
 mod mailing_lists;
+mod progress;
 mod repos;
 mod sagas;
+mod scheduler;
 mod server;
 mod slack_commands;
 // mod tracking_numbers;
@@ -79,6 +82,9 @@ async fn tokio_main() -> Result<()> {
     }
     slog_stdlog::init_with_level(log_level)?;
 
+    // Resolve the credentials before anything needs them.
+    cio_api::secrets::init().await?;
+
     let api = APIConfig::new()?;
 
     let context = ServerContext::new(1, logger).await?;
@@ -105,7 +111,14 @@ pub async fn run_main_cmd(opts: crate::core::Opts, api: APIConfig, context: Serv
             let mut buffer = File::create(spec_file)?;
             api.open_api().write(&mut buffer)?;
         }
-        job => crate::job::run_job_cmd(job, context.app).await?,
+        job => {
+            // Progress bars for a person at a terminal, JSON logs keep the log lines.
+            if !opts.json {
+                cio_api::progress::set_reporter(crate::progress::BarReporter::default());
+            }
+            crate::job::cancel_on_signal()?;
+            crate::job::run_job_cmd(job, context.app).await?
+        }
     }
 
     if let Ok(mem) = SelfMemory::new() {
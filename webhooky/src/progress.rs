@@ -0,0 +1,41 @@
+use cio_api::progress::{ProgressReporter, ProgressUpdate, SyncProgress};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// Draws a progress bar for each sync a command runs, for when it is run by hand.
+#[derive(Debug, Default)]
+pub struct BarReporter {
+    bars: MultiProgress,
+}
+
+impl ProgressReporter for BarReporter {
+    fn start(&self, sync: &str) -> Box<dyn SyncProgress> {
+        // The length is set once the sync knows its total, until then it spins.
+        let bar = self.bars.add(ProgressBar::new_spinner());
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} {prefix} [{bar:40}] {pos}/{len} records {msg}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        bar.set_prefix(sync.to_string());
+
+        Box::new(BarProgress { bar })
+    }
+}
+
+struct BarProgress {
+    bar: ProgressBar,
+}
+
+impl SyncProgress for BarProgress {
+    fn update(&mut self, update: ProgressUpdate) {
+        if let Some(total) = update.total {
+            self.bar.set_length(total.max(update.records));
+        }
+        self.bar.set_position(update.records);
+        self.bar.set_message(format!("({} pages)", update.pages));
+    }
+
+    fn finish(&mut self) {
+        self.bar.finish();
+    }
+}
@@ -2,7 +2,7 @@ use std::io;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use cio_api::{
     db::Database,
     functions::{FnOutput, Function},
@@ -12,6 +12,7 @@ use log::info;
 use serde::{Deserialize, Serialize};
 use slog::Drain;
 use slog_scope_futures::FutureExt as _;
+use tokio::task::JoinHandle;
 
 use crate::health::report_health;
 
@@ -97,13 +98,15 @@ pub fn create_registry() -> steno::ActionRegistry<Saga> {
     registry
 }
 
+/// Start the saga running the command. The saga runs in the background, the returned
+/// handle resolves once it completes, to an error if it failed.
 pub async fn run_cmd(
     db: &Database,
     sec: &steno::SecClient,
     registry: Arc<steno::ActionRegistry<Saga>>,
     id: &uuid::Uuid,
     cmd_name: &str,
-) -> Result<()> {
+) -> Result<JoinHandle<Result<()>>> {
     report_health(&format!("Run cmd [{}]", cmd_name));
 
     let params = Params {
@@ -137,13 +140,18 @@ pub async fn run_cmd(
     let complete_msg = format!("Saga Complete {}", cmd_name);
 
     // Listen for the saga to complete
-    tokio::spawn(async move {
+    let completion = tokio::spawn(async move {
         let result = saga.await;
         info!("Saga completed {:?}", result);
         report_health(&complete_msg);
+
+        result
+            .kind
+            .map(|_| ())
+            .map_err(|e| anyhow!("saga node {:?} failed: {}", e.error_node_name, e.error_source))
     });
 
-    Ok(())
+    Ok(completion)
 }
 
 async fn action_run_cmd(action_context: steno::ActionContext<Saga>) -> Result<FnOutput, steno::ActionError> {
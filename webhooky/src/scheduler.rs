@@ -0,0 +1,282 @@
+//! The scheduler for the cron jobs.
+//!
+//! Each job registers a name, a schedule and a handler. A job that is still running when
+//! it is due again is skipped rather than started twice, and the outcome of the last run
+//! of each job is kept so the server can report it.
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use clokwerk::{AsyncScheduler, Interval, Job};
+use log::{error, info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The handler of a job, called each time the job is due.
+pub type JobHandler = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync>;
+
+/// When a job runs.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Every interval, for example `1.hours()`.
+    Every(Interval),
+    /// Every interval at a time of day, for example `Interval::Monday` at `"8:00 am"`.
+    EveryAt(Interval, &'static str),
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Every(interval) => write!(f, "every {:?}", interval),
+            Schedule::EveryAt(interval, time) => write!(f, "every {:?} at {}", interval, time),
+        }
+    }
+}
+
+/// The outcome of a run of a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOutcome {
+    Succeeded,
+    Failed,
+}
+
+/// The status of a job.
+#[derive(Debug, Default, Clone, JsonSchema, Deserialize, Serialize)]
+pub struct JobStatus {
+    /// When the job runs.
+    pub schedule: String,
+    /// Whether the job is running now.
+    pub running: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_started_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_finished_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_outcome: Option<JobOutcome>,
+    /// The error of the last run, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// The number of times the job was skipped because the previous run was still going.
+    #[serde(default)]
+    pub skipped: u64,
+}
+
+struct ScheduledJob {
+    name: String,
+    handler: JobHandler,
+    running: AtomicBool,
+    status: RwLock<JobStatus>,
+}
+
+impl ScheduledJob {
+    /// Run the job in the background, unless it is already running. Returns false if the
+    /// run was skipped.
+    fn spawn(self: &Arc<Self>) -> bool {
+        if self.running.swap(true, Ordering::SeqCst) {
+            warn!("job `{}` is still running, skipping this run", self.name);
+            self.status.write().unwrap().skipped += 1;
+            return false;
+        }
+
+        {
+            let mut status = self.status.write().unwrap();
+            status.running = true;
+            status.last_started_at = Some(Utc::now());
+        }
+
+        let job = self.clone();
+        tokio::spawn(async move {
+            info!("running job `{}`", job.name);
+            // Run the handler in a task of its own, so a panic fails the run rather than
+            // leaving the job running forever.
+            let handler = job.handler.clone();
+            let result = match tokio::spawn(async move { handler().await }).await {
+                Ok(result) => result,
+                Err(e) => Err(e.into()),
+            };
+
+            let mut status = job.status.write().unwrap();
+            status.running = false;
+            status.last_finished_at = Some(Utc::now());
+            match result {
+                Ok(()) => {
+                    info!("job `{}` finished", job.name);
+                    status.last_outcome = Some(JobOutcome::Succeeded);
+                    status.last_error = None;
+                }
+                Err(e) => {
+                    error!("job `{}` failed: {:?}", job.name, e);
+                    status.last_outcome = Some(JobOutcome::Failed);
+                    status.last_error = Some(e.to_string());
+                }
+            }
+            job.running.store(false, Ordering::SeqCst);
+        });
+
+        true
+    }
+}
+
+/// The jobs registered with a scheduler. This is cheap to clone and can be shared with
+/// the server to trigger jobs and report their status.
+#[derive(Clone, Default)]
+pub struct Jobs {
+    jobs: Arc<RwLock<BTreeMap<String, Arc<ScheduledJob>>>>,
+}
+
+impl std::fmt::Debug for Jobs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.jobs.read().unwrap().keys()).finish()
+    }
+}
+
+impl Jobs {
+    /// Run the job now, outside of its schedule. Returns false if the job is already
+    /// running.
+    pub fn run(&self, name: &str) -> Result<bool> {
+        let job = self
+            .jobs
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("no job named `{}`", name))?;
+
+        Ok(job.spawn())
+    }
+
+    /// Returns the status of each job, by name.
+    pub fn status(&self) -> BTreeMap<String, JobStatus> {
+        self.jobs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, job)| (name.to_string(), job.status.read().unwrap().clone()))
+            .collect()
+    }
+}
+
+/// Runs the registered jobs on their schedules.
+pub struct Scheduler {
+    inner: AsyncScheduler<Tz>,
+    jobs: Jobs,
+}
+
+impl Scheduler {
+    /// Create a scheduler that registers its jobs with `jobs`, so the holders of the handle
+    /// can trigger them and report their status.
+    pub fn new(tz: Tz, jobs: Jobs) -> Self {
+        Scheduler {
+            inner: AsyncScheduler::with_tz(tz),
+            jobs,
+        }
+    }
+
+    /// Register a job. Registering a second job with the same name replaces the first in
+    /// the status, but both keep running on their schedules.
+    pub fn register<F, Fut>(&mut self, name: &str, schedule: Schedule, handler: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let job = Arc::new(ScheduledJob {
+            name: name.to_string(),
+            handler: Arc::new(move || Box::pin(handler())),
+            running: AtomicBool::new(false),
+            status: RwLock::new(JobStatus {
+                schedule: schedule.to_string(),
+                ..Default::default()
+            }),
+        });
+        self.jobs.jobs.write().unwrap().insert(name.to_string(), job.clone());
+
+        let run = move || {
+            job.spawn();
+            async {}
+        };
+        match schedule {
+            Schedule::Every(interval) => {
+                self.inner.every(interval).run(run);
+            }
+            Schedule::EveryAt(interval, time) => {
+                self.inner.every(interval).at(time).run(run);
+            }
+        }
+    }
+
+    /// Run the jobs that are due, forever.
+    pub async fn run(mut self) {
+        info!("starting cron job scheduler with jobs {:?}", self.jobs);
+
+        loop {
+            self.inner.run_pending().await;
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_overlapping_runs_are_skipped() {
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let rx = Arc::new(tokio::sync::Mutex::new(Some(rx)));
+
+        let jobs = Jobs::default();
+        let mut scheduler = Scheduler::new(chrono_tz::US::Pacific, jobs.clone());
+        scheduler.register("slow", Schedule::Every(Interval::Hours(1)), move || {
+            let rx = rx.clone();
+            async move {
+                if let Some(rx) = rx.lock().await.take() {
+                    rx.await?;
+                }
+                Ok(())
+            }
+        });
+
+        assert!(jobs.run("slow").unwrap());
+        assert!(!jobs.run("slow").unwrap());
+        assert!(jobs.run("missing").is_err());
+
+        tx.send(()).unwrap();
+        while jobs.status()["slow"].running {
+            tokio::task::yield_now().await;
+        }
+
+        let status = &jobs.status()["slow"];
+        assert_eq!(status.skipped, 1);
+        assert_eq!(status.last_outcome, Some(JobOutcome::Succeeded));
+        assert!(jobs.run("slow").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_panicking_run_fails() {
+        let jobs = Jobs::default();
+        let mut scheduler = Scheduler::new(chrono_tz::US::Pacific, jobs.clone());
+        scheduler.register("panics", Schedule::Every(Interval::Hours(1)), || async {
+            panic!("boom");
+        });
+
+        assert!(jobs.run("panics").unwrap());
+        while jobs.status()["panics"].running {
+            tokio::task::yield_now().await;
+        }
+
+        let status = &jobs.status()["panics"];
+        assert_eq!(status.last_outcome, Some(JobOutcome::Failed));
+        assert!(status.last_error.as_deref().unwrap().contains("panicked"));
+        assert!(jobs.run("panics").unwrap());
+    }
+}
@@ -9,7 +9,7 @@ use cio_api::{
     rfd::{RFDEntry, RFDIndexEntry},
     swag_store::Order,
 };
-use clokwerk::{AsyncScheduler, Job, TimeUnits};
+use clokwerk::TimeUnits;
 use docusign::DocuSign;
 use dropshot::{
     endpoint, ApiDescription, ConfigDropshot, ConfigLogging, ConfigLoggingLevel, HttpError, HttpResponseAccepted,
@@ -42,6 +42,7 @@ use crate::{
     github_types::GitHubWebhook,
     handlers_hiring::{ApplicantInfo, ApplicantUploadToken},
     handlers_slack::InteractiveEvent,
+    scheduler::{Schedule, Scheduler},
 };
 
 pub struct APIConfig {
@@ -73,6 +74,7 @@ fn create_api() -> ApiDescription<ServerContext> {
      * allowing this metadata to live right alongside the handler function.
      */
     api.register(ping).unwrap();
+    api.register(metrics).unwrap();
     api.register(github_rate_limit).unwrap();
 
     api.register(listen_application_submit_requests).unwrap();
@@ -102,6 +104,7 @@ fn create_api() -> ApiDescription<ServerContext> {
     api.register(listen_auth_slack_consent).unwrap();
     api.register(listen_auth_quickbooks_callback).unwrap();
     api.register(listen_auth_quickbooks_consent).unwrap();
+    api.register(listen_airtable_webhook_notifications).unwrap();
     api.register(listen_checkr_background_update_webhooks).unwrap();
     api.register(listen_docusign_envelope_update_webhooks).unwrap();
     api.register(listen_github_webhooks).unwrap();
@@ -140,6 +143,12 @@ fn create_api() -> ApiDescription<ServerContext> {
     api.register(trigger_sync_travel_create).unwrap();
     api.register(trigger_sync_zoho_create).unwrap();
 
+    api.register(trigger_sync_auth).unwrap();
+    api.register(trigger_sync_zoom).unwrap();
+    api.register(trigger_sync_page_views).unwrap();
+    api.register(job_status).unwrap();
+    api.register(trigger_scheduled_job).unwrap();
+
     api
 }
 
@@ -201,85 +210,143 @@ pub async fn server(
 
     // This really only applied for when we are running with `do-cron` but we need the variable
     // for the scheduler to be in the top level so we can run as async later based on the options.
-    let mut scheduler = AsyncScheduler::with_tz(chrono_tz::US::Pacific);
+    let mut scheduler = Scheduler::new(chrono_tz::US::Pacific, server_context.jobs.clone());
 
     // Copy the Server struct so we can move it into our loop.
     if s.do_cron {
         /*
          * Setup our cron jobs, with our timezone.
          */
-        // scheduler
-        //     .every(1.day())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-analytics")});
-        // scheduler
-        //     .every(23.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-api-tokens")});
-        // scheduler
-        //     .every(7.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-applications")});
-        // scheduler
-        //     .every(2.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-asset-inventory")});
-        // scheduler
-        //     .every(12.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-companies")});
-        // scheduler
-        //     .every(1.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-configs")});
-        // scheduler
-        //     .every(6.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-finance")});
-        // scheduler
-        //     .every(12.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-functions")});
-        // scheduler
-        //     .every(1.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-huddles")});
-        // scheduler
-        //     .every(4.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-interviews")});
-        // scheduler
-        //     .every(12.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-journal-clubs")});
-        // scheduler
-        //     .every(3.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-mailing-lists")});
-        // scheduler
-        //     .every(18.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-other")});
-        // scheduler.every(3.hours()).run(
-        //     enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-recorded-meetings")},
+        // scheduler.register(
+        //     "sync-analytics",
+        //     Schedule::Every(1.day()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-analytics")},
+        // );
+        // scheduler.register(
+        //     "sync-api-tokens",
+        //     Schedule::Every(23.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-api-tokens")},
+        // );
+        // scheduler.register(
+        //     "sync-applications",
+        //     Schedule::Every(7.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-applications")},
+        // );
+        // scheduler.register(
+        //     "sync-asset-inventory",
+        //     Schedule::Every(2.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-asset-inventory")},
+        // );
+        // scheduler.register(
+        //     "sync-companies",
+        //     Schedule::Every(12.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-companies")},
+        // );
+        // scheduler.register(
+        //     "sync-configs",
+        //     Schedule::Every(1.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-configs")},
+        // );
+        // scheduler.register(
+        //     "sync-finance",
+        //     Schedule::Every(6.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-finance")},
+        // );
+        // scheduler.register(
+        //     "sync-functions",
+        //     Schedule::Every(12.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-functions")},
+        // );
+        // scheduler.register(
+        //     "sync-huddles",
+        //     Schedule::Every(1.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-huddles")},
+        // );
+        // scheduler.register(
+        //     "sync-interviews",
+        //     Schedule::Every(4.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-interviews")},
+        // );
+        // scheduler.register(
+        //     "sync-journal-clubs",
+        //     Schedule::Every(12.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-journal-clubs")},
+        // );
+        // scheduler.register(
+        //     "sync-mailing-lists",
+        //     Schedule::Every(3.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-mailing-lists")},
+        // );
+        // scheduler.register(
+        //     "sync-other",
+        //     Schedule::Every(18.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-other")},
+        // );
+        // scheduler.register(
+        //     "sync-recorded-meetings",
+        //     Schedule::Every(3.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-recorded-meetings")},
         // );
-        // scheduler
-        //     .every(16.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-repos")});
-        // scheduler
-        //     .every(14.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-rfds")});
-        // scheduler
-        //     .every(30.minutes())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-salesforce")});
-        // scheduler
-        //     .every(2.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-shipments")});
-        // scheduler
-        //     .every(3.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-shorturls")});
-        // scheduler
-        //     .every(9.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-swag-inventory")});
-        // scheduler
-        //     .every(5.hours())
-        //     .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "sync-travel")});
-        scheduler
-            .every(1.minutes())
-            .run(|| async { crate::health::scheduler_health_check() });
+        // scheduler.register(
+        //     "sync-repos",
+        //     Schedule::Every(16.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-repos")},
+        // );
+        // scheduler.register(
+        //     "sync-rfds",
+        //     Schedule::Every(14.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-rfds")},
+        // );
+        // scheduler.register(
+        //     "sync-salesforce",
+        //     Schedule::Every(30.minutes()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-salesforce")},
+        // );
+        // scheduler.register(
+        //     "sync-shipments",
+        //     Schedule::Every(2.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-shipments")},
+        // );
+        // scheduler.register(
+        //     "sync-shorturls",
+        //     Schedule::Every(3.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-shorturls")},
+        // );
+        // scheduler.register(
+        //     "sync-swag-inventory",
+        //     Schedule::Every(9.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-swag-inventory")},
+        // );
+        // scheduler.register(
+        //     "sync-travel",
+        //     Schedule::Every(5.hours()),
+        //     enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-travel")},
+        // );
+        scheduler.register("health-check", Schedule::Every(1.minutes()), || async {
+            crate::health::scheduler_health_check();
+            Ok(())
+        });
+
+        // Sync the auth users, then their logins and page views, which link to them.
+        scheduler.register(
+            "sync-auth-and-page-views",
+            Schedule::Every(1.hours()),
+            enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-auth-and-page-views")},
+        );
+
+        // Refresh the copies of our tables in other bases.
+        scheduler.register(
+            "sync-airtable-replications",
+            Schedule::Every(1.hours()),
+            enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "sync-airtable-replications")},
+        );
 
         // Run the RFD changelog.
-        scheduler
-            .every(clokwerk::Interval::Monday)
-            .at("8:00 am")
-            .run(enclose! { (server_context) move || create_do_job_fn(server_context.clone(), "send-rfd-changelog")});
+        scheduler.register(
+            "send-rfd-changelog",
+            Schedule::EveryAt(clokwerk::Interval::Monday, "8:00 am"),
+            enclose! { (server_context) move || create_run_job_fn(server_context.clone(), "send-rfd-changelog")},
+        );
     }
 
     // For Cloud run & ctrl+c, shutdown gracefully.
@@ -292,6 +359,10 @@ pub async fn server(
         for sig in signals.forever() {
             let pid = std::process::id();
             info!("received signal: {:?} pid: {}", sig, pid);
+
+            // Let the syncs in flight stop at their next checkpoint while we clean up.
+            cio_api::cancel::shutdown().cancel();
+
             info!("triggering cleanup... {}", pid);
 
             // Run the cleanup job.
@@ -310,13 +381,8 @@ pub async fn server(
             server.await.unwrap();
         });
 
-        info!("starting cron job scheduler...");
-
         // Loop the scheduler.
-        loop {
-            scheduler.run_pending().await;
-            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
-        }
+        scheduler.run().await;
     } else {
         server.await.unwrap();
     }
@@ -324,16 +390,15 @@ pub async fn server(
     Ok(())
 }
 
-pub fn create_do_job_fn(ctx: ServerContext, job: &str) -> Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
-    Box::pin(do_job(ctx, job.to_string()))
-}
-
-pub async fn do_job(ctx: ServerContext, job: String) {
-    info!("triggering cron job `{}`", job);
-
-    if let Err(err) = crate::handlers_cron::run_subcmd_job(&ctx, &job).await {
-        error!("Failed to spawn job: {:?}", err)
-    }
+pub fn create_run_job_fn(
+    ctx: ServerContext,
+    job: &str,
+) -> Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+    let job = job.to_string();
+    Box::pin(async move {
+        info!("triggering cron job `{}`", job);
+        crate::handlers_cron::run_subcmd_job_to_completion(&ctx, &job).await
+    })
 }
 
 /*
@@ -349,6 +414,21 @@ async fn ping(_rqctx: RequestContext<ServerContext>) -> Result<HttpResponseOk<St
     Ok(HttpResponseOk("pong".to_string()))
 }
 
+/** Return the metrics of the sync jobs in the Prometheus text format. */
+#[endpoint {
+    method = GET,
+    path = "/metrics",
+}]
+async fn metrics(_rqctx: RequestContext<ServerContext>) -> Result<http::Response<hyper::Body>, HttpError> {
+    let body = cio_api::metrics::gather().map_err(handle_anyhow_err_as_http_err)?;
+
+    http::Response::builder()
+        .status(http::StatusCode::OK)
+        .header(http::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(body.into())
+        .map_err(|e| HttpError::for_internal_error(e.to_string()))
+}
+
 #[derive(Deserialize, Serialize, Default, Clone, Debug, JsonSchema)]
 pub struct CounterResponse {
     #[serde(default)]
@@ -1157,6 +1237,21 @@ async fn listen_auth_docusign_callback(
         .map_err(handle_anyhow_err_as_http_err)
 }
 
+/** Listen for notifications of changes to our Airtable bases. */
+#[endpoint {
+    method = POST,
+    path = "/airtable/webhook",
+}]
+async fn listen_airtable_webhook_notifications(
+    rqctx: RequestContext<ServerContext>,
+    body: HmacVerifiedBody<crate::handlers_airtable::AirtableWebhookVerification, airtable_api::WebhookNotification>,
+) -> Result<HttpResponseAccepted<String>, HttpError> {
+    crate::handlers::handle_airtable_webhook_notification(&rqctx, body.into_inner()?)
+        .await
+        .map(accepted)
+        .map_err(handle_anyhow_err_as_http_err)
+}
+
 /** Listen for updates to our docusign envelopes. */
 #[endpoint {
     method = POST,
@@ -1682,6 +1777,97 @@ fn accepted(_: impl Any) -> HttpResponseAccepted<String> {
     HttpResponseAccepted("ok".to_string())
 }
 
+/** Trigger a sync of the auth users from Auth0. */
+#[endpoint {
+    method = POST,
+    path = "/sync/auth",
+}]
+async fn trigger_sync_auth(
+    rqctx: RequestContext<ServerContext>,
+    _auth: Bearer<InternalToken>,
+) -> Result<HttpResponseAccepted<uuid::Uuid>, HttpError> {
+    crate::handlers_cron::run_subcmd_job(rqctx.context(), "sync-auth-users")
+        .await
+        .map(HttpResponseAccepted)
+        .map_err(handle_anyhow_err_as_http_err)
+}
+
+/** Trigger a sync of the Zoom users and their licenses. */
+#[endpoint {
+    method = POST,
+    path = "/sync/zoom",
+}]
+async fn trigger_sync_zoom(
+    rqctx: RequestContext<ServerContext>,
+    _auth: Bearer<InternalToken>,
+) -> Result<HttpResponseAccepted<uuid::Uuid>, HttpError> {
+    crate::handlers_cron::run_subcmd_job(rqctx.context(), "sync-zoom-users")
+        .await
+        .map(HttpResponseAccepted)
+        .map_err(handle_anyhow_err_as_http_err)
+}
+
+/** Trigger a sync of the page views to Airtable. */
+#[endpoint {
+    method = POST,
+    path = "/sync/analytics",
+}]
+async fn trigger_sync_page_views(
+    rqctx: RequestContext<ServerContext>,
+    _auth: Bearer<InternalToken>,
+) -> Result<HttpResponseAccepted<uuid::Uuid>, HttpError> {
+    crate::handlers_cron::run_subcmd_job(rqctx.context(), "sync-page-views")
+        .await
+        .map(HttpResponseAccepted)
+        .map_err(handle_anyhow_err_as_http_err)
+}
+
+/**
+ * Return the time and outcome of the last run of each job, and when each sync last
+ * succeeded. With `max_age_hours`, the syncs that have not succeeded within it are
+ * marked stale.
+ */
+#[endpoint {
+    method = GET,
+    path = "/status",
+}]
+async fn job_status(
+    rqctx: RequestContext<ServerContext>,
+    _auth: Bearer<InternalToken>,
+    query_args: Query<crate::handlers_cron::StatusQuery>,
+) -> Result<HttpResponseOk<crate::handlers_cron::ServerStatus>, HttpError> {
+    crate::handlers_cron::get_status(rqctx.context(), query_args.into_inner())
+        .await
+        .map(HttpResponseOk)
+        .map_err(handle_anyhow_err_as_http_err)
+}
+
+#[derive(Deserialize, Debug, JsonSchema)]
+pub struct ScheduledJobPathParams {
+    pub name: String,
+}
+
+/**
+ * Run a cron job of the scheduler now, outside of its schedule. Returns false if the job
+ * is still running from before.
+ */
+#[endpoint {
+    method = POST,
+    path = "/schedule/{name}",
+}]
+async fn trigger_scheduled_job(
+    rqctx: RequestContext<ServerContext>,
+    _auth: Bearer<InternalToken>,
+    path_params: Path<ScheduledJobPathParams>,
+) -> Result<HttpResponseAccepted<bool>, HttpError> {
+    rqctx
+        .context()
+        .jobs
+        .run(&path_params.into_inner().name)
+        .map(HttpResponseAccepted)
+        .map_err(|e| HttpError::for_not_found(None, e.to_string()))
+}
+
 fn handle_anyhow_err_as_http_err(err: anyhow::Error) -> HttpError {
     error!("Http error {:?}", err);
 